@@ -0,0 +1,28 @@
+use wfc_core::bench::Workload;
+
+const SEEDS: [u64; 5] = [1, 2, 3, 4, 5];
+
+fn main() {
+    println!(
+        "{:<30} {:>6} {:>10} {:>10} {:>10} {:>12} {:>10} {:>10} {:>10}",
+        "workload", "ok", "ms", "select_ms", "prop_ms", "prop_ops", "bans", "entropy", "backtracks"
+    );
+    for workload in Workload::standard_suite() {
+        for (seed, interned) in SEEDS.iter().flat_map(|&seed| [(seed, false), (seed, true)]) {
+            let r = if interned { workload.run_interned(seed) } else { workload.run(seed) }
+                .expect("workload should be well-formed");
+            println!(
+                "{:<30} {:>6} {:>10.2} {:>10.2} {:>10.2} {:>12} {:>10} {:>10} {:>10}",
+                format!("{}{}#{}", r.name, if interned { "/interned" } else { "" }, r.seed),
+                r.success,
+                r.elapsed.as_secs_f64() * 1000.0,
+                r.timings.selection.as_secs_f64() * 1000.0,
+                r.timings.propagation.as_secs_f64() * 1000.0,
+                r.stats.propagation_ops,
+                r.stats.bans,
+                r.stats.entropy_evaluations,
+                r.stats.backtracks,
+            );
+        }
+    }
+}
@@ -0,0 +1,96 @@
+//! Post-generation analysis of finished grids, for acceptance checks such as "the largest
+//! landmass covers at least 60% of the map".
+
+use crate::TileId;
+use crate::error::WfcError;
+
+/// One 4-connected component of cells matching a predicate
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Region {
+    /// Position of this region in the returned list; regions are labeled in row-major order
+    /// of their first cell
+    pub label: usize,
+    /// Row-major cell indices, ascending
+    pub cells: Vec<usize>,
+    /// Inclusive bounding box as (min_x, min_y, max_x, max_y)
+    pub bounds: (usize, usize, usize, usize),
+}
+
+impl Region {
+    pub fn size(&self) -> usize {
+        self.cells.len()
+    }
+}
+
+/// Flood-fill a row-major grid into the connected components of cells for which
+/// `predicate` holds. Cells connect through their up/right/down/left neighbors.
+pub fn regions(grid: &[TileId], width: usize, height: usize, predicate: impl Fn(&TileId) -> bool) -> Result<Vec<Region>, WfcError> {
+    if width == 0 || height == 0 || grid.len() != width * height {
+        return Err(WfcError::InvalidDimensions { width, height });
+    }
+
+    let mut labels: Vec<Option<usize>> = vec![None; grid.len()];
+    let mut regions = Vec::new();
+    for seed in 0..grid.len() {
+        if labels[seed].is_some() || !predicate(&grid[seed]) {
+            continue;
+        }
+        let label = regions.len();
+        labels[seed] = Some(label);
+        let mut cells = Vec::new();
+        let mut stack = vec![seed];
+        let (mut min_x, mut min_y, mut max_x, mut max_y) = (width, height, 0, 0);
+        while let Some(index) = stack.pop() {
+            cells.push(index);
+            let (x, y) = (index % width, index / width);
+            (min_x, min_y, max_x, max_y) = (min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y));
+
+            let neighbors = [
+                (y > 0).then(|| index - width),
+                (x + 1 < width).then(|| index + 1),
+                (y + 1 < height).then(|| index + width),
+                (x > 0).then(|| index - 1),
+            ];
+            for neighbor in neighbors.into_iter().flatten() {
+                if labels[neighbor].is_none() && predicate(&grid[neighbor]) {
+                    labels[neighbor] = Some(label);
+                    stack.push(neighbor);
+                }
+            }
+        }
+        cells.sort_unstable();
+        regions.push(Region { label, cells, bounds: (min_x, min_y, max_x, max_y) });
+    }
+    Ok(regions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid(rows: &[&str]) -> Vec<TileId> {
+        rows.iter().flat_map(|row| row.chars().map(|c| c.to_string())).collect()
+    }
+
+    #[test]
+    fn test_regions_label_components() {
+        let map = grid(&[
+            "LL.L",
+            "L..L",
+            ".LLL",
+            "L...",
+        ]);
+        let land = regions(&map, 4, 4, |id| id == "L").unwrap();
+        assert_eq!(land.len(), 3);
+        assert_eq!(land[0].cells, vec![0, 1, 4]);
+        assert_eq!(land[1].size(), 5);
+        assert_eq!(land[1].bounds, (1, 0, 3, 2));
+        assert_eq!(land[2].label, 2);
+        assert_eq!(land[2].bounds, (0, 3, 0, 3));
+
+        let largest = land.iter().map(Region::size).max().unwrap();
+        assert_eq!(largest, 5);
+        assert_eq!(regions(&map, 4, 4, |_| true).unwrap().len(), 1);
+        assert!(regions(&map, 5, 4, |_| true).is_err());
+    }
+}
@@ -0,0 +1,196 @@
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::{TileId, Direction};
+use crate::error::WfcError;
+use crate::model::MAX_DIMENSION;
+use crate::ruleset::RuleSet;
+
+/// Cooling schedule for `SimulatedAnnealing::run`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AnnealingConfig {
+    /// Maximum number of proposed tile changes
+    pub iterations: usize,
+    /// Starting temperature; higher values accept more uphill moves early on
+    pub initial_temperature: f64,
+    /// Multiplicative temperature decay applied after every proposal
+    pub cooling: f64,
+}
+
+impl Default for AnnealingConfig {
+    fn default() -> Self {
+        AnnealingConfig {
+            iterations: 200_000,
+            initial_temperature: 2.0,
+            cooling: 0.9999,
+        }
+    }
+}
+
+/// Best assignment found by the annealer
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnnealingResult {
+    pub grid: Vec<TileId>,
+    /// Adjacent cell pairs that still break the ruleset; 0 means the grid is fully valid
+    pub violations: usize,
+    pub iterations: usize,
+}
+
+/// Fallback solver that starts from a random full assignment and anneals toward zero
+/// adjacency violations. Unlike `Model` it always produces a complete grid, which may be
+/// "mostly valid" when the ruleset is too tight for constructive solving.
+#[derive(Debug, Clone)]
+pub struct SimulatedAnnealing {
+    width: usize,
+    height: usize,
+    rules: RuleSet,
+    tiles: Vec<TileId>,
+    weights: Vec<u32>,
+    rng: StdRng,
+}
+
+impl SimulatedAnnealing {
+    pub fn new(width: usize, height: usize, rules: RuleSet, seed: Option<u64>) -> Result<SimulatedAnnealing, WfcError> {
+        if width == 0 || height == 0 || width > MAX_DIMENSION || height > MAX_DIMENSION {
+            return Err(WfcError::InvalidDimensions { width, height });
+        }
+
+        let mut tiles: Vec<TileId> = rules.get_all_tile_ids().into_iter().cloned().collect();
+        if tiles.is_empty() {
+            return Err(WfcError::NoTilesDefined);
+        }
+        tiles.sort();
+        let weights = tiles.iter().map(|id| rules.get_weight(id).unwrap_or(1).max(1)).collect();
+
+        let rng = match seed {
+            Some(s) => StdRng::seed_from_u64(s),
+            None => StdRng::from_entropy(),
+        };
+
+        Ok(SimulatedAnnealing { width, height, rules, tiles, weights, rng })
+    }
+
+    pub fn run(&mut self, config: &AnnealingConfig) -> AnnealingResult {
+        let mut grid: Vec<usize> = (0..self.width * self.height).map(|_| self.random_tile()).collect();
+        let mut violations = self.total_violations(&grid);
+        let mut best = (grid.clone(), violations);
+        let mut temperature = config.initial_temperature;
+        let mut iterations = 0;
+
+        while iterations < config.iterations && violations > 0 {
+            iterations += 1;
+            let cell = self.rng.gen_range(0..grid.len());
+            let previous = grid[cell];
+            let candidate = self.random_tile();
+            if candidate == previous {
+                continue;
+            }
+
+            let before = self.local_violations(&grid, cell);
+            grid[cell] = candidate;
+            let after = self.local_violations(&grid, cell);
+            let delta = after as f64 - before as f64;
+
+            if delta <= 0.0 || self.rng.gen::<f64>() < (-delta / temperature.max(1e-9)).exp() {
+                violations = violations + after - before;
+                if violations < best.1 {
+                    best = (grid.clone(), violations);
+                }
+            } else {
+                grid[cell] = previous;
+            }
+            temperature *= config.cooling;
+        }
+
+        AnnealingResult {
+            grid: best.0.into_iter().map(|t| self.tiles[t].clone()).collect(),
+            violations: best.1,
+            iterations,
+        }
+    }
+
+    fn random_tile(&mut self) -> usize {
+        let total: u32 = self.weights.iter().sum();
+        let mut roll = self.rng.gen_range(0..total);
+        for (i, &weight) in self.weights.iter().enumerate() {
+            if roll < weight {
+                return i;
+            }
+            roll -= weight;
+        }
+        self.weights.len() - 1
+    }
+
+    fn edge_ok(&self, grid: &[usize], a: usize, b: usize, dir: Direction) -> bool {
+        self.rules.is_compatible(&self.tiles[grid[a]], &self.tiles[grid[b]], dir)
+    }
+
+    /// Violated edges touching `cell`
+    fn local_violations(&self, grid: &[usize], cell: usize) -> usize {
+        let (x, y) = (cell % self.width, cell / self.width);
+        let mut count = 0;
+        if y > 0 && !self.edge_ok(grid, cell, cell - self.width, Direction::Up) {
+            count += 1;
+        }
+        if x + 1 < self.width && !self.edge_ok(grid, cell, cell + 1, Direction::Right) {
+            count += 1;
+        }
+        if y + 1 < self.height && !self.edge_ok(grid, cell, cell + self.width, Direction::Down) {
+            count += 1;
+        }
+        if x > 0 && !self.edge_ok(grid, cell, cell - 1, Direction::Left) {
+            count += 1;
+        }
+        count
+    }
+
+    fn total_violations(&self, grid: &[usize]) -> usize {
+        let mut count = 0;
+        for cell in 0..grid.len() {
+            let (x, y) = (cell % self.width, cell / self.width);
+            if x + 1 < self.width && !self.edge_ok(grid, cell, cell + 1, Direction::Right) {
+                count += 1;
+            }
+            if y + 1 < self.height && !self.edge_ok(grid, cell, cell + self.width, Direction::Down) {
+                count += 1;
+            }
+        }
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_anneals_to_valid_checkerboard() {
+        // Two tiles that must alternate in every direction
+        let mut rules = RuleSet::new();
+        rules.add_tile("black".to_string(), 1);
+        rules.add_tile("white".to_string(), 1);
+        for dir in [Direction::Up, Direction::Right, Direction::Down, Direction::Left] {
+            rules.add_adjacency("black".to_string(), "white".to_string(), dir);
+            rules.add_adjacency("white".to_string(), "black".to_string(), dir);
+        }
+
+        let mut annealer = SimulatedAnnealing::new(6, 6, rules, Some(4)).unwrap();
+        let result = annealer.run(&AnnealingConfig::default());
+
+        assert_eq!(result.violations, 0);
+        assert_eq!(result.grid.len(), 36);
+        assert_ne!(result.grid[0], result.grid[1]);
+        assert_ne!(result.grid[0], result.grid[6]);
+    }
+
+    #[test]
+    fn test_reports_remaining_violations() {
+        // No rules at all: every edge is a violation and nothing can fix it
+        let mut rules = RuleSet::new();
+        rules.add_tile("a".to_string(), 1);
+
+        let mut annealer = SimulatedAnnealing::new(3, 1, rules, Some(1)).unwrap();
+        let result = annealer.run(&AnnealingConfig { iterations: 50, ..AnnealingConfig::default() });
+        assert_eq!(result.violations, 2);
+        assert_eq!(result.grid, vec!["a".to_string(); 3]);
+    }
+}
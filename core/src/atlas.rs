@@ -0,0 +1,147 @@
+use std::collections::{BTreeMap, HashMap};
+use serde::{Deserialize, Serialize};
+use crate::TileId;
+use crate::error::WfcError;
+use crate::image::Image;
+use crate::ruleset::RuleSet;
+
+/// Pixel rectangle inside a spritesheet
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub w: usize,
+    pub h: usize,
+}
+
+/// A spritesheet plus the rectangle holding each tile's artwork.
+///
+/// Stored on the ruleset (the `"atlas"` block in JSON) so a single file describes both the
+/// rules and where the art for every tile lives.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Atlas {
+    /// Path of the spritesheet image, relative to the ruleset file
+    pub image: String,
+    pub tiles: BTreeMap<TileId, Rect>,
+}
+
+impl Atlas {
+    pub fn new(image: impl Into<String>) -> Atlas {
+        Atlas { image: image.into(), tiles: BTreeMap::new() }
+    }
+
+    pub fn insert(&mut self, id: TileId, rect: Rect) {
+        self.tiles.insert(id, rect);
+    }
+
+    /// Tiles of `rules` that have no rectangle in this atlas, sorted by ID
+    pub fn missing_tiles(&self, rules: &RuleSet) -> Vec<TileId> {
+        let mut missing: Vec<TileId> = rules.get_all_tile_ids()
+            .into_iter()
+            .filter(|id| !self.tiles.contains_key(*id))
+            .cloned()
+            .collect();
+        missing.sort();
+        missing
+    }
+
+    /// Check that every tile has art and every rectangle lies inside the sheet
+    pub fn validate(&self, rules: &RuleSet, sheet: &Image) -> Result<(), WfcError> {
+        let missing = self.missing_tiles(rules);
+        if !missing.is_empty() {
+            return Err(WfcError::InvalidAtlas(format!("no artwork for tiles: {}", missing.join(", "))));
+        }
+        for (id, rect) in &self.tiles {
+            if rect.w == 0 || rect.h == 0 || rect.x + rect.w > sheet.width || rect.y + rect.h > sheet.height {
+                return Err(WfcError::InvalidAtlas(format!(
+                    "rect for {} ({},{} {}x{}) is outside the {}x{} sheet",
+                    id, rect.x, rect.y, rect.w, rect.h, sheet.width, sheet.height
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Cut the sheet into one image per tile, ready for `render::render_tiles`
+    pub fn slice(&self, sheet: &Image) -> Result<HashMap<TileId, Image>, WfcError> {
+        let mut out = HashMap::new();
+        for (id, rect) in &self.tiles {
+            if rect.x + rect.w > sheet.width || rect.y + rect.h > sheet.height {
+                return Err(WfcError::InvalidAtlas(format!("rect for {} is outside the sheet", id)));
+            }
+            out.insert(id.clone(), sheet.crop(rect.x, rect.y, rect.w, rect.h));
+        }
+        Ok(out)
+    }
+
+    /// Size shared by every tile rectangle, or `None` if the atlas is empty or mixes sizes
+    pub fn tile_size(&self) -> Option<(usize, usize)> {
+        let mut sizes = self.tiles.values().map(|r| (r.w, r.h));
+        let first = sizes.next()?;
+        sizes.all(|s| s == first).then_some(first)
+    }
+
+    /// Build an atlas for a sheet laid out as a uniform grid, assigning `ids` row by row
+    pub fn from_grid(image: impl Into<String>, tile_w: usize, tile_h: usize, columns: usize, ids: &[TileId]) -> Atlas {
+        let mut atlas = Atlas::new(image);
+        for (i, id) in ids.iter().enumerate() {
+            let (col, row) = (i % columns.max(1), i / columns.max(1));
+            atlas.insert(id.clone(), Rect { x: col * tile_w, y: row * tile_h, w: tile_w, h: tile_h });
+        }
+        atlas
+    }
+}
+
+#[cfg(feature = "png")]
+impl Atlas {
+    /// Load the spritesheet, resolving `image` relative to `base_dir`
+    pub fn load_sheet<P: AsRef<std::path::Path>>(&self, base_dir: P) -> Result<Image, WfcError> {
+        Image::load_png(base_dir.as_ref().join(&self.image))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slice_and_validate() {
+        let mut rules = RuleSet::new();
+        rules.add_tile("a".to_string(), 1);
+        rules.add_tile("b".to_string(), 1);
+
+        // 4x2 sheet: left half red, right half blue
+        let mut sheet = Image::new(4, 2);
+        for y in 0..2 {
+            for x in 0..4 {
+                sheet.set(x, y, if x < 2 { [255, 0, 0, 255] } else { [0, 0, 255, 255] });
+            }
+        }
+
+        let atlas = Atlas::from_grid("sheet.png", 2, 2, 2, &["a".to_string(), "b".to_string()]);
+        assert!(atlas.validate(&rules, &sheet).is_ok());
+        assert_eq!(atlas.tile_size(), Some((2, 2)));
+
+        let tiles = atlas.slice(&sheet).unwrap();
+        assert_eq!(tiles["b"].get(0, 0), [0, 0, 255, 255]);
+
+        rules.add_tile("c".to_string(), 1);
+        assert_eq!(atlas.missing_tiles(&rules), vec!["c".to_string()]);
+        assert!(matches!(atlas.validate(&rules, &sheet), Err(WfcError::InvalidAtlas(_))));
+    }
+
+    #[test]
+    fn test_ruleset_json_carries_atlas() {
+        let json = r#"{
+            "tiles": [{ "id": "a" }],
+            "rules": [],
+            "atlas": { "image": "tiles.png", "tiles": { "a": { "x": 0, "y": 16, "w": 16, "h": 16 } } }
+        }"#;
+        let rules = RuleSet::from_json(json).unwrap();
+        let atlas = rules.atlas.as_ref().unwrap();
+        assert_eq!(atlas.tiles["a"], Rect { x: 0, y: 16, w: 16, h: 16 });
+
+        let rules2 = RuleSet::from_json(&rules.to_json_string().unwrap()).unwrap();
+        assert_eq!(rules2.atlas, rules.atlas);
+    }
+}
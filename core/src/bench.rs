@@ -0,0 +1,276 @@
+use std::collections::HashMap;
+use std::time::Duration;
+use rand::prelude::*;
+use crate::{Direction, TileId, TileKey, DIRECTIONS};
+use crate::clock::Stopwatch;
+use crate::error::WfcError;
+use crate::intern::TileTable;
+use crate::learn::{learn_from_grid_with, LearnOptions};
+use crate::model::{Model, PhaseTimings, SolverStats};
+use crate::ruleset::RuleSet;
+use crate::trace::decision_hash;
+
+/// Seed of `canonical_decision_hash`
+pub const CANONICAL_SEED: u64 = 1;
+
+/// A representative solver workload: a ruleset plus output dimensions
+#[derive(Debug, Clone)]
+pub struct Workload {
+    pub name: &'static str,
+    pub width: usize,
+    pub height: usize,
+    pub rules: RuleSet,
+}
+
+/// Shape of the rulesets made by `random_rules`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RandomRules {
+    pub tile_count: usize,
+    /// How many of the possible tile pairs end up allowed, from 0.0 (each tile mostly next
+    /// to itself, in large patches) to 1.0 (nearly every pair)
+    pub density: f64,
+    /// Tile weights are drawn from `1..=weight_spread`
+    pub weight_spread: u32,
+}
+
+impl Default for RandomRules {
+    fn default() -> Self {
+        RandomRules {
+            tile_count: 8,
+            density: 0.3,
+            weight_spread: 10,
+        }
+    }
+}
+
+/// A random ruleset that is solvable at every grid size. The rules are learned from a
+/// random periodic sample grid, so repeating the sample always gives a valid solution (the
+/// solver may still need to backtrack to find one). Tiles are named `tile_0`, `tile_1`, ...
+/// The same options and seed always give the same rules.
+pub fn random_rules(options: &RandomRules, seed: u64) -> Result<RuleSet, WfcError> {
+    if options.tile_count == 0 {
+        return Err(WfcError::NoTilesDefined);
+    }
+    let mut rng = StdRng::seed_from_u64(seed);
+    let ids: Vec<TileId> = (0..options.tile_count).map(|i| format!("tile_{}", i)).collect();
+
+    // Each cell either starts a new patch or continues the one to its left or above
+    let side = ((options.tile_count * 4) as f64).sqrt().ceil().max(8.0) as usize;
+    let mut sample: Vec<TileId> = Vec::with_capacity(side * side);
+    for i in 0..side * side {
+        let (x, y) = (i % side, i / side);
+        let tile = if (x == 0 && y == 0) || rng.gen_bool(options.density.clamp(0.0, 1.0)) {
+            ids.choose(&mut rng).unwrap().clone()
+        } else if y == 0 || (x > 0 && rng.gen_bool(0.5)) {
+            sample[i - 1].clone()
+        } else {
+            sample[i - side].clone()
+        };
+        sample.push(tile);
+    }
+    // Every tile has to appear somewhere in the sample to get rules. Missing tiles take
+    // random cells whose tile occurs elsewhere too, so no tile is lost on the way; with
+    // four cells per tile there are always enough.
+    let mut counts: HashMap<TileId, usize> = HashMap::new();
+    for tile in &sample {
+        *counts.entry(tile.clone()).or_default() += 1;
+    }
+    let mut cells: Vec<usize> = (0..sample.len()).collect();
+    cells.shuffle(&mut rng);
+    let mut cells = cells.into_iter();
+    for id in &ids {
+        if !counts.contains_key(id) {
+            let cell = cells.by_ref().find(|&cell| counts[&sample[cell]] > 1).expect("the sample has a spare cell");
+            *counts.get_mut(&sample[cell]).unwrap() -= 1;
+            counts.insert(id.clone(), 1);
+            sample[cell] = id.clone();
+        }
+    }
+
+    let periodic = LearnOptions { periodic: true, ..LearnOptions::default() };
+    let mut rules = learn_from_grid_with(&sample, side, side, &periodic)?;
+    for id in &ids {
+        let weight = rng.gen_range(1..=options.weight_spread.max(1));
+        if let Some(info) = rules.tiles.get_mut(id) {
+            info.weight = weight;
+        }
+    }
+    Ok(rules)
+}
+
+/// Outcome of running a workload once
+#[derive(Debug, Clone)]
+pub struct BenchResult {
+    pub name: &'static str,
+    pub seed: u64,
+    pub success: bool,
+    pub stats: SolverStats,
+    pub timings: PhaseTimings,
+    pub elapsed: Duration,
+}
+
+impl Workload {
+    /// Three-terrain coastline ruleset on a `size`x`size` grid
+    pub fn large_grid(size: usize) -> Workload {
+        let mut rules = RuleSet::new();
+        rules.add_tile("water".to_string(), 4);
+        rules.add_tile("sand".to_string(), 1);
+        rules.add_tile("grass".to_string(), 6);
+        for (a, b) in [("water", "water"), ("sand", "sand"), ("grass", "grass"), ("water", "sand"), ("sand", "grass")] {
+            add_both_ways(&mut rules, a, b);
+        }
+        Workload { name: "large_grid", width: size, height: size, rules }
+    }
+
+    /// `tile_count` ordered levels where each level touches itself and its direct neighbors
+    pub fn many_tiles(tile_count: usize) -> Workload {
+        let ids: Vec<String> = (0..tile_count).map(|i| format!("level_{}", i)).collect();
+        let levels: Vec<&str> = ids.iter().map(String::as_str).collect();
+        let rules = RuleSet::from_levels(&levels, &[]).expect("level names are distinct");
+        Workload { name: "many_tiles", width: 32, height: 32, rules }
+    }
+
+    /// Four tiles with sparse, lopsided horizontal/vertical rules; greedy choices regularly
+    /// dead-end, so this exercises backtracking
+    pub fn contradiction_heavy(size: usize) -> Workload {
+        let mut rules = RuleSet::new();
+        let ids = ["a", "b", "c", "d"];
+        for (id, weight) in ids.iter().zip([1, 3, 5, 4]) {
+            rules.add_tile(id.to_string(), weight);
+        }
+        // (left, right) and (top, bottom) pairs
+        let horizontal = [(0, 2), (0, 3), (2, 0), (3, 0), (3, 1), (3, 2), (3, 3)];
+        let vertical = [(0, 1), (1, 1), (1, 2), (1, 3), (2, 2), (2, 3), (3, 1), (3, 3)];
+        for (l, r) in horizontal {
+            rules.add_adjacency(ids[l].to_string(), ids[r].to_string(), Direction::Right);
+            rules.add_adjacency(ids[r].to_string(), ids[l].to_string(), Direction::Left);
+        }
+        for (t, b) in vertical {
+            rules.add_adjacency(ids[t].to_string(), ids[b].to_string(), Direction::Down);
+            rules.add_adjacency(ids[b].to_string(), ids[t].to_string(), Direction::Up);
+        }
+        Workload { name: "contradiction_heavy", width: size, height: size, rules }
+    }
+
+    /// A `size`x`size` grid over `random_rules(options, seed)`
+    pub fn random(size: usize, options: &RandomRules, seed: u64) -> Result<Workload, WfcError> {
+        let rules = random_rules(options, seed)?;
+        Ok(Workload { name: "random", width: size, height: size, rules })
+    }
+
+    /// The default set of workloads used by `cargo bench`
+    pub fn standard_suite() -> Vec<Workload> {
+        let random = RandomRules { tile_count: 24, ..RandomRules::default() };
+        vec![
+            Workload::large_grid(48),
+            Workload::many_tiles(16),
+            Workload::contradiction_heavy(12),
+            Workload::random(32, &random, CANONICAL_SEED).expect("random rules have tiles"),
+        ]
+    }
+
+    /// Run the workload once with the given seed
+    pub fn run(&self, seed: u64) -> Result<BenchResult, WfcError> {
+        self.run_model(Model::new(self.width, self.height, self.rules.clone(), Some(seed))?, seed)
+    }
+
+    /// `run` on `u32` tiles (see the `intern` module), to compare against string tiles
+    pub fn run_interned(&self, seed: u64) -> Result<BenchResult, WfcError> {
+        let rules = TileTable::new(&self.rules).intern_rules(&self.rules)?;
+        self.run_model(Model::new(self.width, self.height, rules, Some(seed))?, seed)
+    }
+
+    fn run_model<T: TileKey>(&self, mut model: Model<T>, seed: u64) -> Result<BenchResult, WfcError> {
+        let stopwatch = Stopwatch::start();
+        let outcome = model.run();
+        let elapsed = stopwatch.elapsed();
+
+        let success = match outcome {
+            Ok(_) => true,
+            Err(WfcError::Contradiction) => false,
+            Err(e) => return Err(e),
+        };
+
+        Ok(BenchResult {
+            name: self.name,
+            seed,
+            success,
+            stats: *model.stats(),
+            timings: model.report().timings,
+            elapsed,
+        })
+    }
+}
+
+/// `trace::decision_hash` of `Workload::contradiction_heavy(12)` run with `CANONICAL_SEED`.
+/// The workload backtracks, so the hash covers every kind of decision. Native and wasm
+/// builds of the same `ALGORITHM_VERSION` must return the same value.
+pub fn canonical_decision_hash() -> Result<String, WfcError> {
+    let workload = Workload::contradiction_heavy(12);
+    let mut model = Model::new(workload.width, workload.height, workload.rules, Some(CANONICAL_SEED))?;
+    decision_hash(&mut model)
+}
+
+fn add_both_ways(rules: &mut RuleSet, a: &str, b: &str) {
+    for dir in DIRECTIONS {
+        rules.add_adjacency(a.to_string(), b.to_string(), dir);
+        rules.add_adjacency(b.to_string(), a.to_string(), dir);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_workloads_report_counters() {
+        let result = Workload::large_grid(8).run(7).unwrap();
+        assert!(result.success);
+        assert_eq!(result.stats.observations, 64);
+        assert!(result.stats.propagation_ops > 0);
+        assert!(result.stats.entropy_evaluations > 0);
+
+        let result = Workload::many_tiles(4).run(7).unwrap();
+        assert!(result.success);
+
+        let result = Workload::contradiction_heavy(12).run(1).unwrap();
+        assert!(result.stats.backtracks > 0);
+    }
+
+    #[test]
+    fn test_random_rules_are_solvable() {
+        let sparse = RandomRules { tile_count: 12, density: 0.1, weight_spread: 5 };
+        let dense = RandomRules { density: 0.9, ..sparse };
+        for seed in 0..4 {
+            let rules = random_rules(&sparse, seed).unwrap();
+            assert_eq!(rules.tiles.len(), 12);
+            assert!(rules.tiles.values().all(|info| (1..=5).contains(&info.weight)));
+            assert_eq!(rules.content_hash(), random_rules(&sparse, seed).unwrap().content_hash());
+
+            let rule_count = |rules: &RuleSet| rules.adjacency.values().map(|set| set.len()).sum::<usize>();
+            assert!(rule_count(&rules) < rule_count(&random_rules(&dense, seed).unwrap()));
+
+            let result = Workload::random(16, &sparse, seed).unwrap().run(seed).unwrap();
+            assert!(result.success, "seed {} should solve", seed);
+        }
+        assert!(matches!(random_rules(&RandomRules { tile_count: 0, ..sparse }, 1), Err(WfcError::NoTilesDefined)));
+
+        // Every tile survives, including at low densities where single patches are common
+        for tile_count in [2, 5, 12, 16, 30, 60] {
+            for density in [0.0, 0.05, 0.1, 0.3] {
+                for seed in 0..50 {
+                    let options = RandomRules { tile_count, density, weight_spread: 5 };
+                    assert_eq!(random_rules(&options, seed).unwrap().tiles.len(), tile_count, "{:?} seed {}", options, seed);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_canonical_decision_hash_is_pinned() {
+        let hash = canonical_decision_hash().unwrap();
+        assert_eq!(hash, canonical_decision_hash().unwrap());
+        // Changing this value means seeded runs changed: bump `model::ALGORITHM_VERSION`
+        assert_eq!(hash, "16e0d81a72b5ee56");
+    }
+}
@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use clap::Args;
+use rand::prelude::*;
+use serde::Serialize;
+use wfc_core::TileId;
+use wfc_core::error::WfcError;
+use wfc_core::intern::InternedModel;
+use wfc_core::model::{OnExhaustion, RunConfig, SolverStats};
+use wfc_core::parallel;
+use wfc_core::render::{render_colors, write_indexed_png};
+use wfc_core::ruleset::RuleSet;
+
+#[derive(Args)]
+pub struct BatchArgs {
+    /// Ruleset to generate from, as JSON, YAML (`.yaml`) or TOML (`.toml`)
+    #[arg(long)]
+    rules: PathBuf,
+    /// Number of grids to generate
+    #[arg(long, default_value_t = 10)]
+    count: usize,
+    /// Directory to write the grids and `index.json` into
+    #[arg(long)]
+    out: PathBuf,
+    #[arg(long, default_value_t = 32)]
+    width: usize,
+    #[arg(long, default_value_t = 32)]
+    height: usize,
+    /// Join the left and right edges, e.g. for a map that wraps east-west
+    #[arg(long)]
+    wrap_x: bool,
+    /// Join the top and bottom edges
+    #[arg(long)]
+    wrap_y: bool,
+    /// Give up on a search attempt after undoing this many decisions
+    #[arg(long)]
+    max_backtracks: Option<u64>,
+    /// Start a failed attempt over with a fresh seed up to this many times
+    #[arg(long, default_value_t = 0)]
+    max_restarts: u32,
+    /// Seed the per-run seeds are drawn from (random if omitted)
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Also render each grid as `<name>.png` with this many pixels per cell (0 to skip,
+    /// ignored with `--indexed`)
+    #[arg(long, default_value_t = 8)]
+    scale: usize,
+    /// Write images as indexed PNGs, one pixel per cell, with the palette order listed in
+    /// `index.json` so each pixel maps back to its tile ID
+    #[arg(long)]
+    indexed: bool,
+}
+
+/// `index.json`: one entry per run, for reviewing a whole batch at once
+#[derive(Serialize)]
+struct BatchIndex {
+    rules: String,
+    ruleset_hash: String,
+    width: usize,
+    height: usize,
+    /// Tile ID of each palette entry of the indexed images
+    #[serde(skip_serializing_if = "Option::is_none")]
+    palette: Option<Vec<TileId>>,
+    succeeded: usize,
+    failed: usize,
+    runs: Vec<RunEntry>,
+}
+
+#[derive(Serialize)]
+struct RunEntry {
+    run: usize,
+    seed: u64,
+    /// `ok`, or the error's code (see `WfcError::code_str`)
+    status: &'static str,
+    /// Manifest file holding the grid, for successful runs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    manifest: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    image: Option<String>,
+    stats: SolverStats,
+    elapsed_ms: f64,
+}
+
+pub fn run(args: BatchArgs) -> Result<(), WfcError> {
+    let rules = crate::load_rules(&args.rules)?;
+    std::fs::create_dir_all(&args.out)?;
+    let mut palette: Vec<TileId> = rules.get_all_tile_ids().into_iter().cloned().collect();
+    palette.sort();
+    let mut rng = match args.seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+
+    // Seeds are drawn up front so the batch does not depend on how runs are scheduled
+    let seeds: Vec<(usize, u64)> = (0..args.count).map(|run| (run, rng.gen())).collect();
+    let runs = parallel::map(&seeds, |&(run, seed)| generate(&args, &rules, &palette, run, seed))
+        .into_iter()
+        .collect::<Result<Vec<RunEntry>, WfcError>>()?;
+
+    let succeeded = runs.iter().filter(|entry| entry.status == "ok").count();
+    let index = BatchIndex {
+        rules: args.rules.display().to_string(),
+        ruleset_hash: rules.content_hash(),
+        width: args.width,
+        height: args.height,
+        palette: args.indexed.then_some(palette),
+        succeeded,
+        failed: runs.len() - succeeded,
+        runs,
+    };
+    let json = serde_json::to_string_pretty(&index)?;
+    std::fs::write(args.out.join("index.json"), json)?;
+
+    eprintln!("{} of {} runs succeeded, index written to {}", succeeded, args.count, args.out.join("index.json").display());
+    Ok(())
+}
+
+/// Run one grid of the batch, writing its manifest and image on success
+fn generate(args: &BatchArgs, rules: &RuleSet, palette: &[TileId], run: usize, seed: u64) -> Result<RunEntry, WfcError> {
+    let mut model = InternedModel::with_wrap(args.width, args.height, args.wrap_x, args.wrap_y, rules, Some(seed))?;
+    model.model_mut().set_run_config(RunConfig {
+        max_backtracks: args.max_backtracks,
+        max_restarts: args.max_restarts,
+        on_exhaustion: if args.max_restarts > 0 { OnExhaustion::Restart } else { OnExhaustion::Fail },
+        ..RunConfig::default()
+    });
+    let outcome = model.run();
+    let report = model.model().report();
+    let mut entry = RunEntry {
+        run,
+        seed,
+        status: "ok",
+        manifest: None,
+        image: None,
+        stats: report.stats,
+        elapsed_ms: report.timings.total().as_secs_f64() * 1000.0,
+    };
+
+    match outcome {
+        Ok(grid) => {
+            let name = format!("run_{:04}", run);
+            let manifest = format!("{}.json", name);
+            std::fs::write(args.out.join(&manifest), model.manifest()?.to_json_string()?)?;
+            entry.manifest = Some(manifest);
+            let image = format!("{}.png", name);
+            if args.indexed {
+                let file = std::io::BufWriter::new(std::fs::File::create(args.out.join(&image))?);
+                write_indexed_png(&grid, args.width, args.height, palette, &HashMap::new(), file)?;
+                entry.image = Some(image);
+            } else if args.scale > 0 {
+                render_colors(&grid, args.width, args.height, args.scale, &HashMap::new())
+                    .save_png(args.out.join(&image))?;
+                entry.image = Some(image);
+            }
+        }
+        Err(e) => entry.status = e.code_str(),
+    }
+    Ok(entry)
+}
@@ -0,0 +1,24 @@
+use std::path::PathBuf;
+use clap::Args;
+use wfc_core::error::WfcError;
+
+#[derive(Args)]
+pub struct DiffArgs {
+    /// Earlier version of the ruleset, as JSON, YAML (`.yaml`) or TOML (`.toml`)
+    old: PathBuf,
+    /// Later version of the ruleset, in any of the same formats
+    new: PathBuf,
+    /// Print the differences as JSON instead of one line per change
+    #[arg(long)]
+    json: bool,
+}
+
+pub fn run(args: DiffArgs) -> Result<(), WfcError> {
+    let diff = crate::load_rules(&args.old)?.diff(&crate::load_rules(&args.new)?);
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&diff)?);
+    } else {
+        print!("{}", diff);
+    }
+    Ok(())
+}
@@ -0,0 +1,117 @@
+use std::path::{Path, PathBuf};
+use clap::Args;
+use wfc_core::error::WfcError;
+use wfc_core::image::Image;
+use wfc_core::learn::{learn_from_image_with, LearnOptions};
+use wfc_core::ruleset::RuleSet;
+use wfc_core::ruleset::JsonOptions;
+
+#[derive(Args)]
+pub struct LearnArgs {
+    /// Sample PNG to learn from, or a Tiled `.tmx` map when built with the `tiled` feature.
+    /// Maps saved as JSON need to be saved as TMX with CSV layer data first.
+    sample: PathBuf,
+    /// Tile layer of a Tiled map to learn from (the first one if omitted); maps only
+    #[arg(long)]
+    layer: Option<String>,
+    /// Edge length of a tile in pixels; images only
+    #[arg(long, default_value_t = 1)]
+    tile_size: usize,
+    /// Number of rotations/reflections of the sample to learn from (1-8); images only
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u8).range(1..=8))]
+    symmetry: u8,
+    /// Merge tiles that are rotations/reflections of each other into one tile; images only
+    #[arg(long)]
+    merge_rotations: bool,
+    /// Treat the sample as tiling seamlessly, learning adjacency across its edges
+    #[arg(long)]
+    periodic: bool,
+    /// Weight tiles by how often they occur in the sample instead of equally
+    #[arg(long)]
+    frequency_weights: bool,
+    /// Write one rule per tile and direction, listing every allowed neighbor; JSON only
+    #[arg(long)]
+    grouped: bool,
+    /// Indent the ruleset JSON for reading and diffing; JSON only
+    #[arg(long)]
+    pretty: bool,
+    /// Where to write the ruleset (stdout if omitted); YAML for `.yaml`, TOML for `.toml`
+    /// and JSON otherwise
+    #[arg(long)]
+    out: Option<PathBuf>,
+    /// Optional directory to write each learned tile as `<id>.png`; images only
+    #[arg(long)]
+    tiles_dir: Option<PathBuf>,
+}
+
+pub fn run(args: LearnArgs) -> Result<(), WfcError> {
+    let format = args.out.as_deref().and_then(Path::extension).and_then(|extension| extension.to_str());
+    if matches!(format, Some("yaml" | "yml" | "toml")) {
+        reject(args.grouped, "--grouped", "YAML or TOML output")?;
+        reject(args.pretty, "--pretty", "YAML or TOML output")?;
+    }
+
+    let options = LearnOptions {
+        symmetry: args.symmetry as usize,
+        merge_rotations: args.merge_rotations,
+        periodic: args.periodic,
+        frequency_weights: args.frequency_weights,
+    };
+    let (rules, tiles) = match args.sample.extension().and_then(|extension| extension.to_str()) {
+        Some("tmx") => {
+            // Maps are made of whole tiles and carry no artwork
+            reject(args.tile_size != 1, "--tile-size", "Tiled maps")?;
+            reject(args.symmetry != 1, "--symmetry", "Tiled maps")?;
+            reject(args.merge_rotations, "--merge-rotations", "Tiled maps")?;
+            reject(args.tiles_dir.is_some(), "--tiles-dir", "Tiled maps")?;
+            (learn_from_map(&args.sample, args.layer.as_deref(), &options)?, Vec::new())
+        }
+        _ => {
+            reject(args.layer.is_some(), "--layer", "images")?;
+            let learned = learn_from_image_with(&Image::load_png(&args.sample)?, args.tile_size, &options)?;
+            (learned.rules, learned.tiles)
+        }
+    };
+    let json = || rules.to_json_string_with(&JsonOptions { grouped: args.grouped, pretty: args.pretty });
+
+    match &args.out {
+        Some(path) => {
+            let text = match format {
+                Some("yaml" | "yml") => rules.to_yaml_string()?,
+                Some("toml") => rules.to_toml_string()?,
+                _ => json()?,
+            };
+            std::fs::write(path, text)?
+        }
+        None => println!("{}", json()?),
+    }
+
+    if let Some(dir) = &args.tiles_dir {
+        std::fs::create_dir_all(dir)?;
+        for (id, tile) in &tiles {
+            tile.save_png(dir.join(format!("{}.png", id)))?;
+        }
+    }
+
+    eprintln!("learned {} tiles from {}", rules.tiles.len(), args.sample.display());
+    Ok(())
+}
+
+/// `Unsupported` if `given`, naming the option and the input it does not apply to
+fn reject(given: bool, option: &str, input: &str) -> Result<(), WfcError> {
+    if given {
+        return Err(WfcError::Unsupported(format!("{} with {}", option, input)));
+    }
+    Ok(())
+}
+
+/// Rules from the tiles touching in a Tiled map; `periodic` and `frequency_weights` apply
+#[cfg(feature = "tiled")]
+fn learn_from_map(path: &Path, layer: Option<&str>, options: &LearnOptions) -> Result<RuleSet, WfcError> {
+    wfc_core::interop::from_tiled_tmx_with(&std::fs::read_to_string(path)?, layer, options)
+}
+
+#[cfg(not(feature = "tiled"))]
+fn learn_from_map(_path: &Path, _layer: Option<&str>, _options: &LearnOptions) -> Result<RuleSet, WfcError> {
+    Err(WfcError::Import("learning from Tiled maps needs the `tiled` feature".to_string()))
+}
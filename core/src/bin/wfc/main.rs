@@ -0,0 +1,57 @@
+use std::path::Path;
+use clap::{Parser, Subcommand};
+use wfc_core::error::WfcError;
+use wfc_core::ruleset::RuleSet;
+
+mod batch;
+mod diff;
+mod learn;
+#[cfg(feature = "serve")]
+mod serve;
+
+#[derive(Parser)]
+#[command(name = "wfc", version, about = "Wave Function Collapse tooling")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Extract a ruleset from a sample image or Tiled map
+    Learn(learn::LearnArgs),
+    /// Generate many grids from one ruleset, with an index of every run
+    Batch(batch::BatchArgs),
+    /// List the tiles and rules that changed between two versions of a ruleset
+    Diff(diff::DiffArgs),
+    /// Serve generation requests over HTTP
+    #[cfg(feature = "serve")]
+    Serve(serve::ServeArgs),
+}
+
+/// Load a ruleset file: YAML for `.yaml`/`.yml`, TOML for `.toml` and JSON otherwise
+fn load_rules(path: &Path) -> Result<RuleSet, WfcError> {
+    let text = std::fs::read_to_string(path)?;
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("yaml" | "yml") => RuleSet::from_yaml(&text),
+        Some("toml") => RuleSet::from_toml(&text),
+        _ => RuleSet::from_json(&text),
+    }
+}
+
+fn main() {
+    let cli = Cli::parse();
+
+    let result: Result<(), WfcError> = match cli.command {
+        Command::Learn(args) => learn::run(args),
+        Command::Batch(args) => batch::run(args),
+        Command::Diff(args) => diff::run(args),
+        #[cfg(feature = "serve")]
+        Command::Serve(args) => serve::run(args),
+    };
+
+    if let Err(e) = result {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+use std::io::Read;
+use std::sync::Arc;
+use clap::Args;
+use serde::{Deserialize, Serialize};
+use tiny_http::{Header, Method, Request, Response, Server};
+use wfc_core::TileId;
+use wfc_core::error::WfcError;
+use wfc_core::intern::InternedModel;
+use wfc_core::model::RunConfig;
+use wfc_core::render::render_colors;
+use wfc_core::ruleset::RuleSet;
+
+#[derive(Args)]
+pub struct ServeArgs {
+    /// Address to listen on
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    addr: String,
+    /// Milliseconds a request may spend generating before it fails with `budget_exceeded`
+    /// (0 for no limit)
+    #[arg(long, default_value_t = 10_000)]
+    max_millis: u64,
+    /// Requests handled at once; further requests wait for a free worker
+    #[arg(long, default_value_t = 4)]
+    workers: usize,
+    /// Largest request body accepted, in bytes
+    #[arg(long, default_value_t = 1 << 20)]
+    max_body_bytes: usize,
+}
+
+/// Largest `scale` a PNG request may ask for
+const MAX_SCALE: usize = 64;
+/// Largest rendered PNG, in pixels
+const MAX_PIXELS: usize = 4096 * 4096;
+
+#[derive(Deserialize)]
+struct GenerateRequest {
+    /// Ruleset in the same shape `RuleSet::from_json` accepts
+    rules: serde_json::Value,
+    width: usize,
+    height: usize,
+    seed: Option<u64>,
+    /// Join the left and right edges
+    #[serde(default)]
+    wrap_x: bool,
+    /// Join the top and bottom edges
+    #[serde(default)]
+    wrap_y: bool,
+    #[serde(default)]
+    format: OutputFormat,
+    /// Pixel size of a cell when rendering PNG output
+    #[serde(default = "default_scale")]
+    scale: usize,
+}
+
+#[derive(Deserialize, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum OutputFormat {
+    #[default]
+    Json,
+    Png,
+}
+
+fn default_scale() -> usize {
+    8
+}
+
+#[derive(Serialize)]
+struct GenerateResponse {
+    width: usize,
+    height: usize,
+    seed: u64,
+    grid: Vec<TileId>,
+}
+
+#[derive(Serialize)]
+struct ErrorResponse<'a> {
+    code: &'a str,
+    error: String,
+}
+
+pub fn run(args: ServeArgs) -> Result<(), WfcError> {
+    let server = Server::http(&args.addr).map_err(|e| WfcError::Io(std::io::Error::other(e)))?;
+    eprintln!("listening on http://{}", args.addr);
+
+    // A fixed pool of workers takes requests off the shared server; each request gets its
+    // own solver, and models share nothing.
+    let budget = RunConfig { max_millis: (args.max_millis > 0).then_some(args.max_millis), ..RunConfig::default() };
+    let server = Arc::new(server);
+    let max_body_bytes = args.max_body_bytes;
+    let workers: Vec<_> = (0..args.workers.max(1))
+        .map(|_| {
+            let server = Arc::clone(&server);
+            std::thread::spawn(move || {
+                for request in server.incoming_requests() {
+                    handle(request, budget, max_body_bytes);
+                }
+            })
+        })
+        .collect();
+    for worker in workers {
+        let _ = worker.join();
+    }
+    Ok(())
+}
+
+fn handle(mut request: Request, budget: RunConfig, max_body_bytes: usize) {
+    let response = match (request.method(), request.url()) {
+        (Method::Get, "/health") => Response::from_string("ok").boxed(),
+        (Method::Post, "/generate") if request.body_length().is_some_and(|len| len > max_body_bytes) => {
+            error_response(413, "body_too_large", &format!("request body is over {} bytes", max_body_bytes))
+        }
+        (Method::Post, "/generate") => {
+            // Chunked bodies carry no length up front, so stop reading one byte past the cap
+            let mut body = String::new();
+            match request.as_reader().take(max_body_bytes as u64 + 1).read_to_string(&mut body) {
+                Ok(len) if len > max_body_bytes => {
+                    error_response(413, "body_too_large", &format!("request body is over {} bytes", max_body_bytes))
+                }
+                Ok(_) => generate(&body, budget),
+                Err(e) => error_response(400, "invalid_request", &e.to_string()),
+            }
+        }
+        _ => error_response(404, "not_found", "not found"),
+    };
+    let _ = request.respond(response);
+}
+
+fn generate(body: &str, budget: RunConfig) -> Response<Box<dyn Read + Send>> {
+    let req: GenerateRequest = match serde_json::from_str(body) {
+        Ok(req) => req,
+        Err(e) => return error_response(400, "invalid_request", &e.to_string()),
+    };
+    if req.format == OutputFormat::Png {
+        if req.scale == 0 || req.scale > MAX_SCALE {
+            return error_response(400, "invalid_request", &format!("scale must be between 1 and {}", MAX_SCALE));
+        }
+        let pixels = req.width.checked_mul(req.height)
+            .and_then(|cells| cells.checked_mul(req.scale * req.scale));
+        if pixels.is_none_or(|pixels| pixels > MAX_PIXELS) {
+            return error_response(400, "invalid_request", &format!("image would be over {} pixels", MAX_PIXELS));
+        }
+    }
+    let seed = req.seed.unwrap_or_else(rand::random);
+
+    let result = RuleSet::from_json(&req.rules.to_string())
+        .and_then(|rules| InternedModel::with_wrap(req.width, req.height, req.wrap_x, req.wrap_y, &rules, Some(seed)))
+        .and_then(|mut model| {
+            model.model_mut().set_run_config(budget);
+            model.run()
+        });
+
+    let grid = match result {
+        Ok(grid) => grid,
+        Err(e @ (WfcError::Contradiction | WfcError::BacktrackLimitExceeded { .. } | WfcError::BudgetExceeded { .. })) => return wfc_error_response(422, &e),
+        Err(e) => return wfc_error_response(400, &e),
+    };
+
+    if req.format == OutputFormat::Png {
+        let image = render_colors(&grid, req.width, req.height, req.scale, &HashMap::new());
+        let mut bytes = Vec::new();
+        if let Err(e) = image.write_png(&mut bytes) {
+            return wfc_error_response(500, &e);
+        }
+        return Response::from_data(bytes)
+            .with_header(header("Content-Type", "image/png"))
+            .with_header(header("X-Wfc-Seed", &seed.to_string()))
+            .boxed();
+    }
+
+    let json = serde_json::to_string(&GenerateResponse {
+        width: req.width,
+        height: req.height,
+        seed,
+        grid,
+    }).unwrap_or_default();
+    Response::from_string(json)
+        .with_header(header("Content-Type", "application/json"))
+        .boxed()
+}
+
+fn wfc_error_response(status: u16, error: &WfcError) -> Response<Box<dyn Read + Send>> {
+    error_response(status, error.code_str(), &error.to_string())
+}
+
+fn error_response(status: u16, code: &str, message: &str) -> Response<Box<dyn Read + Send>> {
+    let json = serde_json::to_string(&ErrorResponse { code, error: message.to_string() }).unwrap_or_default();
+    Response::from_string(json)
+        .with_status_code(status)
+        .with_header(header("Content-Type", "application/json"))
+        .boxed()
+}
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name.as_bytes(), value.as_bytes()).expect("static header is valid")
+}
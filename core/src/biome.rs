@@ -0,0 +1,231 @@
+//! Maps made of several biomes, each generated from its own ruleset.
+//!
+//! One flat ruleset covering every biome needs rules for every pair of tiles that may meet,
+//! and quickly becomes unmanageable. A `Composition` instead keeps one ruleset per biome,
+//! assigns each cell to a biome, and lets a small set of transition tiles (with their own
+//! rules towards the biome tiles) bridge the borders. Cells only ever hold tiles of their
+//! own biome, plus transition tiles within `margin` cells of a border.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use crate::TileId;
+use crate::constraints::Rect;
+use crate::error::WfcError;
+use crate::model::Model;
+use crate::ruleset::{MissingAdjacency, RuleSet};
+
+#[derive(Debug, Clone)]
+pub struct Composition {
+    width: usize,
+    height: usize,
+    biomes: Vec<(String, RuleSet)>,
+    transitions: RuleSet,
+    /// Biome index of every cell, row-major
+    labels: Vec<usize>,
+    margin: usize,
+}
+
+impl Composition {
+    /// An empty composition; the first biome added covers the whole grid until others are
+    /// painted over it
+    pub fn new(width: usize, height: usize) -> Composition {
+        Composition {
+            width,
+            height,
+            biomes: Vec::new(),
+            transitions: RuleSet::new(),
+            labels: vec![0; width * height],
+            margin: 1,
+        }
+    }
+
+    /// Add a biome generated from `rules`. Biomes must use `MissingAdjacency::Closed`, or
+    /// their tiles would be free to sit next to any other biome's.
+    pub fn add_biome(&mut self, name: &str, rules: RuleSet) -> Result<(), WfcError> {
+        if self.biomes.iter().any(|(existing, _)| existing == name) {
+            return Err(WfcError::Import(format!("duplicate biome {:?}", name)));
+        }
+        if rules.missing_adjacency == MissingAdjacency::Open {
+            return Err(WfcError::Import(format!("biome {:?} uses open adjacency", name)));
+        }
+        self.biomes.push((name.to_string(), rules));
+        Ok(())
+    }
+
+    /// Tiles that may appear where biomes meet, together with the rules joining them (and
+    /// possibly biome tiles directly) across borders
+    pub fn set_transitions(&mut self, rules: RuleSet) {
+        self.transitions = rules;
+    }
+
+    /// How many cells on each side of a border may hold transition tiles; 1 by default,
+    /// 0 disables them
+    pub fn set_margin(&mut self, margin: usize) {
+        self.margin = margin;
+    }
+
+    /// Assign the cells of `rect` (clipped to the grid) to `biome`
+    pub fn paint(&mut self, biome: &str, rect: Rect) -> Result<(), WfcError> {
+        let mask: Vec<bool> = (0..self.width * self.height)
+            .map(|i| rect.contains(i % self.width, i / self.width))
+            .collect();
+        self.paint_mask(biome, &mask)
+    }
+
+    /// Assign every cell whose entry in the row-major `mask` is true to `biome`, e.g. from
+    /// a noise map or a hand-drawn guide image
+    pub fn paint_mask(&mut self, biome: &str, mask: &[bool]) -> Result<(), WfcError> {
+        if mask.len() != self.labels.len() {
+            return Err(WfcError::InvalidDimensions { width: self.width, height: self.height });
+        }
+        let index = self.biome_index(biome)?;
+        for (label, _) in self.labels.iter_mut().zip(mask).filter(|(_, &set)| set) {
+            *label = index;
+        }
+        Ok(())
+    }
+
+    /// Name of the biome covering `(x, y)`
+    pub fn biome_at(&self, x: usize, y: usize) -> Option<&str> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        self.biomes.get(self.labels[y * self.width + x]).map(|(name, _)| name.as_str())
+    }
+
+    /// Every biome's tiles and rules plus the transitions, in one ruleset. A tile defined
+    /// by several biomes keeps the first definition; constraints and atlases are not merged.
+    pub fn rules(&self) -> RuleSet {
+        let mut merged = RuleSet::new();
+        for rules in self.biomes.iter().map(|(_, rules)| rules).chain([&self.transitions]) {
+            for (id, info) in &rules.tiles {
+                merged.tiles.entry(id.clone()).or_insert_with(|| info.clone());
+            }
+            for (key, set) in &rules.adjacency {
+                merged.adjacency.entry(key.clone()).or_default().extend(set.iter().cloned());
+            }
+            for (alias, canonical) in &rules.aliases {
+                merged.aliases.entry(alias.clone()).or_insert_with(|| canonical.clone());
+            }
+        }
+        merged
+    }
+
+    /// A model over the merged rules with every cell already restricted to its biome's
+    /// tiles, and transition tiles near borders. Fails with `Contradiction` if some border
+    /// cannot be bridged.
+    pub fn model(&self, seed: Option<u64>) -> Result<Model, WfcError> {
+        if self.biomes.is_empty() {
+            return Err(WfcError::NoTilesDefined);
+        }
+        let mut model = Model::new(self.width, self.height, self.rules(), seed)?;
+
+        // Each biome is two regions of the map: its interior, and the cells near a border
+        // which may also hold transition tiles
+        let transition_tiles: HashSet<TileId> = self.transitions.tiles.keys().cloned().collect();
+        let mut regions = HashMap::new();
+        for (biome, (_, rules)) in self.biomes.iter().enumerate() {
+            let own: HashSet<TileId> = rules.tiles.keys().cloned().collect();
+            regions.insert(2 * biome + 1, own.union(&transition_tiles).cloned().collect());
+            regions.insert(2 * biome, own);
+        }
+        let labels: Vec<usize> = self.labels
+            .iter()
+            .zip(self.near_border())
+            .map(|(&biome, near)| 2 * biome + near as usize)
+            .collect();
+        model.apply_region_map(&labels, &regions)?;
+        Ok(model)
+    }
+
+    fn biome_index(&self, name: &str) -> Result<usize, WfcError> {
+        self.biomes
+            .iter()
+            .position(|(existing, _)| existing == name)
+            .ok_or_else(|| WfcError::Import(format!("unknown biome {:?}", name)))
+    }
+
+    /// Cells less than `margin` steps from a cell of another biome
+    fn near_border(&self) -> Vec<bool> {
+        let mut near = vec![false; self.labels.len()];
+        if self.margin == 0 {
+            return near;
+        }
+        let mut queue = VecDeque::new();
+        for (index, near) in near.iter_mut().enumerate() {
+            if self.neighbors(index).any(|n| self.labels[n] != self.labels[index]) {
+                *near = true;
+                queue.push_back((index, 1));
+            }
+        }
+        while let Some((index, distance)) = queue.pop_front() {
+            if distance == self.margin {
+                continue;
+            }
+            for n in self.neighbors(index) {
+                if !near[n] && self.labels[n] == self.labels[index] {
+                    near[n] = true;
+                    queue.push_back((n, distance + 1));
+                }
+            }
+        }
+        near
+    }
+
+    fn neighbors(&self, index: usize) -> impl Iterator<Item = usize> {
+        let (x, y, width, height) = (index % self.width, index / self.width, self.width, self.height);
+        [
+            (y > 0).then(|| index - width),
+            (x + 1 < width).then(|| index + 1),
+            (y + 1 < height).then(|| index + width),
+            (x > 0).then(|| index - 1),
+        ]
+        .into_iter()
+        .flatten()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Direction;
+
+    fn single_tile(id: &str) -> RuleSet {
+        let mut rules = RuleSet::new();
+        rules.add_tile(id.to_string(), 1);
+        for dir in [Direction::Up, Direction::Right, Direction::Down, Direction::Left] {
+            rules.add_adjacency(id.to_string(), id.to_string(), dir);
+        }
+        rules
+    }
+
+    #[test]
+    fn test_biomes_meet_through_transitions() {
+        let mut composition = Composition::new(10, 4);
+        composition.add_biome("sea", single_tile("water")).unwrap();
+        composition.add_biome("land", single_tile("grass")).unwrap();
+        composition.paint("land", Rect { x: 5, y: 0, width: 5, height: 4 }).unwrap();
+        assert_eq!(composition.biome_at(4, 0), Some("sea"));
+        assert_eq!(composition.biome_at(5, 0), Some("land"));
+
+        // Water and grass have no rules between them, so the border cannot be solved yet
+        assert!(matches!(composition.model(Some(1)), Err(WfcError::Contradiction)));
+
+        let mut transitions = single_tile("sand");
+        for other in ["water", "grass"] {
+            for dir in [Direction::Up, Direction::Right, Direction::Down, Direction::Left] {
+                transitions.add_adjacency_symmetric("sand".to_string(), other.to_string(), dir);
+            }
+        }
+        composition.set_transitions(transitions);
+        let grid = composition.model(Some(1)).unwrap().run().unwrap();
+        for row in grid.chunks(10) {
+            assert!(row[..4].iter().all(|tile| tile == "water"));
+            assert!(row[6..].iter().all(|tile| tile == "grass"));
+            assert!(row[4] == "water" || row[4] == "sand");
+            assert!(row[5] == "grass" || row[5] == "sand");
+            assert!(row[4] == "sand" || row[5] == "sand", "only sand can separate water from grass");
+        }
+
+        assert!(matches!(composition.paint("desert", Rect { x: 0, y: 0, width: 1, height: 1 }), Err(WfcError::Import(_))));
+    }
+}
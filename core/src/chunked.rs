@@ -0,0 +1,204 @@
+//! Unbounded worlds generated one chunk at a time.
+//!
+//! `ChunkedGenerator` splits an endless plane into fixed-size chunks addressed by signed
+//! chunk coordinates and solves each one the first time it is asked for. The edge cells
+//! of a new chunk are limited to tiles compatible with the touching edges of any chunks
+//! already generated next to it, so seams obey the rules just like the inside of a chunk.
+//! A chunk's seed is derived from the generator's seed and the chunk's coordinates, but
+//! its content also depends on which neighbors existed when it was made: the same seed
+//! and the same order of requests always give the same world.
+//!
+//! Ruleset constraints describe one finite grid: borders, masks and tile counts have no
+//! meaning on an endless plane, and applied per chunk they would ring or repeat in every
+//! chunk. Rulesets with any are rejected rather than silently solved without them.
+
+use std::collections::{HashMap, HashSet};
+use crate::{Direction, TileId, TileKey, DIRECTIONS};
+use crate::error::WfcError;
+use crate::model::{splitmix64, Model};
+use crate::ruleset::RuleSet;
+
+#[derive(Debug, Clone)]
+pub struct ChunkedGenerator<T = TileId> {
+    rules: RuleSet<T>,
+    chunk_width: usize,
+    chunk_height: usize,
+    seed: u64,
+    attempts: u32,
+    /// Generated chunks, row-major like `Model::result`, by chunk coordinates
+    chunks: HashMap<(i64, i64), Vec<T>>,
+}
+
+impl<T: TileKey> ChunkedGenerator<T> {
+    /// Fails with `Unsupported` if `rules` have constraints (see the module docs)
+    pub fn new(chunk_width: usize, chunk_height: usize, rules: RuleSet<T>, seed: u64) -> Result<ChunkedGenerator<T>, WfcError> {
+        if chunk_width == 0 || chunk_height == 0 {
+            return Err(WfcError::InvalidDimensions { width: chunk_width, height: chunk_height });
+        }
+        if rules.tiles.is_empty() {
+            return Err(WfcError::NoTilesDefined);
+        }
+        if !rules.constraints.is_empty() {
+            return Err(WfcError::Unsupported("ruleset constraints in an endless chunked world".to_string()));
+        }
+        Ok(ChunkedGenerator { rules, chunk_width, chunk_height, seed, attempts: 8, chunks: HashMap::new() })
+    }
+
+    /// How many seeds to try on a chunk before giving up (see `Model::run_with_retries`);
+    /// 8 by default. Chunks hemmed in on several sides may need more.
+    pub fn set_attempts(&mut self, attempts: u32) {
+        self.attempts = attempts.max(1);
+    }
+
+    pub fn chunk_size(&self) -> (usize, usize) {
+        (self.chunk_width, self.chunk_height)
+    }
+
+    pub fn rules(&self) -> &RuleSet<T> {
+        &self.rules
+    }
+
+    /// The chunk at `(cx, cy)`, generating it first if needed. Fails with `Contradiction`
+    /// if no attempt fits the chunks around it, in which case nothing is stored.
+    pub fn chunk(&mut self, cx: i64, cy: i64) -> Result<&[T], WfcError> {
+        if !self.chunks.contains_key(&(cx, cy)) {
+            let grid = self.generate(cx, cy)?;
+            self.chunks.insert((cx, cy), grid);
+        }
+        Ok(&self.chunks[&(cx, cy)])
+    }
+
+    /// The chunk at `(cx, cy)` if it has been generated
+    pub fn get(&self, cx: i64, cy: i64) -> Option<&[T]> {
+        self.chunks.get(&(cx, cy)).map(Vec::as_slice)
+    }
+
+    /// The tile at world position `(x, y)`, generating its chunk if needed
+    pub fn tile(&mut self, x: i64, y: i64) -> Result<&T, WfcError> {
+        let (w, h) = (self.chunk_width as i64, self.chunk_height as i64);
+        let (lx, ly) = (x.rem_euclid(w) as usize, y.rem_euclid(h) as usize);
+        let width = self.chunk_width;
+        Ok(&self.chunk(x.div_euclid(w), y.div_euclid(h))?[ly * width + lx])
+    }
+
+    /// Coordinates of every generated chunk, sorted
+    pub fn generated(&self) -> Vec<(i64, i64)> {
+        let mut coords: Vec<(i64, i64)> = self.chunks.keys().copied().collect();
+        coords.sort();
+        coords
+    }
+
+    /// Forget a chunk, e.g. to unload it; it is returned so it can be saved. Generating it
+    /// again gives a different chunk that still fits its neighbors.
+    pub fn remove(&mut self, cx: i64, cy: i64) -> Option<Vec<T>> {
+        self.chunks.remove(&(cx, cy))
+    }
+
+    /// Put back a chunk saved from `remove`, or any grid of the chunk size. It is taken as
+    /// given; chunks generated next to it will fit it.
+    pub fn insert(&mut self, cx: i64, cy: i64, grid: Vec<T>) -> Result<(), WfcError> {
+        if grid.len() != self.chunk_width * self.chunk_height {
+            return Err(WfcError::InvalidDimensions { width: self.chunk_width, height: self.chunk_height });
+        }
+        if let Some(unknown) = grid.iter().find(|tile| self.rules.get_tile_info(tile).is_none()) {
+            return Err(WfcError::InvalidTileId(unknown.to_string()));
+        }
+        self.chunks.insert((cx, cy), grid);
+        Ok(())
+    }
+
+    /// Seed of the chunk at `(cx, cy)`
+    fn chunk_seed(&self, cx: i64, cy: i64) -> u64 {
+        splitmix64(splitmix64(self.seed, cx as u64), cy as u64)
+    }
+
+    fn generate(&self, cx: i64, cy: i64) -> Result<Vec<T>, WfcError> {
+        let (width, height) = (self.chunk_width, self.chunk_height);
+        let mut model = Model::new(width, height, self.rules.clone(), Some(self.chunk_seed(cx, cy)))?;
+        for direction in DIRECTIONS {
+            let (dx, dy) = match direction {
+                Direction::Up => (0, -1),
+                Direction::Right => (1, 0),
+                Direction::Down => (0, 1),
+                _ => (-1, 0),
+            };
+            let Some(neighbor) = self.chunks.get(&(cx + dx, cy + dy)) else {
+                continue;
+            };
+            // Pair each edge cell of the new chunk with the touching cell of the neighbor
+            let pairs: Vec<(usize, usize)> = match direction {
+                Direction::Up => (0..width).map(|x| (x, (height - 1) * width + x)).collect(),
+                Direction::Down => (0..width).map(|x| ((height - 1) * width + x, x)).collect(),
+                Direction::Left => (0..height).map(|y| (y * width, y * width + width - 1)).collect(),
+                _ => (0..height).map(|y| (y * width + width - 1, y * width)).collect(),
+            };
+            for (index, touching) in pairs {
+                let fixed = &neighbor[touching];
+                let allowed: HashSet<T> = self.rules.get_all_tile_ids()
+                    .into_iter()
+                    .filter(|id| self.rules.is_compatible(fixed, id, direction.opposite()))
+                    .cloned()
+                    .collect();
+                model.restrict_cell(index, &allowed)?;
+            }
+        }
+        Ok(model.run_with_retries(self.attempts)?.grid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bench::Workload;
+    use crate::constraints::{BorderConstraint, CountConstraint};
+
+    #[test]
+    fn test_chunks_agree_across_seams() {
+        let rules = Workload::large_grid(8).rules;
+        let mut world = ChunkedGenerator::new(6, 5, rules.clone(), 11).unwrap();
+        // Out of order, so chunks get neighbors on every side
+        for (cx, cy) in [(0, 0), (2, 0), (1, 1), (1, -1), (1, 0), (-1, 0), (0, 1)] {
+            world.chunk(cx, cy).unwrap();
+        }
+        assert_eq!(world.generated().len(), 7);
+
+        // Stitched together, the 4x3 block of chunks around the origin obeys the rules
+        let (w, h) = (4 * 6, 3 * 5);
+        let mut grid = Vec::with_capacity(w * h);
+        for y in -5..10 {
+            for x in -6..18 {
+                grid.push(world.tile(x, y).unwrap().clone());
+            }
+        }
+        assert_eq!(world.generated().len(), 12);
+        let checker = Model::new(w, h, rules.clone(), None).unwrap();
+        assert!(checker.verify_result(&grid).unwrap().is_empty());
+        let corner = world.tile(-1, -1).unwrap().clone();
+        assert_eq!(corner, world.get(-1, -1).unwrap()[4 * 6 + 5]);
+
+        // Same seed and order, same world
+        let mut again = ChunkedGenerator::new(6, 5, rules.clone(), 11).unwrap();
+        for (cx, cy) in [(0, 0), (2, 0), (1, 1)] {
+            assert_eq!(again.chunk(cx, cy).unwrap(), world.get(cx, cy).unwrap());
+        }
+
+        // A saved chunk comes back as it was, and new chunks fit against it
+        let saved = world.remove(0, 0).unwrap();
+        assert!(world.get(0, 0).is_none());
+        world.insert(0, 0, saved.clone()).unwrap();
+        assert_eq!(world.chunk(0, 0).unwrap(), &saved[..]);
+        assert!(matches!(world.insert(5, 5, vec!["sand".to_string()]), Err(WfcError::InvalidDimensions { .. })));
+        assert!(ChunkedGenerator::new(0, 4, rules, 1).is_err());
+    }
+
+    #[test]
+    fn test_constraints_are_rejected() {
+        let mut rules = Workload::large_grid(8).rules;
+        rules.constraints.borders.push(BorderConstraint { edges: Vec::new(), tiles: vec!["water".to_string()] });
+        assert!(matches!(ChunkedGenerator::new(6, 5, rules.clone(), 1), Err(WfcError::Unsupported(_))));
+
+        rules.constraints = Default::default();
+        rules.constraints.counts.push(CountConstraint { tile: "sand".to_string(), min: 1, max: None });
+        assert!(matches!(ChunkedGenerator::new(6, 5, rules, 1), Err(WfcError::Unsupported(_))));
+    }
+}
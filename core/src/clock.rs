@@ -0,0 +1,33 @@
+use std::time::Duration;
+
+/// Elapsed-time measurement that also works on wasm32-unknown-unknown,
+/// where `std::time::Instant::now()` panics.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Stopwatch {
+    #[cfg(not(target_arch = "wasm32"))]
+    start: std::time::Instant,
+    #[cfg(target_arch = "wasm32")]
+    start_ms: f64,
+}
+
+impl Stopwatch {
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn start() -> Stopwatch {
+        Stopwatch { start: std::time::Instant::now() }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub(crate) fn start() -> Stopwatch {
+        Stopwatch { start_ms: js_sys::Date::now() }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub(crate) fn elapsed(&self) -> Duration {
+        Duration::from_secs_f64((js_sys::Date::now() - self.start_ms).max(0.0) / 1000.0)
+    }
+}
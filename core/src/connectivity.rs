@@ -0,0 +1,127 @@
+//! Constraints on the wave as a whole.
+//!
+//! Adjacency rules and `constraints::Constraints` are checked cell by cell; some properties
+//! only exist across the whole grid, such as every floor tile of a dungeon being reachable
+//! from every other. A `WaveConstraint` looks at the entire wave after each propagation and
+//! reports whether it can still be completed into a grid that satisfies it. When it
+//! cannot, the solver treats that as a contradiction and backtracks, so the constraint is
+//! repaired during generation instead of rejected afterwards. New constraints only need to
+//! implement `WaveConstraint` and be passed to `Model::add_wave_constraint`; `Connected` is
+//! the built-in one.
+
+use std::collections::HashSet;
+use std::fmt;
+use crate::TileKey;
+use crate::model::Cell;
+use crate::ruleset::RuleSet;
+use crate::topology::Topology;
+
+/// What a wave constraint sees of the solver: the cell layout and each cell's remaining tiles
+pub struct WaveView<'a, T> {
+    pub(crate) topology: &'a dyn Topology,
+    pub(crate) cells: &'a [Cell<T>],
+}
+
+impl<T> WaveView<'_, T> {
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    /// Tiles still possible in a cell; empty for cells left to a `fallback` wildcard
+    pub fn possibilities(&self, index: usize) -> &HashSet<T> {
+        &self.cells[index].possibilities
+    }
+
+    pub fn is_collapsed(&self, index: usize) -> bool {
+        self.cells[index].collapsed
+    }
+
+    /// Indices of the cells next to `index`
+    pub fn neighbors(&self, index: usize) -> impl Iterator<Item = usize> {
+        self.topology.neighbors(index).into_iter().map(|(neighbor, _)| neighbor)
+    }
+}
+
+pub trait WaveConstraint<T>: fmt::Debug + Send + Sync {
+    /// Whether `wave` can still be completed into a grid meeting the constraint. Called
+    /// after every propagation, so this should cost no more than a pass over the grid.
+    fn holds(&self, wave: &WaveView<T>) -> bool;
+}
+
+/// Every cell holding one of `tiles` belongs to a single connected region, e.g. the floor
+/// and corridor tiles of a dungeon. Cells still able to hold one of them may link the
+/// region up; the constraint fails once the cells that must hold them can no longer be
+/// joined. A grid without any of the tiles is connected.
+#[derive(Debug, Clone)]
+pub struct Connected<T> {
+    tiles: HashSet<T>,
+}
+
+impl<T: TileKey> Connected<T> {
+    pub fn new(tiles: HashSet<T>) -> Connected<T> {
+        Connected { tiles }
+    }
+
+    /// The tiles of `rules` matching any of `keys` (IDs or tags, see `TileInfo::matches`)
+    pub fn matching(rules: &RuleSet<T>, keys: &[&str]) -> Connected<T> {
+        Connected::new(keys.iter().flat_map(|key| rules.tiles_matching(key)).collect())
+    }
+
+    pub fn tiles(&self) -> &HashSet<T> {
+        &self.tiles
+    }
+}
+
+impl<T: TileKey> WaveConstraint<T> for Connected<T> {
+    fn holds(&self, wave: &WaveView<T>) -> bool {
+        let possibilities = |index: usize| wave.possibilities(index).iter().filter(|tile| self.tiles.contains(*tile)).count();
+        let may: Vec<bool> = (0..wave.len()).map(|index| possibilities(index) > 0).collect();
+        let must: Vec<usize> = (0..wave.len())
+            .filter(|&index| may[index] && possibilities(index) == wave.possibilities(index).len())
+            .collect();
+        let Some(&start) = must.first() else {
+            return true;
+        };
+
+        // Flood the cells that may hold a tile from one that must, then look for any
+        // required cell the flood did not reach
+        let mut reached = vec![false; wave.len()];
+        reached[start] = true;
+        let mut stack = vec![start];
+        while let Some(index) = stack.pop() {
+            for neighbor in wave.neighbors(index) {
+                if may[neighbor] && !reached[neighbor] {
+                    reached[neighbor] = true;
+                    stack.push(neighbor);
+                }
+            }
+        }
+        must.iter().all(|&index| reached[index])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::topology::SquareGrid;
+
+    #[test]
+    fn test_connected_checks_required_cells() {
+        let id = |name: &str| name.to_string();
+        let cell = |tiles: &[&str]| Cell { collapsed: tiles.len() == 1, possibilities: tiles.iter().map(|t| id(t)).collect() };
+        let floor = Connected::new(HashSet::from([id("floor")]));
+        let topology = SquareGrid::new(3, 1);
+
+        // Two floors with an undecided cell between them may still join up
+        let cells = [cell(&["floor"]), cell(&["floor", "wall"]), cell(&["floor"])];
+        assert!(floor.holds(&WaveView { topology: &topology, cells: &cells }));
+        let cells = [cell(&["floor"]), cell(&["wall"]), cell(&["floor"])];
+        assert!(!floor.holds(&WaveView { topology: &topology, cells: &cells }));
+        let cells = [cell(&["wall"]), cell(&["wall"]), cell(&["wall", "floor"])];
+        assert!(floor.holds(&WaveView { topology: &topology, cells: &cells }));
+    }
+}
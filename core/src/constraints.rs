@@ -0,0 +1,136 @@
+//! Global constraints declared alongside a ruleset.
+//!
+//! Adjacency rules are local; these say something about the grid as a whole: how often a
+//! tile may appear, what may sit on the map's edges, and which tiles named regions are
+//! limited to. They are the `constraints` block of the v2 ruleset JSON:
+//!
+//! ```text
+//! "constraints": {
+//!   "counts": [{"tile": "boss_room", "max": 1}, {"tile": "chest", "min": 3}],
+//!   "borders": [{"edges": ["Up", "Down"], "tiles": ["ocean"]}],
+//!   "regions": {"lake": [{"x": 4, "y": 4, "width": 6, "height": 3}]},
+//!   "masks": [{"region": "lake", "tiles": ["water", "shore"]}]
+//! }
+//! ```
+//!
+//! Tiles are addressed by ID or tag, as in `TileInfo::matches`. Borders and masks are
+//! applied when a `Model` is created, so each affected cell starts out holding only the
+//! allowed tiles; counts are enforced as cells collapse (see `Model::add_count_constraint`).
+
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use crate::{Direction, TileKey};
+use crate::error::WfcError;
+use crate::ruleset::RuleSet;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Constraints {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub counts: Vec<CountConstraint>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub borders: Vec<BorderConstraint>,
+    /// Named areas of the grid, each the union of its rectangles
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub regions: BTreeMap<String, Vec<Rect>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub masks: Vec<MaskConstraint>,
+}
+
+/// Occurrences of the tiles matching `tile` across the whole grid
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CountConstraint {
+    pub tile: String,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub min: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<usize>,
+}
+
+/// Cells on the listed edges (every edge if none are listed) hold only tiles matching `tiles`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BorderConstraint {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub edges: Vec<Direction>,
+    pub tiles: Vec<String>,
+}
+
+impl BorderConstraint {
+    /// Every `(x, y)` of a `width`x`height` grid on one of the constraint's edges, row by row
+    pub fn cells(&self, width: usize, height: usize) -> Vec<(usize, usize)> {
+        border_cells(&self.edges, width, height)
+    }
+}
+
+/// Every `(x, y)` of a `width`x`height` grid on one of `edges` (every edge if empty), row by
+/// row and each cell once. `Forward`/`Backward` name no edge of a 2D grid and are ignored.
+pub fn border_cells(edges: &[Direction], width: usize, height: usize) -> Vec<(usize, usize)> {
+    let on = |edge: Direction| edges.is_empty() || edges.contains(&edge);
+    (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .filter(|&(x, y)| {
+            (y == 0 && on(Direction::Up))
+                || (y + 1 == height && on(Direction::Down))
+                || (x == 0 && on(Direction::Left))
+                || (x + 1 == width && on(Direction::Right))
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Rect {
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        x >= self.x && y >= self.y && x - self.x < self.width && y - self.y < self.height
+    }
+
+    /// Every `(x, y)` inside the rectangle, row by row
+    pub fn cells(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        (self.y..self.y + self.height).flat_map(move |y| (self.x..self.x + self.width).map(move |x| (x, y)))
+    }
+}
+
+/// Cells of the named region hold only tiles matching `tiles`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MaskConstraint {
+    pub region: String,
+    pub tiles: Vec<String>,
+}
+
+fn is_zero(n: &usize) -> bool {
+    *n == 0
+}
+
+impl Constraints {
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty() && self.borders.is_empty() && self.regions.is_empty() && self.masks.is_empty()
+    }
+
+    /// Check that every tile key matches a tile of `rules` and every mask names a region
+    pub fn validate<T: TileKey>(&self, rules: &RuleSet<T>) -> Result<(), WfcError> {
+        let keys = self.counts.iter().map(|count| &count.tile)
+            .chain(self.borders.iter().flat_map(|border| &border.tiles))
+            .chain(self.masks.iter().flat_map(|mask| &mask.tiles));
+        for key in keys {
+            if !rules.tiles.values().any(|info| info.matches(key)) {
+                return Err(WfcError::InvalidTileId(key.clone()));
+            }
+        }
+        for mask in &self.masks {
+            if !self.regions.contains_key(&mask.region) {
+                return Err(WfcError::Import(format!("mask refers to unknown region {:?}", mask.region)));
+            }
+        }
+        for count in &self.counts {
+            if count.max.is_some_and(|max| max < count.min) {
+                return Err(WfcError::Import(format!("count for {:?} has max below min", count.tile)));
+            }
+        }
+        Ok(())
+    }
+}
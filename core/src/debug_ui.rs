@@ -0,0 +1,126 @@
+//! An egui inspector for the solver, behind the `debug-ui` feature.
+//!
+//! `WaveView` draws a model's current wave, one square per cell: collapsed cells in their
+//! tile's color, open cells shaded from black (many options left) to white (nearly decided),
+//! and emptied cells in red. The cell of the latest contradiction is outlined, and hovering
+//! a cell lists what it may still become.
+//!
+//! ```text
+//! ui.add(WaveView::new(&model).cell_size(6.0));
+//! ```
+
+use std::collections::HashMap;
+use egui::{Color32, CornerRadius, Pos2, Rect, Response, Sense, Stroke, StrokeKind, Ui, Vec2, Widget};
+use crate::{TileId, TileKey};
+use crate::image::Pixel;
+use crate::model::Model;
+use crate::render::tile_color;
+
+const CONTRADICTION: Color32 = Color32::from_rgb(220, 40, 40);
+
+pub struct WaveView<'a, T: TileKey = TileId> {
+    model: &'a Model<T>,
+    cell_size: f32,
+    palette: Option<&'a HashMap<TileId, Pixel>>,
+}
+
+impl<'a, T: TileKey> WaveView<'a, T> {
+    pub fn new(model: &'a Model<T>) -> WaveView<'a, T> {
+        WaveView { model, cell_size: 8.0, palette: None }
+    }
+
+    /// Edge length of each cell in points (default 8)
+    pub fn cell_size(mut self, cell_size: f32) -> Self {
+        self.cell_size = cell_size.max(1.0);
+        self
+    }
+
+    /// Colors of collapsed tiles, keyed by their `Display` name; others use `tile_color`
+    pub fn palette(mut self, palette: &'a HashMap<TileId, Pixel>) -> Self {
+        self.palette = Some(palette);
+        self
+    }
+
+    fn color_of(&self, tile: &T) -> Color32 {
+        let name = tile.to_string();
+        let [r, g, b, a] = self.palette.and_then(|palette| palette.get(&name).copied()).unwrap_or_else(|| tile_color(&name));
+        Color32::from_rgba_unmultiplied(r, g, b, a)
+    }
+}
+
+impl<T: TileKey> Widget for WaveView<'_, T> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let (width, height) = (self.model.width(), self.model.height());
+        let size = Vec2::new(width as f32, height as f32) * self.cell_size;
+        let (response, painter) = ui.allocate_painter(size, Sense::hover());
+        let origin = response.rect.min;
+        let cell_rect = |index: usize| {
+            let min = origin + Vec2::new((index % width) as f32, (index / width) as f32) * self.cell_size;
+            Rect::from_min_size(min, Vec2::splat(self.cell_size))
+        };
+
+        // Shade by the share of the tileset still possible, on a log scale like entropy
+        let tile_count = self.model.rules().tiles.len().max(2) as f32;
+        let collapsed = self.model.collapse_order();
+        for (index, step) in collapsed.iter().enumerate() {
+            let options = self.model.possibilities(index);
+            let color = match options.iter().next() {
+                None => CONTRADICTION,
+                Some(tile) if step.is_some() => self.color_of(tile),
+                Some(_) => {
+                    let uncertainty = (options.len() as f32).ln() / tile_count.ln();
+                    Color32::from_gray((255.0 * (1.0 - uncertainty)).round() as u8)
+                }
+            };
+            painter.rect_filled(cell_rect(index), CornerRadius::ZERO, color);
+        }
+
+        if let Some((x, y)) = self.model.last_contradiction() {
+            let rect = cell_rect(y * width + x);
+            let stroke = Stroke::new((self.cell_size / 4.0).clamp(1.0, 3.0), CONTRADICTION);
+            painter.rect_stroke(rect.expand(stroke.width), CornerRadius::ZERO, stroke, StrokeKind::Outside);
+        }
+
+        let hovered = response.hover_pos().map(|pos: Pos2| (pos - origin) / self.cell_size);
+        match hovered {
+            Some(cell) if cell.x >= 0.0 && cell.y >= 0.0 && (cell.x as usize) < width && (cell.y as usize) < height => {
+                let (x, y) = (cell.x as usize, cell.y as usize);
+                let mut options: Vec<String> = self.model.possibilities(y * width + x).iter().map(|tile| tile.to_string()).collect();
+                options.sort();
+                let state = if collapsed[y * width + x].is_some() { "collapsed" } else { "open" };
+                response.on_hover_text(format!("({}, {}) {}: {}", x, y, state, options.join(", ")))
+            }
+            _ => response,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bench::Workload;
+
+    fn shapes_drawn(model: &Model) -> usize {
+        let ctx = egui::Context::default();
+        let output = ctx.run(egui::RawInput::default(), |ctx| {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                ui.add(WaveView::new(model).cell_size(2.0));
+            });
+        });
+        output.shapes.len()
+    }
+
+    #[test]
+    fn test_wave_view_draws_every_cell() {
+        let workload = Workload::contradiction_heavy(12);
+        let mut model = Model::new(workload.width, workload.height, workload.rules, Some(1)).unwrap();
+        let empty = shapes_drawn(&model);
+        assert!(empty >= 144);
+
+        // The contradiction marker adds one more shape
+        while model.last_contradiction().is_none() {
+            assert_ne!(model.step().unwrap(), crate::model::StepStatus::Done);
+        }
+        assert_eq!(shapes_drawn(&model), empty + 1);
+    }
+}
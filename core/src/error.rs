@@ -1,12 +1,96 @@
 use std::fmt;
 
+/// Errors produced by the WFC core.
+///
+/// The enum is `#[non_exhaustive]`: match on `code()`/`code_str()` (or keep a wildcard arm)
+/// when mapping errors across FFI/wasm/HTTP boundaries, so new variants are not breaking.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum WfcError {
     InvalidDimensions { width: usize, height: usize },
     NoTilesDefined,
     Contradiction,
     InvalidTileId(String),
-    JsonParseError(String),
+    JsonParseError(serde_json::Error),
+    InvalidImage(String),
+    Io(std::io::Error),
+    InvalidTrace(String),
+    InvalidAtlas(String),
+    Export(String),
+    Import(String),
+    /// The search gave up after exceeding the backtrack limits of `model::RunConfig`. Unlike
+    /// `Contradiction`, the rules may well be satisfiable; another seed may succeed.
+    BacktrackLimitExceeded { backtracks: u64 },
+    /// The grid was read before every cell was collapsed
+    Incomplete { collapsed: usize, total: usize },
+    /// `run` used up the step or time budget of `model::RunConfig` before finishing
+    BudgetExceeded { iterations: u64, elapsed_ms: u64 },
+    /// The run was stopped through its `model::CancelToken`
+    Cancelled,
+    /// A `topology::CellGraph` names an unknown or duplicate node
+    InvalidGraph(String),
+    /// `RuleSet::merge` met two different definitions it was not allowed to settle
+    MergeConflict(String),
+    /// A cell coordinate lies outside the grid, or outside the area of a masked topology
+    OutOfBounds { x: usize, y: usize, width: usize, height: usize },
+    /// A rule weight that is negative, infinite or NaN
+    InvalidWeight(f64),
+    /// A solver was given an option or constraint it cannot honor
+    Unsupported(String),
+}
+
+impl WfcError {
+    /// Stable numeric code. Codes are never reused or renumbered once released.
+    pub fn code(&self) -> u32 {
+        match self {
+            WfcError::InvalidDimensions { .. } => 1,
+            WfcError::NoTilesDefined => 2,
+            WfcError::Contradiction => 3,
+            WfcError::InvalidTileId(_) => 4,
+            WfcError::JsonParseError(_) => 5,
+            WfcError::InvalidImage(_) => 6,
+            WfcError::Io(_) => 7,
+            WfcError::InvalidTrace(_) => 8,
+            WfcError::InvalidAtlas(_) => 9,
+            WfcError::Export(_) => 10,
+            WfcError::Import(_) => 11,
+            WfcError::BacktrackLimitExceeded { .. } => 12,
+            WfcError::Incomplete { .. } => 13,
+            WfcError::BudgetExceeded { .. } => 14,
+            WfcError::Cancelled => 15,
+            WfcError::InvalidGraph(_) => 16,
+            WfcError::MergeConflict(_) => 17,
+            WfcError::OutOfBounds { .. } => 18,
+            WfcError::InvalidWeight(_) => 19,
+            WfcError::Unsupported(_) => 20,
+        }
+    }
+
+    /// Stable string code, e.g. `"contradiction"`
+    pub fn code_str(&self) -> &'static str {
+        match self {
+            WfcError::InvalidDimensions { .. } => "invalid_dimensions",
+            WfcError::NoTilesDefined => "no_tiles_defined",
+            WfcError::Contradiction => "contradiction",
+            WfcError::InvalidTileId(_) => "invalid_tile_id",
+            WfcError::JsonParseError(_) => "json_parse_error",
+            WfcError::InvalidImage(_) => "invalid_image",
+            WfcError::Io(_) => "io",
+            WfcError::InvalidTrace(_) => "invalid_trace",
+            WfcError::InvalidAtlas(_) => "invalid_atlas",
+            WfcError::Export(_) => "export",
+            WfcError::Import(_) => "import",
+            WfcError::BacktrackLimitExceeded { .. } => "backtrack_limit_exceeded",
+            WfcError::Incomplete { .. } => "incomplete",
+            WfcError::BudgetExceeded { .. } => "budget_exceeded",
+            WfcError::Cancelled => "cancelled",
+            WfcError::InvalidGraph(_) => "invalid_graph",
+            WfcError::MergeConflict(_) => "merge_conflict",
+            WfcError::OutOfBounds { .. } => "out_of_bounds",
+            WfcError::InvalidWeight(_) => "invalid_weight",
+            WfcError::Unsupported(_) => "unsupported",
+        }
+    }
 }
 
 impl fmt::Display for WfcError {
@@ -16,9 +100,69 @@ impl fmt::Display for WfcError {
             WfcError::NoTilesDefined => write!(f, "No tiles defined in the rule set"),
             WfcError::Contradiction => write!(f, "Contradiction reached, generation failed"),
             WfcError::InvalidTileId(id) => write!(f, "Invalid tile ID: {}", id),
-            WfcError::JsonParseError(msg) => write!(f, "JSON parse error: {}", msg),
+            WfcError::JsonParseError(e) => write!(f, "JSON parse error: {}", e),
+            WfcError::InvalidImage(msg) => write!(f, "Invalid image: {}", msg),
+            WfcError::Io(e) => write!(f, "I/O error: {}", e),
+            WfcError::InvalidTrace(msg) => write!(f, "Invalid trace: {}", msg),
+            WfcError::InvalidAtlas(msg) => write!(f, "Invalid atlas: {}", msg),
+            WfcError::Export(msg) => write!(f, "Export failed: {}", msg),
+            WfcError::Import(msg) => write!(f, "Import failed: {}", msg),
+            WfcError::BacktrackLimitExceeded { backtracks } => write!(f, "Backtrack limit exceeded after {} backtracks", backtracks),
+            WfcError::Incomplete { collapsed, total } => write!(f, "Generation incomplete: {} of {} cells collapsed", collapsed, total),
+            WfcError::BudgetExceeded { iterations, elapsed_ms } => write!(f, "Budget exceeded after {} steps in {} ms", iterations, elapsed_ms),
+            WfcError::Cancelled => write!(f, "Generation cancelled"),
+            WfcError::InvalidGraph(msg) => write!(f, "Invalid graph: {}", msg),
+            WfcError::MergeConflict(msg) => write!(f, "Merge conflict: {}", msg),
+            WfcError::OutOfBounds { x, y, width, height } => write!(f, "Cell ({}, {}) is outside the area of the {}x{} grid", x, y, width, height),
+            WfcError::InvalidWeight(weight) => write!(f, "Invalid rule weight: {}", weight),
+            WfcError::Unsupported(msg) => write!(f, "Not supported: {}", msg),
         }
     }
 }
 
-impl std::error::Error for WfcError {}
+impl std::error::Error for WfcError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            WfcError::JsonParseError(e) => Some(e),
+            WfcError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<serde_json::Error> for WfcError {
+    fn from(error: serde_json::Error) -> Self {
+        WfcError::JsonParseError(error)
+    }
+}
+
+impl From<std::io::Error> for WfcError {
+    fn from(error: std::io::Error) -> Self {
+        WfcError::Io(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error;
+
+    #[test]
+    fn test_codes_and_source() {
+        let err = WfcError::Contradiction;
+        assert_eq!(err.code(), 3);
+        assert_eq!(err.code_str(), "contradiction");
+        assert!(err.source().is_none());
+
+        let json_err: WfcError = serde_json::from_str::<u32>("nope").unwrap_err().into();
+        assert_eq!(json_err.code_str(), "json_parse_error");
+        assert!(json_err.source().is_some());
+
+        let limit = WfcError::BacktrackLimitExceeded { backtracks: 40 };
+        assert_eq!((limit.code(), limit.code_str()), (12, "backtrack_limit_exceeded"));
+        assert_eq!(WfcError::Incomplete { collapsed: 3, total: 9 }.to_string(), "Generation incomplete: 3 of 9 cells collapsed");
+        assert_eq!(WfcError::BudgetExceeded { iterations: 5, elapsed_ms: 0 }.code_str(), "budget_exceeded");
+        assert_eq!(WfcError::OutOfBounds { x: 9, y: 2, width: 8, height: 4 }.to_string(), "Cell (9, 2) is outside the area of the 8x4 grid");
+        assert_eq!((WfcError::Unsupported(String::new()).code(), WfcError::Unsupported(String::new()).code_str()), (20, "unsupported"));
+    }
+}
@@ -0,0 +1,192 @@
+use crate::error::WfcError;
+
+/// RGBA pixel
+pub type Pixel = [u8; 4];
+
+/// Simple owned RGBA image buffer used for sample learning and rendering
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Pixel>,
+}
+
+impl Image {
+    /// Create a fully transparent image
+    pub fn new(width: usize, height: usize) -> Image {
+        Image {
+            width,
+            height,
+            pixels: vec![[0, 0, 0, 0]; width * height],
+        }
+    }
+
+    pub fn from_pixels(width: usize, height: usize, pixels: Vec<Pixel>) -> Result<Image, WfcError> {
+        if width == 0 || height == 0 || pixels.len() != width * height {
+            return Err(WfcError::InvalidImage(format!(
+                "expected {}x{} pixels, got {}",
+                width, height, pixels.len()
+            )));
+        }
+        Ok(Image { width, height, pixels })
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Pixel {
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, pixel: Pixel) {
+        self.pixels[y * self.width + x] = pixel;
+    }
+
+    /// Copy out a `w`x`h` region starting at (x, y)
+    pub fn crop(&self, x: usize, y: usize, w: usize, h: usize) -> Image {
+        let mut out = Image::new(w, h);
+        for dy in 0..h {
+            for dx in 0..w {
+                out.set(dx, dy, self.get(x + dx, y + dy));
+            }
+        }
+        out
+    }
+
+    /// Draw `src` onto this image with its top-left corner at (x, y), clipping at the edges
+    pub fn blit(&mut self, src: &Image, x: usize, y: usize) {
+        for dy in 0..src.height {
+            for dx in 0..src.width {
+                if x + dx < self.width && y + dy < self.height {
+                    self.set(x + dx, y + dy, src.get(dx, dy));
+                }
+            }
+        }
+    }
+
+    /// Rotate the image clockwise by 90 degrees
+    pub fn rotate_clockwise(&self) -> Image {
+        let mut out = Image::new(self.height, self.width);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                out.set(self.height - 1 - y, x, self.get(x, y));
+            }
+        }
+        out
+    }
+
+    /// Mirror the image left-to-right
+    pub fn reflect_horizontal(&self) -> Image {
+        let mut out = Image::new(self.width, self.height);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                out.set(self.width - 1 - x, y, self.get(x, y));
+            }
+        }
+        out
+    }
+
+    /// The first `symmetry` (1..=8) elements of the dihedral group applied to this image,
+    /// in the same order as the reference implementation: identity, reflection, rotation,
+    /// rotation + reflection, ...
+    pub fn dihedral_variants(&self, symmetry: usize) -> Vec<Image> {
+        let mut variants = Vec::with_capacity(8);
+        let mut current = self.clone();
+        for _ in 0..4 {
+            let reflected = current.reflect_horizontal();
+            let next = current.rotate_clockwise();
+            variants.push(current);
+            variants.push(reflected);
+            current = next;
+        }
+        variants.truncate(symmetry.clamp(1, 8));
+        variants
+    }
+}
+
+#[cfg(feature = "png")]
+impl Image {
+    /// Decode a PNG stream, converting any color type to 8-bit RGBA
+    pub fn read_png<R: std::io::Read>(reader: R) -> Result<Image, WfcError> {
+        let mut decoder = png::Decoder::new(reader);
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info()
+            .map_err(|e| WfcError::InvalidImage(e.to_string()))?;
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf)
+            .map_err(|e| WfcError::InvalidImage(e.to_string()))?;
+        let bytes = &buf[..info.buffer_size()];
+
+        let pixels: Vec<Pixel> = match info.color_type {
+            png::ColorType::Rgba => bytes.chunks_exact(4).map(|c| [c[0], c[1], c[2], c[3]]).collect(),
+            png::ColorType::Rgb => bytes.chunks_exact(3).map(|c| [c[0], c[1], c[2], 255]).collect(),
+            png::ColorType::GrayscaleAlpha => bytes.chunks_exact(2).map(|c| [c[0], c[0], c[0], c[1]]).collect(),
+            png::ColorType::Grayscale => bytes.iter().map(|&g| [g, g, g, 255]).collect(),
+            png::ColorType::Indexed => {
+                return Err(WfcError::InvalidImage("indexed PNG was not expanded".to_string()));
+            }
+        };
+
+        Image::from_pixels(info.width as usize, info.height as usize, pixels)
+    }
+
+    /// Encode the image as an 8-bit RGBA PNG
+    pub fn write_png<W: std::io::Write>(&self, writer: W) -> Result<(), WfcError> {
+        let mut encoder = png::Encoder::new(writer, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()
+            .map_err(|e| WfcError::InvalidImage(e.to_string()))?;
+        let data: Vec<u8> = self.pixels.iter().flatten().copied().collect();
+        writer.write_image_data(&data)
+            .map_err(|e| WfcError::InvalidImage(e.to_string()))
+    }
+
+    pub fn load_png<P: AsRef<std::path::Path>>(path: P) -> Result<Image, WfcError> {
+        let file = std::fs::File::open(path)?;
+        Image::read_png(std::io::BufReader::new(file))
+    }
+
+    pub fn save_png<P: AsRef<std::path::Path>>(&self, path: P) -> Result<(), WfcError> {
+        let file = std::fs::File::create(path)?;
+        self.write_png(std::io::BufWriter::new(file))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numbered(width: usize, height: usize) -> Image {
+        let pixels = (0..width * height).map(|i| [i as u8, 0, 0, 255]).collect();
+        Image::from_pixels(width, height, pixels).unwrap()
+    }
+
+    #[test]
+    fn test_rotate_and_reflect() {
+        let img = numbered(3, 2);
+        let rotated = img.rotate_clockwise();
+        assert_eq!((rotated.width, rotated.height), (2, 3));
+        // Bottom-left pixel moves to the top-left corner
+        assert_eq!(rotated.get(0, 0), img.get(0, 1));
+        assert_eq!(rotated.rotate_clockwise().rotate_clockwise().rotate_clockwise(), img);
+
+        let reflected = img.reflect_horizontal();
+        assert_eq!(reflected.get(0, 0), img.get(2, 0));
+        assert_eq!(reflected.reflect_horizontal(), img);
+    }
+
+    #[test]
+    fn test_dihedral_variants_count() {
+        let img = numbered(2, 2);
+        assert_eq!(img.dihedral_variants(1).len(), 1);
+        assert_eq!(img.dihedral_variants(8).len(), 8);
+        assert_eq!(img.dihedral_variants(0).len(), 1);
+    }
+
+    #[test]
+    #[cfg(feature = "png")]
+    fn test_png_round_trip() {
+        let img = numbered(4, 3);
+        let mut bytes = Vec::new();
+        img.write_png(&mut bytes).unwrap();
+        assert_eq!(Image::read_png(bytes.as_slice()).unwrap(), img);
+    }
+}
@@ -0,0 +1,232 @@
+//! Dense tile indices.
+//!
+//! The solver is generic over its tile type, and with `u32` tiles every ban, clone and
+//! hash in `propagate` and `collapse_cell` works on an integer instead of a `String`.
+//! `TileTable` numbers a ruleset's tiles and converts at the edges: `intern_rules` turns a
+//! `RuleSet` into a `RuleSet<u32>`, and `resolve` turns a solved grid back into names.
+//! `InternedModel` does both around a `Model<u32>`, so large grids can be solved with the
+//! usual string-based ruleset and output; `wfc batch` and `wfc serve` solve through it.
+//! The wasm `WfcModel` does not, as its snapshots and frames are exchanged by tile name.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use serde_json::Value;
+use crate::TileId;
+use crate::error::WfcError;
+use crate::manifest::Manifest;
+use crate::model::Model;
+use crate::ruleset::RuleSet;
+use crate::topology::Topology;
+
+/// Metadata key under which an interned tile keeps its name, matched by `TileInfo::matches`
+/// in place of the index
+pub const INTERNED_NAME: &str = "interned_name";
+
+/// Tile names and their indices, numbered in sorted name order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TileTable {
+    names: Vec<TileId>,
+    indices: HashMap<TileId, u32>,
+}
+
+impl TileTable {
+    pub fn new(rules: &RuleSet) -> TileTable {
+        let mut names: Vec<TileId> = rules.get_all_tile_ids().into_iter().cloned().collect();
+        names.sort();
+        let indices = names.iter().enumerate().map(|(i, name)| (name.clone(), i as u32)).collect();
+        TileTable { names, indices }
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    pub fn index(&self, name: &str) -> Option<u32> {
+        self.indices.get(name).copied()
+    }
+
+    pub fn name(&self, index: u32) -> Option<&TileId> {
+        self.names.get(index as usize)
+    }
+
+    /// Every name, in index order
+    pub fn names(&self) -> &[TileId] {
+        &self.names
+    }
+
+    /// `rules` over tile indices. Each tile keeps its name as `INTERNED_NAME` metadata, so
+    /// options that address tiles by name (weight gradients, cluster bonuses, constraints)
+    /// still match, and a key never matches a tile whose index merely reads the same.
+    /// Rules naming tiles outside the tileset are dropped, as they can never apply. Fails
+    /// with `InvalidTileId` if a tile of `rules` is missing from the table, e.g. one built
+    /// from another ruleset.
+    pub fn intern_rules(&self, rules: &RuleSet) -> Result<RuleSet<u32>, WfcError> {
+        let mut interned = RuleSet::<u32>::default();
+        for info in rules.get_all_tiles() {
+            let index = self.index(&info.id).ok_or_else(|| WfcError::InvalidTileId(info.id.clone()))?;
+            let mut metadata = info.metadata.clone();
+            metadata.insert(INTERNED_NAME.to_string(), Value::String(info.id.clone()));
+            interned.add_tile_with_metadata(index, info.weight, metadata);
+        }
+        for ((from, direction), targets) in &rules.adjacency {
+            let Some(from) = self.index(from) else { continue };
+            let targets = targets.iter().filter_map(|to| self.index(to)).collect();
+            interned.adjacency.insert((from, *direction), targets);
+        }
+        for (alias, canonical) in &rules.aliases {
+            if let (Some(alias), Some(canonical)) = (self.index(alias), self.index(canonical)) {
+                interned.aliases.insert(alias, canonical);
+            }
+        }
+        for (from, direction, to, weight) in rules.rule_weights.entries() {
+            if let (Some(from), Some(to)) = (self.index(from), self.index(to)) {
+                interned.rule_weights.set(from, direction, to, weight);
+            }
+        }
+        interned.atlas = rules.atlas.clone();
+        interned.missing_adjacency = rules.missing_adjacency;
+        interned.constraints = rules.constraints.clone();
+        Ok(interned)
+    }
+
+    /// Names of a grid of indices. Fails with `InvalidTileId` on an index outside the table.
+    pub fn resolve(&self, grid: &[u32]) -> Result<Vec<TileId>, WfcError> {
+        grid.iter()
+            .map(|&index| self.name(index).cloned().ok_or_else(|| WfcError::InvalidTileId(index.to_string())))
+            .collect()
+    }
+}
+
+/// A `Model<u32>` that takes and returns tile names
+#[derive(Debug, Clone)]
+pub struct InternedModel {
+    model: Model<u32>,
+    table: TileTable,
+    /// `content_hash` of the named ruleset, for manifests
+    ruleset_hash: String,
+}
+
+impl InternedModel {
+    pub fn new(width: usize, height: usize, rules: &RuleSet, seed: Option<u64>) -> Result<InternedModel, WfcError> {
+        InternedModel::with_wrap(width, height, false, false, rules, seed)
+    }
+
+    pub fn with_wrap(width: usize, height: usize, wrap_x: bool, wrap_y: bool, rules: &RuleSet, seed: Option<u64>) -> Result<InternedModel, WfcError> {
+        let table = TileTable::new(rules);
+        let model = Model::with_wrap(width, height, wrap_x, wrap_y, table.intern_rules(rules)?, seed)?;
+        Ok(InternedModel { model, table, ruleset_hash: rules.content_hash() })
+    }
+
+    pub fn with_topology(topology: Arc<dyn Topology>, rules: &RuleSet, seed: Option<u64>) -> Result<InternedModel, WfcError> {
+        let table = TileTable::new(rules);
+        let model = Model::with_topology(topology, table.intern_rules(rules)?, seed)?;
+        Ok(InternedModel { model, table, ruleset_hash: rules.content_hash() })
+    }
+
+    pub fn table(&self) -> &TileTable {
+        &self.table
+    }
+
+    pub fn model(&self) -> &Model<u32> {
+        &self.model
+    }
+
+    /// The underlying solver, for settings and stepping; tiles are given as indices
+    pub fn model_mut(&mut self) -> &mut Model<u32> {
+        &mut self.model
+    }
+
+    /// `Model::set_tile` by name
+    pub fn set_tile(&mut self, x: usize, y: usize, tile_id: &str) -> Result<(), WfcError> {
+        let index = self.table.index(tile_id).ok_or_else(|| WfcError::InvalidTileId(tile_id.to_string()))?;
+        self.model.force(x, y, index)
+    }
+
+    pub fn run(&mut self) -> Result<Vec<TileId>, WfcError> {
+        let grid = self.model.run()?;
+        self.table.resolve(&grid)
+    }
+
+    pub fn result(&self) -> Result<Vec<TileId>, WfcError> {
+        self.table.resolve(&self.model.result()?)
+    }
+
+    /// `Model::manifest` with tile names and the named ruleset's hash, so it matches the
+    /// manifest of the same run on a `Model<TileId>`
+    pub fn manifest(&self) -> Result<Manifest, WfcError> {
+        let name = |index: &u32| self.table.names[*index as usize].clone();
+        self.model.named_manifest(self.ruleset_hash.clone(), &name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bench::Workload;
+    use crate::Direction;
+    use crate::constraints::CountConstraint;
+    use crate::transition::{Axis, WeightGradient};
+
+    #[test]
+    fn test_interned_model_matches_rules() {
+        let workload = Workload::large_grid(16);
+        let table = TileTable::new(&workload.rules);
+        assert_eq!(table.names(), ["grass", "sand", "water"]);
+        assert_eq!(table.index("sand"), Some(1));
+        assert_eq!(table.name(2).map(String::as_str), Some("water"));
+        assert!(table.intern_rules(&workload.rules).unwrap().get_tile_info(&0).unwrap().matches("grass"));
+        let mut other = workload.rules.clone();
+        other.add_tile("lava".to_string(), 1);
+        assert!(matches!(table.intern_rules(&other), Err(WfcError::InvalidTileId(id)) if id == "lava"));
+
+        let mut model = InternedModel::new(16, 16, &workload.rules, Some(2)).unwrap();
+        model.set_tile(8, 8, "water").unwrap();
+        assert!(matches!(model.set_tile(0, 0, "lava"), Err(WfcError::InvalidTileId(_))));
+        // Keys by name still reach the interned tiles
+        model.model_mut().add_weight_gradient(WeightGradient { key: "grass".to_string(), axis: Axis::X, start: 1.0, end: 0.5 }).unwrap();
+
+        let grid = model.run().unwrap();
+        assert_eq!(grid[8 * 16 + 8], "water");
+        assert_eq!(model.result().unwrap(), grid);
+        let checker = Model::new(16, 16, workload.rules.clone(), None).unwrap();
+        assert!(checker.verify_result(&grid).unwrap().is_empty());
+
+        // Same manifest as the same run over names
+        let mut plain = InternedModel::new(16, 16, &workload.rules, Some(2)).unwrap();
+        plain.run().unwrap();
+        let mut named = Model::new(16, 16, workload.rules, Some(2)).unwrap();
+        named.run().unwrap();
+        assert_eq!(plain.manifest().unwrap(), named.manifest().unwrap());
+    }
+
+    #[test]
+    fn test_numeric_names_match_by_name() {
+        // Tile "2" gets index 1, which must not make it answer to key "1"
+        let mut rules = RuleSet::new();
+        for id in ["1", "2", "3"] {
+            rules.add_tile(id.to_string(), 1);
+        }
+        for from in ["1", "2", "3"] {
+            for to in ["1", "2", "3"] {
+                for direction in [Direction::Up, Direction::Right, Direction::Down, Direction::Left] {
+                    rules.add_adjacency(from.to_string(), to.to_string(), direction);
+                }
+            }
+        }
+        let table = TileTable::new(&rules);
+        let interned = table.intern_rules(&rules).unwrap();
+        assert_eq!(interned.tiles_matching("1"), [table.index("1").unwrap()].into_iter().collect());
+        assert_eq!(rules.tiles_matching("1"), ["1".to_string()].into_iter().collect());
+
+        rules.constraints.counts.push(CountConstraint { tile: "1".to_string(), min: 0, max: Some(0) });
+        for seed in 0..5 {
+            let grid = InternedModel::new(8, 8, &rules, Some(seed)).unwrap().run().unwrap();
+            assert!(!grid.contains(&"1".to_string()));
+            assert!(grid.contains(&"2".to_string()) && grid.contains(&"3".to_string()));
+        }
+    }
+}
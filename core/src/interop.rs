@@ -0,0 +1,492 @@
+//! Conversion from and to the configuration formats of other WFC tools.
+//!
+//! DeBroglie configs (`tiles` with `tileSymmetry`/`multiplyFrequency`, `adjacencies` as
+//! `left`/`right` or `up`/`down` groups, `rotationalSymmetry`/`reflectionalSymmetry`) import
+//! into a `RuleSet`. Sample-image and output settings are ignored; only the tile model is kept.
+//!
+//! Rulesets export to the SimpleTiled XML dialect of mxgmn's reference implementation.
+//!
+//! With the `tiled` feature, hand-authored Tiled maps (`.tmx`) can seed a ruleset: every
+//! pair of tiles that touches in a layer becomes an allowed adjacency.
+
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use serde::Deserialize;
+use serde_json::Value;
+use crate::{Direction, SymmetryType, TileId};
+use crate::error::WfcError;
+use crate::ruleset::RuleSet;
+use crate::symmetry::Transform;
+#[cfg(feature = "tiled")]
+use crate::learn::LearnOptions;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DeBroglieConfig {
+    #[serde(default)]
+    tiles: Vec<DeBroglieTile>,
+    #[serde(default)]
+    adjacencies: Vec<DeBroglieAdjacency>,
+    #[serde(default = "default_rotational_symmetry")]
+    rotational_symmetry: u8,
+    #[serde(default)]
+    reflectional_symmetry: bool,
+}
+
+fn default_rotational_symmetry() -> u8 {
+    1
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DeBroglieTile {
+    value: Value,
+    #[serde(default)]
+    tile_symmetry: Option<String>,
+    #[serde(default)]
+    multiply_frequency: Option<f64>,
+}
+
+#[derive(Deserialize)]
+struct DeBroglieAdjacency {
+    #[serde(default)]
+    left: Vec<Value>,
+    #[serde(default)]
+    right: Vec<Value>,
+    #[serde(default)]
+    up: Vec<Value>,
+    #[serde(default)]
+    down: Vec<Value>,
+}
+
+/// Build a ruleset from a DeBroglie JSON config. Every tile in a `left` group may sit left of
+/// every tile in the matching `right` group (and likewise `up` above `down`), in both
+/// directions. Tiles that only appear in adjacencies get DeBroglie's default frequency of 1.
+/// Fractional frequencies are scaled to whole weights with the same ratios, so 0.2 and 0.4
+/// next to the default become 1, 2 and 5.
+pub fn from_debroglie_json(json: &str) -> Result<RuleSet, WfcError> {
+    let config: DeBroglieConfig = serde_json::from_str(json)?;
+    let mut rules = RuleSet::new();
+
+    let frequencies: Vec<f64> = config.tiles.iter().filter_map(|tile| tile.multiply_frequency).collect();
+    let scale = frequency_scale(&frequencies);
+    // A frequency too small to survive the scaling still leaves the tile possible
+    let weight_of = |frequency: f64| if frequency > 0.0 { (frequency * scale).round().max(1.0) as u32 } else { 0 };
+    let default_weight = weight_of(1.0);
+
+    for tile in &config.tiles {
+        let id = tile_id(&tile.value)?;
+        let weight = tile.multiply_frequency.map_or(default_weight, weight_of);
+        match tile.tile_symmetry.as_deref() {
+            None => rules.add_tile(id, weight),
+            Some(symbol) => {
+                let symmetry = debroglie_symmetry(symbol)?;
+                rules.add_tile_with_symmetry(id, symmetry, &[weight]);
+            }
+        }
+    }
+
+    let step = match config.rotational_symmetry {
+        1 | 2 | 4 => 4 / config.rotational_symmetry,
+        other => return Err(WfcError::Import(format!("rotationalSymmetry must be 1, 2 or 4, got {}", other))),
+    };
+    let transforms: Vec<Transform> = Transform::ALL
+        .into_iter()
+        .filter(|t| t.rotation % step == 0 && (config.reflectional_symmetry || !t.reflected))
+        .collect();
+
+    for adjacency in &config.adjacencies {
+        let pairs = [
+            (&adjacency.left, &adjacency.right, Direction::Right),
+            (&adjacency.up, &adjacency.down, Direction::Down),
+        ];
+        for (first, second, direction) in pairs {
+            if first.is_empty() != second.is_empty() {
+                return Err(WfcError::Import("adjacency group is missing its other side".to_string()));
+            }
+            for a in first {
+                for b in second {
+                    let (a, b) = (tile_id(a)?, tile_id(b)?);
+                    for id in [&a, &b] {
+                        if rules.get_tile_info(id).is_none() {
+                            rules.add_tile(id.clone(), default_weight);
+                        }
+                    }
+                    rules.add_adjacency_with_transforms(a.clone(), b.clone(), direction, &transforms);
+                    rules.add_adjacency_with_transforms(b, a, direction.opposite(), &transforms);
+                }
+            }
+        }
+    }
+
+    if rules.tiles.is_empty() {
+        return Err(WfcError::NoTilesDefined);
+    }
+    Ok(rules)
+}
+
+/// Factor turning DeBroglie's float frequencies, and the default of 1, into whole weights
+/// with the same ratios: the smallest power of ten up to 1000 that makes them all whole,
+/// divided by the resulting weights' greatest common divisor
+fn frequency_scale(frequencies: &[f64]) -> f64 {
+    let all = || frequencies.iter().chain(&[1.0]).filter(|&&f| f > 0.0);
+    let power = [1.0, 10.0, 100.0, 1000.0]
+        .into_iter()
+        .find(|&power| all().all(|&f| (f * power - (f * power).round()).abs() < 1e-6))
+        .unwrap_or(1000.0);
+    let gcd = |mut a: u64, mut b: u64| {
+        while b != 0 {
+            (a, b) = (b, a % b);
+        }
+        a
+    };
+    let divisor = all().map(|&f| (f * power).round() as u64).filter(|&w| w > 0).fold(0, gcd);
+    power / divisor.max(1) as f64
+}
+
+/// DeBroglie tile values may be strings or numbers
+fn tile_id(value: &Value) -> Result<TileId, WfcError> {
+    match value {
+        Value::String(s) => Ok(s.clone()),
+        Value::Number(n) => Ok(n.to_string()),
+        other => Err(WfcError::Import(format!("unsupported tile value {}", other))),
+    }
+}
+
+fn debroglie_symmetry(symbol: &str) -> Result<SymmetryType, WfcError> {
+    match symbol {
+        "X" => Ok(SymmetryType::X),
+        "I" => Ok(SymmetryType::I),
+        "T" => Ok(SymmetryType::T),
+        "L" => Ok(SymmetryType::L),
+        "\\" => Ok(SymmetryType::Backslash),
+        "F" => Ok(SymmetryType::F),
+        "N" => Ok(SymmetryType::N),
+        other => Err(WfcError::Import(format!("unsupported tileSymmetry {:?}", other))),
+    }
+}
+
+/// Write `rules` as a SimpleTiled `<set>`: one `<tile>` per base tile with its symmetry and
+/// weight, and one `<neighbor>` per horizontal pair allowed both ways. The reference
+/// implementation derives vertical adjacency by rotating those pairs, so the export is exact
+/// for rotation-consistent rulesets (symmetry families with rules added through
+/// `add_adjacency_with_symmetry`); vertical rules of other rulesets are not carried over.
+/// Per-variant weights collapse to the base tile's weight.
+pub fn to_simple_tiled_xml(rules: &RuleSet) -> String {
+    let mut tiles: Vec<_> = rules.get_all_tiles()
+        .into_iter()
+        .filter(|info| info.symmetry.is_some() || rules.variant_origin(&info.id).is_none())
+        .collect();
+    tiles.sort_by(|a, b| a.id.cmp(&b.id));
+
+    let mut xml = String::from("<set>\n  <tiles>\n");
+    for info in tiles {
+        let symmetry = match info.symmetry.unwrap_or(SymmetryType::X) {
+            SymmetryType::X => "X",
+            SymmetryType::I => "I",
+            SymmetryType::T => "T",
+            SymmetryType::L => "L",
+            SymmetryType::Backslash => "\\",
+            // The reference implementation has a single eight-variant class
+            SymmetryType::F | SymmetryType::N => "F",
+        };
+        let _ = writeln!(xml, r#"    <tile name="{}" symmetry="{}" weight="{}"/>"#, escape(&info.id), symmetry, info.weight);
+    }
+    xml.push_str("  </tiles>\n  <neighbors>\n");
+
+    let mut pairs = BTreeSet::new();
+    for ((from, direction), set) in &rules.adjacency {
+        if *direction != Direction::Right {
+            continue;
+        }
+        for to in set {
+            if rules.is_compatible(from, to, Direction::Right) {
+                pairs.insert((simple_tiled_ref(rules, from), simple_tiled_ref(rules, to)));
+            }
+        }
+    }
+    for (left, right) in pairs {
+        let _ = writeln!(xml, r#"    <neighbor left="{}" right="{}"/>"#, escape(&left), escape(&right));
+    }
+    xml.push_str("  </neighbors>\n</set>\n");
+    xml
+}
+
+/// `name index` reference to a tile variant. The reference implementation numbers variants
+/// by counterclockwise quarter turns, with mirrored variants after the four rotations;
+/// `Transform` turns clockwise.
+fn simple_tiled_ref(rules: &RuleSet, id: &TileId) -> String {
+    match rules.variant_origin(id) {
+        Some((base, symmetry, transform)) => {
+            let turns = match symmetry.variant_count() {
+                1 => 0,
+                2 => transform.rotation as usize % 2,
+                _ => (4 - transform.rotation as usize) % 4,
+            };
+            format!("{} {}", base, turns + if transform.reflected { 4 } else { 0 })
+        }
+        None => format!("{} 0", id),
+    }
+}
+
+/// Tiled keeps flip/rotation flags in the top bits of a GID
+#[cfg(feature = "tiled")]
+const GID_FLAGS: u32 = 0xF000_0000;
+
+/// Infer a ruleset from a tile layer of a Tiled map: the layer called `layer`, or the first
+/// one. Tiles are named `<tileset>_<local id>` (external tilesets by their file stem) and
+/// keep their GID as `tiled_gid` metadata; each tile's weight is how often it occurs.
+/// Pairs touching in the layer are allowed both ways. Empty cells separate tiles rather than
+/// being a tile, and flipped placements count as the unflipped tile. Layer data must be CSV
+/// or plain XML, which are Tiled's uncompressed formats; infinite maps are not supported.
+#[cfg(feature = "tiled")]
+pub fn from_tiled_tmx(xml: &str, layer: Option<&str>) -> Result<RuleSet, WfcError> {
+    from_tiled_tmx_with(xml, layer, &LearnOptions { frequency_weights: true, ..LearnOptions::default() })
+}
+
+/// `from_tiled_tmx` with the options of `learn::learn_from_grid_with`: `periodic` also
+/// pairs tiles across opposite edges of the layer, and without `frequency_weights` every
+/// tile gets weight 1. Symmetry options do not apply to maps.
+#[cfg(feature = "tiled")]
+pub fn from_tiled_tmx_with(xml: &str, layer: Option<&str>, options: &LearnOptions) -> Result<RuleSet, WfcError> {
+    let doc = roxmltree::Document::parse(xml).map_err(|e| WfcError::Import(e.to_string()))?;
+    let map = doc.root_element();
+    if !map.has_tag_name("map") {
+        return Err(WfcError::Import("not a Tiled map".to_string()));
+    }
+    if map.attribute("infinite") == Some("1") {
+        return Err(WfcError::Import("infinite maps are not supported".to_string()));
+    }
+
+    let number = |node: roxmltree::Node, name: &str| -> Result<u32, WfcError> {
+        node.attribute(name)
+            .and_then(|value| value.parse().ok())
+            .ok_or_else(|| WfcError::Import(format!("<{}> needs a numeric {}", node.tag_name().name(), name)))
+    };
+    let mut tilesets = map.children()
+        .filter(|node| node.has_tag_name("tileset"))
+        .map(|node| {
+            let name = node.attribute("name")
+                .or_else(|| node.attribute("source").and_then(|source| std::path::Path::new(source).file_stem()?.to_str()))
+                .unwrap_or("tile");
+            Ok((number(node, "firstgid")?, name.to_string()))
+        })
+        .collect::<Result<Vec<(u32, String)>, WfcError>>()?;
+    tilesets.sort();
+
+    let node = map.descendants()
+        .filter(|node| node.has_tag_name("layer"))
+        .find(|node| layer.is_none_or(|name| node.attribute("name") == Some(name)))
+        .ok_or_else(|| WfcError::Import(format!("no tile layer {}", layer.unwrap_or_default())))?;
+    let (width, height) = (number(node, "width")? as usize, number(node, "height")? as usize);
+    let data = node.children()
+        .find(|child| child.has_tag_name("data"))
+        .ok_or_else(|| WfcError::Import("layer has no data".to_string()))?;
+    let gids = match data.attribute("encoding") {
+        Some("csv") => data.text()
+            .unwrap_or_default()
+            .split(',')
+            .map(|gid| gid.trim().parse::<u32>().map_err(|_| WfcError::Import(format!("bad GID {:?}", gid.trim()))))
+            .collect::<Result<Vec<u32>, WfcError>>()?,
+        None => data.children()
+            .filter(|child| child.has_tag_name("tile"))
+            .map(|tile| tile.attribute("gid").map_or(Ok(0), |_| number(tile, "gid")))
+            .collect::<Result<Vec<u32>, WfcError>>()?,
+        Some(other) => {
+            return Err(WfcError::Import(format!("{} layer data is not supported; save the map with CSV layer format", other)));
+        }
+    };
+    if width == 0 || height == 0 || gids.len() != width * height {
+        return Err(WfcError::InvalidDimensions { width, height });
+    }
+
+    let mut rules = RuleSet::new();
+    let grid: Vec<Option<TileId>> = gids
+        .iter()
+        .map(|&gid| {
+            let gid = gid & !GID_FLAGS;
+            let (first, name) = tilesets.iter().rev().find(|(first, _)| *first <= gid)?;
+            let id = format!("{}_{}", name, gid - first);
+            match rules.tiles.get_mut(&id) {
+                Some(info) => info.weight += 1,
+                None => {
+                    let mut metadata = serde_json::Map::new();
+                    metadata.insert("tiled_gid".to_string(), gid.into());
+                    rules.add_tile_with_metadata(id.clone(), 1, metadata);
+                }
+            }
+            Some(id)
+        })
+        .collect();
+    if rules.tiles.is_empty() {
+        return Err(WfcError::NoTilesDefined);
+    }
+    if !options.frequency_weights {
+        for info in rules.tiles.values_mut() {
+            info.weight = 1;
+        }
+    }
+
+    for y in 0..height {
+        for x in 0..width {
+            let Some(tile) = &grid[y * width + x] else { continue };
+            if let Some(Some(right)) = (x + 1 < width || options.periodic).then(|| &grid[y * width + (x + 1) % width]) {
+                rules.add_adjacency_symmetric(tile.clone(), right.clone(), Direction::Right);
+            }
+            if let Some(Some(below)) = (y + 1 < height || options.periodic).then(|| &grid[(y + 1) % height * width + x]) {
+                rules.add_adjacency_symmetric(tile.clone(), below.clone(), Direction::Down);
+            }
+        }
+    }
+    Ok(rules)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_debroglie_import() {
+        let json = r#"{
+            "src": "castle.png",
+            "width": 20, "height": 20,
+            "tiles": [
+                {"value": "grass", "multiplyFrequency": 3},
+                {"value": "road", "tileSymmetry": "I"},
+                {"value": 7}
+            ],
+            "adjacencies": [
+                {"left": ["grass", "road"], "right": ["grass"]},
+                {"up": ["road"], "down": ["road"]},
+                {"left": ["grass"], "right": ["water"]}
+            ],
+            "rotationalSymmetry": 4
+        }"#;
+        let rules = from_debroglie_json(json).unwrap();
+        let id = |s: &str| s.to_string();
+        assert_eq!(rules.get_weight("grass"), Some(3));
+        assert!(rules.get_tile_info(&id("road_90")).is_some());
+        assert!(rules.get_tile_info(&id("7")).is_some());
+        assert!(rules.get_tile_info(&id("water")).is_some());
+
+        assert!(rules.is_compatible(&id("road"), &id("grass"), Direction::Right));
+        assert!(rules.is_compatible(&id("road"), &id("road"), Direction::Down));
+        // The vertical road rule, turned a quarter, joins horizontal roads sideways
+        assert!(rules.is_compatible(&id("road_90"), &id("road_90"), Direction::Right));
+        assert!(!rules.is_compatible(&id("road"), &id("road"), Direction::Right));
+    }
+
+    #[test]
+    fn test_debroglie_fractional_frequencies() {
+        let json = r#"{
+            "tiles": [
+                {"value": "tower", "multiplyFrequency": 0.2},
+                {"value": "wall", "multiplyFrequency": 0.4},
+                {"value": "grass"},
+                {"value": "rare", "multiplyFrequency": 0.0001},
+                {"value": "never", "multiplyFrequency": 0}
+            ],
+            "adjacencies": [{"left": ["grass"], "right": ["road"]}]
+        }"#;
+        let rules = from_debroglie_json(json).unwrap();
+        for (id, weight) in [("tower", 1), ("wall", 2), ("grass", 5), ("road", 5), ("never", 0)] {
+            assert_eq!(rules.get_weight(id), Some(weight), "{}", id);
+        }
+        // Too rare to keep its ratio, but still a possible tile
+        assert!(rules.get_weight("rare").unwrap() >= 1);
+
+        // Whole frequencies stay as they are
+        let whole = from_debroglie_json(r#"{"tiles": [{"value": "a", "multiplyFrequency": 3}, {"value": "b"}]}"#).unwrap();
+        assert_eq!((whole.get_weight("a"), whole.get_weight("b")), (Some(3), Some(1)));
+    }
+
+    #[test]
+    fn test_simple_tiled_export() {
+        let rules = RuleSet::from_json(r#"{
+            "tiles": [
+                {"id": "grass", "weight": 4},
+                {"id": "corner", "symmetry": "L"},
+                {"id": "a<b"}
+            ],
+            "rules": [
+                {"from": "grass", "to": "grass", "direction": "Right"},
+                {"from": "grass", "to": "grass", "direction": "Left"},
+                {"from": "corner", "to": "grass", "direction": "Right", "symmetric": true},
+                {"from": "a<b", "to": "grass", "direction": "Right"}
+            ]
+        }"#).unwrap();
+        let xml = to_simple_tiled_xml(&rules);
+        assert!(xml.contains(r#"<tile name="grass" symmetry="X" weight="4"/>"#));
+        assert!(xml.contains(r#"<tile name="corner" symmetry="L" weight="1"/>"#));
+        assert!(!xml.contains("corner_90"));
+        assert!(xml.contains(r#"<neighbor left="grass 0" right="grass 0"/>"#));
+        assert!(xml.contains(r#"<neighbor left="corner 0" right="grass 0"/>"#));
+        // corner_90, a quarter turn clockwise, is three counterclockwise turns there
+        assert!(xml.contains(r#"<neighbor left="corner 3" right="grass 0"/>"#));
+        assert!(xml.contains(r#"<neighbor left="grass 0" right="corner 2"/>"#));
+        // One-way rules have no SimpleTiled equivalent
+        assert!(!xml.contains("a&lt;b 0"));
+        assert!(xml.contains(r#"<tile name="a&lt;b""#));
+    }
+
+    #[test]
+    #[cfg(feature = "tiled")]
+    fn test_tiled_import() {
+        let tmx = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <map version="1.10" orientation="orthogonal" width="3" height="2" tilewidth="16" tileheight="16" infinite="0">
+              <tileset firstgid="1" name="terrain" tilewidth="16" tileheight="16" tilecount="4" columns="2"/>
+              <tileset firstgid="5" source="props.tsx"/>
+              <layer id="1" name="ground" width="3" height="2">
+                <data encoding="csv">
+            1,1,2,
+            2147483649,0,5
+            </data>
+              </layer>
+              <layer id="2" name="xml" width="2" height="1">
+                <data><tile gid="2"/><tile/></data>
+              </layer>
+            </map>"#;
+        let rules = from_tiled_tmx(tmx, None).unwrap();
+        let mut ids: Vec<&TileId> = rules.get_all_tile_ids();
+        ids.sort();
+        assert_eq!(ids, ["props_0", "terrain_0", "terrain_1"]);
+        // The horizontally flipped GID still counts as terrain_0
+        assert_eq!(rules.get_weight("terrain_0"), Some(3));
+        assert_eq!(rules.get_tile_info(&"props_0".to_string()).unwrap().metadata["tiled_gid"], 5);
+
+        let t0 = "terrain_0".to_string();
+        assert!(rules.is_compatible(&t0, &t0, Direction::Right));
+        assert!(rules.is_compatible(&t0, &"terrain_1".to_string(), Direction::Right));
+        assert!(rules.is_compatible(&t0, &t0, Direction::Down));
+        assert!(rules.is_compatible(&"terrain_1".to_string(), &"props_0".to_string(), Direction::Down));
+        // The empty cell keeps props_0 from touching anything sideways
+        assert!(!rules.is_compatible(&t0, &"props_0".to_string(), Direction::Right));
+
+        // Periodic maps also join opposite edges; unweighted, every tile counts the same
+        let periodic = from_tiled_tmx_with(tmx, None, &LearnOptions { periodic: true, ..LearnOptions::default() }).unwrap();
+        assert!(periodic.is_compatible(&"props_0".to_string(), &t0, Direction::Right));
+        assert!(periodic.is_compatible(&"props_0".to_string(), &"terrain_1".to_string(), Direction::Down));
+        assert!(periodic.tiles.values().all(|info| info.weight == 1));
+
+        let xml_layer = from_tiled_tmx(tmx, Some("xml")).unwrap();
+        assert_eq!(xml_layer.tiles.len(), 1);
+        assert!(matches!(from_tiled_tmx(tmx, Some("missing")), Err(WfcError::Import(_))));
+        let base64 = tmx.replace(r#"<data encoding="csv">"#, r#"<data encoding="base64">"#);
+        assert!(matches!(from_tiled_tmx(&base64, None), Err(WfcError::Import(_))));
+    }
+
+    #[test]
+    fn test_debroglie_import_errors() {
+        assert!(matches!(from_debroglie_json(r#"{"tiles": [{"value": "a", "tileSymmetry": "Q"}]}"#), Err(WfcError::Import(_))));
+        assert!(matches!(from_debroglie_json(r#"{"tiles": [{"value": "a"}], "rotationalSymmetry": 3}"#), Err(WfcError::Import(_))));
+        assert!(matches!(from_debroglie_json(r#"{"adjacencies": [{"left": ["a"]}]}"#), Err(WfcError::Import(_))));
+        assert!(matches!(from_debroglie_json(r#"{}"#), Err(WfcError::NoTilesDefined)));
+    }
+}
@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+use crate::{TileId, Direction};
+use crate::ruleset::RuleSet;
+use crate::image::Image;
+use crate::error::WfcError;
+
+/// Result of learning a tileset from a sample image
+#[derive(Debug, Clone)]
+pub struct LearnedTileset {
+    pub rules: RuleSet,
+    /// Artwork for every learned tile, in order of first appearance
+    pub tiles: Vec<(TileId, Image)>,
+    /// How often each tile occurred, summed over every symmetry variant of the sample
+    pub counts: HashMap<TileId, u32>,
+}
+
+/// Options for `learn_from_image_with`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LearnOptions {
+    /// Number of rotations/reflections of the sample to learn from (1..=8)
+    pub symmetry: usize,
+    /// Treat tiles that are rotations/reflections of an earlier tile as that tile. This
+    /// keeps the tile count down for orientation-free artwork (rocks, flowers), but the
+    /// merged tile's adjacency then ignores orientation, so leave it off for roads/walls.
+    pub merge_rotations: bool,
+    /// Treat the sample as a torus, so tiles on opposite edges are also learned as
+    /// neighbors (the reference implementation's `periodicInput`)
+    pub periodic: bool,
+    /// Weight each tile by how often it occurs in the sample (over every symmetry variant),
+    /// so the output's tile proportions resemble the sample's. Otherwise every tile gets
+    /// weight 1.
+    pub frequency_weights: bool,
+}
+
+impl Default for LearnOptions {
+    fn default() -> Self {
+        LearnOptions {
+            symmetry: 1,
+            merge_rotations: false,
+            periodic: false,
+            frequency_weights: false,
+        }
+    }
+}
+
+/// Infer a RuleSet from a sample grid of tile IDs (row-major, `width * height` entries).
+/// Every pair of orthogonally adjacent tiles in the sample becomes an allowed adjacency,
+/// recorded in both directions.
+pub fn learn_from_grid(grid: &[TileId], width: usize, height: usize) -> Result<RuleSet, WfcError> {
+    learn_from_grid_with(grid, width, height, &LearnOptions::default())
+}
+
+/// `learn_from_grid` with options; only `periodic` and `frequency_weights` apply to grids
+pub fn learn_from_grid_with(grid: &[TileId], width: usize, height: usize, options: &LearnOptions) -> Result<RuleSet, WfcError> {
+    if width == 0 || height == 0 || grid.len() != width * height {
+        return Err(WfcError::InvalidDimensions { width, height });
+    }
+
+    let mut rules = RuleSet::new();
+    for id in grid {
+        if rules.get_tile_info(id).is_none() {
+            rules.add_tile(id.clone(), 1);
+        }
+    }
+    record_adjacency(&mut rules, grid, width, height, options.periodic);
+    if options.frequency_weights {
+        apply_frequency_weights(&mut rules, grid);
+    }
+
+    Ok(rules)
+}
+
+/// Set the weight of every tile in `rules` to its number of occurrences in `grid`, e.g. to
+/// match hand-written rules to the proportions of an example map. Tiles absent from `grid`
+/// keep their weight.
+pub fn apply_frequency_weights(rules: &mut RuleSet, grid: &[TileId]) {
+    let mut counts: HashMap<&TileId, u32> = HashMap::new();
+    for id in grid {
+        *counts.entry(id).or_insert(0) += 1;
+    }
+    for (id, count) in counts {
+        if let Some(info) = rules.tiles.get_mut(id) {
+            info.weight = count;
+        }
+    }
+}
+
+/// Slice a sample image into `tile_size`x`tile_size` tiles and infer a RuleSet from how they
+/// are arranged. Pixel-identical tiles share an ID. `symmetry` (1..=8) additionally learns
+/// from rotated/reflected copies of the sample, like the reference implementation.
+pub fn learn_from_image(sample: &Image, tile_size: usize, symmetry: usize) -> Result<LearnedTileset, WfcError> {
+    learn_from_image_with(sample, tile_size, &LearnOptions { symmetry, ..LearnOptions::default() })
+}
+
+/// `learn_from_image` with full control over duplicate detection
+pub fn learn_from_image_with(sample: &Image, tile_size: usize, options: &LearnOptions) -> Result<LearnedTileset, WfcError> {
+    if tile_size == 0 || !sample.width.is_multiple_of(tile_size) || !sample.height.is_multiple_of(tile_size) {
+        return Err(WfcError::InvalidImage(format!(
+            "{}x{} sample is not divisible into {}px tiles",
+            sample.width, sample.height, tile_size
+        )));
+    }
+
+    let mut ids: HashMap<Image, TileId> = HashMap::new();
+    let mut tiles: Vec<(TileId, Image)> = Vec::new();
+    let mut counts: HashMap<TileId, u32> = HashMap::new();
+    let mut rules = RuleSet::new();
+
+    for variant in sample.dihedral_variants(options.symmetry) {
+        let width = variant.width / tile_size;
+        let height = variant.height / tile_size;
+        let mut grid = Vec::with_capacity(width * height);
+
+        for y in 0..height {
+            for x in 0..width {
+                let tile = variant.crop(x * tile_size, y * tile_size, tile_size, tile_size);
+                let id = match ids.get(&tile) {
+                    Some(id) => id.clone(),
+                    None => {
+                        let id = format!("tile_{}", tiles.len());
+                        rules.add_tile(id.clone(), 1);
+                        // Register every orientation up front so later rotated copies hit the lookup
+                        let aliases = if options.merge_rotations { tile.dihedral_variants(8) } else { vec![tile.clone()] };
+                        for alias in aliases {
+                            ids.entry(alias).or_insert_with(|| id.clone());
+                        }
+                        tiles.push((id.clone(), tile));
+                        id
+                    }
+                };
+                *counts.entry(id.clone()).or_insert(0) += 1;
+                grid.push(id);
+            }
+        }
+
+        record_adjacency(&mut rules, &grid, width, height, options.periodic);
+    }
+
+    if options.frequency_weights {
+        for (id, &count) in &counts {
+            if let Some(info) = rules.tiles.get_mut(id) {
+                info.weight = count;
+            }
+        }
+    }
+
+    Ok(LearnedTileset { rules, tiles, counts })
+}
+
+fn record_adjacency(rules: &mut RuleSet, grid: &[TileId], width: usize, height: usize, periodic: bool) {
+    for y in 0..height {
+        for x in 0..width {
+            let tile = &grid[y * width + x];
+            if x + 1 < width || periodic {
+                let right = &grid[y * width + (x + 1) % width];
+                rules.add_adjacency(tile.clone(), right.clone(), Direction::Right);
+                rules.add_adjacency(right.clone(), tile.clone(), Direction::Left);
+            }
+            if y + 1 < height || periodic {
+                let below = &grid[(y + 1) % height * width + x];
+                rules.add_adjacency(tile.clone(), below.clone(), Direction::Down);
+                rules.add_adjacency(below.clone(), tile.clone(), Direction::Up);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_learn_from_grid() {
+        let grid: Vec<TileId> = ["a", "b", "a", "b"].iter().map(|s| s.to_string()).collect();
+        let rules = learn_from_grid(&grid, 2, 2).unwrap();
+
+        assert_eq!(rules.tiles.len(), 2);
+        assert!(rules.get_valid_neighbors(&"a".to_string(), Direction::Right).unwrap().contains("b"));
+        assert!(rules.get_valid_neighbors(&"b".to_string(), Direction::Left).unwrap().contains("a"));
+        assert!(rules.get_valid_neighbors(&"a".to_string(), Direction::Down).unwrap().contains("a"));
+        assert!(rules.get_valid_neighbors(&"a".to_string(), Direction::Left).is_none());
+
+        assert!(matches!(learn_from_grid(&grid, 3, 2), Err(WfcError::InvalidDimensions { .. })));
+    }
+
+    #[test]
+    fn test_learn_periodic_wraps_edges() {
+        let grid: Vec<TileId> = ["a", "b", "c"].iter().map(|s| s.to_string()).collect();
+        let open = learn_from_grid(&grid, 3, 1).unwrap();
+        assert!(open.get_valid_neighbors(&"c".to_string(), Direction::Right).is_none());
+
+        let options = LearnOptions { periodic: true, ..LearnOptions::default() };
+        let wrapped = learn_from_grid_with(&grid, 3, 1, &options).unwrap();
+        assert!(wrapped.is_compatible(&"c".to_string(), &"a".to_string(), Direction::Right));
+        // A single row wraps onto itself vertically
+        assert!(wrapped.is_compatible(&"b".to_string(), &"b".to_string(), Direction::Down));
+    }
+
+    #[test]
+    fn test_learn_from_image_dedupes_tiles() {
+        // 2x1 tiles of 2px: left tile red, right tile red -> a single learned tile
+        let red = [255, 0, 0, 255];
+        let sample = Image::from_pixels(4, 2, vec![red; 8]).unwrap();
+        let learned = learn_from_image(&sample, 2, 1).unwrap();
+        assert_eq!(learned.tiles.len(), 1);
+        assert!(learned.rules.get_valid_neighbors(&"tile_0".to_string(), Direction::Right).unwrap().contains("tile_0"));
+
+        assert!(learn_from_image(&sample, 3, 1).is_err());
+    }
+
+    #[test]
+    fn test_learn_counts_and_merges_rotations() {
+        // An asymmetric 2px tile on the left and its quarter turn on the right
+        let tile = Image::from_pixels(2, 2, vec![[1, 0, 0, 255], [0, 0, 0, 255], [0, 0, 0, 255], [0, 0, 0, 255]]).unwrap();
+        let mut sample = Image::new(4, 2);
+        sample.blit(&tile, 0, 0);
+        sample.blit(&tile.rotate_clockwise(), 2, 0);
+
+        let plain = learn_from_image(&sample, 2, 1).unwrap();
+        assert_eq!(plain.tiles.len(), 2);
+        assert_eq!(plain.counts["tile_0"], 1);
+
+        let options = LearnOptions { merge_rotations: true, ..LearnOptions::default() };
+        let merged = learn_from_image_with(&sample, 2, &options).unwrap();
+        assert_eq!(merged.tiles.len(), 1);
+        assert_eq!(merged.counts["tile_0"], 2);
+        assert!(merged.rules.get_valid_neighbors(&"tile_0".to_string(), Direction::Right).unwrap().contains("tile_0"));
+        assert_eq!(merged.rules.get_weight("tile_0"), Some(1));
+
+        let weighted = LearnOptions { frequency_weights: true, ..options };
+        assert_eq!(learn_from_image_with(&sample, 2, &weighted).unwrap().rules.get_weight("tile_0"), Some(2));
+    }
+
+    #[test]
+    fn test_frequency_weights_follow_sample() {
+        let grid: Vec<TileId> = ["a", "a", "a", "b", "a", "a"].iter().map(|s| s.to_string()).collect();
+        let options = LearnOptions { frequency_weights: true, ..LearnOptions::default() };
+        let rules = learn_from_grid_with(&grid, 3, 2, &options).unwrap();
+        assert_eq!(rules.get_weight("a"), Some(5));
+        assert_eq!(rules.get_weight("b"), Some(1));
+
+        let mut rules = learn_from_grid(&grid, 3, 2).unwrap();
+        rules.add_tile("c".to_string(), 7);
+        apply_frequency_weights(&mut rules, &grid);
+        assert_eq!((rules.get_weight("a"), rules.get_weight("c")), (Some(5), Some(7)));
+    }
+
+    #[test]
+    fn test_learn_from_image_symmetry_adds_variants() {
+        // A single asymmetric 2x2 tile gains rotated variants with symmetry 8
+        let sample = Image::from_pixels(2, 2, vec![[1, 0, 0, 255], [0, 0, 0, 255], [0, 0, 0, 255], [0, 0, 0, 255]]).unwrap();
+        assert_eq!(learn_from_image(&sample, 2, 1).unwrap().tiles.len(), 1);
+        assert_eq!(learn_from_image(&sample, 2, 8).unwrap().tiles.len(), 4);
+    }
+}
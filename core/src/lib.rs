@@ -2,19 +2,68 @@ pub mod model;
 pub mod ruleset;
 pub mod error;
 pub mod wasm;
+pub mod image;
+pub mod learn;
+pub mod render;
+pub mod bench;
+pub mod trace;
+pub mod observer;
+pub mod synthesis;
+pub mod annealing;
+pub mod atlas;
+pub mod vox;
+pub mod topology;
+pub mod symmetry;
+pub mod sockets;
+pub mod intern;
+pub mod overlapping;
+pub mod transition;
+pub mod parallel;
+pub mod analysis;
+pub mod manifest;
+pub mod interop;
+pub mod heuristic;
+pub mod constraints;
+pub mod connectivity;
+pub mod biome;
+pub mod volume;
+pub mod chunked;
+#[cfg(feature = "debug-ui")]
+pub mod debug_ui;
+mod clock;
 
 use serde::{Deserialize, Serialize};
 
 pub type TileId = String;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+/// Anything usable as a tile in `RuleSet`/`Model`: `TileId` strings by default, or small
+/// integers, `Arc<str>` and the like to skip string hashing and cloning. `Display` names the
+/// tile in traces and tag lookups.
+pub trait TileKey: Clone + Eq + std::hash::Hash + Ord + std::fmt::Debug + std::fmt::Display + Send + Sync + 'static {}
+
+impl<T> TileKey for T where T: Clone + Eq + std::hash::Hash + Ord + std::fmt::Debug + std::fmt::Display + Send + Sync + 'static {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub enum Direction {
     Up,
     Right,
     Down,
     Left,
+    /// Towards the next layer of a volume (`z + 1`), see `volume::Model3D`
+    Forward,
+    /// Towards the previous layer of a volume (`z - 1`)
+    Backward,
+    /// Diagonal neighbors, only used by topologies with diagonal adjacency such as
+    /// `topology::DiagonalGrid`
+    UpRight,
+    DownRight,
+    DownLeft,
+    UpLeft,
 }
 
+/// The four directions of a plain 2D grid, clockwise from `Up`
+pub const DIRECTIONS: [Direction; 4] = [Direction::Up, Direction::Right, Direction::Down, Direction::Left];
+
 impl Direction {
     pub fn opposite(&self) -> Self {
         match self {
@@ -22,16 +71,52 @@ impl Direction {
             Direction::Right => Direction::Left,
             Direction::Down => Direction::Up,
             Direction::Left => Direction::Right,
+            Direction::Forward => Direction::Backward,
+            Direction::Backward => Direction::Forward,
+            Direction::UpRight => Direction::DownLeft,
+            Direction::DownRight => Direction::UpLeft,
+            Direction::DownLeft => Direction::UpRight,
+            Direction::UpLeft => Direction::DownRight,
+        }
+    }
+
+    /// Whether the direction points at a corner neighbor, e.g. `UpLeft`
+    pub fn is_diagonal(&self) -> bool {
+        matches!(self, Direction::UpRight | Direction::DownRight | Direction::DownLeft | Direction::UpLeft)
+    }
+
+    /// The direction seen in a left-right mirror image: `Left` and `Right` swap, and so do
+    /// the diagonals on either side
+    pub fn mirrored(&self) -> Self {
+        match self {
+            Direction::Left | Direction::Right => self.opposite(),
+            Direction::UpRight => Direction::UpLeft,
+            Direction::UpLeft => Direction::UpRight,
+            Direction::DownRight => Direction::DownLeft,
+            Direction::DownLeft => Direction::DownRight,
+            _ => *self,
         }
     }
 
-    /// Rotate direction clockwise by 90 degrees
+    /// Whether the direction lies in the plane of a 2D grid, i.e. is not `Forward` or
+    /// `Backward`
+    pub fn is_planar(&self) -> bool {
+        !matches!(self, Direction::Forward | Direction::Backward)
+    }
+
+    /// Rotate direction clockwise by 90 degrees (about the depth axis, which `Forward` and
+    /// `Backward` stay on)
     pub fn rotate_clockwise(&self) -> Self {
         match self {
             Direction::Up => Direction::Right,
             Direction::Right => Direction::Down,
             Direction::Down => Direction::Left,
             Direction::Left => Direction::Up,
+            Direction::UpRight => Direction::DownRight,
+            Direction::DownRight => Direction::DownLeft,
+            Direction::DownLeft => Direction::UpLeft,
+            Direction::UpLeft => Direction::UpRight,
+            Direction::Forward | Direction::Backward => *self,
         }
     }
 
@@ -42,6 +127,11 @@ impl Direction {
             Direction::Right => Direction::Up,
             Direction::Down => Direction::Right,
             Direction::Left => Direction::Down,
+            Direction::UpRight => Direction::UpLeft,
+            Direction::UpLeft => Direction::DownLeft,
+            Direction::DownLeft => Direction::DownRight,
+            Direction::DownRight => Direction::UpRight,
+            Direction::Forward | Direction::Backward => *self,
         }
     }
 }
@@ -83,14 +173,15 @@ impl SymmetryType {
     }
 
     /// Get the transformations (rotation + reflection) for this symmetry type
-    /// Returns a vector of (rotation_degrees, reflect_horizontal, reflect_vertical)
+    /// Returns a vector of (rotation_degrees, reflect_horizontal, reflect_vertical),
+    /// the web editor's table of `variants()` in the same order
     pub fn transformations(&self) -> Vec<(u16, bool, bool)> {
         match self {
             SymmetryType::X => vec![(0, false, false)],
-            SymmetryType::I => vec![(0, false, false), (0, true, false)],
+            SymmetryType::I => vec![(0, false, false), (90, false, false)],
             SymmetryType::T => vec![(0, false, false), (90, false, false), (180, false, false), (270, false, false)],
             SymmetryType::L => vec![(0, false, false), (90, false, false), (180, false, false), (270, false, false)],
-            SymmetryType::Backslash => vec![(0, false, false), (0, true, true)],
+            SymmetryType::Backslash => vec![(0, false, false), (90, false, false)],
             SymmetryType::F => vec![
                 (0, false, false), (90, false, false), (180, false, false), (270, false, false),
                 (0, true, false), (90, true, false), (180, true, false), (270, true, false),
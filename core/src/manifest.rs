@@ -0,0 +1,113 @@
+//! Reproducibility manifests.
+//!
+//! A manifest records everything that went into a finished run (seed, dimensions, a hash of
+//! the ruleset, crate version and solver options) next to the grid it produced, so a saved
+//! map can be traced back to exactly how it was made and regenerated.
+
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use crate::{Direction, TileId};
+use crate::error::WfcError;
+use crate::ruleset::RuleSet;
+use crate::transition::{UsageDecay, WeightGradient};
+
+pub const MANIFEST_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// Layout version of the manifest itself
+    pub manifest_version: u32,
+    /// Version of this crate that ran the solver
+    pub crate_version: String,
+    pub seed: u64,
+    pub width: usize,
+    pub height: usize,
+    pub ruleset_hash: String,
+    pub config: ManifestConfig,
+    pub grid: Vec<TileId>,
+}
+
+/// Solver options that influence the output beyond the ruleset and seed. Cells fixed by hand
+/// before the run (`carve_path`, constraints) are not listed; the grid shows their outcome.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ManifestConfig {
+    /// `Debug` form of the topology, e.g. `SquareGrid { width: 8, height: 8 }`
+    pub topology: String,
+    /// `Debug` form of the cell selection heuristic, e.g. `MinEntropy`
+    #[serde(default = "default_heuristic")]
+    pub heuristic: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub transitions: Vec<TransitionEntry>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage_decay: Option<UsageDecay>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub cluster_bonus: BTreeMap<String, f64>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub max_run_lengths: BTreeMap<TileId, usize>,
+    /// In the order they were added
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub weight_gradients: Vec<WeightGradient>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fallback_tile: Option<TileId>,
+}
+
+/// Manifests written before heuristics were selectable always used minimum entropy
+fn default_heuristic() -> String {
+    "MinEntropy".to_string()
+}
+
+/// One `TransitionWeights::set` call
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransitionEntry {
+    pub from: TileId,
+    pub direction: Direction,
+    pub to: TileId,
+    pub factor: f64,
+}
+
+impl Manifest {
+    pub fn to_json_string(&self) -> Result<String, WfcError> {
+        serde_json::to_string(self).map_err(WfcError::JsonParseError)
+    }
+
+    pub fn from_json(json: &str) -> Result<Manifest, WfcError> {
+        serde_json::from_str(json).map_err(WfcError::JsonParseError)
+    }
+
+    /// Whether `rules` is the ruleset this manifest was generated with
+    pub fn matches_rules(&self, rules: &RuleSet) -> bool {
+        self.ruleset_hash == rules.content_hash()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Model;
+
+    #[test]
+    fn test_manifest_reproduces_run() {
+        let rules = RuleSet::from_json(r#"{
+            "tiles": [{"id": "a", "weight": 2}, {"id": "b"}],
+            "rules": [
+                {"from": "a", "to": "a", "direction": "Right", "symmetric": true},
+                {"from": "a", "to": "b", "direction": "Right", "symmetric": true},
+                {"from": "b", "to": "b", "direction": "Right", "symmetric": true},
+                {"from": "a", "to": "a", "direction": "Down", "symmetric": true},
+                {"from": "b", "to": "b", "direction": "Down", "symmetric": true}
+            ]
+        }"#).unwrap();
+        let mut model = Model::new(6, 3, rules.clone(), None).unwrap();
+        model.set_max_run_length("a".to_string(), 3);
+        assert!(model.manifest().is_err());
+        model.run().unwrap();
+
+        let manifest = Manifest::from_json(&model.manifest().unwrap().to_json_string().unwrap()).unwrap();
+        assert!(manifest.matches_rules(&rules));
+        assert_eq!(manifest.config.max_run_lengths["a"], 3);
+
+        let mut replay = Model::new(manifest.width, manifest.height, rules, Some(manifest.seed)).unwrap();
+        replay.set_max_run_length("a".to_string(), 3);
+        assert_eq!(replay.run().unwrap(), manifest.grid);
+    }
+}
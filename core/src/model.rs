@@ -1,28 +1,544 @@
-use std::collections::HashSet;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeSet, BinaryHeap, HashMap, HashSet};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+use std::time::Duration;
 use rand::prelude::*;
-use crate::{TileId, Direction};
-use crate::ruleset::RuleSet;
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+use crate::{Direction, TileId, TileKey};
+use crate::ruleset::{MissingAdjacency, RuleSet};
 use crate::error::WfcError;
+use crate::clock::Stopwatch;
+use crate::observer::{Observer, Observers, SolverEvent};
+use crate::trace::{Trace, TraceEvent, TRACE_SCHEMA, TRACE_VERSION};
+use crate::topology::{CellGraph, DiagonalGrid, MaskedGrid, SquareGrid, Topology, WrappingGrid};
+use crate::heuristic::{Candidate, Frontier, Heuristic, MinEntropy};
+use crate::transition::{ClusterBonus, TransitionWeights, UsageDecay, WeightGradient};
+use crate::manifest::{Manifest, ManifestConfig, TransitionEntry, MANIFEST_VERSION};
+use crate::constraints::{border_cells, Constraints, CountConstraint, Rect};
+use crate::connectivity::{WaveConstraint, WaveView};
 
 #[derive(Debug, Clone)]
-pub struct Cell {
+pub struct Cell<T = TileId> {
     pub collapsed: bool,
-    pub possibilities: HashSet<TileId>,
+    pub possibilities: HashSet<T>,
+}
+
+/// Counters describing how much work the solver has done
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SolverStats {
+    /// Cells collapsed to a single tile
+    pub observations: u64,
+    /// Neighbor constraint checks performed during propagation
+    pub propagation_ops: u64,
+    /// Tile possibilities removed from cells
+    pub bans: u64,
+    /// Entropy calculations performed while selecting cells
+    pub entropy_evaluations: u64,
+    /// Decisions undone by backtracking
+    pub backtracks: u64,
+    /// Contradictions resolved by placing the fallback tile (see `Model::set_fallback_tile`),
+    /// or by leaving the cell empty in `Model::run_greedy`
+    #[serde(default)]
+    pub fallbacks: u64,
+    /// Searches started over with a fresh seed (see `RunConfig`)
+    #[serde(default)]
+    pub restarts: u64,
+}
+
+/// Limits on the backtracking search, set with `Model::set_run_config`. An attempt that
+/// runs out of options or exceeds a limit is exhausted, and `on_exhaustion` decides what
+/// happens next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunConfig {
+    /// Decisions one attempt may undo in total; `None` searches exhaustively
+    pub max_backtracks: Option<u64>,
+    /// Decisions a single contradiction may undo before the attempt is abandoned, which
+    /// catches the deep unwinding that signals a hopeless early choice
+    pub max_backtrack_depth: Option<usize>,
+    /// Fresh attempts allowed with `OnExhaustion::Restart`
+    pub max_restarts: u32,
+    pub on_exhaustion: OnExhaustion,
+    /// Steps one `run` call may take before failing with `BudgetExceeded`
+    #[serde(default)]
+    pub max_iterations: Option<u64>,
+    /// Milliseconds one `run` call may take before failing with `BudgetExceeded`
+    #[serde(default)]
+    pub max_millis: Option<u64>,
+}
+
+impl Default for RunConfig {
+    fn default() -> Self {
+        RunConfig {
+            max_backtracks: None,
+            max_backtrack_depth: None,
+            max_restarts: 3,
+            on_exhaustion: OnExhaustion::Fail,
+            max_iterations: None,
+            max_millis: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OnExhaustion {
+    /// Fail with `Contradiction` when the search ran out of options, otherwise with
+    /// `BacktrackLimitExceeded`
+    #[default]
+    Fail,
+    /// Undo every decision and search again with a seed drawn from the current one,
+    /// keeping edits made with `force`/`ban`, up to `max_restarts` times before failing.
+    /// The new seed replaces the model's own, so manifests still replay the final attempt.
+    Restart,
+}
+
+/// Wall-clock time spent in each phase of the run loop
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PhaseTimings {
+    /// Finding the lowest-entropy cell
+    pub selection: Duration,
+    /// Weighted random choice of a tile for the selected cell
+    pub collapse: Duration,
+    /// Constraint propagation after a collapse
+    pub propagation: Duration,
+    /// Undoing decisions after a contradiction
+    pub backtracking: Duration,
+}
+
+impl PhaseTimings {
+    pub fn total(&self) -> Duration {
+        self.selection + self.collapse + self.propagation + self.backtracking
+    }
+}
+
+/// Summary of the work done by `Model::run`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RunReport {
+    pub stats: SolverStats,
+    pub timings: PhaseTimings,
+}
+
+/// Outcome of a single `Model::step`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StepStatus {
+    /// A cell was collapsed (or a contradiction was backtracked); more work remains
+    Running,
+    /// Every cell is collapsed
+    Done,
+}
+
+/// Per-cell view of the wave, for rendering the superposition "haze" mid-run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WaveSnapshot<T = TileId> {
+    pub width: usize,
+    pub height: usize,
+    /// Tile order of the `probabilities` columns (sorted by ID)
+    pub tiles: Vec<T>,
+    /// Remaining possibility count per cell, row-major
+    pub counts: Vec<u32>,
+    /// Row-major `cells x tiles` matrix of each tile's share of the cell's remaining weight;
+    /// banned tiles are 0 and each cell's row sums to 1 unless the cell is contradicted
+    pub probabilities: Vec<f32>,
+}
+
+/// Per-cell uncertainty of the wave, for heatmaps while stepping
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EntropyMap {
+    pub width: usize,
+    pub height: usize,
+    /// Weighted Shannon entropy of each cell's remaining tiles in bits, row-major; 0 once a
+    /// cell is down to one tile (or none)
+    pub entropy: Vec<f64>,
+    /// Remaining possibility count per cell, row-major
+    pub counts: Vec<u32>,
+    /// Entropy of a cell with every tile still possible, for normalizing `entropy` to 0..1
+    pub max_entropy: f64,
+}
+
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// A paused run, from `Model::to_snapshot`: the wave, the backtracking log, the RNG state,
+/// the ruleset and the solver settings, so `Model::from_snapshot` carries on exactly as the
+/// original model would have. Traces and observers are not included.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelSnapshot {
+    pub snapshot_version: u32,
+    pub width: usize,
+    pub height: usize,
+    pub seed: u64,
+    /// The ruleset as `RuleSet::to_json_string` writes it
+    pub rules: String,
+    pub ruleset_hash: String,
+    pub config: ManifestConfig,
+    pub stats: SolverStats,
+    rng: ChaCha12Rng,
+    cells: Vec<CellState>,
+    history: Vec<Decision<TileId>>,
+    trail: Vec<Change<TileId>>,
+    pins: Vec<(usize, Edit<TileId>)>,
+    run_config: RunConfig,
+    run_start: Option<usize>,
+    attempt_backtracks: u64,
+    fallback_cells: BTreeSet<usize>,
+    #[serde(default)]
+    backtracking_spent: bool,
+    contradiction_at: Option<usize>,
+    observed_at: Vec<u64>,
+    confidence: Vec<CellConfidence>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct CellState {
+    collapsed: bool,
+    /// Sorted by ID
+    possibilities: Vec<TileId>,
+}
+
+impl ModelSnapshot {
+    pub fn to_json_string(&self) -> Result<String, WfcError> {
+        serde_json::to_string(self).map_err(WfcError::JsonParseError)
+    }
+
+    pub fn from_json(json: &str) -> Result<ModelSnapshot, WfcError> {
+        serde_json::from_str(json).map_err(WfcError::JsonParseError)
+    }
+}
+
+/// Periodic copies of the collapsed-so-far grid, taken by `Model::run_collect_frames`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Frames<T = TileId> {
+    pub width: usize,
+    pub height: usize,
+    /// Tile order the frame indices refer to (sorted by ID)
+    pub tiles: Vec<T>,
+    /// Row-major indices into `tiles` per frame, `Frames::OPEN` for cells not collapsed yet.
+    /// The last frame is the finished grid.
+    pub frames: Vec<Vec<u32>>,
+}
+
+impl<T> Frames<T> {
+    /// Frame entry of a cell that is not collapsed
+    pub const OPEN: u32 = u32::MAX;
+}
+
+/// Shared flag for stopping a run from another thread or callback. Clones share the flag;
+/// hand one to `Model::set_cancel_token` and keep the other.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> CancelToken {
+        CancelToken::default()
+    }
+
+    /// Make the run fail with `Cancelled` before its next step
+    pub fn cancel(&self) {
+        self.0.store(true, AtomicOrdering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(AtomicOrdering::Relaxed)
+    }
+
+    /// Clear the flag, e.g. before a `run_steps` call when a cancellation arrived after the
+    /// last one had already finished
+    pub fn reset(&self) {
+        self.0.store(false, AtomicOrdering::Relaxed);
+    }
+
+    /// Clear the flag, returning whether it was set
+    fn take(&self) -> bool {
+        self.0.swap(false, AtomicOrdering::Relaxed)
+    }
+}
+
+/// Steps and time used so far by one `run` call, checked against `RunConfig`
+struct Budget {
+    iterations: u64,
+    stopwatch: Stopwatch,
+}
+
+impl Budget {
+    fn start() -> Budget {
+        Budget { iterations: 0, stopwatch: Stopwatch::start() }
+    }
+
+    /// Count one more step, failing first if the limits of `config` are used up
+    fn spend(&mut self, config: &RunConfig) -> Result<(), WfcError> {
+        let elapsed_ms = self.stopwatch.elapsed().as_millis() as u64;
+        if config.max_iterations.is_some_and(|limit| self.iterations >= limit)
+            || config.max_millis.is_some_and(|limit| elapsed_ms >= limit)
+        {
+            return Err(WfcError::BudgetExceeded { iterations: self.iterations, elapsed_ms });
+        }
+        self.iterations += 1;
+        Ok(())
+    }
+}
+
+/// Grid found by `Model::run_with_retries`, and the attempt that found it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RetryOutcome<T = TileId> {
+    pub grid: Vec<T>,
+    /// 0 for the first attempt
+    pub attempt: u32,
+    /// Seed of the successful attempt, which the model now reports as its own
+    pub seed: u64,
+}
+
+/// Rough result of `Model::run_greedy`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Preview<T = TileId> {
+    pub width: usize,
+    pub height: usize,
+    /// Row-major; `None` for cells that ran out of options
+    pub cells: Vec<Option<T>>,
+    /// Cells where propagation hit a contradiction, ascending. They hold `None`, or the
+    /// fallback tile if one is set.
+    pub conflicts: Vec<usize>,
+}
+
+impl<T> Preview<T> {
+    /// Whether the preview is a valid grid, without conflicts
+    pub fn is_complete(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+}
+
+/// An adjacency in a finished grid that the ruleset does not allow
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Violation<T = TileId> {
+    pub cell: usize,
+    pub neighbor: usize,
+    /// Where `neighbor` lies as seen from `cell`
+    pub direction: Direction,
+    pub tile: T,
+    pub neighbor_tile: T,
+}
+
+/// How undecided a cell was when the solver collapsed it. Cells observed with many
+/// options left were settled by chance rather than by the rules; clusters of them show
+/// where a ruleset is under-constrained.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CellConfidence {
+    /// Tiles still possible just before the collapse; 0 for fallback placements
+    pub options: usize,
+    /// Weighted Shannon entropy of those tiles, in bits
+    pub entropy: f64,
+}
+
+impl CellConfidence {
+    /// Settled with no choice left: forced by propagation or by an edit
+    pub fn is_certain(&self) -> bool {
+        self.options <= 1
+    }
+}
+
+/// Estimated heap footprint of a model, in bytes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryUsage {
+    /// Cells and their remaining possibilities
+    pub wave: usize,
+    /// Undo log kept for backtracking
+    pub history: usize,
+}
+
+impl MemoryUsage {
+    pub fn total(&self) -> usize {
+        self.wave + self.history
+    }
+}
+
+/// Largest width or height accepted by `Model::new`
+pub const MAX_DIMENSION: usize = 500;
+
+/// Largest cell count accepted by `Model::with_topology`
+pub const MAX_CELLS: usize = MAX_DIMENSION * MAX_DIMENSION;
+
+/// Version of the solving algorithm. Bumped whenever a change makes the same rules, options
+/// and seed produce a different grid, so stored seeds can be checked for replayability.
+pub const ALGORITHM_VERSION: u32 = 2;
+
+/// Scale of the per-cell noise that breaks ties between equal entropies
+pub(crate) const ENTROPY_NOISE: f64 = 0.001;
+
+/// A cell's selection score when it was queued; outdated once the cell's `generation` moves on
+#[derive(Debug, Clone, Copy)]
+struct QueueEntry {
+    score: f64,
+    index: usize,
+    generation: u32,
+}
+
+/// Lowest score first, then lowest index, the order a scan of the grid would pick in
+impl Ord for QueueEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.score.total_cmp(&other.score).then(self.index.cmp(&other.index))
+    }
+}
+
+impl PartialOrd for QueueEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for QueueEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for QueueEntry {}
+
+/// Assumed heap bytes per tile ID string when predicting memory for a tile count
+const ESTIMATED_ID_BYTES: usize = 8;
+
+/// One entry of the undo log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+enum Change<T> {
+    /// A tile was removed from a cell's possibilities
+    Ban(usize, T),
+    /// A cell was marked collapsed
+    Collapse(usize),
+    /// The fallback tile was placed in a cell
+    Fallback(usize),
+}
+
+/// A caller's edit to one cell, kept across backtracking
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+enum Edit<T> {
+    Force(T),
+    Ban(T),
+    /// Allow only these tiles, sorted
+    Mask(Vec<T>),
+}
+
+/// A collapse decision that can be undone: everything logged after `trail_len` follows from it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Decision<T> {
+    index: usize,
+    tile: T,
+    trail_len: usize,
 }
 
 #[derive(Debug, Clone)]
-pub struct Model {
+pub struct Model<T = TileId> {
     width: usize,
     height: usize,
-    grid: Vec<Cell>,
-    rules: RuleSet,
-    rng: StdRng,
+    topology: Arc<dyn Topology>,
+    heuristic: Arc<dyn Heuristic>,
+    grid: Vec<Cell<T>>,
+    rules: RuleSet<T>,
+    /// `StdRng`'s algorithm, named so snapshots can serialize it
+    rng: ChaCha12Rng,
+    seed: u64,
+    stats: SolverStats,
+    timings: PhaseTimings,
+    /// Open decisions, oldest first
+    history: Vec<Decision<T>>,
+    /// Every change to the wave since the first open decision. Backtracking pops entries
+    /// back to the decision's `trail_len` instead of restoring a copy of the grid, so the
+    /// log never holds more than one ban per cell and tile.
+    trail: Vec<Change<T>>,
+    trace: Trace,
+    observers: Observers<T>,
+    cancel: Option<CancelToken>,
+    transitions: Option<TransitionWeights<T>>,
+    usage_decay: Option<UsageDecay>,
+    cluster_bonus: Option<ClusterBonus>,
+    /// Weight gradients with the tiles each one matches; later ones take precedence
+    gradients: Vec<(WeightGradient, HashSet<T>)>,
+    /// Longest allowed straight run of each limited tile
+    max_runs: HashMap<T, usize>,
+    /// Tiles matching each of `rules.constraints.counts`, in the same order
+    count_tiles: Vec<HashSet<T>>,
+    wave_constraints: Vec<Arc<dyn WaveConstraint<T>>>,
+    fallback: Option<T>,
+    /// Reported by `result` for cells outside the topology's area
+    outside: Option<T>,
+    /// `force`/`ban` edits, re-applied whenever backtracking undoes changes
+    pins: Vec<(usize, Edit<T>)>,
+    run_config: RunConfig,
+    /// Trail length at the first decision, where a restart returns to
+    run_start: Option<usize>,
+    /// `stats.backtracks` when the current attempt began
+    attempt_backtracks: u64,
+    /// Cells holding the fallback tile, which propagation treats as wildcards
+    fallback_cells: HashSet<usize>,
+    /// Whether the finished grid has been reported, so further steps report nothing
+    finished: bool,
+    /// Set once backtracking gave up in a run with a fallback tile; contradictions then
+    /// place the fallback instead of backtracking
+    backtracking_spent: bool,
+    /// Whether some rule lacks its reverse, so propagation under closed adjacency has to
+    /// check each pair from both tiles' side like `verify_result` does
+    one_sided: bool,
+    /// Cell whose possibilities ran out in the latest contradiction
+    contradiction_at: Option<usize>,
+    /// Observation step at which each cell was last collapsed; stale for cells that
+    /// backtracking has since re-opened
+    observed_at: Vec<u64>,
+    /// Options and entropy each cell had when last collapsed, stale like `observed_at`
+    confidence: Vec<CellConfidence>,
+    /// Cells changed since the last `take_dirty`, flagged and in the order first changed
+    dirty: Vec<bool>,
+    dirty_cells: Vec<usize>,
+    /// Fixed tie-breaking noise of each cell, in `0..1`, derived from the seed
+    noise: Vec<f64>,
+    /// Open cells by score, for heuristics that only look at the cell itself. Entries are
+    /// added when a cell changes and skipped once outdated, so selection costs
+    /// O(log n) per changed cell instead of a scan of the whole grid.
+    queue: BinaryHeap<Reverse<QueueEntry>>,
+    generation: Vec<u32>,
+    /// Cells changed since the queue was last brought up to date
+    rescore: Vec<bool>,
+    rescore_cells: Vec<usize>,
 }
 
-impl Model {
-    pub fn new(width: usize, height: usize, rules: RuleSet, seed: Option<u64>) -> Result<Model, WfcError> {
+impl<T: TileKey> Model<T> {
+    pub fn new(width: usize, height: usize, rules: RuleSet<T>, seed: Option<u64>) -> Result<Model<T>, WfcError> {
         // Requirement 17.1: Invalid Dimensions
-        if width == 0 || height == 0 || width > 500 || height > 500 {
+        if width == 0 || height == 0 || width > MAX_DIMENSION || height > MAX_DIMENSION {
+            return Err(WfcError::InvalidDimensions { width, height });
+        }
+
+        Model::with_topology(Arc::new(SquareGrid::new(width, height)), rules, seed)
+    }
+
+    /// `new` with the left/right edges joined when `wrap_x` and the top/bottom edges joined
+    /// when `wrap_y`, e.g. `wrap_x` alone for a cylindrical world map
+    pub fn with_wrap(width: usize, height: usize, wrap_x: bool, wrap_y: bool, rules: RuleSet<T>, seed: Option<u64>) -> Result<Model<T>, WfcError> {
+        Model::with_topology(grid_topology(width, height, wrap_x, wrap_y)?, rules, seed)
+    }
+
+    /// `with_wrap` with each cell also constrained by its four corner neighbors, through
+    /// rules in the diagonal directions such as `UpLeft` (see `topology::DiagonalGrid`)
+    pub fn with_diagonals(width: usize, height: usize, wrap_x: bool, wrap_y: bool, rules: RuleSet<T>, seed: Option<u64>) -> Result<Model<T>, WfcError> {
+        let topology = DiagonalGrid::new(grid_topology(width, height, wrap_x, wrap_y)?);
+        Model::with_topology(Arc::new(topology), rules, seed)
+    }
+
+    /// Generate only the cells whose `inside` flag is set (row-major, one per cell), such as
+    /// an island outline. The rest are skipped by cell selection, treated as missing
+    /// neighbors and reported as `set_outside_tile`'s tile (see `topology::MaskedGrid`).
+    pub fn with_mask(width: usize, height: usize, inside: &[bool], rules: RuleSet<T>, seed: Option<u64>) -> Result<Model<T>, WfcError> {
+        let topology = MaskedGrid::new(Arc::new(SquareGrid::new(width, height)), inside.to_vec())?;
+        Model::with_topology(Arc::new(topology), rules, seed)
+    }
+
+    /// Solve over the nodes of `graph`, one cell per node in the order listed; `result()`
+    /// is then a single row (see `topology::CellGraph`)
+    pub fn with_graph(graph: &CellGraph, rules: RuleSet<T>, seed: Option<u64>) -> Result<Model<T>, WfcError> {
+        Model::with_topology(Arc::new(graph.topology()?), rules, seed)
+    }
+
+    /// Solve over an arbitrary cell layout such as `topology::CubeMap`. `width()`/`height()`
+    /// and result indices follow the topology's `dimensions()`.
+    pub fn with_topology(topology: Arc<dyn Topology>, rules: RuleSet<T>, seed: Option<u64>) -> Result<Model<T>, WfcError> {
+        let (width, height) = topology.dimensions();
+        if width == 0 || height == 0 || topology.cell_count() > MAX_CELLS {
             return Err(WfcError::InvalidDimensions { width, height });
         }
 
@@ -31,345 +547,3225 @@ impl Model {
             return Err(WfcError::NoTilesDefined);
         }
 
-        let all_tiles: HashSet<TileId> = rules.get_all_tile_ids().into_iter().cloned().collect();
+        let all_tiles: HashSet<T> = rules.get_all_tile_ids().into_iter().cloned().collect();
         
-        // Initialize grid with all cells in superposition
+        // Initialize grid with all cells in superposition. Cells outside the topology's
+        // area start out settled and empty, so they are never selected or counted.
         let grid = (0..width * height)
-            .map(|_| Cell {
-                collapsed: false,
-                possibilities: all_tiles.clone(),
+            .map(|index| if topology.contains(index) {
+                Cell { collapsed: false, possibilities: all_tiles.clone() }
+            } else {
+                Cell { collapsed: true, possibilities: HashSet::new() }
             })
             .collect();
 
         // Initialize RNG
-        // Requirement 13.8: Deterministic generation with seed
-        let rng = match seed {
-            Some(s) => StdRng::seed_from_u64(s),
-            None => StdRng::from_entropy(),
-        };
+        // Requirement 13.8: Deterministic generation with seed. Without one a seed is drawn
+        // and kept, so every run can be reproduced from its manifest or trace.
+        let seed = seed.unwrap_or_else(|| StdRng::from_entropy().gen());
+        let rng = ChaCha12Rng::seed_from_u64(seed);
+        let one_sided = rules.adjacency
+            .iter()
+            .any(|((from, direction), set)| set.iter().any(|to| !rules.allows(to, from, direction.opposite())));
 
-        Ok(Model {
+        let mut model = Model {
             width,
             height,
+            topology,
+            heuristic: Arc::new(MinEntropy),
             grid,
             rules,
             rng,
-        })
+            seed,
+            stats: SolverStats::default(),
+            timings: PhaseTimings::default(),
+            history: Vec::new(),
+            trail: Vec::new(),
+            trace: Trace::default(),
+            observers: Observers::default(),
+            cancel: None,
+            transitions: None,
+            usage_decay: None,
+            cluster_bonus: None,
+            gradients: Vec::new(),
+            max_runs: HashMap::new(),
+            count_tiles: Vec::new(),
+            wave_constraints: Vec::new(),
+            fallback: None,
+            outside: None,
+            pins: Vec::new(),
+            run_config: RunConfig::default(),
+            run_start: None,
+            attempt_backtracks: 0,
+            fallback_cells: HashSet::new(),
+            finished: false,
+            backtracking_spent: false,
+            one_sided,
+            contradiction_at: None,
+            observed_at: vec![0; width * height],
+            confidence: vec![CellConfidence { options: 0, entropy: 0.0 }; width * height],
+            dirty: vec![false; width * height],
+            dirty_cells: Vec::new(),
+            noise: (0..width * height).map(|index| cell_noise(seed, index)).collect(),
+            queue: BinaryHeap::new(),
+            generation: vec![0; width * height],
+            rescore: vec![true; width * height],
+            rescore_cells: (0..width * height).collect(),
+        };
+        model.apply_constraints()?;
+        Ok(model)
     }
 
-    // Helper for grid indexing
-    fn get_index(&self, x: usize, y: usize) -> usize {
-        y * self.width + x
+    /// Restrict the border cells and the cells of each masked region named in the ruleset's
+    /// constraints, then apply its tile counts. Region cells outside the grid are ignored,
+    /// so one ruleset serves several map sizes. Fails with `InvalidTileId` on a key that
+    /// matches no tile, as `RuleSet::from_json` would, since constraints may also be added
+    /// in code.
+    fn apply_constraints(&mut self) -> Result<(), WfcError> {
+        let constraints = self.rules.constraints.clone();
+        constraints.validate(&self.rules)?;
+        for border in &constraints.borders {
+            let allowed: HashSet<T> = border.tiles.iter().flat_map(|key| self.rules.tiles_matching(key)).collect();
+            for (x, y) in border.cells(self.width, self.height) {
+                self.restrict_cell(y * self.width + x, &allowed)?;
+            }
+        }
+        for mask in &constraints.masks {
+            let allowed: HashSet<T> = mask.tiles.iter().flat_map(|key| self.rules.tiles_matching(key)).collect();
+            let (width, height) = (self.width, self.height);
+            let cells = constraints.regions.get(&mask.region).into_iter().flatten().flat_map(Rect::cells);
+            for (x, y) in cells.filter(|&(x, y)| x < width && y < height) {
+                self.restrict_cell(y * width + x, &allowed)?;
+            }
+        }
+        self.count_tiles = constraints.counts.iter().map(|count| self.rules.tiles_matching(&count.tile)).collect();
+        self.enforce_counts()
     }
 
-    fn get_coords(&self, index: usize) -> (usize, usize) {
-        (index % self.width, index / self.width)
+    pub fn width(&self) -> usize {
+        self.width
     }
 
-    // Task 3.3: Implement entropy calculation
-    fn calculate_entropy(&mut self, cell_index: usize) -> f64 {
-        let cell = &self.grid[cell_index];
-        if cell.collapsed {
-            return f64::INFINITY; // Already collapsed, shouldn't be picked
-        }
+    /// The RNG seed, drawn at construction if none was given
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
 
-        let total_weight: f64 = cell.possibilities
-            .iter()
-            .map(|id| self.rules.get_weight(id).unwrap_or(1) as f64)
-            .sum();
+    pub fn height(&self) -> usize {
+        self.height
+    }
 
-        if total_weight == 0.0 {
-            return 0.0; // Should handle contradiction elsewhere, but entropy is 0 here
-        }
+    pub fn rules(&self) -> &RuleSet<T> {
+        &self.rules
+    }
 
-        let entropy: f64 = cell.possibilities
-            .iter()
-            .map(|id| {
-                let weight = self.rules.get_weight(id).unwrap_or(1) as f64;
-                let p = weight / total_weight;
-                -p * p.log2()
-            })
-            .sum();
+    /// Work counters accumulated since the model was created
+    pub fn stats(&self) -> &SolverStats {
+        &self.stats
+    }
 
-        // Add small random noise to break ties (Req 13.2)
-        entropy - self.rng.gen::<f64>() * 0.001
+    /// Stream every solver action to `trace` as JSON lines (see the `trace` module).
+    /// The `start` header is written immediately.
+    pub fn set_trace(&mut self, trace: Trace) {
+        self.trace = trace;
+        let mut tiles: Vec<T> = self.rules.get_all_tile_ids().into_iter().cloned().collect();
+        tiles.sort();
+        self.trace.record(&TraceEvent::Start {
+            schema: TRACE_SCHEMA.to_string(),
+            version: TRACE_VERSION,
+            width: self.width,
+            height: self.height,
+            seed: Some(self.seed),
+            tiles: tiles.iter().map(T::to_string).collect(),
+            sampling: self.trace.sampling(),
+        });
     }
 
-    fn find_lowest_entropy(&mut self) -> Option<usize> {
-        let mut min_entropy = f64::INFINITY;
-        let mut min_index = None;
+    /// Call `observer` on every collapse, ban, backtrack and at the end of each run (see the
+    /// `observer` module). Observers are kept until `clear_observers`.
+    pub fn add_observer(&mut self, observer: impl Observer<T> + 'static) {
+        self.observers.push(Box::new(observer));
+    }
 
-        for i in 0..self.grid.len() {
-            if !self.grid[i].collapsed {
-                let entropy = self.calculate_entropy(i);
-                if entropy < min_entropy {
-                    min_entropy = entropy;
-                    min_index = Some(i);
-                }
-            }
+    pub fn clear_observers(&mut self) {
+        self.observers.clear();
+    }
+
+    /// Poll `token` between the steps of `run`, `run_with_retries` and
+    /// `run_collect_frames`, which fail with `Cancelled` once it is set. The model is left
+    /// mid-run and the token is cleared, so the next `run` continues. Those calls clear the
+    /// token as they start, so only a cancellation issued during a run stops it; `run_steps`
+    /// does not, so a cancellation between its calls stops the next one. Clones of the
+    /// model share the token.
+    pub fn set_cancel_token(&mut self, token: CancelToken) {
+        self.cancel = Some(token);
+    }
+
+    /// How the next cell to collapse is chosen (see the `heuristic` module); minimum
+    /// entropy by default
+    pub fn set_heuristic(&mut self, heuristic: Arc<dyn Heuristic>) {
+        self.heuristic = heuristic;
+        self.rescore_all();
+    }
+
+    /// Bias each collapse by the tiles already placed around the cell (see the `transition`
+    /// module). Cell selection still uses the plain tile weights.
+    pub fn set_transitions(&mut self, transitions: TransitionWeights<T>) {
+        self.transitions = Some(transitions).filter(|t| !t.is_empty());
+    }
+
+    /// Discourage long same-tile sprawl by damping a tile's weight near cells that already
+    /// hold it (see `UsageDecay`). Like transitions, this only affects which tile a cell
+    /// collapses to.
+    pub fn set_usage_decay(&mut self, decay: UsageDecay) {
+        self.usage_decay = Some(decay).filter(|d| d.strength > 0.0 && d.radius > 0);
+    }
+
+    /// Boost tiles next to collapsed neighbors of the same tile or tag (see `ClusterBonus`)
+    pub fn set_cluster_bonus(&mut self, bonus: ClusterBonus) {
+        self.cluster_bonus = Some(bonus).filter(|b| !b.is_empty());
+    }
+
+    /// Vary the weight of the tiles matching `gradient.key` across the grid (see
+    /// `WeightGradient`). Where gradients overlap, the one added last wins.
+    pub fn add_weight_gradient(&mut self, gradient: WeightGradient) -> Result<(), WfcError> {
+        let tiles = self.rules.tiles_matching(&gradient.key);
+        if tiles.is_empty() {
+            return Err(WfcError::InvalidTileId(gradient.key));
         }
+        self.gradients.push((gradient, tiles));
+        Ok(())
+    }
 
-        min_index
+    /// Require between `count.min` and `count.max` cells of the tiles matching `count.tile`
+    /// across the grid, e.g. at most one boss room or at least three chests. The constraint
+    /// joins the ruleset's (see `constraints::Constraints`) and is enforced as cells
+    /// collapse: a tile whose maximum is reached is banned everywhere else, and once only
+    /// the minimum number of cells can still hold it, they are limited to it. Fails with
+    /// `Contradiction`, leaving the model as it was, if the grid can no longer satisfy it.
+    pub fn add_count_constraint(&mut self, count: CountConstraint) -> Result<(), WfcError> {
+        Constraints { counts: vec![count.clone()], ..Constraints::default() }.validate(&self.rules)?;
+        let trail_len = self.trail.len();
+        self.count_tiles.push(self.rules.tiles_matching(&count.tile));
+        self.rules.constraints.counts.push(count);
+        if let Err(e) = self.enforce_counts() {
+            self.undo_to(trail_len);
+            self.count_tiles.pop();
+            self.rules.constraints.counts.pop();
+            return Err(e);
+        }
+        Ok(())
     }
 
-    // Task 3.5: Implement cell collapse logic
-    fn collapse_cell(&mut self, index: usize) -> Result<TileId, WfcError> {
-        let cell = &mut self.grid[index];
-        if cell.possibilities.is_empty() {
+    /// Check `constraint` against the whole wave after every propagation, backtracking
+    /// whenever it fails (see the `connectivity` module). Fails with `Contradiction` if the
+    /// wave already breaks it. Like observers, wave constraints are not part of snapshots.
+    pub fn add_wave_constraint(&mut self, constraint: Arc<dyn WaveConstraint<T>>) -> Result<(), WfcError> {
+        if !constraint.holds(&self.wave_view()) {
             return Err(WfcError::Contradiction);
         }
+        self.wave_constraints.push(constraint);
+        Ok(())
+    }
 
-        let total_weight: u32 = cell.possibilities
-            .iter()
-            .map(|id| self.rules.get_weight(id).unwrap_or(1))
-            .sum();
+    fn wave_view(&self) -> WaveView<'_, T> {
+        WaveView { topology: self.topology.as_ref(), cells: &self.grid }
+    }
 
-        if total_weight == 0 {
-             return Err(WfcError::Contradiction);
+    /// Count constraints, then every wave constraint
+    fn check_constraints(&mut self) -> Result<(), WfcError> {
+        self.enforce_counts()?;
+        let wave = self.wave_view();
+        if self.wave_constraints.iter().all(|constraint| constraint.holds(&wave)) {
+            Ok(())
+        } else {
+            Err(WfcError::Contradiction)
         }
+    }
 
-        let mut roll = self.rng.gen_range(0..total_weight);
-        let mut selected_tile = None;
+    /// Allow at most `max` consecutive `tile` cells in any row or column of the grid layout
+    /// (at least 1). Enforced as cells collapse: once a run reaches the limit, the tile is
+    /// banned from the cells at both ends.
+    pub fn set_max_run_length(&mut self, tile: T, max: usize) {
+        self.max_runs.insert(tile, max.max(1));
+    }
 
-        // Sort possibilities for deterministic selection
-        let mut sorted_possibilities: Vec<&TileId> = cell.possibilities.iter().collect();
-        sorted_possibilities.sort();
+    /// Give up with `BacktrackLimitExceeded` once more than `limit` decisions have been
+    /// undone, instead of searching on. Without a limit the search is exhaustive, so it
+    /// only fails with `Contradiction` when no grid satisfies the rules at all. Shorthand
+    /// for `RunConfig::max_backtracks`.
+    pub fn set_max_backtracks(&mut self, limit: u64) {
+        self.run_config.max_backtracks = Some(limit);
+    }
 
-        for id in sorted_possibilities {
-            let weight = self.rules.get_weight(id).unwrap_or(1);
-            if roll < weight {
-                selected_tile = Some(id.clone());
-                break;
+    /// Backtracking limits and what to do once a search attempt is exhausted
+    pub fn set_run_config(&mut self, config: RunConfig) {
+        self.run_config = config;
+    }
+
+    pub fn run_config(&self) -> RunConfig {
+        self.run_config
+    }
+
+    /// Resolve contradictions that backtracking cannot by placing `tile` in the cell that ran
+    /// out of options, so runs always finish. Backtracking is tried first; once it runs out
+    /// of alternatives, the run starts over from its seed and places the fallback at each
+    /// contradiction, and once it passes the limits of `set_run_config`, later
+    /// contradictions place the fallback. The fallback is a wildcard: it accepts any
+    /// neighbor and constrains none whatever its rules say, and is never chosen otherwise.
+    /// Suits decoration layers where a blemish beats a failed run; `stats().fallbacks`
+    /// counts the placements and `verify_result` lists the mismatches they leave. The tile
+    /// must be part of the ruleset.
+    pub fn set_fallback_tile(&mut self, tile: T) -> Result<(), WfcError> {
+        if self.rules.get_tile_info(&tile).is_none() {
+            return Err(WfcError::InvalidTileId(tile.to_string()));
+        }
+        for index in 0..self.grid.len() {
+            if !self.grid[index].collapsed && self.grid[index].possibilities.remove(&tile) {
+                self.mark_dirty(index);
             }
-            roll -= weight;
         }
+        self.fallback = Some(tile);
+        Ok(())
+    }
 
-        let selected = selected_tile.expect("Weighted random selection failed");
-        
-        cell.collapsed = true;
-        cell.possibilities.clear();
-        cell.possibilities.insert(selected.clone());
+    /// The tile `result` reports for cells outside the area of a masked topology (see
+    /// `with_mask`), e.g. open sea around an island. It takes no part in solving. Without
+    /// one, those cells hold the tile with the lowest ID.
+    pub fn set_outside_tile(&mut self, tile: T) -> Result<(), WfcError> {
+        if self.rules.get_tile_info(&tile).is_none() {
+            return Err(WfcError::InvalidTileId(tile.to_string()));
+        }
+        self.outside = Some(tile);
+        Ok(())
+    }
 
-        Ok(selected)
+    /// Counters and per-phase timings accumulated since the model was created
+    pub fn report(&self) -> RunReport {
+        RunReport {
+            stats: self.stats,
+            timings: self.timings,
+        }
     }
 
-    // Task 3.6: Implement constraint propagation
-    fn get_neighbors(&self, index: usize) -> Vec<(usize, Direction)> {
-        let (x, y) = self.get_coords(index);
-        let mut neighbors = Vec::new();
+    /// Capture every cell's remaining possibilities and their weight-normalized shares
+    pub fn wave_snapshot(&self) -> WaveSnapshot<T> {
+        let mut tiles: Vec<T> = self.rules.get_all_tile_ids().into_iter().cloned().collect();
+        tiles.sort();
 
-        if y > 0 {
-            neighbors.push((self.get_index(x, y - 1), Direction::Up));
-        }
-        if x < self.width - 1 {
-            neighbors.push((self.get_index(x + 1, y), Direction::Right));
-        }
-        if y < self.height - 1 {
-            neighbors.push((self.get_index(x, y + 1), Direction::Down));
-        }
-        if x > 0 {
-            neighbors.push((self.get_index(x - 1, y), Direction::Left));
+        let mut counts = Vec::with_capacity(self.grid.len());
+        let mut probabilities = Vec::with_capacity(self.grid.len() * tiles.len());
+        for cell in &self.grid {
+            counts.push(cell.possibilities.len() as u32);
+            let total: f64 = cell.possibilities
+                .iter()
+                .map(|id| self.rules.weight(id).unwrap_or(1) as f64)
+                .sum();
+            for id in &tiles {
+                let share = if !cell.possibilities.contains(id) {
+                    0.0
+                } else if total > 0.0 {
+                    self.rules.weight(id).unwrap_or(1) as f64 / total
+                } else {
+                    // Only zero-weight tiles remain, and one of them is forced
+                    1.0 / cell.possibilities.len() as f64
+                };
+                probabilities.push(share as f32);
+            }
         }
 
-        neighbors
+        WaveSnapshot {
+            width: self.width,
+            height: self.height,
+            tiles,
+            counts,
+            probabilities,
+        }
     }
 
-    fn propagate(&mut self, start_index: usize) -> Result<(), WfcError> {
-        let mut stack = vec![start_index];
+    /// Current entropy and possibility count of every cell, without the tie-breaking noise
+    /// the solver adds when choosing where to collapse next
+    pub fn entropy_map(&self) -> EntropyMap {
+        let weights = self.rules.get_all_tile_ids().into_iter().map(|id| self.rules.weight(id).unwrap_or(1) as f64);
+        EntropyMap {
+            width: self.width,
+            height: self.height,
+            entropy: (0..self.grid.len()).map(|index| self.weighted_entropy(index)).collect(),
+            counts: self.grid.iter().map(|cell| cell.possibilities.len() as u32).collect(),
+            max_entropy: shannon_entropy(weights),
+        }
+    }
 
-        while let Some(current_idx) = stack.pop() {
-            let current_possibilities = self.grid[current_idx].possibilities.clone();
-            
-            // Check for contradiction
-            if current_possibilities.is_empty() {
-                return Err(WfcError::Contradiction);
-            }
+    fn get_coords(&self, index: usize) -> (usize, usize) {
+        (index % self.width, index / self.width)
+    }
 
-            let neighbors = self.get_neighbors(current_idx);
+    // Task 3.3: Implement entropy calculation
+    fn calculate_entropy(&mut self, cell_index: usize) -> f64 {
+        self.stats.entropy_evaluations += 1;
+        if self.grid[cell_index].collapsed {
+            return f64::INFINITY; // Already collapsed, shouldn't be picked
+        }
 
-            for (neighbor_idx, direction) in neighbors {
-                let neighbor = &mut self.grid[neighbor_idx];
-                
-                if neighbor.collapsed {
-                    continue;
-                }
+        // Add small noise to break ties (Req 13.2)
+        self.weighted_entropy(cell_index) - self.noise[cell_index] * ENTROPY_NOISE
+    }
 
-                let original_count = neighbor.possibilities.len();
-                
-                // Keep only tiles in neighbor that are compatible with AT LEAST ONE tile in current_possibilities
-                let mut allowed_in_neighbor = HashSet::new();
-                for tile_c in &current_possibilities {
-                    if let Some(valid_neighbors) = self.rules.get_valid_neighbors(tile_c, direction) {
-                         allowed_in_neighbor.extend(valid_neighbors.iter().cloned());
-                    }
-                }
+    /// Shannon entropy of a cell's remaining tiles under their weights, in bits
+    fn weighted_entropy(&self, cell_index: usize) -> f64 {
+        shannon_entropy(self.grid[cell_index].possibilities.iter().map(|id| self.rules.weight(id).unwrap_or(1) as f64))
+    }
 
-                neighbor.possibilities.retain(|tile_n| allowed_in_neighbor.contains(tile_n));
+    fn find_lowest_entropy(&mut self, mask: Option<&[bool]>) -> Option<usize> {
+        if mask.is_none() && !self.heuristic.uses_neighbors() {
+            return self.pop_lowest_score();
+        }
 
-                if neighbor.possibilities.len() < original_count {
-                    if neighbor.possibilities.is_empty() {
-                        return Err(WfcError::Contradiction);
-                    }
-                    stack.push(neighbor_idx);
+        let mut min_score = f64::INFINITY;
+        let mut min_index = None;
+        for i in 0..self.grid.len() {
+            if !self.grid[i].collapsed && mask.is_none_or(|m| m[i]) {
+                let score = self.score(i);
+                if score < min_score {
+                    min_score = score;
+                    min_index = Some(i);
                 }
             }
         }
-        Ok(())
+
+        min_index
     }
 
-    fn backtrack(&mut self, history: &mut Vec<(Vec<Cell>, usize, TileId)>) -> bool {
-        while let Some((snapshot, index, tried_tile)) = history.pop() {
-            self.grid = snapshot;
-            
-            // Remove the failed tile
-            self.grid[index].possibilities.remove(&tried_tile);
-            
-            if self.grid[index].possibilities.is_empty() {
+    /// `find_lowest_entropy` from the queue: re-score the cells changed since the last
+    /// call, then drop outdated entries off the top. The winner stays queued until its
+    /// collapse outdates it, so a failed collapse cannot lose it.
+    fn pop_lowest_score(&mut self) -> Option<usize> {
+        for index in std::mem::take(&mut self.rescore_cells) {
+            self.rescore[index] = false;
+            self.generation[index] = self.generation[index].wrapping_add(1);
+            if self.grid[index].collapsed {
                 continue;
             }
-            
-            if let Ok(_) = self.propagate(index) {
-                return true;
+            let score = self.score(index);
+            // Like the scan, never pick a cell scored infinite (or NaN)
+            if score < f64::INFINITY {
+                self.queue.push(Reverse(QueueEntry { score, index, generation: self.generation[index] }));
             }
         }
-        false
+        if self.queue.len() > 2 * self.grid.len() + 64 {
+            let (grid, generation) = (&self.grid, &self.generation);
+            self.queue.retain(|Reverse(entry)| !grid[entry.index].collapsed && entry.generation == generation[entry.index]);
+        }
+
+        while let Some(Reverse(entry)) = self.queue.peek() {
+            if !self.grid[entry.index].collapsed && entry.generation == self.generation[entry.index] {
+                return Some(entry.index);
+            }
+            self.queue.pop();
+        }
+        None
     }
 
-    // Task 3.8: Implement main run loop
-    pub fn run(&mut self) -> Result<Vec<TileId>, WfcError> {
-        let mut history: Vec<(Vec<Cell>, usize, TileId)> = Vec::new();
+    /// The heuristic's score of open cell `index`
+    fn score(&mut self, index: usize) -> f64 {
+        let entropy = self.calculate_entropy(index);
+        let latest_neighbor_step = if self.heuristic.uses_neighbors() {
+            self.topology
+                .neighbors(index)
+                .into_iter()
+                .filter(|&(n, _)| self.grid[n].collapsed)
+                .map(|(n, _)| self.observed_at[n])
+                .max()
+        } else {
+            None
+        };
+        self.heuristic.score(&Candidate {
+            index,
+            entropy,
+            remaining: self.grid[index].possibilities.len(),
+            latest_neighbor_step,
+            step: self.stats.observations,
+        })
+    }
 
-        loop {
-            // Find cell with lowest entropy
-            if let Some(index) = self.find_lowest_entropy() {
-                let snapshot = self.grid.clone();
-
-                // Collapse it
-                match self.collapse_cell(index) {
-                    Ok(selected_tile) => {
-                        history.push((snapshot, index, selected_tile));
-                        
-                        // Propagate constraints
-                        if let Err(_) = self.propagate(index) {
-                            if !self.backtrack(&mut history) {
-                                return Err(WfcError::Contradiction);
-                            }
-                        }
-                    },
-                    Err(_) => {
-                         // Contradiction encountered
-                        if !self.backtrack(&mut history) {
-                            return Err(WfcError::Contradiction);
+    /// Tile weights for collapsing `index`, taken from the gradients or the rules and scaled
+    /// by the transition weights, usage decay and cluster bonus
+    fn biased_weights(&self, index: usize, candidates: &[&T]) -> Vec<f64> {
+        let collapsed_tile = |n: usize| {
+            let cell = &self.grid[n];
+            cell.possibilities.iter().next().filter(|_| cell.collapsed)
+        };
+        let placed: Vec<(&T, Direction)> = self.topology
+            .neighbors(index)
+            .into_iter()
+            .filter_map(|(n, dir)| collapsed_tile(n).map(|tile| (tile, dir)))
+            .collect();
+
+        // Same-tile cells within the decay radius, with their grid distance
+        let mut nearby: Vec<(&T, usize)> = Vec::new();
+        if let Some(decay) = &self.usage_decay {
+            let (x, y) = self.get_coords(index);
+            let r = decay.radius;
+            for ny in y.saturating_sub(r)..(y + r + 1).min(self.height) {
+                for nx in x.saturating_sub(r)..(x + r + 1).min(self.width) {
+                    let distance = x.abs_diff(nx) + y.abs_diff(ny);
+                    if distance > 0 && distance <= r {
+                        if let Some(tile) = collapsed_tile(ny * self.width + nx) {
+                            nearby.push((tile, distance));
+                        }
+                    }
+                }
+            }
+        }
+
+        candidates
+            .iter()
+            .map(|&id| {
+                let gradient = self.gradients.iter().rev().find(|(_, tiles)| tiles.contains(id));
+                let mut weight = match gradient {
+                    Some((gradient, _)) => {
+                        let (x, y) = self.get_coords(index);
+                        gradient.weight_at(x, y, self.width, self.height)
+                    }
+                    None => self.rules.weight(id).unwrap_or(1) as f64,
+                };
+                // The cell sits in the opposite direction as seen from each neighbor
+                if !self.rules.rule_weights.is_empty() {
+                    for &(from, dir) in &placed {
+                        weight *= self.rules.rule_weight(from, id, dir.opposite());
+                    }
+                }
+                if let Some(transitions) = &self.transitions {
+                    for &(from, dir) in &placed {
+                        weight *= transitions.factor(from, dir.opposite(), id);
+                    }
+                }
+                if let Some(bonus) = &self.cluster_bonus {
+                    for &(neighbor, _) in &placed {
+                        weight *= bonus.factor(&self.rules, id, neighbor);
+                    }
+                }
+                if let Some(decay) = &self.usage_decay {
+                    for &(tile, distance) in &nearby {
+                        if tile == id {
+                            weight *= decay.factor(distance);
+                        }
+                    }
+                }
+                weight
+            })
+            .collect()
+    }
+
+    /// Apply the count constraints to the wave, until no constraint restricts any more cells:
+    /// limiting one tile can settle cells another constraint counts. A cell counts once all
+    /// of its possibilities match. Each pass scans the grid once per constraint.
+    fn enforce_counts(&mut self) -> Result<(), WfcError> {
+        loop {
+            let mut restrictions: Vec<(usize, HashSet<T>)> = Vec::new();
+            for (count, tiles) in self.rules.constraints.counts.iter().zip(&self.count_tiles) {
+                let mut settled = 0;
+                let mut open = Vec::new();
+                for (index, cell) in self.grid.iter().enumerate() {
+                    let matching = cell.possibilities.iter().filter(|tile| tiles.contains(*tile)).count();
+                    if matching > 0 && matching == cell.possibilities.len() {
+                        settled += 1;
+                    } else if matching > 0 {
+                        open.push(index);
+                    }
+                }
+                if count.max.is_some_and(|max| settled > max) || settled + open.len() < count.min {
+                    return Err(WfcError::Contradiction);
+                }
+                let keep_matching = if count.max == Some(settled) {
+                    false
+                } else if settled + open.len() == count.min {
+                    true
+                } else {
+                    continue;
+                };
+                restrictions.extend(open.into_iter().map(|index| {
+                    let allowed = self.grid[index].possibilities.iter().filter(|tile| tiles.contains(*tile) == keep_matching).cloned().collect();
+                    (index, allowed)
+                }));
+                if !restrictions.is_empty() {
+                    break;
+                }
+            }
+            if restrictions.is_empty() {
+                return Ok(());
+            }
+            for (index, allowed) in restrictions {
+                self.restrict_cell(index, &allowed)?;
+            }
+        }
+    }
+
+    /// Apply `set_max_run_length` around a freshly collapsed cell. Runs count every cell
+    /// already settled on the tile, whether collapsed yet or forced by propagation.
+    fn limit_runs(&mut self, index: usize) -> Result<(), WfcError> {
+        let Some(tile) = self.grid[index].possibilities.iter().next() else {
+            return Ok(());
+        };
+        let Some(&max) = self.max_runs.get(tile) else {
+            return Ok(());
+        };
+        let tile = tile.clone();
+        let settled = |model: &Self, i: usize| {
+            let possibilities = &model.grid[i].possibilities;
+            possibilities.len() == 1 && possibilities.contains(&tile)
+        };
+
+        let (x, y) = self.get_coords(index);
+        for (dx, dy) in [(1, 0), (0, 1)] {
+            let mut run = 1;
+            let mut ends = Vec::new();
+            for sign in [-1isize, 1] {
+                let (mut cx, mut cy) = (x as isize, y as isize);
+                loop {
+                    cx += sign * dx;
+                    cy += sign * dy;
+                    if cx < 0 || cy < 0 || cx >= self.width as isize || cy >= self.height as isize {
+                        break;
+                    }
+                    let i = cy as usize * self.width + cx as usize;
+                    if !settled(self, i) {
+                        ends.push(i);
+                        break;
+                    }
+                    run += 1;
+                }
+            }
+            if run > max {
+                return Err(WfcError::Contradiction);
+            }
+            if run == max {
+                for end in ends {
+                    let mut allowed = self.grid[end].possibilities.clone();
+                    if allowed.remove(&tile) {
+                        self.restrict_cell(end, &allowed)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Task 3.5: Implement cell collapse logic
+    fn collapse_cell(&mut self, index: usize) -> Result<T, WfcError> {
+        let cell = &self.grid[index];
+        if cell.possibilities.is_empty() {
+            return Err(WfcError::Contradiction);
+        }
+
+        // Sort possibilities for deterministic selection
+        let mut sorted_possibilities: Vec<&T> = cell.possibilities.iter().collect();
+        sorted_possibilities.sort();
+
+        // Zero-weight tiles are never picked by chance, but once they are all that is left
+        // the constraints have forced one of them
+        let forced = sorted_possibilities.iter().all(|id| self.rules.weight(id) == Some(0));
+
+        let biased = self.transitions.is_some() || self.usage_decay.is_some() || self.cluster_bonus.is_some()
+            || !self.gradients.is_empty() || !self.rules.rule_weights.is_empty();
+
+        let selected_tile = if forced {
+            let pick = self.rng.gen_range(0..sorted_possibilities.len());
+            Some(sorted_possibilities[pick].clone())
+        } else if !biased {
+            let total_weight: u32 = sorted_possibilities
+                .iter()
+                .map(|id| self.rules.weight(id).unwrap_or(1))
+                .sum();
+
+            if total_weight == 0 {
+                 return Err(WfcError::Contradiction);
+            }
+
+            let mut roll = self.rng.gen_range(0..total_weight);
+            let mut selected_tile = None;
+            for id in sorted_possibilities {
+                let weight = self.rules.weight(id).unwrap_or(1);
+                if roll < weight {
+                    selected_tile = Some(id.clone());
+                    break;
+                }
+                roll -= weight;
+            }
+            selected_tile
+        } else {
+            let weights = self.biased_weights(index, &sorted_possibilities);
+            let total_weight: f64 = weights.iter().sum();
+
+            if total_weight <= 0.0 {
+                return Err(WfcError::Contradiction);
+            }
+
+            let mut roll = self.rng.gen::<f64>() * total_weight;
+            let mut selected_tile = sorted_possibilities.last().map(|id| (*id).clone());
+            for (id, weight) in sorted_possibilities.into_iter().zip(weights) {
+                if roll < weight {
+                    selected_tile = Some(id.clone());
+                    break;
+                }
+                roll -= weight;
+            }
+            selected_tile
+        };
+
+        self.confidence[index] = CellConfidence {
+            options: self.grid[index].possibilities.len(),
+            entropy: self.weighted_entropy(index),
+        };
+        let cell = &mut self.grid[index];
+        let selected = selected_tile.expect("Weighted random selection failed");
+        
+        self.stats.bans += cell.possibilities.len() as u64 - 1;
+        self.stats.observations += 1;
+        self.observed_at[index] = self.stats.observations;
+        cell.collapsed = true;
+        let discarded: Vec<T> = cell.possibilities.drain().filter(|tile| *tile != selected).collect();
+        cell.possibilities.insert(selected.clone());
+        self.trail.push(Change::Collapse(index));
+        self.log_bans(index, discarded);
+        self.mark_dirty(index);
+        self.observers.notify(&SolverEvent::Collapse { cell: index, tile: &selected });
+
+        if self.trace.is_enabled() {
+            let (x, y) = self.get_coords(index);
+            self.trace.record(&TraceEvent::Observe {
+                step: self.stats.observations,
+                cell: index,
+                x,
+                y,
+                tile: selected.to_string(),
+            });
+        }
+
+        Ok(selected)
+    }
+
+    // Task 3.6: Implement constraint propagation
+    fn get_neighbors(&self, index: usize) -> Vec<(usize, Direction)> {
+        self.topology.neighbors(index)
+    }
+
+    /// Tiles still possible in a cell
+    pub(crate) fn possibilities(&self, index: usize) -> &HashSet<T> {
+        &self.grid[index].possibilities
+    }
+
+    /// Narrow a cell to the tiles in `allowed` and propagate the consequences
+    pub(crate) fn restrict_cell(&mut self, index: usize, allowed: &HashSet<T>) -> Result<(), WfcError> {
+        if !self.topology.contains(index) {
+            return Ok(());
+        }
+        let cell = &mut self.grid[index];
+        let banned: Vec<T> = cell.possibilities.iter().filter(|tile| !allowed.contains(*tile)).cloned().collect();
+        for tile in &banned {
+            cell.possibilities.remove(tile);
+        }
+        let now_empty = cell.possibilities.is_empty();
+        let changed = !banned.is_empty();
+        self.stats.bans += banned.len() as u64;
+        self.log_bans(index, banned);
+        if changed {
+            self.mark_dirty(index);
+        }
+
+        if now_empty {
+            self.contradiction_at = Some(index);
+            return Err(WfcError::Contradiction);
+        }
+        if changed {
+            self.propagate(index)?;
+        }
+        Ok(())
+    }
+
+    fn propagate(&mut self, start_index: usize) -> Result<(), WfcError> {
+        let mut stack = vec![start_index];
+
+        while let Some(current_idx) = stack.pop() {
+            if self.fallback_cells.contains(&current_idx) {
+                continue;
+            }
+            let current_possibilities = self.grid[current_idx].possibilities.clone();
+            
+            // Check for contradiction
+            if current_possibilities.is_empty() {
+                self.contradiction_at = Some(current_idx);
+                return Err(WfcError::Contradiction);
+            }
+
+            let neighbors = self.get_neighbors(current_idx);
+
+            for (neighbor_idx, direction) in neighbors {
+                self.stats.propagation_ops += 1;
+                let neighbor = &mut self.grid[neighbor_idx];
+                
+                if neighbor.collapsed {
+                    continue;
+                }
+
+                // Keep only tiles in neighbor that are compatible with AT LEAST ONE tile in current_possibilities
+                let mut banned: Vec<T> = if self.rules.missing_adjacency == MissingAdjacency::Open {
+                    // Either tile's rules may be the restricting ones, so check the pair both ways
+                    neighbor.possibilities
+                        .iter()
+                        .filter(|tile_n| !current_possibilities.iter().any(|tile_c| self.rules.is_compatible(tile_c, tile_n, direction)))
+                        .cloned()
+                        .collect()
+                } else {
+                    let mut allowed_in_neighbor = HashSet::new();
+                    for tile_c in &current_possibilities {
+                        if let Some(valid_neighbors) = self.rules.get_valid_neighbors(tile_c, direction) {
+                             allowed_in_neighbor.extend(valid_neighbors.iter().cloned());
+                        }
+                    }
+                    // A rule only holds if the neighbor's rules allow the pair back
+                    let one_sided = self.one_sided;
+                    neighbor.possibilities
+                        .iter()
+                        .filter(|tile_n| {
+                            !allowed_in_neighbor.contains(*tile_n)
+                                || one_sided && !current_possibilities.iter().any(|tile_c| self.rules.is_compatible(tile_c, tile_n, direction))
+                        })
+                        .cloned()
+                        .collect()
+                };
+                if banned.is_empty() {
+                    continue;
+                }
+                for tile in &banned {
+                    neighbor.possibilities.remove(tile);
+                }
+                let now_empty = neighbor.possibilities.is_empty();
+
+                if self.trace.wants_bans() {
+                    banned.sort();
+                    let (x, y) = (neighbor_idx % self.width, neighbor_idx / self.width);
+                    for tile in &banned {
+                        self.trace.record(&TraceEvent::Ban { step: self.stats.observations, cell: neighbor_idx, x, y, tile: tile.to_string() });
+                    }
+                }
+                self.stats.bans += banned.len() as u64;
+                self.log_bans(neighbor_idx, banned);
+                self.mark_dirty(neighbor_idx);
+                if now_empty {
+                    self.contradiction_at = Some(neighbor_idx);
+                    return Err(WfcError::Contradiction);
+                }
+                stack.push(neighbor_idx);
+            }
+        }
+        Ok(())
+    }
+
+    /// Log the bans of `banned` from `cell` for undoing, and tell the observers
+    fn log_bans(&mut self, cell: usize, banned: Vec<T>) {
+        if !self.observers.is_empty() {
+            for tile in &banned {
+                self.observers.notify(&SolverEvent::Ban { cell, tile });
+            }
+        }
+        self.trail.extend(banned.into_iter().map(|tile| Change::Ban(cell, tile)));
+    }
+
+    /// Undo logged changes until the log is `len` entries long
+    fn undo_to(&mut self, len: usize) {
+        while self.trail.len() > len {
+            let index = match self.trail.pop() {
+                Some(Change::Ban(index, tile)) => {
+                    self.grid[index].possibilities.insert(tile);
+                    index
+                }
+                Some(Change::Collapse(index)) => {
+                    self.grid[index].collapsed = false;
+                    index
+                }
+                Some(Change::Fallback(index)) => {
+                    self.grid[index].possibilities.clear();
+                    self.fallback_cells.remove(&index);
+                    index
+                }
+                None => break,
+            };
+            self.mark_dirty(index);
+        }
+    }
+
+    fn backtrack(&mut self) -> bool {
+        while let Some(decision) = self.history.pop() {
+            self.stats.backtracks += 1;
+            self.undo_to(decision.trail_len);
+            let Decision { index, tile: tried_tile, .. } = decision;
+
+            if self.trace.is_enabled() {
+                let (x, y) = self.get_coords(index);
+                self.trace.record(&TraceEvent::Backtrack {
+                    step: self.stats.observations,
+                    cell: index,
+                    x,
+                    y,
+                    tile: tried_tile.to_string(),
+                });
+            }
+            
+            self.observers.notify(&SolverEvent::Backtrack { cell: index, tile: &tried_tile });
+
+            // Remove the failed tile
+            self.grid[index].possibilities.remove(&tried_tile);
+            self.log_bans(index, vec![tried_tile]);
+            self.stats.bans += 1;
+            self.mark_dirty(index);
+            
+            if self.grid[index].possibilities.is_empty() {
+                continue;
+            }
+            
+            if self.propagate(index).is_ok() && self.reapply_pins().is_ok() && self.check_constraints().is_ok() {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Collapse `(x, y)` to `tile` and propagate, before the run or between `step` calls.
+    /// The edit outlives backtracking: undoing earlier decisions re-applies it. Fails with
+    /// `Contradiction`, leaving the model as it was, if `tile` is no longer possible there,
+    /// and with `OutOfBounds` if the cell is off the grid or outside a masked topology's area.
+    pub fn force(&mut self, x: usize, y: usize, tile: T) -> Result<(), WfcError> {
+        self.pin(x, y, Edit::Force(tile))
+    }
+
+    /// Remove `tile` from the possibilities of `(x, y)` and propagate; see `force`
+    pub fn ban(&mut self, x: usize, y: usize, tile: T) -> Result<(), WfcError> {
+        self.pin(x, y, Edit::Ban(tile))
+    }
+
+    /// Limit `(x, y)` to `tiles` and propagate, e.g. to keep a lake to water and shore; see
+    /// `force`. Other tiles are banned as if by `ban`, so the cell still collapses to one of
+    /// `tiles` that fits its neighbors.
+    pub fn mask_cell(&mut self, x: usize, y: usize, tiles: &HashSet<T>) -> Result<(), WfcError> {
+        let mut tiles: Vec<T> = tiles.iter().cloned().collect();
+        tiles.sort();
+        self.pin(x, y, Edit::Mask(tiles))
+    }
+
+    /// `mask_cell` for every cell of `rect` inside the topology's area. Either the whole
+    /// rectangle is masked or, on a contradiction, none of it.
+    pub fn mask_rect(&mut self, rect: Rect, tiles: &HashSet<T>) -> Result<(), WfcError> {
+        if rect.x + rect.width > self.width || rect.y + rect.height > self.height {
+            let (x, y) = (rect.x + rect.width.max(1) - 1, rect.y + rect.height.max(1) - 1);
+            return Err(WfcError::OutOfBounds { x, y, width: self.width, height: self.height });
+        }
+        let mut tiles: Vec<T> = tiles.iter().cloned().collect();
+        tiles.sort();
+        let edits: Vec<(usize, usize, Edit<T>)> = rect.cells()
+            .filter(|&(x, y)| self.topology.contains(y * self.width + x))
+            .map(|(x, y)| (x, y, Edit::Mask(tiles.clone())))
+            .collect();
+        self.pin_all(edits)
+    }
+
+    /// `mask_cell` for every cell on the given edges of the grid, or on all four if `edges`
+    /// is empty, e.g. to surround a map with ocean. Cells outside the topology's area are
+    /// skipped.
+    pub fn mask_border(&mut self, edges: &[Direction], tiles: &HashSet<T>) -> Result<(), WfcError> {
+        let mut tiles: Vec<T> = tiles.iter().cloned().collect();
+        tiles.sort();
+        let edits = border_cells(edges, self.width, self.height)
+            .into_iter()
+            .filter(|&(x, y)| self.topology.contains(y * self.width + x))
+            .map(|(x, y)| (x, y, Edit::Mask(tiles.clone())))
+            .collect();
+        self.pin_all(edits)
+    }
+
+    fn pin(&mut self, x: usize, y: usize, edit: Edit<T>) -> Result<(), WfcError> {
+        self.pin_all(vec![(x, y, edit)])
+    }
+
+    /// Apply `edits` and keep them as pins, or undo all of them if one fails
+    fn pin_all(&mut self, edits: Vec<(usize, usize, Edit<T>)>) -> Result<(), WfcError> {
+        for (x, y, edit) in &edits {
+            if *x >= self.width || *y >= self.height || !self.topology.contains(y * self.width + x) {
+                return Err(WfcError::OutOfBounds { x: *x, y: *y, width: self.width, height: self.height });
+            }
+            let unknown = match edit {
+                Edit::Force(tile) | Edit::Ban(tile) => Some(tile).filter(|tile| self.rules.get_tile_info(tile).is_none()),
+                Edit::Mask(tiles) => tiles.iter().find(|tile| self.rules.get_tile_info(tile).is_none()),
+            };
+            if let Some(tile) = unknown {
+                return Err(WfcError::InvalidTileId(tile.to_string()));
+            }
+        }
+        let trail_len = self.trail.len();
+        let applied = edits.iter()
+            .try_for_each(|(x, y, edit)| self.apply_edit(y * self.width + x, edit))
+            .and_then(|_| self.check_constraints());
+        if let Err(e) = applied {
+            self.undo_to(trail_len);
+            return Err(e);
+        }
+        self.pins.extend(edits.into_iter().map(|(x, y, edit)| (y * self.width + x, edit)));
+        Ok(())
+    }
+
+    fn apply_edit(&mut self, index: usize, edit: &Edit<T>) -> Result<(), WfcError> {
+        match edit {
+            Edit::Force(tile) => {
+                self.restrict_cell(index, &HashSet::from([tile.clone()]))?;
+                if !self.grid[index].collapsed {
+                    self.grid[index].collapsed = true;
+                    self.trail.push(Change::Collapse(index));
+                    self.confidence[index] = CellConfidence { options: 1, entropy: 0.0 };
+                    self.mark_dirty(index);
+                }
+            }
+            Edit::Ban(tile) => {
+                if self.grid[index].possibilities.contains(tile) {
+                    let mut allowed = self.grid[index].possibilities.clone();
+                    allowed.remove(tile);
+                    self.restrict_cell(index, &allowed)?;
+                }
+            }
+            Edit::Mask(tiles) => self.restrict_cell(index, &tiles.iter().cloned().collect())?,
+        }
+        Ok(())
+    }
+
+    fn reapply_pins(&mut self) -> Result<(), WfcError> {
+        let pins = std::mem::take(&mut self.pins);
+        let applied = pins.iter().try_for_each(|(index, edit)| self.apply_edit(*index, edit));
+        self.pins = pins;
+        applied
+    }
+
+    /// Put the fallback tile in `cell` and finish propagating the bans logged since
+    /// `trail_len`, which the contradiction interrupted. Further contradictions get a
+    /// fallback of their own; each one turns another open cell into a wildcard, so this ends.
+    fn place_fallbacks(&mut self, cell: usize, trail_len: usize) -> bool {
+        match self.fallback.clone() {
+            Some(fallback) => self.place_wildcards(cell, trail_len, Some(fallback)),
+            None => false,
+        }
+    }
+
+    /// `place_fallbacks` with `tile` as the fallback, or leaving the cells empty if `None`
+    fn place_wildcards(&mut self, mut cell: usize, trail_len: usize, tile: Option<T>) -> bool {
+        loop {
+            let banned: Vec<T> = self.grid[cell].possibilities.drain().collect();
+            self.stats.bans += banned.len() as u64;
+            self.log_bans(cell, banned);
+            self.grid[cell].possibilities.extend(tile.clone());
+            self.trail.push(Change::Fallback(cell));
+            self.mark_dirty(cell);
+            if !self.grid[cell].collapsed {
+                self.grid[cell].collapsed = true;
+                self.trail.push(Change::Collapse(cell));
+            }
+            self.fallback_cells.insert(cell);
+            self.stats.fallbacks += 1;
+            self.observed_at[cell] = self.stats.observations;
+            self.confidence[cell] = CellConfidence { options: 0, entropy: 0.0 };
+            if let Some(tile) = tile.as_ref().filter(|_| self.trace.is_enabled()) {
+                let (x, y) = self.get_coords(cell);
+                self.trace.record(&TraceEvent::Observe { step: self.stats.observations, cell, x, y, tile: tile.to_string() });
+            }
+            if let Some(tile) = &tile {
+                self.observers.notify(&SolverEvent::Collapse { cell, tile });
+            }
+
+            let touched: BTreeSet<usize> = self.trail[trail_len..]
+                .iter()
+                .filter_map(|change| match change {
+                    Change::Ban(index, _) => Some(*index),
+                    _ => None,
+                })
+                .collect();
+            self.contradiction_at = None;
+            if touched.into_iter().try_for_each(|index| self.propagate(index)).is_ok() {
+                return true;
+            }
+            match self.contradiction_at.take() {
+                Some(next) => cell = next,
+                None => return false,
+            }
+        }
+    }
+
+    fn finish(&mut self, success: bool) {
+        self.trace.record(&TraceEvent::Finish { step: self.stats.observations, success });
+        self.observers.notify(&SolverEvent::Finish { success });
+    }
+
+    /// Advance the solver by one observe/propagate cycle, backtracking on contradiction.
+    /// Returns `Err(WfcError::Contradiction)` once no alternatives remain, or
+    /// `BacktrackLimitExceeded` past the limits of `set_run_config`, unless the run config
+    /// restarts the search instead. Once the grid is done, further calls return `Done`
+    /// without recording or notifying anything.
+    pub fn step(&mut self) -> Result<StepStatus, WfcError> {
+        self.step_within(None)
+    }
+
+    /// `step`, but only cells whose `mask` entry is true are chosen for collapse. `Done`
+    /// means every masked cell is collapsed; the rest of the wave is only propagated.
+    pub(crate) fn step_within(&mut self, mask: Option<&[bool]>) -> Result<StepStatus, WfcError> {
+        // Find cell with lowest entropy
+        let stopwatch = Stopwatch::start();
+        let next = self.find_lowest_entropy(mask);
+        self.timings.selection += stopwatch.elapsed();
+
+        let Some(index) = next else {
+            // All cells collapsed; the first step to see it reports it
+            if mask.is_none() && !self.finished {
+                self.finished = true;
+                self.finish(true);
+            }
+            return Ok(StepStatus::Done);
+        };
+        self.finished = false;
+
+        // Collapse it
+        let stopwatch = Stopwatch::start();
+        let trail_len = self.trail.len();
+        self.contradiction_at = None;
+        let collapsed = self.collapse_cell(index);
+        self.timings.collapse += stopwatch.elapsed();
+
+        let consistent = match collapsed {
+            Ok(selected_tile) => {
+                if self.history.is_empty() && self.run_start.is_none() {
+                    self.run_start = Some(trail_len);
+                }
+                self.history.push(Decision { index, tile: selected_tile, trail_len });
+
+                // Propagate constraints
+                let stopwatch = Stopwatch::start();
+                let propagated = self.propagate(index)
+                    .and_then(|_| self.limit_runs(index))
+                    .and_then(|_| self.check_constraints());
+                self.timings.propagation += stopwatch.elapsed();
+                propagated.is_ok()
+            },
+            // Contradiction encountered
+            Err(_) => false,
+        };
+
+        if !consistent && self.backtracking_spent {
+            let cell = self.contradiction_at.take().unwrap_or(index);
+            let stopwatch = Stopwatch::start();
+            let recovered = self.place_fallbacks(cell, trail_len);
+            self.timings.backtracking += stopwatch.elapsed();
+            if !recovered {
+                self.finish(false);
+                return Err(WfcError::Contradiction);
+            }
+        } else if !consistent {
+            let stopwatch = Stopwatch::start();
+            let depth = self.history.len();
+            let recovered = self.backtrack();
+            self.timings.backtracking += stopwatch.elapsed();
+            let backtracks = self.stats.backtracks - self.attempt_backtracks;
+            let config = self.run_config;
+            let over_limit = config.max_backtracks.is_some_and(|limit| backtracks > limit)
+                || config.max_backtrack_depth.is_some_and(|limit| depth - self.history.len() > limit);
+            if self.fallback.is_some() && (!recovered || over_limit) {
+                self.backtracking_spent = true;
+                // A failed search leaves nothing to place the fallback in, so replay the run
+                if !recovered && !self.rewind(self.seed) {
+                    self.finish(false);
+                    return Err(WfcError::Contradiction);
+                }
+            } else if !recovered {
+                return self.exhausted(WfcError::Contradiction);
+            } else if over_limit {
+                return self.exhausted(WfcError::BacktrackLimitExceeded { backtracks });
+            }
+        }
+
+        Ok(StepStatus::Running)
+    }
+
+    /// End the current attempt with `error`, or restart the search if `RunConfig` allows
+    fn exhausted(&mut self, error: WfcError) -> Result<StepStatus, WfcError> {
+        let config = self.run_config;
+        if config.on_exhaustion == OnExhaustion::Restart && self.stats.restarts < config.max_restarts as u64 && self.restart() {
+            return Ok(StepStatus::Running);
+        }
+        self.finish(false);
+        Err(error)
+    }
+
+    /// Undo every decision and reseed from the current RNG; see `rewind`
+    fn restart(&mut self) -> bool {
+        self.stats.restarts += 1;
+        let seed = self.rng.gen();
+        self.rewind(seed)
+    }
+
+    /// Undo every decision and start a new attempt with `seed`. Fails if the
+    /// `force`/`ban` edits no longer hold once re-applied.
+    fn rewind(&mut self, seed: u64) -> bool {
+        self.attempt_backtracks = self.stats.backtracks;
+        self.history.clear();
+        self.undo_to(self.run_start.unwrap_or(self.trail.len()));
+        self.contradiction_at = None;
+        self.seed = seed;
+        self.rng = ChaCha12Rng::seed_from_u64(seed);
+        self.noise = (0..self.grid.len()).map(|index| cell_noise(self.seed, index)).collect();
+        self.rescore_all();
+        self.reapply_pins().is_ok()
+    }
+
+    /// Queue every cell for re-scoring, after a change that affects all scores
+    fn rescore_all(&mut self) {
+        self.queue.clear();
+        for index in 0..self.grid.len() {
+            if !self.rescore[index] {
+                self.rescore[index] = true;
+                self.rescore_cells.push(index);
+            }
+        }
+    }
+
+    /// Cells collapsed so far
+    pub fn collapsed_count(&self) -> usize {
+        self.grid.iter().filter(|cell| cell.collapsed).count()
+    }
+
+    /// The collapsed grid, row-major. Fails with `Incomplete` if any cell is not yet
+    /// collapsed.
+    pub fn result(&self) -> Result<Vec<T>, WfcError> {
+        let collapsed = self.collapsed_count();
+        if collapsed < self.grid.len() {
+            return Err(WfcError::Incomplete { collapsed, total: self.grid.len() });
+        }
+        let outside = self.outside.as_ref().or_else(|| self.rules.tiles.keys().min());
+        self.grid.iter().enumerate().map(|(index, cell)| {
+             if cell.collapsed && cell.possibilities.len() == 1 {
+                 Ok(cell.possibilities.iter().next().unwrap().clone())
+             } else if !self.topology.contains(index) {
+                 outside.cloned().ok_or(WfcError::NoTilesDefined)
+             } else {
+                 Err(WfcError::Contradiction)
+             }
+        }).collect()
+    }
+
+    /// The step (1-based, counting observations) at which each cell was collapsed, row-major
+    /// like `result()`, or `None` for cells not collapsed yet. After backtracking a cell keeps
+    /// the step of its final collapse, so gaps in the sequence show where the solver retried.
+    pub fn collapse_order(&self) -> Vec<Option<u64>> {
+        self.grid
+            .iter()
+            .zip(&self.observed_at)
+            .map(|(cell, &step)| cell.collapsed.then_some(step))
+            .collect()
+    }
+
+    /// Coordinates of the cell whose possibilities ran out in the most recent contradiction,
+    /// whether or not backtracking has since recovered from it
+    pub fn last_contradiction(&self) -> Option<(usize, usize)> {
+        self.contradiction_at.map(|index| self.get_coords(index))
+    }
+
+    /// Cells that collapsed or lost or regained possibilities since the previous call (or
+    /// since the model was created), in ascending order. Renderers stepping the model can
+    /// redraw just these instead of the whole grid each frame.
+    pub fn take_dirty(&mut self) -> Vec<usize> {
+        let mut cells = std::mem::take(&mut self.dirty_cells);
+        for &index in &cells {
+            self.dirty[index] = false;
+        }
+        cells.sort_unstable();
+        cells
+    }
+
+    /// Note a change to cell `index`, for `take_dirty` and the selection queue
+    fn mark_dirty(&mut self, index: usize) {
+        if !self.dirty[index] {
+            self.dirty[index] = true;
+            self.dirty_cells.push(index);
+        }
+        if !self.rescore[index] {
+            self.rescore[index] = true;
+            self.rescore_cells.push(index);
+        }
+    }
+
+    /// How undecided each cell was at its collapse, row-major like `result()`, or `None`
+    /// for cells still open. High entropy in a finished grid marks areas the rules left to
+    /// chance.
+    pub fn confidence_map(&self) -> Vec<Option<CellConfidence>> {
+        self.grid
+            .iter()
+            .zip(&self.confidence)
+            .map(|(cell, &confidence)| cell.collapsed.then_some(confidence))
+            .collect()
+    }
+
+    /// Re-check every adjacency of a finished grid (laid out like `result()`) against the
+    /// ruleset. Each disallowed pair is reported from both cells' point of view.
+    pub fn verify_result(&self, grid: &[T]) -> Result<Vec<Violation<T>>, WfcError> {
+        if grid.len() != self.grid.len() {
+            return Err(WfcError::InvalidDimensions { width: self.width, height: self.height });
+        }
+
+        let mut violations = Vec::new();
+        for (cell, tile) in grid.iter().enumerate() {
+            for (neighbor, direction) in self.get_neighbors(cell) {
+                let neighbor_tile = &grid[neighbor];
+                if !self.rules.allows(tile, neighbor_tile, direction) {
+                    violations.push(Violation {
+                        cell,
+                        neighbor,
+                        direction,
+                        tile: tile.clone(),
+                        neighbor_tile: neighbor_tile.clone(),
+                    });
+                }
+            }
+        }
+        Ok(violations)
+    }
+
+    /// Restrict cells by region before solving. `labels` gives every cell's region (row-major,
+    /// like `result()`) and `regions` the tiles each region allows, e.g. the tile keys of a
+    /// per-region sub-ruleset merged into this model's rules. Cells whose label is missing
+    /// from `regions` are left as they are. Fails with `Contradiction` if the regions cannot
+    /// meet under the rules.
+    pub fn apply_region_map(&mut self, labels: &[usize], regions: &HashMap<usize, HashSet<T>>) -> Result<(), WfcError> {
+        if labels.len() != self.grid.len() {
+            return Err(WfcError::InvalidDimensions { width: self.width, height: self.height });
+        }
+        for (index, label) in labels.iter().enumerate() {
+            if let Some(allowed) = regions.get(label) {
+                self.restrict_cell(index, allowed)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reserve a meandering path of tiles matching `tag` (a tile ID or tag, see
+    /// `TileInfo::matches`) from `start` to `end` before solving, for rivers and roads. The
+    /// path is a random walk drawn towards `end` that only enters cells still able to hold
+    /// a matching tile; each cell on it is then restricted to those tiles and the rest of
+    /// the grid is left to the solver. Returns the path's cell indices in walking order. If
+    /// the cells cannot all take the path, none of them is restricted.
+    pub fn carve_path(&mut self, start: (usize, usize), end: (usize, usize), tag: &str) -> Result<Vec<usize>, WfcError> {
+        for (x, y) in [start, end] {
+            if x >= self.width || y >= self.height {
+                return Err(WfcError::OutOfBounds { x, y, width: self.width, height: self.height });
+            }
+        }
+        let path_tiles = self.rules.tiles_matching(tag);
+        if path_tiles.is_empty() {
+            return Err(WfcError::InvalidTileId(tag.to_string()));
+        }
+
+        let start = start.1 * self.width + start.0;
+        let end = end.1 * self.width + end.0;
+        let distance = |model: &Self, index: usize| {
+            let ((x, y), (ex, ey)) = (model.get_coords(index), model.get_coords(end));
+            x.abs_diff(ex) + y.abs_diff(ey)
+        };
+        let open = |model: &Self, index: usize| !model.grid[index].possibilities.is_disjoint(&path_tiles);
+        if !open(self, start) || !open(self, end) {
+            return Err(WfcError::Contradiction);
+        }
+
+        const ATTEMPTS: usize = 20;
+        let mut found = None;
+        for _ in 0..ATTEMPTS {
+            let mut path = vec![start];
+            let mut visited = HashSet::from([start]);
+            let mut current = start;
+            while current != end {
+                let here = distance(self, current);
+                let moves: Vec<(usize, f64)> = self.get_neighbors(current)
+                    .into_iter()
+                    .map(|(n, _)| n)
+                    .filter(|n| !visited.contains(n) && open(self, *n))
+                    .map(|n| {
+                        // Mostly head for the goal, with enough sideways steps to meander
+                        let there = distance(self, n);
+                        (n, if there < here { 4.0 } else if there == here { 1.5 } else { 0.5 })
+                    })
+                    .collect();
+                let Ok(&(next, _)) = moves.choose_weighted(&mut self.rng, |&(_, w)| w) else {
+                    break;
+                };
+                visited.insert(next);
+                path.push(next);
+                current = next;
+            }
+            if current == end {
+                found = Some(path);
+                break;
+            }
+        }
+        let path = found.ok_or(WfcError::Contradiction)?;
+
+        let trail_len = self.trail.len();
+        if let Err(e) = path.iter().try_for_each(|&index| self.restrict_cell(index, &path_tiles)) {
+            self.undo_to(trail_len);
+            return Err(e);
+        }
+        Ok(path)
+    }
+
+    /// Grow a finished grid (laid out like `result()`) by `amount` rows or columns on its
+    /// `direction` side, keeping the existing cells fixed and solving only the new strip
+    /// against the old edge. Returns the combined grid with its new width and height; the
+    /// strip is seeded from this model's RNG, so repeated extensions stay reproducible.
+    /// `direction` must be `Up`, `Right`, `Down` or `Left`.
+    pub fn extend(&mut self, result: &[T], direction: Direction, amount: usize) -> Result<(Vec<T>, usize, usize), WfcError> {
+        self.check_result_len(result)?;
+        self.check_planar(direction)?;
+        if amount == 0 {
+            return Ok((result.to_vec(), self.width, self.height));
+        }
+        let strip = self.solve_strip(direction, amount, &[(edge(result, self.width, self.height, direction), 0, direction)])?;
+        Ok(self.join(&[(result, self.extent(direction)), (&strip, amount)], direction))
+    }
+
+    /// Join two finished grids of this model's size, `second` placed in `direction` of
+    /// `first`, by solving a `seam`-wide strip between them that agrees with both edges.
+    /// The grids may come from different seeds or rulesets as long as this model's rules
+    /// know the edge tiles. Returns the combined grid with its width and height.
+    pub fn stitch(&mut self, first: &[T], second: &[T], direction: Direction, seam: usize) -> Result<(Vec<T>, usize, usize), WfcError> {
+        self.check_result_len(first)?;
+        self.check_result_len(second)?;
+        self.check_planar(direction)?;
+        let extent = self.extent(direction);
+        if seam == 0 {
+            return Err(WfcError::InvalidDimensions { width: seam, height: seam });
+        }
+        let fences = [
+            (edge(first, self.width, self.height, direction), 0, direction),
+            (edge(second, self.width, self.height, direction.opposite()), seam - 1, direction.opposite()),
+        ];
+        let strip = self.solve_strip(direction, seam, &fences)?;
+        Ok(self.join(&[(first, extent), (&strip, seam), (second, extent)], direction))
+    }
+
+    fn check_result_len(&self, result: &[T]) -> Result<(), WfcError> {
+        if result.len() != self.width * self.height {
+            return Err(WfcError::InvalidDimensions { width: self.width, height: self.height });
+        }
+        Ok(())
+    }
+
+    /// Grids only grow within their plane, across one of their edges
+    fn check_planar(&self, direction: Direction) -> Result<(), WfcError> {
+        if !direction.is_planar() || direction.is_diagonal() {
+            return Err(WfcError::InvalidDimensions { width: self.width, height: self.height });
+        }
+        Ok(())
+    }
+
+    /// This model's size across the axis of `direction`; a grid is one layer deep
+    fn extent(&self, direction: Direction) -> usize {
+        match direction {
+            Direction::Left | Direction::Right => self.width,
+            Direction::Up | Direction::Down => self.height,
+            Direction::Forward | Direction::Backward => 1,
+            _ => unreachable!("grids grow across an edge"),
+        }
+    }
+
+    /// Solve a strip `depth` cells deep that grows away from a grid in `direction`. Each
+    /// fence pins the strip's layer at a given depth to be compatible with a row of fixed
+    /// tiles, the strip lying in the fence's direction of them.
+    fn solve_strip(&mut self, direction: Direction, depth: usize, fences: &[(Vec<&T>, usize, Direction)]) -> Result<Vec<T>, WfcError> {
+        let horizontal = matches!(direction, Direction::Left | Direction::Right);
+        let (strip_w, strip_h) = if horizontal { (depth, self.height) } else { (self.width, depth) };
+        let mut strip = Model::new(strip_w, strip_h, self.rules.clone(), Some(self.rng.gen()))?;
+
+        for (fixed_row, layer, towards) in fences {
+            for (along, fixed) in fixed_row.iter().enumerate() {
+                // Layers count from the side facing the original grid
+                let k = match direction {
+                    Direction::Right | Direction::Down | Direction::Forward => *layer,
+                    Direction::Left | Direction::Up | Direction::Backward => depth - 1 - layer,
+                    _ => unreachable!("strips grow across an edge"),
+                };
+                let index = if horizontal { along * depth + k } else { k * strip_w + along };
+                let allowed: HashSet<T> = self.rules.get_all_tile_ids()
+                    .into_iter()
+                    .filter(|id| self.rules.is_compatible(fixed, id, *towards))
+                    .cloned()
+                    .collect();
+                strip.restrict_cell(index, &allowed)?;
+            }
+        }
+        strip.run()
+    }
+
+    /// Lay `parts` (row-major grids with their size along `direction`) side by side, each
+    /// following the previous one in `direction`. Returns the grid with its width and height.
+    fn join(&self, parts: &[(&[T], usize)], direction: Direction) -> (Vec<T>, usize, usize) {
+        let mut parts = parts.to_vec();
+        if matches!(direction, Direction::Left | Direction::Up) {
+            parts.reverse();
+        }
+        let total: usize = parts.iter().map(|(_, extent)| extent).sum();
+        if matches!(direction, Direction::Up | Direction::Down) {
+            let grid = parts.iter().flat_map(|(part, _)| part.iter().cloned()).collect();
+            return (grid, self.width, total);
+        }
+        let mut grid = Vec::with_capacity(total * self.height);
+        for y in 0..self.height {
+            for (part, w) in &parts {
+                grid.extend(part[y * w..(y + 1) * w].iter().cloned());
+            }
+        }
+        (grid, total, self.height)
+    }
+
+    // Task 3.8: Implement main run loop
+    /// Step until every cell is collapsed and return the grid. With the `metrics` feature,
+    /// each call reports to the `metrics` facade: counters `wfc_runs_total` (labeled with
+    /// `outcome`, `ok` or an error code), `wfc_collapses_total`, `wfc_bans_total`,
+    /// `wfc_backtracks_total` and `wfc_fallbacks_total`, and the histogram
+    /// `wfc_run_duration_seconds`.
+    ///
+    /// Fails with `BudgetExceeded` once the run takes more steps or time than `RunConfig`
+    /// allows. The model is left mid-run, so calling `run` again continues with a fresh
+    /// budget.
+    pub fn run(&mut self) -> Result<Vec<T>, WfcError> {
+        self.run_within_budget(&mut self.start_run(), &mut |_| {})
+    }
+
+    /// `run`, calling `progress(collapsed, total)` every `every_n` steps (at least 1) and
+    /// once more when the grid is complete, e.g. to drive a progress bar. `collapsed` is
+    /// `collapsed_count`, which drops back when the solver backtracks.
+    pub fn run_with_progress(&mut self, every_n: usize, mut progress: impl FnMut(usize, usize)) -> Result<Vec<T>, WfcError> {
+        let every_n = every_n.max(1);
+        let mut steps = 0;
+        let grid = self.run_within_budget(&mut self.start_run(), &mut |model| {
+            steps += 1;
+            if steps % every_n == 0 {
+                progress(model.collapsed_count(), model.grid.len());
+            }
+        })?;
+        progress(grid.len(), grid.len());
+        Ok(grid)
+    }
+
+    /// Up to `steps` calls of `step`, stopping early once the grid is done, so a long run
+    /// can be spread over several calls (e.g. one per animation frame). Checks the cancel
+    /// token and the `RunConfig` budget like `run`, with a fresh budget per call.
+    pub fn run_steps(&mut self, steps: usize) -> Result<StepStatus, WfcError> {
+        let mut budget = Budget::start();
+        for _ in 0..steps {
+            self.spend_step(&mut budget)?;
+            if self.step()? == StepStatus::Done {
+                return Ok(StepStatus::Done);
+            }
+        }
+        Ok(StepStatus::Running)
+    }
+
+    fn run_within_budget(&mut self, budget: &mut Budget, on_step: &mut dyn FnMut(&Self)) -> Result<Vec<T>, WfcError> {
+        #[cfg(feature = "metrics")]
+        let (before, stopwatch) = (self.stats, Stopwatch::start());
+        let outcome = self.run_to_end(budget, on_step);
+        #[cfg(feature = "metrics")]
+        record_metrics(&before, &self.stats, stopwatch.elapsed(), &outcome);
+        outcome
+    }
+
+    /// `run`, starting over with a new seed after a failed attempt, up to `attempts` times
+    /// in all. Retry seeds are derived from the model's seed, so the whole sequence is
+    /// reproducible. Returns the last error if every attempt fails. The `RunConfig` budget
+    /// covers all attempts together.
+    pub fn run_with_retries(&mut self, attempts: u32) -> Result<RetryOutcome<T>, WfcError> {
+        let base = self.seed;
+        let mut budget = self.start_run();
+        let mut attempt = 0;
+        loop {
+            let error = match self.run_within_budget(&mut budget, &mut |_| {}) {
+                Ok(grid) => return Ok(RetryOutcome { grid, attempt, seed: self.seed }),
+                Err(e @ (WfcError::Contradiction | WfcError::BacktrackLimitExceeded { .. })) => e,
+                Err(e) => return Err(e),
+            };
+            attempt += 1;
+            // Without a decision to undo, the rules or pins fail on their own and no seed helps
+            if attempt >= attempts || self.run_start.is_none() || !self.rewind(retry_seed(base, attempt)) {
+                return Err(error);
+            }
+        }
+    }
+
+    /// Budget for a new run. A cancellation issued while the model was idle is dropped, so
+    /// it cannot abort a run that had not started.
+    fn start_run(&self) -> Budget {
+        if let Some(token) = &self.cancel {
+            token.reset();
+        }
+        Budget::start()
+    }
+
+    /// Check for cancellation and charge one step to `budget`
+    fn spend_step(&self, budget: &mut Budget) -> Result<(), WfcError> {
+        if self.cancel.as_ref().is_some_and(CancelToken::take) {
+            return Err(WfcError::Cancelled);
+        }
+        budget.spend(&self.run_config)
+    }
+
+    fn run_to_end(&mut self, budget: &mut Budget, on_step: &mut dyn FnMut(&Self)) -> Result<Vec<T>, WfcError> {
+        loop {
+            self.spend_step(budget)?;
+            if self.step()? == StepStatus::Done {
+                break;
+            }
+            on_step(self);
+        }
+
+        // Validate completeness and construct result
+        let result = self.result()?;
+        // Only fallback placements may break the rules
+        debug_assert!(
+            self.verify_result(&result).is_ok_and(|violations| violations
+                .iter()
+                .all(|v| self.fallback_cells.contains(&v.cell) || self.fallback_cells.contains(&v.neighbor))),
+            "solver produced a grid that breaks its own rules"
+        );
+        Ok(result)
+    }
+
+    /// A quick rough solve for live previews: collapse, propagate and check constraints as
+    /// usual, but never backtrack. A contradiction leaves the emptied cell blank (or places the fallback
+    /// tile, if set) and carries on, so this always finishes in one pass and the result
+    /// says which cells are in conflict. Keeps no undo history, so the model cannot
+    /// backtrack afterwards; use a fresh one for the full solve.
+    pub fn run_greedy(&mut self) -> Result<Preview<T>, WfcError> {
+        self.history.clear();
+        while let Some(index) = self.find_lowest_entropy(None) {
+            // With no decisions to return to, each step's log is only needed within it
+            self.trail.clear();
+            self.contradiction_at = None;
+            let consistent = match self.collapse_cell(index) {
+                Ok(_) => self.propagate(index)
+                    .and_then(|_| self.limit_runs(index))
+                    .and_then(|_| self.check_constraints())
+                    .is_ok(),
+                Err(_) => false,
+            };
+            if !consistent {
+                let cell = self.contradiction_at.take().unwrap_or(index);
+                if !self.place_wildcards(cell, 0, self.fallback.clone()) {
+                    return Err(WfcError::Contradiction);
+                }
+            }
+        }
+        self.trail.clear();
+
+        let mut conflicts: Vec<usize> = self.fallback_cells.iter().copied().collect();
+        conflicts.sort_unstable();
+        Ok(Preview {
+            width: self.width,
+            height: self.height,
+            cells: self.grid.iter().map(|cell| cell.possibilities.iter().next().cloned()).collect(),
+            conflicts,
+        })
+    }
+
+    /// `run`, copying the grid every `every_n_steps` steps (at least 1) and once more at the
+    /// end, for timelapse renders. Frames store tile indices rather than IDs to stay cheap.
+    pub fn run_collect_frames(&mut self, every_n_steps: usize) -> Result<Frames<T>, WfcError> {
+        let mut tiles: Vec<T> = self.rules.get_all_tile_ids().into_iter().cloned().collect();
+        tiles.sort();
+        let index: HashMap<&T, u32> = tiles.iter().enumerate().map(|(i, id)| (id, i as u32)).collect();
+        let frame = |model: &Self| -> Vec<u32> {
+            model.grid
+                .iter()
+                .map(|cell| match cell.possibilities.iter().next() {
+                    Some(tile) if cell.collapsed => index[tile],
+                    _ => Frames::<T>::OPEN,
+                })
+                .collect()
+        };
+
+        let every_n_steps = every_n_steps.max(1);
+        let mut frames = Vec::new();
+        let mut budget = self.start_run();
+        let mut steps = 0;
+        loop {
+            self.spend_step(&mut budget)?;
+            if self.step()? == StepStatus::Done {
+                break;
+            }
+            steps += 1;
+            if steps % every_n_steps == 0 {
+                frames.push(frame(self));
+            }
+        }
+        self.result()?;
+        frames.push(frame(self));
+
+        Ok(Frames { width: self.width, height: self.height, tiles, frames })
+    }
+
+    /// `manifest` with each tile written as `name` gives it, for models over tile indices
+    /// (see `intern::InternedModel::manifest`)
+    pub(crate) fn named_manifest(&self, ruleset_hash: String, name: &dyn Fn(&T) -> TileId) -> Result<Manifest, WfcError> {
+        Ok(Manifest {
+            manifest_version: MANIFEST_VERSION,
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            seed: self.seed,
+            width: self.width,
+            height: self.height,
+            ruleset_hash,
+            config: self.named_manifest_config(name),
+            grid: self.result()?.iter().map(name).collect(),
+        })
+    }
+
+    /// Settings that shape the run beyond the rules and seed, as listed in manifests
+    fn named_manifest_config(&self, name: &dyn Fn(&T) -> TileId) -> ManifestConfig {
+        ManifestConfig {
+            topology: format!("{:?}", self.topology),
+            heuristic: format!("{:?}", self.heuristic),
+            transitions: self.transitions
+                .iter()
+                .flat_map(|t| t.entries())
+                .map(|(from, direction, to, factor)| TransitionEntry { from: name(from), direction, to: name(to), factor })
+                .collect(),
+            usage_decay: self.usage_decay,
+            cluster_bonus: self.cluster_bonus
+                .iter()
+                .flat_map(|b| b.entries())
+                .map(|(key, factor)| (key.to_string(), factor))
+                .collect(),
+            max_run_lengths: self.max_runs.iter().map(|(tile, &max)| (name(tile), max)).collect(),
+            weight_gradients: self.gradients.iter().map(|(gradient, _)| gradient.clone()).collect(),
+            fallback_tile: self.fallback.as_ref().map(name),
+        }
+    }
+}
+
+/// Tie-breaking noise of cell `index` in `0..1`: SplitMix64 of the seed and index, so it
+/// needs no RNG draws and is the same for a model restored from a snapshot
+fn cell_noise(seed: u64, index: usize) -> f64 {
+    (splitmix64(seed, index as u64) >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Seed of retry `attempt` of `Model::run_with_retries` on a model seeded with `base`
+fn retry_seed(base: u64, attempt: u32) -> u64 {
+    splitmix64(!base, attempt as u64)
+}
+
+/// Output `n + 1` of the SplitMix64 generator started at `seed`
+pub(crate) fn splitmix64(seed: u64, n: u64) -> u64 {
+    let mut z = seed.wrapping_add(n.wrapping_add(1).wrapping_mul(0x9e3779b97f4a7c15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+/// Shannon entropy in bits of picking among tiles with these weights
+fn shannon_entropy(weights: impl Iterator<Item = f64> + Clone) -> f64 {
+    let total_weight: f64 = weights.clone().sum();
+    if total_weight == 0.0 {
+        return 0.0; // Should handle contradiction elsewhere, but entropy is 0 here
+    }
+
+    weights
+        .map(|weight| {
+            let p = weight / total_weight;
+            // Zero-weight tiles can't be rolled, so they add no uncertainty
+            if p > 0.0 { -p * p.log2() } else { 0.0 }
+        })
+        .sum()
+}
+
+/// Report the work of one `Model::run` call, the difference between `before` and `after`
+#[cfg(feature = "metrics")]
+fn record_metrics<T>(before: &SolverStats, after: &SolverStats, elapsed: Duration, outcome: &Result<T, WfcError>) {
+    let outcome = match outcome {
+        Ok(_) => "ok",
+        Err(e) => e.code_str(),
+    };
+    metrics::counter!("wfc_runs_total", "outcome" => outcome).increment(1);
+    metrics::counter!("wfc_collapses_total").increment(after.observations - before.observations);
+    metrics::counter!("wfc_bans_total").increment(after.bans - before.bans);
+    metrics::counter!("wfc_backtracks_total").increment(after.backtracks - before.backtracks);
+    metrics::counter!("wfc_fallbacks_total").increment(after.fallbacks - before.fallbacks);
+    metrics::counter!("wfc_restarts_total").increment(after.restarts - before.restarts);
+    metrics::histogram!("wfc_run_duration_seconds").record(elapsed.as_secs_f64());
+}
+
+/// Methods that depend on tile IDs being strings
+impl Model {
+    /// Pin `(x, y)` to the tile named `tile_id` before running, e.g. a castle at the center
+    /// of the map. Propagates right away and fails with `Contradiction` if the pin cannot
+    /// coexist with earlier ones; `run` keeps it through backtracking (see `force`).
+    pub fn set_tile(&mut self, x: usize, y: usize, tile_id: &str) -> Result<(), WfcError> {
+        self.force(x, y, tile_id.to_string())
+    }
+
+    /// Current heap footprint of the wave and the backtracking log
+    pub fn memory_usage(&self) -> MemoryUsage {
+        let entry = std::mem::size_of::<TileId>() + 1;
+        let wave = self.grid.capacity() * std::mem::size_of::<Cell>()
+            + self.grid
+                .iter()
+                .map(|cell| cell.possibilities.capacity() * entry + cell.possibilities.iter().map(String::capacity).sum::<usize>())
+                .sum::<usize>();
+        let history = self.history.capacity() * std::mem::size_of::<Decision<TileId>>()
+            + self.trail.capacity() * std::mem::size_of::<Change<TileId>>()
+            + self.trail
+                .iter()
+                .map(|change| match change {
+                    Change::Ban(_, tile) => tile.capacity(),
+                    Change::Collapse(_) | Change::Fallback(_) => 0,
+                })
+                .sum::<usize>();
+        MemoryUsage { wave, history }
+    }
+
+    /// Predicted peak footprint for a `width` x `height` grid over `tile_count` tiles, for
+    /// deciding up front whether a run fits. The history bound holds because each tile can
+    /// be banned from each cell at most once along the current decision path; allocation
+    /// slack from growing the log can add up to as much again.
+    pub fn estimate_memory(width: usize, height: usize, tile_count: usize) -> MemoryUsage {
+        let cells = width * height;
+        // Hash sets keep at least 1/8 of their slots free
+        let slots = tile_count * 8 / 7 + 1;
+        let wave = cells * (std::mem::size_of::<Cell>() + slots * (std::mem::size_of::<TileId>() + 1) + tile_count * ESTIMATED_ID_BYTES);
+        let history = cells * (std::mem::size_of::<Decision<TileId>>() + ESTIMATED_ID_BYTES + std::mem::size_of::<Change<TileId>>())
+            + cells * tile_count * (std::mem::size_of::<Change<TileId>>() + ESTIMATED_ID_BYTES);
+        MemoryUsage { wave, history }
+    }
+
+    /// Everything needed to regenerate the finished grid (see the `manifest` module).
+    /// Fails until the model has been run to completion.
+    pub fn manifest(&self) -> Result<Manifest, WfcError> {
+        self.named_manifest(self.rules.content_hash(), &TileId::clone)
+    }
+
+    /// Capture the run so far for `from_snapshot`, e.g. to pause a long generation and
+    /// resume it in another session. Only square and wrapping grids and the built-in
+    /// heuristics can be restored, so other models fail with `Export`.
+    pub fn to_snapshot(&self) -> Result<ModelSnapshot, WfcError> {
+        let config = self.manifest_config();
+        if restore_topology(&config.topology, self.width, self.height).is_none() || restore_heuristic(&config.heuristic).is_none() {
+            return Err(WfcError::Export(format!("cannot snapshot a model with topology {} and heuristic {}", config.topology, config.heuristic)));
+        }
+        let cells = self.grid
+            .iter()
+            .map(|cell| {
+                let mut possibilities: Vec<TileId> = cell.possibilities.iter().cloned().collect();
+                possibilities.sort();
+                CellState { collapsed: cell.collapsed, possibilities }
+            })
+            .collect();
+        Ok(ModelSnapshot {
+            snapshot_version: SNAPSHOT_VERSION,
+            width: self.width,
+            height: self.height,
+            seed: self.seed,
+            rules: self.rules.to_json_string()?,
+            ruleset_hash: self.rules.content_hash(),
+            config,
+            stats: self.stats,
+            rng: self.rng.clone(),
+            cells,
+            history: self.history.clone(),
+            trail: self.trail.clone(),
+            pins: self.pins.clone(),
+            run_config: self.run_config,
+            run_start: self.run_start,
+            attempt_backtracks: self.attempt_backtracks,
+            fallback_cells: self.fallback_cells.iter().copied().collect(),
+            backtracking_spent: self.backtracking_spent,
+            contradiction_at: self.contradiction_at,
+            observed_at: self.observed_at.clone(),
+            confidence: self.confidence.clone(),
+        })
+    }
+
+    /// Rebuild a model from `to_snapshot`. Every cell starts out dirty (see `take_dirty`)
+    /// so views redraw the whole grid.
+    pub fn from_snapshot(snapshot: ModelSnapshot) -> Result<Model, WfcError> {
+        if snapshot.snapshot_version != SNAPSHOT_VERSION {
+            return Err(WfcError::Import(format!("unsupported snapshot version {}", snapshot.snapshot_version)));
+        }
+        let rules = RuleSet::from_json(&snapshot.rules)?;
+        if rules.content_hash() != snapshot.ruleset_hash {
+            return Err(WfcError::Import("snapshot ruleset does not match its hash".to_string()));
+        }
+        let config = &snapshot.config;
+        let topology = restore_topology(&config.topology, snapshot.width, snapshot.height)
+            .ok_or_else(|| WfcError::Import(format!("unsupported topology {}", config.topology)))?;
+        let heuristic = restore_heuristic(&config.heuristic)
+            .ok_or_else(|| WfcError::Import(format!("unsupported heuristic {}", config.heuristic)))?;
+
+        let mut model = Model::with_topology(topology, rules, Some(snapshot.seed))?;
+        let cell_count = model.grid.len();
+        let in_grid = |index: &usize| *index < cell_count;
+        let consistent = snapshot.cells.len() == cell_count
+            && snapshot.observed_at.len() == cell_count
+            && snapshot.confidence.len() == cell_count
+            && snapshot.history.iter().map(|decision| decision.index).all(|index| in_grid(&index))
+            && snapshot.trail.iter().all(|change| match change {
+                Change::Ban(index, _) | Change::Collapse(index) | Change::Fallback(index) => in_grid(index),
+            })
+            && snapshot.pins.iter().map(|(index, _)| index).all(in_grid)
+            && snapshot.fallback_cells.iter().all(in_grid)
+            && snapshot.contradiction_at.iter().all(in_grid);
+        if !consistent {
+            return Err(WfcError::Import("snapshot cells do not match its dimensions".to_string()));
+        }
+        for (cell, state) in model.grid.iter_mut().zip(snapshot.cells) {
+            if let Some(unknown) = state.possibilities.iter().find(|tile| model.rules.get_tile_info(tile).is_none()) {
+                return Err(WfcError::InvalidTileId(unknown.clone()));
+            }
+            cell.collapsed = state.collapsed;
+            cell.possibilities = state.possibilities.into_iter().collect();
+        }
+
+        model.heuristic = heuristic;
+        model.apply_config(config)?;
+        model.rng = snapshot.rng;
+        model.stats = snapshot.stats;
+        model.history = snapshot.history;
+        model.trail = snapshot.trail;
+        model.pins = snapshot.pins;
+        model.run_config = snapshot.run_config;
+        model.run_start = snapshot.run_start;
+        model.attempt_backtracks = snapshot.attempt_backtracks;
+        model.fallback_cells = snapshot.fallback_cells.into_iter().collect();
+        model.backtracking_spent = snapshot.backtracking_spent;
+        model.contradiction_at = snapshot.contradiction_at;
+        model.observed_at = snapshot.observed_at;
+        model.confidence = snapshot.confidence;
+        for index in 0..cell_count {
+            model.mark_dirty(index);
+        }
+        Ok(model)
+    }
+
+    fn manifest_config(&self) -> ManifestConfig {
+        self.named_manifest_config(&TileId::clone)
+    }
+
+    /// Re-apply the settings of `manifest_config`, apart from topology and heuristic. The
+    /// fallback tile is set without touching the wave, which already reflects it.
+    fn apply_config(&mut self, config: &ManifestConfig) -> Result<(), WfcError> {
+        let mut transitions = TransitionWeights::new();
+        for entry in &config.transitions {
+            transitions.set(entry.from.clone(), entry.direction, entry.to.clone(), entry.factor);
+        }
+        self.set_transitions(transitions);
+        if let Some(decay) = config.usage_decay {
+            self.set_usage_decay(decay);
+        }
+        let mut bonus = ClusterBonus::new();
+        for (key, &factor) in &config.cluster_bonus {
+            bonus.set(key.as_str(), factor);
+        }
+        self.set_cluster_bonus(bonus);
+        for (tile, &max) in &config.max_run_lengths {
+            self.set_max_run_length(tile.clone(), max);
+        }
+        for gradient in &config.weight_gradients {
+            self.add_weight_gradient(gradient.clone())?;
+        }
+        if let Some(tile) = &config.fallback_tile {
+            if self.rules.get_tile_info(tile).is_none() {
+                return Err(WfcError::InvalidTileId(tile.clone()));
+            }
+            self.fallback = Some(tile.clone());
+        }
+        Ok(())
+    }
+}
+
+/// The square or wrapping grid whose `Debug` form is `name`
+fn restore_topology(name: &str, width: usize, height: usize) -> Option<Arc<dyn Topology>> {
+    let grids: [Arc<dyn Topology>; 5] = [
+        Arc::new(SquareGrid::new(width, height)),
+        Arc::new(WrappingGrid::with_axes(width, height, true, true)),
+        Arc::new(WrappingGrid::with_axes(width, height, true, false)),
+        Arc::new(WrappingGrid::with_axes(width, height, false, true)),
+        Arc::new(WrappingGrid::with_axes(width, height, false, false)),
+    ];
+    let diagonal = grids.clone().map(|grid| Arc::new(DiagonalGrid::new(grid)) as Arc<dyn Topology>);
+    grids.into_iter().chain(diagonal).find(|topology| format!("{:?}", topology) == name)
+}
+
+/// The plain grid `with_wrap` solves over
+fn grid_topology(width: usize, height: usize, wrap_x: bool, wrap_y: bool) -> Result<Arc<dyn Topology>, WfcError> {
+    if width == 0 || height == 0 || width > MAX_DIMENSION || height > MAX_DIMENSION {
+        return Err(WfcError::InvalidDimensions { width, height });
+    }
+    Ok(if wrap_x || wrap_y {
+        Arc::new(WrappingGrid::with_axes(width, height, wrap_x, wrap_y))
+    } else {
+        Arc::new(SquareGrid::new(width, height))
+    })
+}
+
+/// The built-in heuristic whose `Debug` form is `name`
+fn restore_heuristic(name: &str) -> Option<Arc<dyn Heuristic>> {
+    let candidates: [Arc<dyn Heuristic>; 2] = [Arc::new(MinEntropy), Arc::new(Frontier)];
+    candidates.into_iter().find(|heuristic| format!("{:?}", heuristic) == name)
+}
+
+/// Cells along the `side` edge of a row-major grid, left to right or top to bottom. The
+/// whole grid faces `Forward` and `Backward`; corners are no edge.
+fn edge<T>(grid: &[T], width: usize, height: usize, side: Direction) -> Vec<&T> {
+    match side {
+        Direction::Forward | Direction::Backward => grid.iter().collect(),
+        Direction::Up => grid[..width].iter().collect(),
+        Direction::Down => grid[(height - 1) * width..].iter().collect(),
+        Direction::Left => (0..height).map(|y| &grid[y * width]).collect(),
+        Direction::Right => (0..height).map(|y| &grid[y * width + width - 1]).collect(),
+        _ => unreachable!("grids have no diagonal edge"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use crate::transition::Axis;
+    use crate::constraints::{BorderConstraint, CountConstraint, MaskConstraint};
+    use crate::connectivity::Connected;
+
+    // Helper to create a simple RuleSet
+    fn create_simple_ruleset() -> RuleSet {
+        let mut rs = RuleSet::new();
+        rs.add_tile("grass".to_string(), 10);
+        rs.add_tile("water".to_string(), 1);
+        
+        // Grass next to Grass (all directions)
+        rs.add_adjacency("grass".to_string(), "grass".to_string(), Direction::Up);
+        rs.add_adjacency("grass".to_string(), "grass".to_string(), Direction::Down);
+        rs.add_adjacency("grass".to_string(), "grass".to_string(), Direction::Left);
+        rs.add_adjacency("grass".to_string(), "grass".to_string(), Direction::Right);
+
+        // Water next to Water
+        rs.add_adjacency("water".to_string(), "water".to_string(), Direction::Up);
+        rs.add_adjacency("water".to_string(), "water".to_string(), Direction::Down);
+        rs.add_adjacency("water".to_string(), "water".to_string(), Direction::Left);
+        rs.add_adjacency("water".to_string(), "water".to_string(), Direction::Right);
+
+        // Grass next to Water
+        rs.add_adjacency("grass".to_string(), "water".to_string(), Direction::Right);
+        rs.add_adjacency("water".to_string(), "grass".to_string(), Direction::Left);
+        
+        rs
+    }
+
+    #[test]
+    fn test_2x2_basic() {
+        let rules = create_simple_ruleset();
+        let mut model = Model::new(2, 2, rules, Some(42)).expect("Model creation failed");
+        let result = model.run();
+        assert!(result.is_ok(), "Generation should succeed");
+        let grid = result.unwrap();
+        assert_eq!(grid.len(), 4);
+    }
+
+    #[test]
+    fn test_contradiction() {
+        let mut rules = RuleSet::new();
+        rules.add_tile("a".to_string(), 1);
+        rules.add_tile("b".to_string(), 1);
+        // No adjacency rules -> contradiction
+        
+        let mut model = Model::new(2, 1, rules, Some(1)).expect("Model creation failed");
+        let result = model.run();
+        assert!(matches!(result, Err(WfcError::Contradiction)));
+    }
+
+    #[test]
+    fn test_backtracking_success() {
+        let mut rules = RuleSet::new();
+        // Tiles: T1, T2 (start options), T3 (dead end), T4 (path), T5 (end)
+        rules.add_tile("T1".to_string(), 100); // High weight to pick T1 first
+        rules.add_tile("T2".to_string(), 1);
+        rules.add_tile("T3".to_string(), 1);
+        rules.add_tile("T4".to_string(), 1);
+        rules.add_tile("T5".to_string(), 1);
+
+        // Adjacency
+        // T1 -> T3 (Right)
+        rules.add_adjacency("T1".to_string(), "T3".to_string(), Direction::Right);
+        rules.add_adjacency("T3".to_string(), "T1".to_string(), Direction::Left);
+
+        // T2 -> T4 (Right)
+        rules.add_adjacency("T2".to_string(), "T4".to_string(), Direction::Right);
+        rules.add_adjacency("T4".to_string(), "T2".to_string(), Direction::Left);
+
+        // T4 -> T5 (Right)
+        rules.add_adjacency("T4".to_string(), "T5".to_string(), Direction::Right);
+        rules.add_adjacency("T5".to_string(), "T4".to_string(), Direction::Left);
+
+        // T3 has NO right neighbors defined.
+        
+        let mut model = Model::new(3, 1, rules, Some(1)).expect("Model creation failed");
+        
+        // Expected: A=T2, B=T4, C=T5.
+        // Even though T1 has higher weight, it leads to dead end.
+        let result = model.run();
+        
+        assert!(result.is_ok(), "Backtracking should find the solution");
+        let grid = result.unwrap();
+        assert_eq!(grid[0], "T2");
+        assert_eq!(grid[1], "T4");
+        assert_eq!(grid[2], "T5");
+    }
+
+    #[test]
+    fn test_backtracking_restores_wave() {
+        let mut model = Model::new(3, 3, create_simple_ruleset(), Some(8)).unwrap();
+        model.step().unwrap();
+        let Decision { index, tile, .. } = model.history[0].clone();
+        assert!(model.memory_usage().history > 0);
+
+        assert!(model.backtrack());
+        // Everything is reopened and only the tried tile is gone from the decided cell
+        assert!(model.history.is_empty());
+        assert!(model.grid.iter().all(|cell| !cell.collapsed));
+        assert!(!model.grid[index].possibilities.contains(&tile));
+        assert_eq!(model.grid[index].possibilities.len(), 1);
+    }
+
+    #[test]
+    fn test_undo_log_holds_one_ban_per_cell_and_tile() {
+        let workload = crate::bench::Workload::contradiction_heavy(24);
+        let mut model = Model::new(24, 24, workload.rules, Some(2)).unwrap();
+        let mut peak = 0;
+        while model.step().unwrap() == StepStatus::Running {
+            peak = peak.max(model.trail.len());
+        }
+        assert!(model.stats().backtracks > 0);
+        let bans = model.trail.iter().filter(|change| matches!(change, Change::Ban(..))).count();
+        let collapses = model.trail.len() - bans;
+        assert!(bans <= 24 * 24 * 4 && collapses <= 24 * 24);
+        assert!(peak <= 24 * 24 * 5);
+    }
+
+    #[test]
+    fn test_memory_estimate_bounds_usage() {
+        let mut model = Model::new(10, 10, create_simple_ruleset(), Some(1)).unwrap();
+        let estimate = Model::estimate_memory(10, 10, 2);
+        assert!(model.memory_usage().wave <= estimate.wave);
+        model.run().unwrap();
+        // Vec growth slack may double the history's allocation
+        assert!(model.memory_usage().history <= estimate.history * 2);
+        assert!(estimate.total() > estimate.wave);
+    }
+
+    #[test]
+    fn test_extend_keeps_edge_consistent() {
+        let mut model = Model::new(4, 3, create_simple_ruleset(), Some(5)).unwrap();
+        let result = model.run().unwrap();
+
+        for direction in [Direction::Up, Direction::Right, Direction::Down, Direction::Left] {
+            let (grid, w, h) = model.extend(&result, direction, 2).unwrap();
+            let horizontal = matches!(direction, Direction::Left | Direction::Right);
+            assert_eq!((w, h), if horizontal { (6, 3) } else { (4, 5) });
+
+            let checker = Model::new(w, h, create_simple_ruleset(), None).unwrap();
+            assert!(checker.verify_result(&grid).unwrap().is_empty(), "{:?}", direction);
+            // The original map is still there
+            let (ox, oy) = match direction {
+                Direction::Left => (2, 0),
+                Direction::Up => (0, 2),
+                _ => (0, 0),
+            };
+            assert_eq!(grid[oy * w + ox], result[0]);
+        }
+    }
+
+    #[test]
+    fn test_stitch_joins_grids() {
+        // Sand bridges water and grass, so any two edges can be joined by a one-cell seam
+        let mut coast = RuleSet::new();
+        for id in ["water", "sand", "grass"] {
+            coast.add_tile(id.to_string(), 1);
+        }
+        for (a, b) in [("water", "water"), ("sand", "sand"), ("grass", "grass"), ("water", "sand"), ("sand", "grass")] {
+            for dir in [Direction::Up, Direction::Right, Direction::Down, Direction::Left] {
+                coast.add_adjacency_symmetric(a.to_string(), b.to_string(), dir);
+            }
+        }
+        let mut left = Model::new(3, 4, coast.clone(), Some(1)).unwrap();
+        let mut right = Model::new(3, 4, coast.clone(), Some(2)).unwrap();
+        let (a, b) = (left.run().unwrap(), right.run().unwrap());
+
+        for seam in [1, 2] {
+            let (grid, w, h) = left.stitch(&a, &b, Direction::Right, seam).unwrap();
+            assert_eq!((w, h), (6 + seam, 4));
+            let checker = Model::new(w, h, coast.clone(), None).unwrap();
+            assert!(checker.verify_result(&grid).unwrap().is_empty());
+            assert_eq!(grid[w - 1], b[2]);
+        }
+        let (grid, w, h) = left.stitch(&a, &b, Direction::Up, 1).unwrap();
+        assert_eq!((w, h), (3, 9));
+        assert_eq!(&grid[..12], b.as_slice());
+        assert!(left.stitch(&a, &b, Direction::Up, 0).is_err());
+    }
+
+    #[test]
+    fn test_zero_weight_tiles_only_when_forced() {
+        let mut rules = RuleSet::new();
+        rules.add_tile("grass".to_string(), 5);
+        rules.add_tile("tower".to_string(), 0);
+        for dir in [Direction::Up, Direction::Right, Direction::Down, Direction::Left] {
+            rules.add_adjacency("grass".to_string(), "grass".to_string(), dir);
+            rules.add_adjacency_symmetric("grass".to_string(), "tower".to_string(), dir);
+        }
+
+        let mut model = Model::new(8, 8, rules.clone(), Some(3)).unwrap();
+        assert!(model.calculate_entropy(0).is_finite());
+        assert!(model.run().unwrap().iter().all(|id| id == "grass"));
+
+        let mut model = Model::new(8, 8, rules, Some(3)).unwrap();
+        model.restrict_cell(27, &HashSet::from(["tower".to_string()])).unwrap();
+        assert_eq!(model.wave_snapshot().probabilities[27 * 2 + 1], 1.0);
+        let result = model.run().unwrap();
+        assert_eq!(result[27], "tower");
+        assert_eq!(result.iter().filter(|id| *id == "tower").count(), 1);
+    }
+
+    #[test]
+    fn test_usage_decay_breaks_up_runs() {
+        let mut rules = RuleSet::new();
+        for id in ["a", "b", "c"] {
+            rules.add_tile(id.to_string(), 1);
+        }
+        for from in ["a", "b", "c"] {
+            for to in ["a", "b", "c"] {
+                for dir in [Direction::Up, Direction::Right, Direction::Down, Direction::Left] {
+                    rules.add_adjacency(from.to_string(), to.to_string(), dir);
+                }
+            }
+        }
+        let repeats = |grid: &[TileId]| (0..grid.len() - 1).filter(|&i| i % 20 != 19 && grid[i] == grid[i + 1]).count();
+
+        let mut plain = 0;
+        let mut decayed = 0;
+        for seed in 0..5 {
+            plain += repeats(&Model::new(20, 20, rules.clone(), Some(seed)).unwrap().run().unwrap());
+            let mut model = Model::new(20, 20, rules.clone(), Some(seed)).unwrap();
+            model.set_usage_decay(UsageDecay { strength: 0.9, radius: 2 });
+            decayed += repeats(&model.run().unwrap());
+        }
+        assert!(decayed * 2 < plain, "{} vs {}", decayed, plain);
+    }
+
+    #[test]
+    fn test_cluster_bonus_grows_blobs() {
+        let tagged = |tag: &str| {
+            let mut metadata = serde_json::Map::new();
+            metadata.insert("tags".to_string(), serde_json::json!([tag]));
+            metadata
+        };
+        let mut rules = RuleSet::new();
+        rules.add_tile_with_metadata("oak".to_string(), 1, tagged("forest"));
+        rules.add_tile_with_metadata("pine".to_string(), 1, tagged("forest"));
+        rules.add_tile("field".to_string(), 2);
+        for from in ["oak", "pine", "field"] {
+            for to in ["oak", "pine", "field"] {
+                for dir in [Direction::Up, Direction::Right, Direction::Down, Direction::Left] {
+                    rules.add_adjacency(from.to_string(), to.to_string(), dir);
+                }
+            }
+        }
+        let is_forest = |id: &TileId| id != "field";
+        // Horizontal pairs where forest meets field
+        let edges = |grid: &[TileId]| (0..grid.len() - 1).filter(|&i| i % 16 != 15 && is_forest(&grid[i]) != is_forest(&grid[i + 1])).count();
+
+        let mut plain = 0;
+        let mut clustered = 0;
+        for seed in 0..4 {
+            plain += edges(&Model::new(16, 16, rules.clone(), Some(seed)).unwrap().run().unwrap());
+            let mut model = Model::new(16, 16, rules.clone(), Some(seed)).unwrap();
+            let mut bonus = ClusterBonus::new();
+            bonus.set("forest", 6.0);
+            bonus.set("field", 6.0);
+            model.set_cluster_bonus(bonus);
+            clustered += edges(&model.run().unwrap());
+        }
+        assert!(clustered * 3 < plain * 2, "{} vs {}", clustered, plain);
+    }
+
+    #[test]
+    fn test_carve_path() {
+        let mut river = serde_json::Map::new();
+        river.insert("tags".to_string(), serde_json::json!(["water"]));
+        let mut rules = RuleSet::new();
+        rules.add_tile("grass".to_string(), 1);
+        // Weight 0: rivers only appear where the path puts them
+        rules.add_tile_with_metadata("river".to_string(), 0, river);
+        for from in ["grass", "river"] {
+            for to in ["grass", "river"] {
+                for dir in [Direction::Up, Direction::Right, Direction::Down, Direction::Left] {
+                    rules.add_adjacency(from.to_string(), to.to_string(), dir);
+                }
+            }
+        }
+
+        let mut model = Model::new(10, 10, rules.clone(), Some(12)).unwrap();
+        let path = model.carve_path((0, 0), (9, 9), "water").unwrap();
+        assert_eq!((path[0], *path.last().unwrap()), (0, 99));
+        for pair in path.windows(2) {
+            assert!(model.get_neighbors(pair[0]).iter().any(|&(n, _)| n == pair[1]));
+        }
+        let result = model.run().unwrap();
+        assert!(path.iter().all(|&i| result[i] == "river"));
+        assert_eq!(result.iter().filter(|id| *id == "river").count(), path.len());
+
+        assert!(model.carve_path((0, 0), (10, 0), "water").is_err());
+        let mut model = Model::new(10, 10, rules, Some(12)).unwrap();
+        assert!(model.carve_path((0, 0), (9, 9), "lava").is_err());
+
+        // Rivers that cannot touch fail partway along the path and leave nothing behind
+        let mut apart = RuleSet::new();
+        apart.add_tile("grass".to_string(), 1);
+        apart.add_tile("river".to_string(), 1);
+        for dir in [Direction::Up, Direction::Right, Direction::Down, Direction::Left] {
+            apart.add_adjacency_symmetric("grass".to_string(), "grass".to_string(), dir);
+            apart.add_adjacency_symmetric("grass".to_string(), "river".to_string(), dir);
+        }
+        let mut model = Model::new(3, 1, apart, Some(12)).unwrap();
+        let before = model.wave_snapshot();
+        assert!(matches!(model.carve_path((0, 0), (2, 0), "river"), Err(WfcError::Contradiction)));
+        assert_eq!(model.wave_snapshot(), before);
+    }
+
+    #[test]
+    fn test_apply_region_map() {
+        let mut rules = RuleSet::new();
+        for id in ["snow", "rock", "sand"] {
+            rules.add_tile(id.to_string(), 1);
+        }
+        for from in ["snow", "rock", "sand"] {
+            for to in ["snow", "rock", "sand"] {
+                for dir in [Direction::Up, Direction::Right, Direction::Down, Direction::Left] {
+                    rules.add_adjacency(from.to_string(), to.to_string(), dir);
+                }
+            }
+        }
+        let tiles = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<HashSet<TileId>>();
+        let regions = HashMap::from([(1, tiles(&["snow"])), (2, tiles(&["sand", "rock"]))]);
+        // Top row snowy, bottom row sandy or rocky, middle row unlabeled
+        let labels: Vec<usize> = (0..12).map(|i| [1, 0, 2][i / 4]).collect();
+
+        let mut model = Model::new(4, 3, rules, Some(3)).unwrap();
+        model.apply_region_map(&labels, &regions).unwrap();
+        let result = model.run().unwrap();
+        assert!(result[..4].iter().all(|id| id == "snow"));
+        assert!(result[8..].iter().all(|id| id != "snow"));
+        assert!(matches!(model.apply_region_map(&labels[1..], &regions), Err(WfcError::InvalidDimensions { .. })));
+    }
+
+    #[test]
+    fn test_weight_gradient_fades_tile() {
+        let mut rules = RuleSet::new();
+        rules.add_tile("snow".to_string(), 1);
+        rules.add_tile("grass".to_string(), 5);
+        for from in ["snow", "grass"] {
+            for to in ["snow", "grass"] {
+                for dir in [Direction::Up, Direction::Right, Direction::Down, Direction::Left] {
+                    rules.add_adjacency(from.to_string(), to.to_string(), dir);
+                }
+            }
+        }
+
+        let mut model = Model::new(20, 20, rules, Some(8)).unwrap();
+        model.add_weight_gradient(WeightGradient { key: "snow".to_string(), axis: Axis::Y, start: 50.0, end: 0.0 }).unwrap();
+        let result = model.run().unwrap();
+        let snow_in = |rows: std::ops::Range<usize>| rows.flat_map(|y| &result[y * 20..(y + 1) * 20]).filter(|id| *id == "snow").count();
+        assert!(snow_in(0..5) > 80, "{}", snow_in(0..5));
+        assert_eq!(snow_in(19..20), 0);
+        assert_eq!(model.manifest().unwrap().config.weight_gradients.len(), 1);
+
+        let lava = WeightGradient { key: "lava".to_string(), axis: Axis::X, start: 1.0, end: 0.0 };
+        assert!(matches!(model.add_weight_gradient(lava), Err(WfcError::InvalidTileId(_))));
+    }
+
+    #[test]
+    fn test_fallback_tile_after_backtrack_limit() {
+        let mut rules = crate::bench::Workload::contradiction_heavy(12).rules;
+        rules.add_tile("gap".to_string(), 0);
+        let mut model = Model::new(12, 12, rules, Some(1)).unwrap();
+        assert!(model.set_fallback_tile("lava".to_string()).is_err());
+        model.set_fallback_tile("gap".to_string()).unwrap();
+        model.set_run_config(RunConfig { max_backtracks: Some(0), ..RunConfig::default() });
+
+        let result = model.run().unwrap();
+        let stats = *model.stats();
+        assert!(stats.backtracks > 0);
+        assert!(stats.fallbacks > 0);
+        assert_eq!(result.iter().filter(|id| *id == "gap").count() as u64, stats.fallbacks);
+        for violation in model.verify_result(&result).unwrap() {
+            assert!(violation.tile == "gap" || violation.neighbor_tile == "gap", "{:?}", violation);
+        }
+    }
+
+    #[test]
+    fn test_fallback_tile_waits_for_backtracking() {
+        let mut rules = crate::bench::Workload::contradiction_heavy(12).rules;
+        rules.add_tile("gap".to_string(), 0);
+        let mut model = Model::new(12, 12, rules, Some(1)).unwrap();
+        model.set_fallback_tile("gap".to_string()).unwrap();
+        let result = model.run().unwrap();
+        // Backtracking alone recovers, so no gaps are needed
+        assert!(model.stats().backtracks > 0);
+        assert_eq!(model.stats().fallbacks, 0);
+        assert!(!result.contains(&"gap".to_string()));
+        assert!(model.verify_result(&result).unwrap().is_empty());
+
+        // A tile with no rules at all can never be placed; once the search is exhausted the
+        // run is replayed with gaps
+        let mut stuck = RuleSet::new();
+        stuck.add_tile("lonely".to_string(), 1);
+        stuck.add_tile("gap".to_string(), 0);
+        let mut model = Model::new(3, 2, stuck, Some(4)).unwrap();
+        model.set_fallback_tile("gap".to_string()).unwrap();
+        let result = model.run().unwrap();
+        assert!(model.stats().fallbacks > 0);
+        assert!(result.contains(&"gap".to_string()));
+    }
+
+    #[test]
+    fn test_run_greedy_reports_conflicts() {
+        let workload = crate::bench::Workload::contradiction_heavy(12);
+        let mut model = Model::new(12, 12, workload.rules.clone(), Some(1)).unwrap();
+        let preview = model.run_greedy().unwrap();
+        assert_eq!(model.stats().backtracks, 0);
+        assert!(!preview.is_complete());
+        assert_eq!(preview.conflicts.len() as u64, model.stats().fallbacks);
+        for (index, cell) in preview.cells.iter().enumerate() {
+            assert_eq!(cell.is_none(), preview.conflicts.contains(&index));
+        }
+        // Away from the conflicts the preview obeys the rules
+        for (index, tile) in preview.cells.iter().enumerate() {
+            for (neighbor, dir) in model.get_neighbors(index) {
+                if let (Some(tile), Some(other)) = (tile, &preview.cells[neighbor]) {
+                    assert!(workload.rules.is_compatible(tile, other, dir));
+                }
+            }
+        }
+
+        let mut model = Model::new(8, 8, create_simple_ruleset(), Some(1)).unwrap();
+        let preview = model.run_greedy().unwrap();
+        assert!(preview.is_complete());
+        assert!(preview.cells.iter().all(Option::is_some));
+
+        // Count constraints hold in previews too
+        for seed in 0..10 {
+            let mut model = Model::new(8, 8, create_simple_ruleset(), Some(seed)).unwrap();
+            model.add_count_constraint(CountConstraint { tile: "water".to_string(), min: 0, max: Some(2) }).unwrap();
+            let preview = model.run_greedy().unwrap();
+            let water = preview.cells.iter().filter(|cell| cell.as_deref() == Some("water")).count();
+            assert!(water <= 2, "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn test_backtrack_limit_and_incomplete() {
+        let workload = crate::bench::Workload::contradiction_heavy(12);
+        let mut model = Model::new(12, 12, workload.rules, Some(1)).unwrap();
+        assert!(matches!(model.result(), Err(WfcError::Incomplete { collapsed: 0, total: 144 })));
+        model.set_max_backtracks(0);
+        match model.run() {
+            Err(WfcError::BacktrackLimitExceeded { backtracks }) => assert!(backtracks > 0),
+            other => panic!("expected the backtrack limit, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_run_with_retries_reports_successful_seed() {
+        let workload = crate::bench::Workload::contradiction_heavy(8);
+        let mut model = Model::new(8, 8, workload.rules.clone(), Some(1)).unwrap();
+        model.set_max_backtracks(0);
+        let outcome = model.run_with_retries(50).unwrap();
+        assert!(outcome.attempt > 0);
+        assert_eq!(outcome.seed, model.seed());
+        assert!(model.verify_result(&outcome.grid).unwrap().is_empty());
+
+        // The reported seed replays the successful attempt on its own
+        let mut replay = Model::new(8, 8, workload.rules.clone(), Some(outcome.seed)).unwrap();
+        replay.set_max_backtracks(0);
+        assert_eq!(replay.run().unwrap(), outcome.grid);
+
+        let mut model = Model::new(8, 8, workload.rules, Some(1)).unwrap();
+        model.set_max_backtracks(0);
+        assert!(matches!(model.run_with_retries(1), Err(WfcError::BacktrackLimitExceeded { .. })));
+    }
+
+    #[test]
+    fn test_run_budget_stops_and_resumes() {
+        let workload = crate::bench::Workload::large_grid(16);
+        let mut model = Model::new(16, 16, workload.rules, Some(4)).unwrap();
+        model.set_run_config(RunConfig { max_iterations: Some(10), ..RunConfig::default() });
+        match model.run() {
+            Err(WfcError::BudgetExceeded { iterations, .. }) => assert_eq!(iterations, 10),
+            other => panic!("expected the budget to run out, got {:?}", other),
+        }
+        assert_eq!(model.stats().observations, 10);
+
+        model.set_run_config(RunConfig { max_millis: Some(0), ..RunConfig::default() });
+        assert!(matches!(model.run(), Err(WfcError::BudgetExceeded { iterations: 0, .. })));
+        model.set_run_config(RunConfig::default());
+        let grid = model.run().unwrap();
+        assert!(model.verify_result(&grid).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_cancel_token_stops_run_from_observer() {
+        let workload = crate::bench::Workload::large_grid(16);
+        let mut model = Model::new(16, 16, workload.rules, Some(4)).unwrap();
+        let token = CancelToken::new();
+        model.set_cancel_token(token.clone());
+        let handle = token.clone();
+        model.add_observer(move |event: &SolverEvent| {
+            if matches!(event, SolverEvent::Collapse { .. }) {
+                handle.cancel();
+            }
+        });
+        assert!(matches!(model.run(), Err(WfcError::Cancelled)));
+        assert_eq!(model.stats().observations, 1);
+        assert!(!token.is_cancelled());
+
+        // A cancellation while idle does not carry over into the next run
+        model.clear_observers();
+        token.cancel();
+        let grid = model.run().unwrap();
+        assert!(model.verify_result(&grid).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_run_with_progress_reports_collapsed_cells() {
+        let workload = crate::bench::Workload::large_grid(10);
+        let mut model = Model::new(10, 10, workload.rules, Some(4)).unwrap();
+        let mut reports = Vec::new();
+        model.run_with_progress(25, |collapsed, total| reports.push((collapsed, total))).unwrap();
+        // This workload never backtracks, so every step collapses at least one more cell
+        assert_eq!(reports.len(), model.stats().observations as usize / 25 + 1);
+        assert!(reports.windows(2).all(|pair| pair[0].0 <= pair[1].0));
+        assert!(reports[0].0 >= 25);
+        assert_eq!(reports.last(), Some(&(100, 100)));
+    }
+
+    #[test]
+    fn test_masks_restrict_cells() {
+        let rules = crate::bench::Workload::large_grid(10).rules;
+        let tiles = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<HashSet<_>>();
+        let lake = Rect { x: 2, y: 2, width: 4, height: 3 };
+
+        let mut model = Model::new(10, 10, rules.clone(), Some(5)).unwrap();
+        model.mask_rect(lake, &tiles(&["water"])).unwrap();
+        model.mask_cell(9, 9, &tiles(&["grass", "sand"])).unwrap();
+        let grid = model.run().unwrap();
+        assert!(lake.cells().all(|(x, y)| grid[y * 10 + x] == "water"));
+        assert_ne!(grid[99], "water");
+
+        // Water cannot touch grass, so the second mask fails and is rolled back
+        let mut model = Model::new(10, 10, rules.clone(), Some(5)).unwrap();
+        model.mask_rect(lake, &tiles(&["water"])).unwrap();
+        let before = model.wave_snapshot();
+        let beside = Rect { x: 6, y: 2, width: 1, height: 3 };
+        assert!(matches!(model.mask_rect(beside, &tiles(&["grass"])), Err(WfcError::Contradiction)));
+        assert_eq!(model.wave_snapshot(), before);
+        assert!(matches!(model.mask_rect(Rect { x: 8, y: 8, width: 3, height: 1 }, &tiles(&["sand"])), Err(WfcError::OutOfBounds { x: 10, y: 8, .. })));
+        assert!(matches!(model.mask_cell(0, 0, &tiles(&["lava"])), Err(WfcError::InvalidTileId(_))));
+
+        // Masks declared in the ruleset apply from the start; cells past the grid are skipped
+        let mut declared = rules;
+        declared.constraints.regions.insert("lake".to_string(), vec![lake, Rect { x: 8, y: 8, width: 5, height: 5 }]);
+        declared.constraints.masks.push(MaskConstraint { region: "lake".to_string(), tiles: vec!["water".to_string()] });
+        let model = Model::new(10, 10, declared, Some(5)).unwrap();
+        assert_eq!(model.grid[2 * 10 + 2].possibilities, tiles(&["water"]));
+        assert_eq!(model.grid[9 * 10 + 9].possibilities, tiles(&["water"]));
+        assert_eq!(model.grid[0].possibilities.len(), 3);
+    }
+
+    #[test]
+    fn test_borders_restrict_edge_cells() {
+        let rules = crate::bench::Workload::large_grid(8).rules;
+        let tiles = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<HashSet<_>>();
+        let on_border = |i: usize| matches!((i % 8, i / 8), (0 | 7, _) | (_, 0 | 7));
+
+        let mut model = Model::new(8, 8, rules.clone(), Some(3)).unwrap();
+        model.mask_border(&[], &tiles(&["water"])).unwrap();
+        let grid = model.run().unwrap();
+        assert!((0..64).filter(|&i| on_border(i)).all(|i| grid[i] == "water"));
+
+        let mut model = Model::new(8, 8, rules.clone(), Some(3)).unwrap();
+        model.mask_border(&[Direction::Up, Direction::Down], &tiles(&["water"])).unwrap();
+        // The corners already have to be water
+        let before = model.wave_snapshot();
+        assert!(matches!(model.mask_border(&[Direction::Left], &tiles(&["grass"])), Err(WfcError::Contradiction)));
+        assert_eq!(model.wave_snapshot(), before);
+        model.mask_border(&[Direction::Left], &tiles(&["water", "sand"])).unwrap();
+        let grid = model.run().unwrap();
+        assert!(grid[..8].iter().chain(&grid[56..]).all(|id| id == "water"));
+        assert!((0..8).all(|y| grid[y * 8] != "grass"));
+
+        // Declared in the ruleset: ocean all round, water on the top edge only
+        let mut declared = rules;
+        declared.constraints.borders.push(BorderConstraint { edges: vec![], tiles: vec!["water".to_string(), "sand".to_string()] });
+        declared.constraints.borders.push(BorderConstraint { edges: vec![Direction::Up], tiles: vec!["water".to_string()] });
+        let json = declared.to_json_string().unwrap();
+        let grid = Model::new(8, 8, RuleSet::from_json(&json).unwrap(), Some(3)).unwrap().run().unwrap();
+        assert!(grid[..8].iter().all(|id| id == "water"));
+        assert!((0..64).filter(|&i| on_border(i)).all(|i| grid[i] != "grass"));
+
+        // A key matching no tile is an error, not an empty border
+        declared.constraints.borders.push(BorderConstraint { edges: vec![Direction::Down], tiles: vec!["lava".to_string()] });
+        assert!(matches!(Model::new(8, 8, declared, Some(3)), Err(WfcError::InvalidTileId(key)) if key == "lava"));
+    }
+
+    #[test]
+    fn test_count_constraints_limit_occurrences() {
+        let json = r#"{
+            "tiles": [
+                { "id": "floor", "weight": 20 },
+                { "id": "chest", "weight": 1, "metadata": { "tags": ["loot"] } },
+                { "id": "boss_room", "weight": 10 }
+            ],
+            "rules": [],
+            "missing_adjacency": "open",
+            "constraints": { "counts": [{ "tile": "boss_room", "max": 1 }, { "tile": "loot", "min": 3 }] }
+        }"#;
+        let rules = RuleSet::from_json(json).unwrap();
+        let occurrences = |grid: &[TileId], id: &str| grid.iter().filter(|tile| *tile == id).count();
+        for seed in 0..5 {
+            let grid = Model::new(6, 6, rules.clone(), Some(seed)).unwrap().run().unwrap();
+            assert!(occurrences(&grid, "boss_room") <= 1, "seed {}", seed);
+            assert!(occurrences(&grid, "chest") >= 3, "seed {}", seed);
+        }
+
+        // Added to a model, a count counts what is already placed
+        let mut model = Model::new(6, 6, rules.clone(), Some(1)).unwrap();
+        model.force(0, 0, "floor".to_string()).unwrap();
+        let floors = CountConstraint { tile: "floor".to_string(), min: 0, max: Some(1) };
+        model.add_count_constraint(floors).unwrap();
+        let grid = model.run().unwrap();
+        assert_eq!(occurrences(&grid, "floor"), 1);
+
+        let mut model = Model::new(2, 2, rules.clone(), Some(1)).unwrap();
+        let before = model.wave_snapshot();
+        let too_many = CountConstraint { tile: "chest".to_string(), min: 5, max: None };
+        assert!(matches!(model.add_count_constraint(too_many), Err(WfcError::Contradiction)));
+        assert_eq!(model.wave_snapshot(), before);
+        assert_eq!(model.rules().constraints.counts.len(), 2);
+        assert!(matches!(model.force(0, 0, "boss_room".to_string()).and_then(|_| model.force(1, 1, "boss_room".to_string())), Err(WfcError::Contradiction)));
+        assert!(matches!(Model::new(1, 2, rules, None), Err(WfcError::Contradiction)));
+    }
+
+    #[test]
+    fn test_connected_constraint_repairs_during_run() {
+        let mut rules = RuleSet::new();
+        rules.add_tile("floor".to_string(), 1);
+        rules.add_tile("wall".to_string(), 1);
+        rules.missing_adjacency = MissingAdjacency::Open;
+        let floor_regions = |grid: &[TileId]| {
+            let mut seen = vec![false; grid.len()];
+            let mut regions = 0;
+            for start in 0..grid.len() {
+                if grid[start] != "floor" || seen[start] {
+                    continue;
+                }
+                regions += 1;
+                let mut stack = vec![start];
+                seen[start] = true;
+                while let Some(i) = stack.pop() {
+                    for (n, _) in SquareGrid::new(10, 10).neighbors(i) {
+                        if grid[n] == "floor" && !seen[n] {
+                            seen[n] = true;
+                            stack.push(n);
                         }
                     }
                 }
-            } else {
-                // All cells collapsed (or none left to collapse)
-                break;
             }
+            regions
+        };
+
+        let unconstrained = Model::new(10, 10, rules.clone(), Some(1)).unwrap().run().unwrap();
+        assert!(floor_regions(&unconstrained) > 1);
+        for seed in 0..4 {
+            let mut model = Model::new(10, 10, rules.clone(), Some(seed)).unwrap();
+            model.add_wave_constraint(Arc::new(Connected::matching(&rules, &["floor"]))).unwrap();
+            let grid = model.run().unwrap();
+            assert_eq!(floor_regions(&grid), 1, "seed {}", seed);
+            assert!(model.stats().backtracks > 0);
         }
 
-        // Validate completeness and construct result
-        let result: Result<Vec<TileId>, WfcError> = self.grid.iter().map(|cell| {
-             if cell.collapsed && cell.possibilities.len() == 1 {
-                 Ok(cell.possibilities.iter().next().unwrap().clone())
-             } else {
-                 Err(WfcError::Contradiction) 
-             }
+        // Two floors walled apart cannot be joined
+        let mut model = Model::new(3, 1, rules.clone(), Some(1)).unwrap();
+        model.force(0, 0, "floor".to_string()).unwrap();
+        model.force(1, 0, "wall".to_string()).unwrap();
+        model.force(2, 0, "floor".to_string()).unwrap();
+        assert!(matches!(model.add_wave_constraint(Arc::new(Connected::matching(&rules, &["floor"]))), Err(WfcError::Contradiction)));
+    }
+
+    #[test]
+    fn test_masked_area_generates_inside_only() {
+        let rules = crate::bench::Workload::large_grid(10).rules;
+        // A round island
+        let inside: Vec<bool> = (0..100).map(|i| {
+            let (dx, dy) = ((i % 10) as f64 - 4.5, (i / 10) as f64 - 4.5);
+            dx * dx + dy * dy < 16.0
         }).collect();
+        let cells = inside.iter().filter(|&&flag| flag).count();
 
-        result
+        let mut model = Model::with_mask(10, 10, &inside, rules.clone(), Some(4)).unwrap();
+        model.set_outside_tile("water".to_string()).unwrap();
+        // Outside cells are absent, so they can neither take nor break a border
+        model.mask_border(&[], &["grass".to_string()].into()).unwrap();
+        model.force(4, 4, "grass".to_string()).unwrap();
+        // Single-cell edits outside the island are rejected
+        assert!(matches!(model.force(0, 0, "sand".to_string()), Err(WfcError::OutOfBounds { x: 0, y: 0, .. })));
+        assert!(matches!(model.ban(9, 9, "sand".to_string()), Err(WfcError::OutOfBounds { .. })));
+        assert!(model.mask_cell(0, 9, &["sand".to_string()].into()).is_err());
+        let grid = model.run().unwrap();
+        assert_eq!(model.stats().observations as usize, cells - 1);
+        assert!((0..100).filter(|&i| !inside[i]).all(|i| grid[i] == "water"));
+        assert_eq!(grid[44], "grass");
+        // Grass may sit right next to the outside water, which no rule would allow
+        assert!((0..100).any(|i| inside[i] && grid[i] == "grass" && (i < 10 || !inside[i - 10])));
+
+        let preview = Model::with_mask(10, 10, &inside, rules.clone(), Some(4)).unwrap().run_greedy().unwrap();
+        assert_eq!(preview.cells[0], None);
+        assert!(matches!(Model::with_mask(10, 10, &inside[1..], rules, None), Err(WfcError::InvalidDimensions { .. })));
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use proptest::prelude::*;
+    #[test]
+    fn test_diagonal_rules_constrain_corner_neighbors() {
+        // Open cardinal edges, but diagonal neighbors must differ
+        let json = r#"{
+            "tiles": [{ "id": "a", "weight": 1 }, { "id": "b", "weight": 1 }],
+            "rules": [
+                { "from": "a", "to": "b", "direction": "UpRight", "symmetric": true },
+                { "from": "a", "to": "b", "direction": "DownRight", "symmetric": true },
+                { "from": "a", "to": "b", "direction": "DownLeft", "symmetric": true },
+                { "from": "a", "to": "b", "direction": "UpLeft", "symmetric": true }
+            ],
+            "missing_adjacency": "open"
+        }"#;
+        let rules = RuleSet::from_json(json).unwrap();
+        assert!(!rules.is_compatible(&"b".to_string(), &"b".to_string(), Direction::DownLeft));
 
-    // Helper to create a simple RuleSet
-    fn create_simple_ruleset() -> RuleSet {
-        let mut rs = RuleSet::new();
-        rs.add_tile("grass".to_string(), 10);
-        rs.add_tile("water".to_string(), 1);
-        
-        // Grass next to Grass (all directions)
-        rs.add_adjacency("grass".to_string(), "grass".to_string(), Direction::Up);
-        rs.add_adjacency("grass".to_string(), "grass".to_string(), Direction::Down);
-        rs.add_adjacency("grass".to_string(), "grass".to_string(), Direction::Left);
-        rs.add_adjacency("grass".to_string(), "grass".to_string(), Direction::Right);
+        let mut model = Model::with_diagonals(6, 6, false, false, rules.clone(), Some(3)).unwrap();
+        let grid = model.run().unwrap();
+        assert!(model.verify_result(&grid).unwrap().is_empty());
+        for (x, y) in (0..5).flat_map(|y| (0..5).map(move |x| (x, y))) {
+            assert_ne!(grid[y * 6 + x], grid[(y + 1) * 6 + x + 1]);
+            assert_ne!(grid[y * 6 + x + 1], grid[(y + 1) * 6 + x]);
+        }
+        // A plain grid ignores the diagonal rules
+        let plain = Model::new(6, 6, rules.clone(), Some(3)).unwrap().run().unwrap();
+        assert!(!model.verify_result(&plain).unwrap().is_empty());
 
-        // Water next to Water
-        rs.add_adjacency("water".to_string(), "water".to_string(), Direction::Up);
-        rs.add_adjacency("water".to_string(), "water".to_string(), Direction::Down);
-        rs.add_adjacency("water".to_string(), "water".to_string(), Direction::Left);
-        rs.add_adjacency("water".to_string(), "water".to_string(), Direction::Right);
+        let mut torus = Model::with_diagonals(6, 6, true, true, rules, Some(3)).unwrap();
+        torus.step().unwrap();
+        let json = torus.to_snapshot().unwrap().to_json_string().unwrap();
+        let mut resumed = Model::from_snapshot(ModelSnapshot::from_json(&json).unwrap()).unwrap();
+        assert_eq!(resumed.run().unwrap(), torus.run().unwrap());
+    }
 
-        // Grass next to Water
-        rs.add_adjacency("grass".to_string(), "water".to_string(), Direction::Right);
-        rs.add_adjacency("water".to_string(), "grass".to_string(), Direction::Left);
-        
-        rs
+    #[test]
+    fn test_run_steps_matches_run() {
+        let workload = crate::bench::Workload::contradiction_heavy(8);
+        let mut expected = Model::new(8, 8, workload.rules.clone(), Some(2)).unwrap();
+        let expected = expected.run().unwrap();
+
+        let mut model = Model::new(8, 8, workload.rules, Some(2)).unwrap();
+        assert_eq!(model.run_steps(0).unwrap(), StepStatus::Running);
+        let mut calls = 0;
+        while model.run_steps(7).unwrap() == StepStatus::Running {
+            calls += 1;
+        }
+        assert!(calls > 1);
+        assert_eq!(model.result().unwrap(), expected);
     }
 
     #[test]
-    fn test_2x2_basic() {
-        let rules = create_simple_ruleset();
-        let mut model = Model::new(2, 2, rules, Some(42)).expect("Model creation failed");
-        let result = model.run();
-        assert!(result.is_ok(), "Generation should succeed");
-        let grid = result.unwrap();
-        assert_eq!(grid.len(), 4);
+    fn test_run_config_restarts_on_exhaustion() {
+        let workload = crate::bench::Workload::contradiction_heavy(12);
+        let fail = RunConfig { max_backtrack_depth: Some(0), ..RunConfig::default() };
+        let mut model = Model::new(12, 12, workload.rules.clone(), Some(1)).unwrap();
+        model.set_run_config(fail);
+        assert!(matches!(model.run(), Err(WfcError::BacktrackLimitExceeded { .. })));
+        assert_eq!(model.stats().restarts, 0);
+
+        let mut model = Model::new(12, 12, workload.rules, Some(1)).unwrap();
+        model.set_tile(0, 0, "d").unwrap();
+        model.set_run_config(RunConfig { max_backtracks: Some(10), max_backtrack_depth: Some(3), max_restarts: 50, on_exhaustion: OnExhaustion::Restart, ..fail });
+        let result = model.run().unwrap();
+        assert!(model.stats().restarts > 0);
+        assert_eq!(result[0], "d");
+        assert!(model.verify_result(&result).unwrap().is_empty());
     }
 
     #[test]
-    fn test_contradiction() {
+    #[cfg(feature = "metrics")]
+    fn test_run_reports_metrics() {
+        use std::sync::{Arc, Mutex};
+        use metrics::{Counter, CounterFn, Gauge, Histogram, HistogramFn, Key, KeyName, Metadata, Recorder, SharedString, Unit};
+
+        /// Sums every counter increment and histogram sample under the metric's name
+        #[derive(Default)]
+        struct Totals(Arc<Mutex<HashMap<String, f64>>>);
+
+        struct Handle(Arc<Mutex<HashMap<String, f64>>>, String);
+
+        impl CounterFn for Handle {
+            fn increment(&self, value: u64) {
+                *self.0.lock().unwrap().entry(self.1.clone()).or_default() += value as f64;
+            }
+
+            fn absolute(&self, _: u64) {}
+        }
+
+        impl HistogramFn for Handle {
+            fn record(&self, _: f64) {
+                *self.0.lock().unwrap().entry(self.1.clone()).or_default() += 1.0;
+            }
+        }
+
+        impl Recorder for Totals {
+            fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+            fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+            fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+            fn register_counter(&self, key: &Key, _: &Metadata<'_>) -> Counter {
+                Counter::from_arc(Arc::new(Handle(self.0.clone(), key.name().to_string())))
+            }
+
+            fn register_gauge(&self, _: &Key, _: &Metadata<'_>) -> Gauge {
+                Gauge::noop()
+            }
+
+            fn register_histogram(&self, key: &Key, _: &Metadata<'_>) -> Histogram {
+                Histogram::from_arc(Arc::new(Handle(self.0.clone(), key.name().to_string())))
+            }
+        }
+
+        let recorder = Totals::default();
+        let mut model = Model::new(4, 4, create_simple_ruleset(), Some(2)).unwrap();
+        metrics::with_local_recorder(&recorder, || model.run().unwrap());
+
+        let totals = recorder.0.lock().unwrap();
+        assert_eq!(totals["wfc_runs_total"], 1.0);
+        assert_eq!(totals["wfc_collapses_total"], model.stats().observations as f64);
+        assert_eq!(totals["wfc_bans_total"], model.stats().bans as f64);
+        assert_eq!(totals["wfc_run_duration_seconds"], 1.0);
+    }
+
+    #[test]
+    fn test_force_and_ban_between_steps() {
         let mut rules = RuleSet::new();
-        rules.add_tile("a".to_string(), 1);
-        rules.add_tile("b".to_string(), 1);
-        // No adjacency rules -> contradiction
-        
-        let mut model = Model::new(2, 1, rules, Some(1)).expect("Model creation failed");
-        let result = model.run();
-        assert!(matches!(result, Err(WfcError::Contradiction)));
+        let tiles: Vec<TileId> = ["a", "b", "c"].iter().map(|id| id.to_string()).collect();
+        for id in &tiles {
+            rules.add_tile(id.clone(), 1);
+        }
+        for from in &tiles {
+            for to in &tiles {
+                for dir in [Direction::Up, Direction::Right, Direction::Down, Direction::Left] {
+                    rules.add_adjacency(from.clone(), to.clone(), dir);
+                }
+            }
+        }
+        let mut model = Model::new(6, 6, rules, Some(5)).unwrap();
+        model.ban(0, 0, tiles[0].clone()).unwrap();
+        for _ in 0..3 {
+            model.step().unwrap();
+        }
+        let open = (0..36).rev().find(|&i| model.possibilities(i).len() > 1).unwrap();
+        model.force(open % 6, open / 6, tiles[2].clone()).unwrap();
+
+        // Undoing a decision made before the edits re-applies them
+        assert!(model.backtrack());
+        assert_eq!(model.possibilities(open), &HashSet::from([tiles[2].clone()]));
+        assert!(!model.possibilities(0).contains(&tiles[0]));
+
+        let result = model.run().unwrap();
+        assert_eq!(result[open], tiles[2]);
+        assert_ne!(result[0], tiles[0]);
+
+        // A failed edit leaves the finished grid untouched
+        let other = tiles.iter().find(|id| **id != result[5]).unwrap().clone();
+        assert!(matches!(model.force(5, 0, other), Err(WfcError::Contradiction)));
+        assert_eq!(model.result().unwrap(), result);
+        assert!(matches!(model.ban(6, 0, tiles[0].clone()), Err(WfcError::OutOfBounds { x: 6, y: 0, .. })));
+        assert!(matches!(model.ban(0, 0, "lava".to_string()), Err(WfcError::InvalidTileId(_))));
     }
 
     #[test]
-    fn test_backtracking_success() {
+    fn test_set_tile_pins_cells_for_run() {
+        let mut model = Model::new(5, 3, create_simple_ruleset(), Some(8)).unwrap();
+        model.set_tile(2, 1, "water").unwrap();
+        // Water can only be followed by more water to its right
+        assert!(model.possibilities(8).iter().all(|tile| tile == "water"));
+        assert!(matches!(model.set_tile(3, 1, "grass"), Err(WfcError::Contradiction)));
+        assert!(matches!(model.set_tile(0, 0, "lava"), Err(WfcError::InvalidTileId(_))));
+
+        let grid = model.run().unwrap();
+        assert_eq!(grid[7], "water");
+        assert_eq!(grid[9], "water");
+    }
+
+    #[test]
+    fn test_take_dirty_lists_changed_cells() {
+        let mut model = Model::new(6, 6, create_simple_ruleset(), Some(3)).unwrap();
+        assert!(model.take_dirty().is_empty());
+
+        model.step().unwrap();
+        let collapsed = model.collapse_order().iter().position(Option::is_some).unwrap();
+        let changed: Vec<usize> = (0..36).filter(|&i| model.possibilities(i).len() < 2).collect();
+        assert!(changed.len() > 1, "the collapse should propagate");
+        assert_eq!(model.take_dirty(), changed);
+        assert!(model.take_dirty().is_empty());
+
+        // Undoing the decision touches the same cells again
+        assert!(model.backtrack());
+        assert!(model.take_dirty().contains(&collapsed));
+
+        model.run().unwrap();
+        assert_eq!(model.take_dirty().len(), 36);
+    }
+
+    #[test]
+    fn test_snapshot_resumes_run() {
+        let workload = crate::bench::Workload::contradiction_heavy(12);
+        let mut model = Model::new(workload.width, workload.height, workload.rules, Some(3)).unwrap();
+        let tile = model.rules().get_all_tile_ids().into_iter().max().unwrap().clone();
+        model.set_tile(0, 0, &tile).unwrap();
+        model.set_usage_decay(crate::transition::UsageDecay::default());
+        for _ in 0..40 {
+            model.step().unwrap();
+        }
+        assert!(model.stats().backtracks > 0);
+
+        let json = model.to_snapshot().unwrap().to_json_string().unwrap();
+        let mut resumed = Model::from_snapshot(ModelSnapshot::from_json(&json).unwrap()).unwrap();
+        assert_eq!(resumed.take_dirty().len(), 144);
+        assert_eq!(resumed.manifest_config(), model.manifest_config());
+        assert_eq!(resumed.wave_snapshot(), model.wave_snapshot());
+
+        let grid = model.run().unwrap();
+        assert_eq!(resumed.run().unwrap(), grid);
+        // The restored queue starts empty, so only entropy evaluations differ
+        let stats = SolverStats { entropy_evaluations: model.stats().entropy_evaluations, ..*resumed.stats() };
+        assert_eq!(&stats, model.stats());
+        assert_eq!(grid[0], tile);
+
+        let mut hex = Model::with_topology(Arc::new(crate::topology::HexGrid::new(4, 4)), create_simple_ruleset(), None).unwrap();
+        hex.step().unwrap();
+        assert!(matches!(hex.to_snapshot(), Err(WfcError::Export(_))));
+    }
+
+    /// Minimum entropy, but claiming to read neighbors so the solver scans every cell
+    #[derive(Debug)]
+    struct ScannedEntropy;
+
+    impl Heuristic for ScannedEntropy {
+        fn score(&self, cell: &Candidate) -> f64 {
+            cell.entropy
+        }
+
+        fn uses_neighbors(&self) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn test_queue_selects_like_full_scan() {
+        let workload = crate::bench::Workload::contradiction_heavy(12);
+        let mut queued = Model::new(12, 12, workload.rules.clone(), Some(9)).unwrap();
+        let mut scanned = Model::new(12, 12, workload.rules, Some(9)).unwrap();
+        scanned.set_heuristic(Arc::new(ScannedEntropy));
+
+        let grid = queued.run().unwrap();
+        assert_eq!(scanned.run().unwrap(), grid);
+        assert_eq!(queued.collapse_order(), scanned.collapse_order());
+        assert!(queued.stats().backtracks > 0);
+        assert!(queued.stats().entropy_evaluations < scanned.stats().entropy_evaluations);
+    }
+
+    #[test]
+    fn test_entropy_map() {
+        let mut model = Model::new(4, 3, create_simple_ruleset(), Some(5)).unwrap();
+        let fresh = model.entropy_map();
+        // grass 10 : water 1
+        let p: f64 = 10.0 / 11.0;
+        assert!((fresh.max_entropy - (-p * p.log2() - (1.0 - p) * (1.0 - p).log2())).abs() < 1e-9);
+        assert!(fresh.entropy.iter().all(|&e| e == fresh.max_entropy));
+
+        model.step().unwrap();
+        let map = model.entropy_map();
+        assert_eq!(map.counts, model.wave_snapshot().counts);
+        for (&entropy, &count) in map.entropy.iter().zip(&map.counts) {
+            assert_eq!(entropy == 0.0, count <= 1);
+            assert!(entropy <= map.max_entropy);
+        }
+        assert!(map.counts.contains(&1));
+    }
+
+    #[test]
+    fn test_confidence_map() {
+        let mut model = Model::new(6, 6, create_simple_ruleset(), Some(3)).unwrap();
+        assert!(model.confidence_map().iter().all(Option::is_none));
+
+        // The first cell is chosen with both tiles open; once it settles its column, the
+        // vertical rules leave every cell of that column no choice
+        model.step().unwrap();
+        let first = model.collapse_order().iter().position(Option::is_some).unwrap();
+        let confidence = model.confidence_map()[first].unwrap();
+        assert_eq!(confidence.options, 2);
+        let (p, q) = (10.0 / 11.0, 1.0 / 11.0);
+        assert!((confidence.entropy - -(p * f64::log2(p) + q * f64::log2(q))).abs() < 1e-9);
+
+        model.run().unwrap();
+        let map = model.confidence_map();
+        assert!(map.iter().all(Option::is_some));
+        for y in (0..6).filter(|&y| y != first / 6) {
+            assert!(map[y * 6 + first % 6].unwrap().is_certain());
+        }
+    }
+
+    #[test]
+    fn test_max_run_length() {
         let mut rules = RuleSet::new();
-        // Tiles: T1, T2 (start options), T3 (dead end), T4 (path), T5 (end)
-        rules.add_tile("T1".to_string(), 100); // High weight to pick T1 first
-        rules.add_tile("T2".to_string(), 1);
-        rules.add_tile("T3".to_string(), 1);
-        rules.add_tile("T4".to_string(), 1);
-        rules.add_tile("T5".to_string(), 1);
+        rules.add_tile("wall".to_string(), 20);
+        rules.add_tile("floor".to_string(), 1);
+        for from in ["wall", "floor"] {
+            for to in ["wall", "floor"] {
+                for dir in [Direction::Up, Direction::Right, Direction::Down, Direction::Left] {
+                    rules.add_adjacency(from.to_string(), to.to_string(), dir);
+                }
+            }
+        }
+        let longest = |grid: &[TileId]| {
+            let mut longest = 0;
+            for (step, starts) in [(1, (0..12).map(|y| y * 12).collect::<Vec<_>>()), (12, (0..12).collect())] {
+                for start in starts {
+                    let mut run = 0;
+                    for k in 0..12 {
+                        run = if grid[start + k * step] == "wall" { run + 1 } else { 0 };
+                        longest = longest.max(run);
+                    }
+                }
+            }
+            longest
+        };
 
-        // Adjacency
-        // T1 -> T3 (Right)
-        rules.add_adjacency("T1".to_string(), "T3".to_string(), Direction::Right);
-        rules.add_adjacency("T3".to_string(), "T1".to_string(), Direction::Left);
+        let mut model = Model::new(12, 12, rules.clone(), Some(8)).unwrap();
+        assert!(longest(&model.run().unwrap()) > 3);
 
-        // T2 -> T4 (Right)
-        rules.add_adjacency("T2".to_string(), "T4".to_string(), Direction::Right);
-        rules.add_adjacency("T4".to_string(), "T2".to_string(), Direction::Left);
+        let mut model = Model::new(12, 12, rules, Some(8)).unwrap();
+        model.set_max_run_length("wall".to_string(), 3);
+        assert!(longest(&model.run().unwrap()) <= 3);
+    }
 
-        // T4 -> T5 (Right)
-        rules.add_adjacency("T4".to_string(), "T5".to_string(), Direction::Right);
-        rules.add_adjacency("T5".to_string(), "T4".to_string(), Direction::Left);
+    #[test]
+    fn test_run_collect_frames() {
+        let mut model = Model::new(4, 3, create_simple_ruleset(), Some(2)).unwrap();
+        let frames = model.run_collect_frames(5).unwrap();
+        assert_eq!(frames.tiles, vec!["grass".to_string(), "water".to_string()]);
+        assert!(frames.frames.len() >= 3);
 
-        // T3 has NO right neighbors defined.
-        
-        let mut model = Model::new(3, 1, rules, Some(1)).expect("Model creation failed");
-        
-        // Expected: A=T2, B=T4, C=T5.
-        // Even though T1 has higher weight, it leads to dead end.
-        let result = model.run();
-        
-        assert!(result.is_ok(), "Backtracking should find the solution");
-        let grid = result.unwrap();
-        assert_eq!(grid[0], "T2");
-        assert_eq!(grid[1], "T4");
-        assert_eq!(grid[2], "T5");
+        let open = |frame: &[u32]| frame.iter().filter(|&&i| i == Frames::<TileId>::OPEN).count();
+        assert_eq!(open(&frames.frames[0]), 12 - 5);
+        let last = frames.frames.last().unwrap();
+        assert_eq!(open(last), 0);
+        let grid: Vec<TileId> = last.iter().map(|&i| frames.tiles[i as usize].clone()).collect();
+        assert_eq!(grid, model.result().unwrap());
+    }
+
+    #[test]
+    fn test_integer_tiles() {
+        // A checkerboard over u32 tiles, which never touches a string
+        let mut rules = RuleSet::<u32>::default();
+        rules.add_tile(0, 1);
+        rules.add_tile(1, 1);
+        for dir in [Direction::Up, Direction::Right, Direction::Down, Direction::Left] {
+            rules.add_adjacency(0, 1, dir);
+            rules.add_adjacency(1, 0, dir);
+        }
+
+        let mut model = Model::new(5, 4, rules, Some(6)).unwrap();
+        model.set_max_run_length(0, 1);
+        let grid = model.run().unwrap();
+        assert!(model.verify_result(&grid).unwrap().is_empty());
+        for (i, &tile) in grid.iter().enumerate() {
+            assert_eq!(tile, (grid[0] + (i % 5 + i / 5) as u32) % 2);
+        }
+        assert_eq!(model.wave_snapshot().tiles, vec![0, 1]);
+    }
+
+    #[test]
+    fn test_shared_str_tiles() {
+        // Cloning an `Arc<str>` tile only bumps a reference count
+        let workload = crate::bench::Workload::large_grid(12);
+        let mut rules = RuleSet::<Arc<str>>::default();
+        for info in workload.rules.get_all_tiles() {
+            rules.add_tile(Arc::from(info.id.as_str()), info.weight);
+        }
+        for ((from, direction), targets) in &workload.rules.adjacency {
+            for to in targets {
+                rules.add_adjacency(Arc::from(from.as_str()), Arc::from(to.as_str()), *direction);
+            }
+        }
+
+        let mut model = Model::new(12, 12, rules, Some(5)).unwrap();
+        model.force(6, 6, Arc::from("water")).unwrap();
+        let grid = model.run().unwrap();
+        assert_eq!(&*grid[6 * 12 + 6], "water");
+
+        let names: Vec<TileId> = grid.iter().map(|tile| tile.to_string()).collect();
+        let mut expected = Model::new(12, 12, workload.rules, Some(5)).unwrap();
+        expected.set_tile(6, 6, "water").unwrap();
+        assert_eq!(expected.run().unwrap(), names);
+    }
+
+    #[test]
+    fn test_with_wrap_joins_requested_edges() {
+        // A checkerboard can only wrap across an even extent
+        let mut rules = RuleSet::<u32>::default();
+        rules.add_tile(0, 1);
+        rules.add_tile(1, 1);
+        for dir in [Direction::Up, Direction::Right, Direction::Down, Direction::Left] {
+            rules.add_adjacency(0, 1, dir);
+            rules.add_adjacency(1, 0, dir);
+        }
+
+        let mut cylinder = Model::with_wrap(6, 5, true, false, rules.clone(), Some(3)).unwrap();
+        let grid = cylinder.run().unwrap();
+        assert_ne!(grid[0], grid[5]);
+        assert_eq!(grid[0], grid[24]);
+
+        let mut model = Model::with_wrap(6, 5, false, true, rules.clone(), Some(3)).unwrap();
+        assert!(model.run().is_err());
+        assert!(Model::with_wrap(0, 5, true, true, rules, None).is_err());
+    }
+
+    #[test]
+    fn test_collapse_order() {
+        let mut model = Model::new(3, 3, create_simple_ruleset(), Some(4)).unwrap();
+        assert!(model.collapse_order().iter().all(Option::is_none));
+        model.step().unwrap();
+        assert_eq!(model.collapse_order().iter().flatten().count(), 1);
+
+        model.run().unwrap();
+        let mut steps: Vec<u64> = model.collapse_order().into_iter().map(Option::unwrap).collect();
+        steps.sort();
+        steps.dedup();
+        assert_eq!(steps.len(), 9);
+    }
+
+    #[test]
+    fn test_verify_result() {
+        let model = Model::new(2, 1, create_simple_ruleset(), Some(1)).unwrap();
+        let ok: Vec<TileId> = vec!["grass".to_string(), "water".to_string()];
+        assert!(model.verify_result(&ok).unwrap().is_empty());
+
+        // Water may not sit left of grass, seen from either side
+        let bad: Vec<TileId> = vec!["water".to_string(), "grass".to_string()];
+        let violations = model.verify_result(&bad).unwrap();
+        assert_eq!(violations.len(), 2);
+        assert_eq!(violations[0].direction, Direction::Right);
+        assert_eq!(violations[1].neighbor_tile, "water");
+
+        assert!(model.verify_result(&ok[..1]).is_err());
+    }
+
+    #[test]
+    fn test_one_sided_rules_agree_with_verify_result() {
+        let id = |name: &str| name.to_string();
+        // Only `a` lists `b` to its right; `b` never allows `a` back, so the pair never fits
+        let mut one_way = RuleSet::new();
+        one_way.add_tile(id("a"), 1);
+        one_way.add_tile(id("b"), 1);
+        one_way.add_adjacency(id("a"), id("b"), Direction::Right);
+        for seed in 0..50 {
+            assert!(matches!(Model::new(2, 1, one_way.clone(), Some(seed)).unwrap().run(), Err(WfcError::Contradiction)));
+        }
+
+        // With mutual rules alongside, one-sided `a`-`a` pairs are never placed
+        let mut mixed = one_way;
+        mixed.add_adjacency(id("b"), id("a"), Direction::Left);
+        mixed.add_adjacency_symmetric(id("b"), id("b"), Direction::Right);
+        mixed.add_adjacency(id("a"), id("a"), Direction::Right);
+        for seed in 0..50 {
+            let mut model = Model::new(4, 1, mixed.clone(), Some(seed)).unwrap();
+            let grid = model.run().unwrap();
+            assert!(model.verify_result(&grid).unwrap().is_empty(), "seed {}", seed);
+            assert!(grid.windows(2).all(|pair| pair != [id("a"), id("a")]));
+        }
+    }
+
+    #[test]
+    fn test_transitions_bias_collapse() {
+        let mut rs = RuleSet::new();
+        rs.add_tile("deep".to_string(), 1);
+        rs.add_tile("shallow".to_string(), 1);
+        for a in ["deep", "shallow"] {
+            for b in ["deep", "shallow"] {
+                for dir in [Direction::Up, Direction::Right, Direction::Down, Direction::Left] {
+                    rs.add_adjacency(a.to_string(), b.to_string(), dir);
+                }
+            }
+        }
+
+        // Forbidding the deep/shallow transition leaves only uniform grids
+        let mut transitions = TransitionWeights::new();
+        transitions.set_all_directions("deep".to_string(), "shallow".to_string(), 0.0);
+        transitions.set_all_directions("shallow".to_string(), "deep".to_string(), 0.0);
+        for seed in 0..5 {
+            let mut model = Model::new(4, 4, rs.clone(), Some(seed)).unwrap();
+            model.set_transitions(transitions.clone());
+            let result = model.run().unwrap();
+            assert!(result.iter().all(|id| id == &result[0]), "seed {}: {:?}", seed, result);
+        }
+    }
+
+    #[test]
+    fn test_run_report() {
+        let rules = create_simple_ruleset();
+        let mut model = Model::new(4, 4, rules, Some(3)).expect("Model creation failed");
+        model.run().expect("Generation should succeed");
+
+        let report = model.report();
+        assert_eq!(report.stats.observations, 16);
+        assert!(report.timings.total() > Duration::ZERO);
+        assert_eq!(report.timings.backtracking, Duration::ZERO);
+    }
+
+    #[test]
+    fn test_step_and_wave_snapshot() {
+        let rules = create_simple_ruleset();
+        let mut model = Model::new(3, 3, rules, Some(5)).expect("Model creation failed");
+
+        let before = model.wave_snapshot();
+        assert_eq!(before.tiles, vec!["grass".to_string(), "water".to_string()]);
+        assert!(before.counts.iter().all(|&c| c == 2));
+        // grass has weight 10 of 11
+        assert!((before.probabilities[0] - 10.0 / 11.0).abs() < 1e-6);
+
+        assert_eq!(model.step().unwrap(), StepStatus::Running);
+        let after = model.wave_snapshot();
+        assert!(after.counts.contains(&1));
+
+        while model.step().unwrap() == StepStatus::Running {}
+        assert!(model.wave_snapshot().counts.iter().all(|&c| c == 1));
+        assert_eq!(model.result().unwrap().len(), 9);
     }
 
     proptest! {
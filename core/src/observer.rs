@@ -0,0 +1,135 @@
+//! Live hooks into a run.
+//!
+//! Observers registered with `Model::add_observer` are told about collapses, bans,
+//! backtracks and the end of the run as they happen, as plain values rather than JSON.
+//! Where a `Trace` only records the bans made by propagation, observers see every ban, so
+//! they can mirror the wave exactly. They suit live visualizations and logging;
+//! they cannot influence the run. Any `FnMut(&SolverEvent<T>) + Send` closure is an
+//! observer, and types that need more state can implement `Observer` directly.
+
+use std::fmt;
+use crate::TileId;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SolverEvent<'a, T = TileId> {
+    /// A cell was collapsed to `tile`, by choice or as the fallback tile
+    Collapse { cell: usize, tile: &'a T },
+    /// `tile` was removed from a cell's possibilities, by propagation, by collapsing the cell
+    /// to another tile, by an edit such as `Model::ban`, or by backtracking
+    Ban { cell: usize, tile: &'a T },
+    /// The decision to place `tile` in a cell was undone
+    Backtrack { cell: usize, tile: &'a T },
+    /// The run ended, either fully collapsed or with an unrecoverable contradiction
+    Finish { success: bool },
+}
+
+pub trait Observer<T = TileId>: Send {
+    fn notify(&mut self, event: &SolverEvent<T>);
+}
+
+impl<T, F: FnMut(&SolverEvent<T>) + Send> Observer<T> for F {
+    fn notify(&mut self, event: &SolverEvent<T>) {
+        self(event)
+    }
+}
+
+/// The observers of one model. Like a `Trace`, they are not cloned with the model.
+pub(crate) struct Observers<T>(Vec<Box<dyn Observer<T>>>);
+
+impl<T> Observers<T> {
+    pub(crate) fn push(&mut self, observer: Box<dyn Observer<T>>) {
+        self.0.push(observer);
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub(crate) fn notify(&mut self, event: &SolverEvent<T>) {
+        for observer in &mut self.0 {
+            observer.notify(event);
+        }
+    }
+}
+
+impl<T> Default for Observers<T> {
+    fn default() -> Self {
+        Observers(Vec::new())
+    }
+}
+
+impl<T> Clone for Observers<T> {
+    fn clone(&self) -> Self {
+        Observers::default()
+    }
+}
+
+impl<T> fmt::Debug for Observers<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Observers").field("count", &self.0.len()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use crate::bench::Workload;
+    use crate::model::Model;
+
+    /// Tallies events by kind: collapse, ban, backtrack, finish
+    #[derive(Clone, Default)]
+    struct Counter(Arc<Mutex<[u64; 4]>>);
+
+    impl Observer for Counter {
+        fn notify(&mut self, event: &SolverEvent) {
+            let kind = match event {
+                SolverEvent::Collapse { .. } => 0,
+                SolverEvent::Ban { .. } => 1,
+                SolverEvent::Backtrack { .. } => 2,
+                SolverEvent::Finish { .. } => 3,
+            };
+            self.0.lock().unwrap()[kind] += 1;
+        }
+    }
+
+    #[test]
+    fn test_observers_see_every_event() {
+        let workload = Workload::contradiction_heavy(12);
+        let mut model = Model::new(workload.width, workload.height, workload.rules, Some(3)).unwrap();
+        let counter = Counter::default();
+        model.add_observer(counter.clone());
+        let mut copy = model.clone();
+        let finished = Arc::new(Mutex::new(Vec::new()));
+        let log = finished.clone();
+        model.add_observer(move |event: &SolverEvent| {
+            if let SolverEvent::Finish { success } = event {
+                log.lock().unwrap().push(*success);
+            }
+        });
+
+        model.run().unwrap();
+        let [collapses, bans, backtracks, finishes] = *counter.0.lock().unwrap();
+        let stats = model.stats();
+        assert_eq!(collapses, stats.observations);
+        assert_eq!(bans, stats.bans);
+        assert_eq!(backtracks, stats.backtracks);
+        assert!(backtracks > 0 && bans > 0);
+        assert_eq!(finishes, 1);
+        assert_eq!(*finished.lock().unwrap(), vec![true]);
+
+        // Stepping a finished model reports nothing more
+        assert_eq!(model.step().unwrap(), crate::model::StepStatus::Done);
+        model.run().unwrap();
+        assert_eq!(counter.0.lock().unwrap()[3], 1);
+        assert_eq!(*finished.lock().unwrap(), vec![true]);
+
+        // Clones start without observers
+        copy.run().unwrap();
+        assert_eq!(counter.0.lock().unwrap()[3], 1);
+    }
+}
@@ -0,0 +1,361 @@
+//! The overlapping model.
+//!
+//! Every `n`x`n` window of a sample grid becomes a pattern; two patterns may sit next to
+//! each other when they agree on the `n`x`(n-1)` region where they overlap. Patterns are
+//! stored as interned tile indices so extraction and compatibility checks hash small integer
+//! slices instead of strings. `OverlappingModel` solves a grid of patterns with the regular
+//! solver and reads the output tiles off them, so every `n`x`n` window of the output also
+//! occurs in the sample.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use crate::{Direction, TileId, DIRECTIONS};
+use crate::error::WfcError;
+use crate::model::Model;
+use crate::ruleset::RuleSet;
+use crate::topology::{SquareGrid, Topology, WrappingGrid};
+
+/// Supported pattern sizes. N=3 suits most textures; 4-5 keep larger structures intact at
+/// the cost of many more patterns.
+pub const PATTERN_SIZES: std::ops::RangeInclusive<usize> = 2..=5;
+
+
+/// Distinct `n`x`n` patterns of a sample and which may neighbor which
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatternSet {
+    pub n: usize,
+    /// Tile for each interned index used in `patterns`
+    pub tiles: Vec<TileId>,
+    /// Row-major `n * n` tile indices per pattern, in order of first appearance
+    pub patterns: Vec<Vec<u32>>,
+    /// Occurrences of each pattern in the sample
+    pub counts: Vec<u32>,
+    /// `propagator[p][d]`: patterns that may sit in direction `DIRECTIONS[d]` of pattern `p`, sorted
+    propagator: Vec<[Vec<usize>; 4]>,
+}
+
+impl PatternSet {
+    /// Collect every `n`x`n` window of a row-major sample grid. With `periodic` the windows
+    /// wrap around the sample's edges, as if it tiled seamlessly.
+    pub fn extract(sample: &[TileId], width: usize, height: usize, n: usize, periodic: bool) -> Result<PatternSet, WfcError> {
+        if width == 0 || height == 0 || sample.len() != width * height {
+            return Err(WfcError::InvalidDimensions { width, height });
+        }
+        if !PATTERN_SIZES.contains(&n) || (!periodic && (n > width || n > height)) {
+            return Err(WfcError::InvalidDimensions { width: n, height: n });
+        }
+
+        let mut tiles: Vec<TileId> = Vec::new();
+        let mut tile_index: HashMap<&TileId, u32> = HashMap::new();
+        let cells: Vec<u32> = sample
+            .iter()
+            .map(|id| {
+                *tile_index.entry(id).or_insert_with(|| {
+                    tiles.push(id.clone());
+                    tiles.len() as u32 - 1
+                })
+            })
+            .collect();
+
+        let (max_x, max_y) = if periodic { (width, height) } else { (width - n + 1, height - n + 1) };
+        let mut pattern_index: HashMap<Vec<u32>, usize> = HashMap::new();
+        let mut patterns = Vec::new();
+        let mut counts = Vec::new();
+        for y in 0..max_y {
+            for x in 0..max_x {
+                let pattern: Vec<u32> = (0..n * n)
+                    .map(|i| cells[(y + i / n) % height * width + (x + i % n) % width])
+                    .collect();
+                match pattern_index.get(&pattern) {
+                    Some(&p) => counts[p] += 1,
+                    None => {
+                        pattern_index.insert(pattern.clone(), patterns.len());
+                        patterns.push(pattern);
+                        counts.push(1);
+                    }
+                }
+            }
+        }
+
+        let propagator = build_propagator(&patterns, n);
+        Ok(PatternSet { n, tiles, patterns, counts, propagator })
+    }
+
+    pub fn len(&self) -> usize {
+        self.patterns.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    /// Patterns allowed in `direction` of `pattern`; none for `Forward`/`Backward`, as
+    /// patterns are flat, or for diagonals, which the edge overlaps already imply
+    pub fn compatible(&self, pattern: usize, direction: Direction) -> &[usize] {
+        match direction_index(direction) {
+            Some(d) => &self.propagator[pattern][d],
+            None => &[],
+        }
+    }
+
+    /// The tile at a pattern's top-left corner, which is what an output cell shows
+    pub fn top_left(&self, pattern: usize) -> &TileId {
+        &self.tiles[self.patterns[pattern][0] as usize]
+    }
+
+    /// Pattern tile ID used in `to_ruleset`
+    pub fn pattern_id(pattern: usize) -> TileId {
+        format!("p{}", pattern)
+    }
+
+    /// A ruleset whose tiles are the patterns (`p0`, `p1`, ...) weighted by frequency, so
+    /// the regular solver can run the overlapping model
+    pub fn to_ruleset(&self) -> RuleSet {
+        let mut rules = RuleSet::new();
+        for (p, &count) in self.counts.iter().enumerate() {
+            rules.add_tile(PatternSet::pattern_id(p), count);
+        }
+        for p in 0..self.len() {
+            for dir in DIRECTIONS {
+                for &q in self.compatible(p, dir) {
+                    rules.add_adjacency(PatternSet::pattern_id(p), PatternSet::pattern_id(q), dir);
+                }
+            }
+        }
+        rules
+    }
+}
+
+/// Options for `OverlappingModel::new`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OverlappingOptions {
+    /// Pattern size, one of `PATTERN_SIZES`
+    pub n: usize,
+    /// Treat the sample as tiling seamlessly, so windows wrap around its edges
+    pub periodic_input: bool,
+    /// Make the output tile seamlessly too
+    pub periodic_output: bool,
+}
+
+impl Default for OverlappingOptions {
+    fn default() -> Self {
+        OverlappingOptions {
+            n: 3,
+            periodic_input: true,
+            periodic_output: false,
+        }
+    }
+}
+
+/// Generates grids that look like a sample, with no adjacency rules to write: the rules
+/// and weights come from the sample's patterns and how often they occur.
+pub struct OverlappingModel {
+    patterns: PatternSet,
+    model: Model,
+    width: usize,
+    height: usize,
+    /// Pattern index of each `PatternSet::pattern_id`
+    index: HashMap<TileId, usize>,
+}
+
+impl OverlappingModel {
+    /// Learn `options.n`x`options.n` patterns from a row-major sample and set up a
+    /// `width`x`height` output
+    pub fn new(sample: &[TileId], sample_width: usize, sample_height: usize, width: usize, height: usize, options: &OverlappingOptions, seed: Option<u64>) -> Result<OverlappingModel, WfcError> {
+        let patterns = PatternSet::extract(sample, sample_width, sample_height, options.n, options.periodic_input)?;
+        OverlappingModel::with_patterns(patterns, width, height, options.periodic_output, seed)
+    }
+
+    /// Set up a `width`x`height` output over already extracted patterns. Without
+    /// `periodic_output` the output must be at least `n` cells each way, since each
+    /// pattern along the bottom and right edges also supplies the cells past it.
+    pub fn with_patterns(patterns: PatternSet, width: usize, height: usize, periodic_output: bool, seed: Option<u64>) -> Result<OverlappingModel, WfcError> {
+        let n = patterns.n;
+        let topology: Arc<dyn Topology> = if periodic_output {
+            Arc::new(WrappingGrid::new(width, height))
+        } else if width >= n && height >= n {
+            Arc::new(SquareGrid::new(width - n + 1, height - n + 1))
+        } else {
+            return Err(WfcError::InvalidDimensions { width, height });
+        };
+        let model = Model::with_topology(topology, patterns.to_ruleset(), seed)?;
+        let index = (0..patterns.len()).map(|p| (PatternSet::pattern_id(p), p)).collect();
+        Ok(OverlappingModel { patterns, model, width, height, index })
+    }
+
+    pub fn patterns(&self) -> &PatternSet {
+        &self.patterns
+    }
+
+    /// The solver over the pattern grid, whose tiles are pattern IDs, for stepping,
+    /// tracing and the like
+    pub fn model(&mut self) -> &mut Model {
+        &mut self.model
+    }
+
+    /// Solve and return the output grid, row-major, like `Model::run`
+    pub fn run(&mut self) -> Result<Vec<TileId>, WfcError> {
+        self.model.run()?;
+        self.result()
+    }
+
+    /// The output of a finished solve
+    pub fn result(&self) -> Result<Vec<TileId>, WfcError> {
+        let grid = self.model.result()?;
+        let (columns, rows) = (self.model.width(), self.model.height());
+        let n = self.patterns.n;
+        (0..self.width * self.height)
+            .map(|i| {
+                // Cells past the last pattern row/column are read from inside that pattern
+                let (x, y) = (i % self.width, i / self.width);
+                let (px, py) = (x.min(columns - 1), y.min(rows - 1));
+                let pattern = self.index.get(&grid[py * columns + px])
+                    .ok_or_else(|| WfcError::InvalidTileId(grid[py * columns + px].clone()))?;
+                let tile = self.patterns.patterns[*pattern][(y - py) * n + (x - px)];
+                Ok(self.patterns.tiles[tile as usize].clone())
+            })
+            .collect()
+    }
+}
+
+fn direction_index(direction: Direction) -> Option<usize> {
+    DIRECTIONS.iter().position(|&dir| dir == direction)
+}
+
+/// Offset of the neighbor in one of `DIRECTIONS`
+fn offset(direction: Direction) -> (isize, isize) {
+    match direction {
+        Direction::Up => (0, -1),
+        Direction::Right => (1, 0),
+        Direction::Down => (0, 1),
+        Direction::Left => (-1, 0),
+        _ => unreachable!("patterns only overlap across an edge"),
+    }
+}
+
+/// Tiles of `pattern` inside the window shifted by (dx, dy), row-major. Two patterns `a`, `b`
+/// with `b` at offset (dx, dy) from `a` agree exactly when
+/// `overlap(a, dx, dy) == overlap(b, -dx, -dy)`.
+fn overlap(pattern: &[u32], n: usize, dx: isize, dy: isize) -> Vec<u32> {
+    let range = |d: isize| (d.max(0) as usize)..((n as isize + d.min(0)) as usize);
+    let mut out = Vec::with_capacity(n * (n - 1));
+    for y in range(dy) {
+        for x in range(dx) {
+            out.push(pattern[y * n + x]);
+        }
+    }
+    out
+}
+
+/// Compatible pairs found by bucketing patterns on their overlap slices, which keeps the
+/// work linear in the pattern count instead of comparing every pair
+fn build_propagator(patterns: &[Vec<u32>], n: usize) -> Vec<[Vec<usize>; 4]> {
+    let mut propagator: Vec<[Vec<usize>; 4]> = vec![Default::default(); patterns.len()];
+    for dir in DIRECTIONS {
+        let (dx, dy) = offset(dir);
+        let mut by_overlap: HashMap<Vec<u32>, Vec<usize>> = HashMap::new();
+        for (q, pattern) in patterns.iter().enumerate() {
+            by_overlap.entry(overlap(pattern, n, -dx, -dy)).or_default().push(q);
+        }
+        for (p, pattern) in patterns.iter().enumerate() {
+            if let Some(matches) = by_overlap.get(&overlap(pattern, n, dx, dy)) {
+                propagator[p][direction_index(dir).unwrap()] = matches.clone();
+            }
+        }
+    }
+    propagator
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid(rows: &[&str]) -> (Vec<TileId>, usize, usize) {
+        let cells = rows.iter().flat_map(|row| row.chars().map(|c| c.to_string())).collect();
+        (cells, rows[0].len(), rows.len())
+    }
+
+    #[test]
+    fn test_extract_counts_patterns() {
+        let (sample, w, h) = grid(&["ab", "ab", "ab"]);
+        let set = PatternSet::extract(&sample, w, h, 2, false).unwrap();
+        assert_eq!(set.len(), 1);
+        assert_eq!(set.counts, vec![2]);
+        // "ab"/"ab" stacks onto itself vertically but cannot shift sideways
+        assert_eq!(set.compatible(0, Direction::Down), &[0]);
+        assert!(set.compatible(0, Direction::Right).is_empty());
+
+        let wrapped = PatternSet::extract(&sample, w, h, 2, true).unwrap();
+        assert_eq!(wrapped.len(), 2);
+        assert_eq!(wrapped.counts.iter().sum::<u32>(), 6);
+    }
+
+    #[test]
+    fn test_compatibility_matches_brute_force() {
+        let (sample, w, h) = grid(&["abcab", "bcaab", "caabc", "abbca", "cabca"]);
+        for n in [2, 3, 4] {
+            let set = PatternSet::extract(&sample, w, h, n, true).unwrap();
+            for p in 0..set.len() {
+                for dir in DIRECTIONS {
+                    let (dx, dy) = offset(dir);
+                    let expected: Vec<usize> = (0..set.len())
+                        .filter(|&q| {
+                            (0..n * n).all(|i| {
+                                let (x, y) = ((i % n) as isize, (i / n) as isize);
+                                let (bx, by) = (x - dx, y - dy);
+                                bx < 0 || by < 0 || bx >= n as isize || by >= n as isize
+                                    || set.patterns[p][i] == set.patterns[q][(by as usize) * n + bx as usize]
+                            })
+                        })
+                        .collect();
+                    assert_eq!(set.compatible(p, dir), expected.as_slice(), "n={} p={} {:?}", n, p, dir);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_overlapping_model_windows_come_from_sample() {
+        let (sample, w, h) = grid(&["aaab", "abbb", "abcb", "abbb"]);
+        let options = OverlappingOptions { n: 2, periodic_input: true, periodic_output: false };
+        let mut model = OverlappingModel::new(&sample, w, h, 9, 7, &options, Some(3)).unwrap();
+        let output = model.run().unwrap();
+        assert_eq!(output.len(), 63);
+
+        let known: Vec<Vec<u32>> = model.patterns().patterns.clone();
+        let tiles = &model.patterns().tiles;
+        for y in 0..6 {
+            for x in 0..8 {
+                let window: Vec<u32> = [(0, 0), (1, 0), (0, 1), (1, 1)]
+                    .iter()
+                    .map(|(dx, dy)| tiles.iter().position(|t| *t == output[(y + dy) * 9 + x + dx]).unwrap() as u32)
+                    .collect();
+                assert!(known.contains(&window), "window at ({}, {}) is not in the sample", x, y);
+            }
+        }
+
+        let periodic = OverlappingOptions { periodic_output: true, ..options };
+        assert_eq!(OverlappingModel::new(&sample, w, h, 8, 8, &periodic, Some(1)).unwrap().run().unwrap().len(), 64);
+        assert!(matches!(OverlappingModel::new(&sample, w, h, 1, 5, &options, None), Err(WfcError::InvalidDimensions { .. })));
+    }
+
+    #[test]
+    fn test_pattern_size_validation() {
+        let (sample, w, h) = grid(&["ab", "ba"]);
+        assert!(PatternSet::extract(&sample, w, h, 1, false).is_err());
+        assert!(PatternSet::extract(&sample, w, h, 6, true).is_err());
+        assert!(PatternSet::extract(&sample, w, h, 3, false).is_err());
+        assert!(PatternSet::extract(&sample, w, h, 3, true).is_ok());
+    }
+
+    #[test]
+    fn test_ruleset_solves() {
+        let (sample, w, h) = grid(&["aab", "abb", "bba"]);
+        let set = PatternSet::extract(&sample, w, h, 3, true).unwrap();
+        let rules = set.to_ruleset();
+        assert_eq!(rules.tiles.len(), set.len());
+        let mut model = crate::model::Model::new(6, 6, rules, Some(2)).unwrap();
+        let result = model.run().unwrap();
+        assert!(result.iter().all(|id| id.starts_with('p')));
+    }
+}
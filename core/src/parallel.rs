@@ -0,0 +1,221 @@
+//! Experimental multi-threaded solving of a single grid.
+//!
+//! The grid is cut into a `columns` x `rows` arrangement of regions separated by one-cell
+//! divider lines. The dividers are collapsed first on the full grid, which fixes every
+//! region's surroundings; the regions are then independent and are solved concurrently,
+//! each starting from the wave the divider pass left behind.
+//!
+//! Border and mask constraints of the ruleset apply to the full grid in the divider pass,
+//! and the regions inherit them through its wave. Count constraints span the whole grid,
+//! which no region can see, so rulesets with any are rejected.
+//!
+//! Threads come from rayon with the `parallel` feature. Without it, and always on wasm32,
+//! `map` and everything built on it run on the calling thread and give the same results,
+//! so callers need no cfg of their own.
+
+use std::collections::HashSet;
+use rand::prelude::*;
+use crate::TileId;
+use crate::error::WfcError;
+use crate::model::{Model, StepStatus, MAX_DIMENSION};
+use crate::ruleset::RuleSet;
+
+#[derive(Debug, Clone)]
+pub struct PartitionedSolver {
+    width: usize,
+    height: usize,
+    rules: RuleSet,
+    /// `rules` without their constraints, which the regions get from the divider pass
+    region_rules: RuleSet,
+    seed: Option<u64>,
+    columns: usize,
+    rows: usize,
+    retries: usize,
+}
+
+/// Half-open span of one region along an axis
+type Span = (usize, usize);
+
+impl PartitionedSolver {
+    /// Fails with `Unsupported` if `rules` have count constraints
+    pub fn new(width: usize, height: usize, rules: RuleSet, seed: Option<u64>) -> Result<PartitionedSolver, WfcError> {
+        if width == 0 || height == 0 || width > MAX_DIMENSION || height > MAX_DIMENSION {
+            return Err(WfcError::InvalidDimensions { width, height });
+        }
+        if rules.get_all_tile_ids().is_empty() {
+            return Err(WfcError::NoTilesDefined);
+        }
+        if !rules.constraints.counts.is_empty() {
+            return Err(WfcError::Unsupported("count constraints in a partitioned grid".to_string()));
+        }
+        let mut region_rules = rules.clone();
+        region_rules.constraints = Default::default();
+        let mut solver = PartitionedSolver { width, height, rules, region_rules, seed, columns: 1, rows: 1, retries: 3 };
+        solver.set_regions(2, 2);
+        Ok(solver)
+    }
+
+    /// Number of regions across and down (default 2x2). Regions narrower than two cells are
+    /// not worth a thread, so the counts are clamped to fit the grid.
+    pub fn set_regions(&mut self, columns: usize, rows: usize) {
+        self.columns = columns.clamp(1, ((self.width + 1) / 3).max(1));
+        self.rows = rows.clamp(1, ((self.height + 1) / 3).max(1));
+    }
+
+    /// Fresh attempts per region, with new seeds, before giving up (default 3)
+    pub fn set_retries(&mut self, retries: usize) {
+        self.retries = retries.max(1);
+    }
+
+    pub fn run(&self) -> Result<Vec<TileId>, WfcError> {
+        let mut rng = match self.seed {
+            Some(s) => StdRng::seed_from_u64(s),
+            None => StdRng::from_entropy(),
+        };
+
+        let xs = spans(self.width, self.columns);
+        let ys = spans(self.height, self.rows);
+        let mut divider = vec![true; self.width * self.height];
+        for &(y0, y1) in &ys {
+            for &(x0, x1) in &xs {
+                for y in y0..y1 {
+                    divider[y * self.width + x0..y * self.width + x1].fill(false);
+                }
+            }
+        }
+
+        let mut model = Model::new(self.width, self.height, self.rules.clone(), Some(rng.gen()))?;
+        while model.step_within(Some(&divider))? == StepStatus::Running {}
+
+        // Seeds are drawn up front so the result does not depend on thread scheduling
+        let regions: Vec<(Span, Span, u64)> = ys
+            .iter()
+            .flat_map(|&ys| xs.iter().map(move |&xs| (xs, ys)))
+            .map(|(xs, ys)| (xs, ys, rng.gen()))
+            .collect();
+
+        let wave: Vec<HashSet<TileId>> = (0..self.width * self.height)
+            .map(|i| model.possibilities(i).clone())
+            .collect();
+        let mut grid: Vec<TileId> = wave
+            .iter()
+            .map(|tiles| tiles.iter().next().cloned().unwrap_or_default())
+            .collect();
+        let solved = map(&regions, |&(xs, ys, seed)| self.solve_region(&wave, xs, ys, seed));
+
+        for (&((x0, x1), (y0, y1), _), region) in regions.iter().zip(solved) {
+            let region = region?;
+            let w = x1 - x0;
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    grid[y * self.width + x] = region[(y - y0) * w + (x - x0)].clone();
+                }
+            }
+        }
+        Ok(grid)
+    }
+
+    /// Solve one region, starting from the divider pass's wave
+    fn solve_region(&self, wave: &[HashSet<TileId>], (x0, x1): Span, (y0, y1): Span, seed: u64) -> Result<Vec<TileId>, WfcError> {
+        let (w, h) = (x1 - x0, y1 - y0);
+        let mut rng = StdRng::seed_from_u64(seed);
+        for _ in 0..self.retries {
+            let mut model = Model::new(w, h, self.region_rules.clone(), Some(rng.gen()))?;
+            let restricted = (0..w * h).try_for_each(|i| {
+                model.restrict_cell(i, &wave[(y0 + i / w) * self.width + x0 + i % w])
+            });
+            if restricted.is_ok() {
+                if let Ok(region) = model.run() {
+                    return Ok(region);
+                }
+            }
+        }
+        Err(WfcError::Contradiction)
+    }
+}
+
+/// `f` applied to every item, in order. Items are spread over rayon's thread pool with the
+/// `parallel` feature on native targets, and processed one by one otherwise.
+pub fn map<I: Sync, O: Send>(items: &[I], f: impl Fn(&I) -> O + Send + Sync) -> Vec<O> {
+    #[cfg(all(feature = "parallel", not(target_arch = "wasm32")))]
+    {
+        use rayon::prelude::*;
+        items.par_iter().map(f).collect()
+    }
+    #[cfg(not(all(feature = "parallel", not(target_arch = "wasm32"))))]
+    {
+        items.iter().map(f).collect()
+    }
+}
+
+/// Split `len` cells into `count` regions separated by single divider cells
+fn spans(len: usize, count: usize) -> Vec<Span> {
+    let usable = len - (count - 1);
+    let mut spans = Vec::with_capacity(count);
+    let mut start = 0;
+    for i in 0..count {
+        let size = usable / count + usize::from(i < usable % count);
+        spans.push((start, start + size));
+        start += size + 1;
+    }
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Direction;
+    use crate::bench::Workload;
+    use crate::constraints::{BorderConstraint, CountConstraint, MaskConstraint, Rect};
+
+    #[test]
+    fn test_spans_leave_divider_cells() {
+        assert_eq!(spans(10, 3), vec![(0, 3), (4, 7), (8, 10)]);
+        assert_eq!(spans(5, 1), vec![(0, 5)]);
+
+        let mut solver = PartitionedSolver::new(4, 1, RuleSet::from_json(r#"{"tiles":[{"id":"a"}],"rules":[]}"#).unwrap(), None).unwrap();
+        solver.set_regions(4, 4);
+        assert_eq!((solver.columns, solver.rows), (1, 1));
+    }
+
+    #[test]
+    fn test_map_keeps_order() {
+        let items: Vec<u64> = (0..100).collect();
+        assert_eq!(map(&items, |n| n * n), items.iter().map(|n| n * n).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_partitioned_result_is_valid_and_deterministic() {
+        let mut solver = PartitionedSolver::new(24, 18, Workload::large_grid(24).rules, Some(9)).unwrap();
+        solver.set_regions(3, 2);
+        let grid = solver.run().unwrap();
+
+        let checker = Model::new(24, 18, Workload::large_grid(24).rules, None).unwrap();
+        assert!(checker.verify_result(&grid).unwrap().is_empty());
+        assert_eq!(solver.run().unwrap(), grid);
+    }
+
+    #[test]
+    fn test_partitioned_constraints_cover_the_full_grid() {
+        let mut rules = Workload::large_grid(24).rules;
+        rules.constraints.borders.push(BorderConstraint { edges: vec![Direction::Up], tiles: vec!["grass".to_string()] });
+        rules.constraints.regions.insert("corner".to_string(), vec![Rect { x: 16, y: 8, width: 4, height: 4 }]);
+        rules.constraints.masks.push(MaskConstraint { region: "corner".to_string(), tiles: vec!["water".to_string()] });
+
+        for seed in 0..5 {
+            let mut solver = PartitionedSolver::new(20, 12, rules.clone(), Some(seed)).unwrap();
+            solver.set_regions(2, 2);
+            let grid = solver.run().unwrap();
+            let checker = Model::new(20, 12, Workload::large_grid(24).rules, None).unwrap();
+            assert!(checker.verify_result(&grid).unwrap().is_empty());
+            assert!(grid[..20].iter().all(|tile| tile == "grass"));
+            assert!((8..12).all(|y| (16..20).all(|x| grid[y * 20 + x] == "water")));
+            // Only the grid's top edge is a border, not the top of the lower regions
+            let lower_top = spans(12, 2)[1].0;
+            assert!(grid[lower_top * 20..(lower_top + 1) * 20].iter().any(|tile| tile != "grass"));
+        }
+
+        rules.constraints.counts.push(CountConstraint { tile: "sand".to_string(), min: 0, max: Some(1) });
+        assert!(matches!(PartitionedSolver::new(20, 12, rules, None), Err(WfcError::Unsupported(_))));
+    }
+}
@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+use crate::TileId;
+#[cfg(feature = "png")]
+use crate::error::WfcError;
+use crate::image::{Image, Pixel};
+use crate::model::WaveSnapshot;
+
+/// Deterministic fallback color for a tile that has no artwork.
+/// Uses FNV-1a so colors stay the same across platforms and compiler versions.
+pub fn tile_color(id: &TileId) -> Pixel {
+    let h = id.bytes().fold(0xcbf29ce484222325u64, |h, b| (h ^ b as u64).wrapping_mul(0x100000001b3));
+    [(h >> 16) as u8, (h >> 8) as u8, h as u8, 255]
+}
+
+/// Render a generated grid with one pixel block of `scale`x`scale` per cell, colored from
+/// `palette` (falling back to `tile_color` for tiles missing from it)
+pub fn render_colors(grid: &[TileId], width: usize, height: usize, scale: usize, palette: &HashMap<TileId, Pixel>) -> Image {
+    let scale = scale.max(1);
+    let mut out = Image::new(width * scale, height * scale);
+    for (i, id) in grid.iter().enumerate().take(width * height) {
+        let color = palette.get(id).copied().unwrap_or_else(|| tile_color(id));
+        let (x, y) = (i % width, i / width);
+        for dy in 0..scale {
+            for dx in 0..scale {
+                out.set(x * scale + dx, y * scale + dy, color);
+            }
+        }
+    }
+    out
+}
+
+/// Render a generated grid using per-tile artwork of `tile_size`x`tile_size` pixels.
+/// Tiles without artwork are drawn as solid `tile_color` blocks.
+pub fn render_tiles(grid: &[TileId], width: usize, height: usize, tile_size: usize, tiles: &HashMap<TileId, Image>) -> Image {
+    let mut out = Image::new(width * tile_size, height * tile_size);
+    for (i, id) in grid.iter().enumerate().take(width * height) {
+        let (x, y) = (i % width * tile_size, i / width * tile_size);
+        match tiles.get(id) {
+            Some(art) => out.blit(art, x, y),
+            None => {
+                let color = tile_color(id);
+                for dy in 0..tile_size {
+                    for dx in 0..tile_size {
+                        out.set(x + dx, y + dy, color);
+                    }
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Render a wave snapshot by blending each cell's remaining tile colors by their probability
+pub fn render_wave(snapshot: &WaveSnapshot, scale: usize, palette: &HashMap<TileId, Pixel>) -> Image {
+    let scale = scale.max(1);
+    let colors: Vec<Pixel> = snapshot.tiles
+        .iter()
+        .map(|id| palette.get(id).copied().unwrap_or_else(|| tile_color(id)))
+        .collect();
+
+    let mut out = Image::new(snapshot.width * scale, snapshot.height * scale);
+    for cell in 0..snapshot.width * snapshot.height {
+        let shares = &snapshot.probabilities[cell * colors.len()..(cell + 1) * colors.len()];
+        let mut blended = [0.0f32; 4];
+        for (share, color) in shares.iter().zip(&colors) {
+            for (channel, value) in blended.iter_mut().zip(color) {
+                *channel += share * *value as f32;
+            }
+        }
+        let pixel = blended.map(|c| c.round().clamp(0.0, 255.0) as u8);
+
+        let (x, y) = (cell % snapshot.width, cell / snapshot.width);
+        for dy in 0..scale {
+            for dx in 0..scale {
+                out.set(x * scale + dx, y * scale + dy, pixel);
+            }
+        }
+    }
+    out
+}
+
+/// Write a grid as an 8-bit indexed PNG with one pixel per cell, where palette entry `i`
+/// is `tiles[i]` colored from `palette` (or `tile_color`). Unlike rendered images the file
+/// maps back to tile IDs exactly, given `tiles`, so it suits diffs and review tooling.
+/// At most 256 tiles fit; every grid tile must be listed in `tiles`.
+#[cfg(feature = "png")]
+pub fn write_indexed_png<W: std::io::Write>(grid: &[TileId], width: usize, height: usize, tiles: &[TileId], palette: &HashMap<TileId, Pixel>, writer: W) -> Result<(), WfcError> {
+    if tiles.len() > 256 {
+        return Err(WfcError::Export(format!("{} tiles do not fit an 8-bit palette", tiles.len())));
+    }
+    if grid.len() != width * height {
+        return Err(WfcError::InvalidDimensions { width, height });
+    }
+    let index: HashMap<&TileId, u8> = tiles.iter().enumerate().map(|(i, id)| (id, i as u8)).collect();
+    let data = grid
+        .iter()
+        .map(|id| index.get(id).copied().ok_or_else(|| WfcError::InvalidTileId(id.clone())))
+        .collect::<Result<Vec<u8>, WfcError>>()?;
+
+    let colors: Vec<Pixel> = tiles
+        .iter()
+        .map(|id| palette.get(id).copied().unwrap_or_else(|| tile_color(id)))
+        .collect();
+    let mut encoder = png::Encoder::new(writer, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_palette(colors.iter().flat_map(|c| [c[0], c[1], c[2]]).collect::<Vec<u8>>());
+    if colors.iter().any(|c| c[3] != 255) {
+        encoder.set_trns(colors.iter().map(|c| c[3]).collect::<Vec<u8>>());
+    }
+    let mut writer = encoder.write_header()
+        .map_err(|e| WfcError::Export(e.to_string()))?;
+    writer.write_image_data(&data)
+        .map_err(|e| WfcError::Export(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_colors_uses_palette() {
+        let grid: Vec<TileId> = vec!["a".to_string(), "b".to_string()];
+        let mut palette = HashMap::new();
+        palette.insert("a".to_string(), [1, 2, 3, 255]);
+
+        let img = render_colors(&grid, 2, 1, 2, &palette);
+        assert_eq!((img.width, img.height), (4, 2));
+        assert_eq!(img.get(1, 1), [1, 2, 3, 255]);
+        assert_eq!(img.get(2, 0), tile_color(&"b".to_string()));
+    }
+
+    #[test]
+    fn test_render_wave_blends_colors() {
+        let snapshot = WaveSnapshot {
+            width: 1,
+            height: 1,
+            tiles: vec!["a".to_string(), "b".to_string()],
+            counts: vec![2],
+            probabilities: vec![0.5, 0.5],
+        };
+        let mut palette = HashMap::new();
+        palette.insert("a".to_string(), [0, 0, 0, 255]);
+        palette.insert("b".to_string(), [200, 100, 0, 255]);
+
+        let img = render_wave(&snapshot, 1, &palette);
+        assert_eq!(img.get(0, 0), [100, 50, 0, 255]);
+    }
+
+    #[test]
+    #[cfg(feature = "png")]
+    fn test_indexed_png_maps_back_to_tiles() {
+        let grid: Vec<TileId> = ["b", "a", "a", "c"].iter().map(|id| id.to_string()).collect();
+        let tiles: Vec<TileId> = ["a", "b", "c"].iter().map(|id| id.to_string()).collect();
+        let mut palette = HashMap::new();
+        palette.insert("a".to_string(), [10, 20, 30, 255]);
+
+        let mut bytes = Vec::new();
+        write_indexed_png(&grid, 2, 2, &tiles, &palette, &mut bytes).unwrap();
+        let mut reader = png::Decoder::new(bytes.as_slice()).read_info().unwrap();
+        assert_eq!(reader.info().color_type, png::ColorType::Indexed);
+        assert_eq!(&reader.info().palette.as_ref().unwrap()[..3], &[10, 20, 30]);
+        let mut buf = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut buf).unwrap();
+        assert_eq!(&buf[..4], &[1, 0, 0, 2]);
+
+        let missing = write_indexed_png(&grid, 2, 2, &tiles[..2], &palette, Vec::new());
+        assert!(matches!(missing, Err(WfcError::InvalidTileId(_))));
+    }
+
+    #[test]
+    fn test_render_tiles_blits_art() {
+        let grid: Vec<TileId> = vec!["a".to_string()];
+        let mut tiles = HashMap::new();
+        tiles.insert("a".to_string(), Image::from_pixels(1, 1, vec![[9, 9, 9, 255]]).unwrap());
+
+        let img = render_tiles(&grid, 1, 1, 1, &tiles);
+        assert_eq!(img.get(0, 0), [9, 9, 9, 255]);
+    }
+}
@@ -1,14 +1,59 @@
-use wasm_bindgen::prelude::*;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
 use serde::{Deserialize, Serialize};
-use crate::{TileId, Direction};
+use serde_json::{Map, Value};
+use crate::{TileId, TileKey, Direction, SymmetryType, DIRECTIONS};
 use crate::error::WfcError;
+use crate::atlas::Atlas;
+use crate::constraints::Constraints;
+use crate::intern::INTERNED_NAME;
+use crate::sockets::{reversed, sockets_fit, EdgeSockets};
+use crate::symmetry::Transform;
+use crate::topology::TriDirection;
+use crate::transition::TransitionWeights;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct TileInfo {
-    pub id: TileId,
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TileInfo<T = TileId> {
+    pub id: T,
     #[serde(default = "default_weight")]
     pub weight: u32,
+    /// Free-form data for renderers/gameplay (sprite paths, collision flags, ...),
+    /// carried through serialization untouched
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub metadata: Map<String, Value>,
+    /// Set on the base tile of a symmetry family (see `RuleSet::add_tile_with_symmetry`);
+    /// loading JSON with this field expands the variants
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub symmetry: Option<SymmetryType>,
+    /// Per-variant weights of a symmetry family, in `SymmetryType::variants()` order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub variant_weights: Vec<u32>,
+    /// Edge labels that adjacency is derived from (see the `sockets` module)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sockets: Option<EdgeSockets>,
+}
+
+impl<T: TileKey> TileInfo<T> {
+    /// Strings listed under the `"tags"` metadata key, used to address groups of tiles
+    /// (e.g. every `forest` tile) in weighting and constraint options
+    pub fn tags(&self) -> impl Iterator<Item = &str> {
+        self.metadata
+            .get("tags")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+    }
+
+    /// Whether `key` is this tile's ID or one of its tags. A tile interned by
+    /// `intern::TileTable` goes by the name it was interned from, never by its index.
+    pub fn matches(&self, key: &str) -> bool {
+        let named = match self.metadata.get(INTERNED_NAME).and_then(Value::as_str) {
+            Some(name) => name == key,
+            None => self.id.to_string() == key,
+        };
+        named || self.tags().any(|tag| tag == key)
+    }
 }
 
 fn default_weight() -> u32 {
@@ -18,154 +63,1175 @@ fn default_weight() -> u32 {
 #[derive(Serialize, Deserialize)]
 struct RuleJson {
     from: TileId,
-    to: TileId,
+    to: RuleTargets,
     direction: Direction,
+    /// Also allow `from` in the opposite direction of `to`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    symmetric: bool,
+    /// How strongly the rule favors `to` next to `from` (see `RuleSet::add_weighted_adjacency`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    weight: Option<f64>,
+}
+
+/// A rule's `to`: one tile, or in the grouped format every tile allowed there
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum RuleTargets {
+    One(TileId),
+    Many(Vec<TileId>),
+}
+
+/// Layout options for `RuleSet::to_json_string_with`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct JsonOptions {
+    /// Write one rule per (tile, direction) with every allowed neighbor in a `to` list,
+    /// instead of one rule per pair
+    pub grouped: bool,
+    /// Indent the output, one entry per line
+    pub pretty: bool,
+}
+
+/// What a (tile, direction) pair with no rules at all allows
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MissingAdjacency {
+    /// Nothing may sit there; every allowed neighbor has to be listed
+    #[default]
+    Closed,
+    /// Anything may sit there, so rules only need to restrict an otherwise permissive world
+    Open,
+}
+
+/// What `RuleSet::merge` does with something both rulesets define differently
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MergeConflict {
+    /// Fail with `WfcError::MergeConflict`
+    #[default]
+    Error,
+    /// Combine the two where possible: a tile's metadata gains the other side's keys, and
+    /// lists such as `tags` are joined. Differences that cannot be combined still fail.
+    Union,
+    /// Keep this ruleset's definition
+    PreferSelf,
+}
+
+fn is_closed(missing: &MissingAdjacency) -> bool {
+    *missing == MissingAdjacency::Closed
+}
+
+/// Version written by `RuleSet::to_json_string`. Files without a `version` are v1, which
+/// lacks the `tags`, `symmetry`, `aliases` and `constraints` blocks but is otherwise the same format.
+pub const RULESET_VERSION: u32 = 2;
+
+fn v1() -> u32 {
+    1
 }
 
 #[derive(Serialize, Deserialize)]
 struct RuleSetJson {
+    #[serde(default = "v1")]
+    version: u32,
     tiles: Vec<TileInfo>,
     rules: Vec<RuleJson>,
+    #[serde(default, skip_serializing_if = "is_closed")]
+    missing_adjacency: MissingAdjacency,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    atlas: Option<Atlas>,
+    /// Tag -> tiles carrying it, merged into each tile's `tags` metadata on load
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    tags: BTreeMap<String, Vec<TileId>>,
+    /// Tile -> symmetry, for declaring families without repeating it on each tile entry
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    symmetry: BTreeMap<TileId, SymmetryType>,
+    /// Canonical tile -> its aliases (see `RuleSet::add_alias`). Rules are only written for
+    /// canonical tiles and re-expanded on load.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    aliases: BTreeMap<TileId, Vec<TileId>>,
+    #[serde(default, skip_serializing_if = "Constraints::is_empty")]
+    constraints: Constraints,
 }
 
-#[wasm_bindgen]
-#[derive(Debug, Clone, Default)]
-pub struct RuleSet {
-    // Fields are not pub to wasm (HashMap not supported), but we can use them effectively in Rust
-    #[wasm_bindgen(skip)]
-    pub tiles: HashMap<TileId, TileInfo>,
-    #[wasm_bindgen(skip)]
-    pub adjacency: HashMap<(TileId, Direction), HashSet<TileId>>,
+/// Why `RuleSet::minimize` dropped a rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RemovalReason {
+    /// One of the tiles is not defined in the ruleset
+    UnknownTile,
+    /// One of the tiles can never be placed (see `MinimizeReport::unreachable`)
+    UnreachableTile,
+    /// The other tile's rules forbid the pair, so the rule can never apply
+    OneSided,
+    /// The rule's (tile, direction) allowed every tile, which open adjacency already implies
+    Wildcard,
 }
 
-#[wasm_bindgen]
-impl RuleSet {
-    #[wasm_bindgen(constructor)]
-    pub fn new() -> RuleSet {
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RemovedRule<T = TileId> {
+    pub from: T,
+    pub direction: Direction,
+    pub to: T,
+    pub reason: RemovalReason,
+}
+
+/// What `RuleSet::minimize` took out, sorted
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MinimizeReport<T = TileId> {
+    /// Tiles with no placeable neighbor in any direction. On any grid of two or more cells
+    /// they are banned before the first observation; they stay in `tiles`, without rules.
+    pub unreachable: Vec<T>,
+    pub removed: Vec<RemovedRule<T>>,
+}
+
+impl<T> MinimizeReport<T> {
+    pub fn is_empty(&self) -> bool {
+        self.unreachable.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// What changed from one ruleset to another (see `RuleSet::diff`), each list sorted.
+/// `Display` lists one change per line, `+` for added, `-` for removed and `~` for changed.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RuleSetDiff<T = TileId> {
+    pub added_tiles: Vec<T>,
+    pub removed_tiles: Vec<T>,
+    /// Tiles in both whose weight, metadata, symmetry or sockets differ
+    pub changed_tiles: Vec<T>,
+    /// Rules `(from, direction, to)` only in the newer ruleset
+    pub added_rules: Vec<(T, Direction, T)>,
+    pub removed_rules: Vec<(T, Direction, T)>,
+}
+
+impl<T> RuleSetDiff<T> {
+    pub fn is_empty(&self) -> bool {
+        self.added_tiles.is_empty()
+            && self.removed_tiles.is_empty()
+            && self.changed_tiles.is_empty()
+            && self.added_rules.is_empty()
+            && self.removed_rules.is_empty()
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for RuleSetDiff<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (sign, tiles) in [("+", &self.added_tiles), ("-", &self.removed_tiles), ("~", &self.changed_tiles)] {
+            for tile in tiles {
+                writeln!(f, "{} tile `{}`", sign, tile)?;
+            }
+        }
+        for (sign, rules) in [("+", &self.added_rules), ("-", &self.removed_rules)] {
+            for (from, direction, to) in rules {
+                writeln!(f, "{} rule `{}` {:?} `{}`", sign, from, direction, to)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Problems found by `RuleSet::validate`, each list sorted. Any of them can make generation
+/// fail with a bare `Contradiction` or quietly never use a tile; `Display` lists them with
+/// what to fix.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ValidationReport<T = TileId> {
+    /// Tiles named by rules but never defined
+    pub unknown_tiles: Vec<T>,
+    /// (tile, direction) pairs with no compatible neighbor, so the tile only fits against
+    /// that edge of the grid
+    pub dead_ends: Vec<(T, Direction)>,
+    /// Tiles that can never be placed (see `MinimizeReport::unreachable`)
+    pub unreachable: Vec<T>,
+    /// Groups of placeable tiles with no compatible pair between groups, so a grid holds
+    /// tiles of one group only. Empty when every placeable tile can reach every other.
+    pub isolated_groups: Vec<Vec<T>>,
+    /// Rules `(from, direction, to)` whose reverse is missing: `to` does not allow `from`
+    /// in the opposite direction, so the rule never applies
+    pub asymmetric: Vec<(T, Direction, T)>,
+    /// Tiles of weight 0, which are never chosen
+    pub zero_weight: Vec<T>,
+}
+
+impl<T> ValidationReport<T> {
+    pub fn is_ok(&self) -> bool {
+        self.unknown_tiles.is_empty()
+            && self.dead_ends.is_empty()
+            && self.unreachable.is_empty()
+            && self.isolated_groups.is_empty()
+            && self.asymmetric.is_empty()
+            && self.zero_weight.is_empty()
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for ValidationReport<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for tile in &self.unknown_tiles {
+            writeln!(f, "a rule names `{}`, which is not a tile; add it or drop the rule", tile)?;
+        }
+        for tile in &self.unreachable {
+            writeln!(f, "`{}` can never be placed; give it compatible neighbors", tile)?;
+        }
+        for (tile, direction) in &self.dead_ends {
+            writeln!(f, "`{}` has no compatible neighbor {:?}, so it only fits against that edge of the grid", tile, direction)?;
+        }
+        for (from, direction, to) in &self.asymmetric {
+            writeln!(
+                f,
+                "`{}` allows `{}` {:?}, but `{}` does not allow `{}` {:?}; add the reverse rule",
+                from, to, direction, to, from, direction.opposite()
+            )?;
+        }
+        for tile in &self.zero_weight {
+            writeln!(f, "`{}` has weight 0 and is never chosen", tile)?;
+        }
+        if !self.isolated_groups.is_empty() {
+            let groups: Vec<String> = self.isolated_groups
+                .iter()
+                .map(|group| group.iter().map(|tile| format!("`{}`", tile)).collect::<Vec<_>>().join(", "))
+                .collect();
+            writeln!(f, "tiles split into {} groups that never touch: [{}]", groups.len(), groups.join("], ["))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RuleSet<T = TileId> {
+    pub tiles: HashMap<T, TileInfo<T>>,
+    pub adjacency: HashMap<(T, Direction), HashSet<T>>,
+    /// Optional spritesheet describing each tile's artwork
+    pub atlas: Option<Atlas>,
+    /// How pairs absent from `adjacency` are treated (see `allows`)
+    pub missing_adjacency: MissingAdjacency,
+    /// Grid-wide constraints declared with the rules (see the `constraints` module)
+    pub constraints: Constraints,
+    /// Alias -> the canonical tile whose adjacency it shares (see `add_alias`)
+    pub aliases: HashMap<T, T>,
+    /// Weights of individual rules (see `add_weighted_adjacency`); rules without one weigh 1
+    pub rule_weights: TransitionWeights<T>,
+}
+
+impl<T> Default for RuleSet<T> {
+    fn default() -> Self {
         RuleSet {
             tiles: HashMap::new(),
             adjacency: HashMap::new(),
+            atlas: None,
+            missing_adjacency: MissingAdjacency::Closed,
+            constraints: Constraints::default(),
+            aliases: HashMap::new(),
+            rule_weights: TransitionWeights::default(),
         }
     }
 }
 
-// Internal Rust methods (not exposed to Wasm)
-impl RuleSet {
-    pub fn add_tile(&mut self, id: TileId, weight: u32) {
-        self.tiles.insert(id.clone(), TileInfo { id, weight });
+// Methods for any tile type
+impl<T: TileKey> RuleSet<T> {
+    pub fn add_tile(&mut self, id: T, weight: u32) {
+        self.add_tile_with_metadata(id, weight, Map::new());
+    }
+
+    pub fn add_tile_with_metadata(&mut self, id: T, weight: u32, metadata: Map<String, Value>) {
+        self.tiles.insert(id.clone(), TileInfo { id, weight, metadata, symmetry: None, variant_weights: Vec::new(), sockets: None });
+    }
+
+    /// Add a tile with socket labels on its edges; `derive_socket_adjacency` then adds its
+    /// rules once every tile is in
+    pub fn add_tile_with_sockets(&mut self, id: T, weight: u32, sockets: EdgeSockets) {
+        self.add_tile(id.clone(), weight);
+        if let Some(info) = self.tiles.get_mut(&id) {
+            info.sockets = Some(sockets);
+        }
     }
 
-    pub fn add_adjacency(&mut self, from: TileId, to: TileId, direction: Direction) {
+    /// Allow every pair of tiles with sockets whose touching edges fit (see
+    /// `sockets::sockets_fit`), in the four planar directions. Existing rules are kept, so
+    /// hand-written rules can add exceptions on top.
+    pub fn derive_socket_adjacency(&mut self) {
+        let socketed: Vec<(T, EdgeSockets)> = self.tiles
+            .values()
+            .filter_map(|info| Some((info.id.clone(), info.sockets.clone()?)))
+            .collect();
+        for direction in DIRECTIONS {
+            // Tiles by the label an edge needs to fit theirs on the side facing `direction`
+            let mut by_fitting_label: HashMap<String, Vec<&T>> = HashMap::new();
+            for (id, sockets) in &socketed {
+                let edge = sockets.edge(direction.opposite()).unwrap_or_default();
+                by_fitting_label.entry(reversed(edge)).or_default().push(id);
+            }
+            for (from, sockets) in &socketed {
+                let edge = sockets.edge(direction).unwrap_or_default();
+                for &to in by_fitting_label.get(edge).into_iter().flatten() {
+                    self.add_adjacency(from.clone(), to.clone(), direction);
+                }
+            }
+        }
+    }
+
+    /// Allow `to` in `direction` of `from`. Rules involving a tile with aliases apply to
+    /// the whole alias group (see `add_alias`).
+    pub fn add_adjacency(&mut self, from: T, to: T, direction: Direction) {
         // Assume if A -> B in Direction, then B is a valid neighbor of A in Direction.
         // The adjacency map stores: (CurrentTile, Direction) -> AllowedNeighborTiles
 
-        self.adjacency
-            .entry((from, direction))
-            .or_insert_with(HashSet::new)
-            .insert(to);
+        if self.aliases.is_empty() {
+            self.adjacency
+                .entry((from, direction))
+                .or_default()
+                .insert(to);
+            return;
+        }
+        let targets = self.alias_group(&to);
+        for from in self.alias_group(&from) {
+            self.adjacency
+                .entry((from, direction))
+                .or_default()
+                .extend(targets.iter().cloned());
+        }
+    }
+
+    /// Allow `to` in `direction` of `from` with a weight: when a cell collapses next to a
+    /// placed `from`, `to`'s tile weight is multiplied by `weight`. Sand next to water can be
+    /// common (say 4.0) and sand next to grass rare (0.25) while both stay allowed. Like
+    /// `add_adjacency`, this covers the alias groups of both tiles. Fails with
+    /// `InvalidWeight`, adding nothing, unless `weight` is finite and at least 0.
+    pub fn add_weighted_adjacency(&mut self, from: T, to: T, direction: Direction, weight: f64) -> Result<(), WfcError> {
+        if !weight.is_finite() || weight < 0.0 {
+            return Err(WfcError::InvalidWeight(weight));
+        }
+        self.add_adjacency(from.clone(), to.clone(), direction);
+        let targets = self.alias_group(&to);
+        for from in self.alias_group(&from) {
+            for to in &targets {
+                self.rule_weights.set(from.clone(), direction, to.clone(), weight);
+            }
+        }
+        Ok(())
+    }
+
+    /// Weight of the rule allowing `to` in `direction` of `from`, 1 if it has none
+    pub fn rule_weight(&self, from: &T, to: &T, direction: Direction) -> f64 {
+        self.rule_weights.factor(from, direction, to)
+    }
+
+    /// Add `alias` as a tile of its own weight that shares all adjacency with `canonical`,
+    /// both the rules so far and any added later, so visually different variants of one
+    /// logical tile need no rules of their own. The alias starts with `canonical`'s metadata
+    /// (so tags carry over); replace it to point renderers at different artwork. Aliasing an
+    /// alias joins the same group.
+    pub fn add_alias(&mut self, alias: T, canonical: T, weight: u32) -> Result<(), WfcError> {
+        let canonical = self.canonical(&canonical).clone();
+        let metadata = match self.tiles.get(&canonical) {
+            Some(info) => info.metadata.clone(),
+            None => return Err(WfcError::InvalidTileId(canonical.to_string())),
+        };
+        self.add_tile_with_metadata(alias.clone(), weight, metadata);
+        self.aliases.insert(alias.clone(), canonical.clone());
+        self.share_adjacency(alias, canonical);
+        Ok(())
+    }
+
+    /// Give `alias` every rule `canonical` has so far, from both sides
+    fn share_adjacency(&mut self, alias: T, canonical: T) {
+        for set in self.adjacency.values_mut() {
+            if set.contains(&canonical) {
+                set.insert(alias.clone());
+            }
+        }
+        let copied: Vec<(Direction, HashSet<T>)> = self.adjacency
+            .iter()
+            .filter(|((from, _), _)| *from == canonical)
+            .map(|((_, dir), set)| (*dir, set.clone()))
+            .collect();
+        for (dir, set) in copied {
+            self.adjacency.entry((alias.clone(), dir)).or_default().extend(set);
+        }
+    }
+
+    /// The tile `id` is an alias of, or `id` itself
+    pub fn canonical<'a>(&'a self, id: &'a T) -> &'a T {
+        self.aliases.get(id).unwrap_or(id)
+    }
+
+    /// `id`'s canonical tile followed by all of its aliases
+    pub fn alias_group(&self, id: &T) -> Vec<T> {
+        let canonical = self.canonical(id);
+        let mut group = vec![canonical.clone()];
+        group.extend(self.aliases.iter().filter(|(_, c)| *c == canonical).map(|(alias, _)| alias.clone()));
+        group
+    }
+
+    /// Allow `to` in `direction` of `from` and record the reciprocal rule (`from` in the
+    /// opposite direction of `to`), which is what most tilesets mean by adjacency
+    pub fn add_adjacency_symmetric(&mut self, from: T, to: T, direction: Direction) {
+        self.add_adjacency(to.clone(), from.clone(), direction.opposite());
+        self.add_adjacency(from, to, direction);
+    }
+
+    /// For every rule, allow the reverse (`from` in the opposite direction of `to`) if it is
+    /// not allowed already, so adjacency works both ways as most tilesets intend. Returns
+    /// how many rules were added; `validate` then finds no asymmetric rules.
+    pub fn symmetrize(&mut self) -> usize {
+        let missing: Vec<(T, Direction, T)> = self.adjacency
+            .iter()
+            .flat_map(|((from, dir), set)| set.iter().map(move |to| (from, *dir, to)))
+            .filter(|&(from, dir, to)| !self.allows(to, from, dir.opposite()))
+            .map(|(from, dir, to)| (to.clone(), dir.opposite(), from.clone()))
+            .collect();
+        let added = missing.len();
+        for (from, direction, to) in missing {
+            self.add_adjacency(from, to, direction);
+        }
+        added
+    }
+
+    /// Allow `to` across edge `edge` of `from` on a `topology::TriangleGrid`, whichever way
+    /// `from` points. Like `add_adjacency`, the reverse rule is not implied.
+    pub fn add_tri_adjacency(&mut self, from: T, to: T, edge: TriDirection) {
+        for points_up in [true, false] {
+            self.add_adjacency(from.clone(), to.clone(), edge.to_direction(points_up));
+        }
+    }
+
+    /// Whether `derive_socket_adjacency` allows `to` in `direction` of `from`
+    fn sockets_allow(&self, from: &T, to: &T, direction: Direction) -> bool {
+        let edge = |id: &T, direction: Direction| self.tiles.get(id)?.sockets.as_ref()?.edge(direction);
+        match (edge(from, direction), edge(to, direction.opposite())) {
+            (Some(a), Some(b)) => sockets_fit(a, b),
+            _ => false,
+        }
     }
 
-    pub fn get_tile_info(&self, id: &TileId) -> Option<&TileInfo> {
+    pub fn get_tile_info(&self, id: &T) -> Option<&TileInfo<T>> {
         self.tiles.get(id)
     }
 
-    pub fn get_all_tiles(&self) -> Vec<&TileInfo> {
+    pub fn get_all_tiles(&self) -> Vec<&TileInfo<T>> {
         self.tiles.values().collect()
     }
 
-    pub fn get_all_tile_ids(&self) -> Vec<&TileId> {
+    pub fn get_all_tile_ids(&self) -> Vec<&T> {
         self.tiles.keys().collect()
     }
 
-    pub fn get_valid_neighbors(&self, tile: &TileId, direction: Direction) -> Option<&HashSet<TileId>> {
+    /// Every tile whose ID or tags match `key` (see `TileInfo::matches`)
+    pub fn tiles_matching(&self, key: &str) -> HashSet<T> {
+        self.tiles.values().filter(|info| info.matches(key)).map(|info| info.id.clone()).collect()
+    }
+
+    /// The rules listed for `tile` in `direction`. `None` means no rules were given, which
+    /// only forbids everything under `MissingAdjacency::Closed`; prefer `allows` for checks.
+    pub fn get_valid_neighbors(&self, tile: &T, direction: Direction) -> Option<&HashSet<T>> {
         self.adjacency.get(&(tile.clone(), direction))
     }
 
-    pub fn to_json_string(&self) -> Result<String, WfcError> {
-        let json = RuleSetJson {
-            tiles: self.tiles.values().cloned().collect(),
-            rules: self.adjacency.iter().flat_map(|((from, dir), set)| {
-                set.iter().map(move |to| RuleJson {
-                    from: from.clone(),
-                    to: to.clone(),
-                    direction: *dir,
-                })
-            }).collect(),
+    /// Whether `from`'s rules let `to` sit in `direction` of it
+    pub fn allows(&self, from: &T, to: &T, direction: Direction) -> bool {
+        match self.get_valid_neighbors(from, direction) {
+            Some(set) => set.contains(to),
+            None => self.missing_adjacency == MissingAdjacency::Open,
+        }
+    }
+
+    /// Whether `to` may sit in `direction` of `from`, checked from both tiles' point of view
+    pub fn is_compatible(&self, from: &T, to: &T, direction: Direction) -> bool {
+        self.allows(from, to, direction) && self.allows(to, from, direction.opposite())
+    }
+
+    pub fn weight(&self, id: &T) -> Option<u32> {
+        self.tiles.get(id).map(|info| info.weight)
+    }
+
+    /// Remove rules that can never take effect, without changing what any grid of two or
+    /// more cells may contain: rules naming undefined tiles, rules involving unreachable
+    /// tiles, and one-sided rules the other tile forbids. With `prune_wildcards`, open
+    /// adjacency rulesets also drop (tile, direction) entries allowing every placeable
+    /// tile, which the open default already allows. Exact duplicates never survive loading,
+    /// since rules are stored as sets.
+    pub fn minimize(&mut self, prune_wildcards: bool) -> MinimizeReport<T> {
+        let mut removed = Vec::new();
+        let mut remove = |adjacency: &mut HashMap<(T, Direction), HashSet<T>>, reason: RemovalReason, doomed: Vec<(T, Direction, T)>| {
+            for (from, direction, to) in doomed {
+                if let Some(set) = adjacency.get_mut(&(from.clone(), direction)) {
+                    set.remove(&to);
+                }
+                removed.push(RemovedRule { from, direction, to, reason });
+            }
         };
-        serde_json::to_string(&json)
-            .map_err(|e| WfcError::JsonParseError(e.to_string()))
+        let rules = |adjacency: &HashMap<(T, Direction), HashSet<T>>| -> Vec<(T, Direction, T)> {
+            adjacency
+                .iter()
+                .flat_map(|((from, dir), set)| set.iter().map(move |to| (from.clone(), *dir, to.clone())))
+                .collect()
+        };
+
+        let unknown = rules(&self.adjacency)
+            .into_iter()
+            .filter(|(from, _, to)| !self.tiles.contains_key(from) || !self.tiles.contains_key(to))
+            .collect();
+        remove(&mut self.adjacency, RemovalReason::UnknownTile, unknown);
+        self.adjacency.retain(|(from, _), _| self.tiles.contains_key(from));
+
+        let live = self.placeable_tiles();
+        let unreachable = rules(&self.adjacency)
+            .into_iter()
+            .filter(|(from, _, to)| !live.contains(from) || !live.contains(to))
+            .collect();
+        remove(&mut self.adjacency, RemovalReason::UnreachableTile, unreachable);
+
+        let one_sided = rules(&self.adjacency)
+            .into_iter()
+            .filter(|(from, dir, to)| !self.allows(to, from, dir.opposite()))
+            .collect();
+        remove(&mut self.adjacency, RemovalReason::OneSided, one_sided);
+
+        if prune_wildcards && self.missing_adjacency == MissingAdjacency::Open {
+            let wildcards: Vec<(T, Direction)> = self.adjacency
+                .iter()
+                .filter(|(_, set)| live.iter().all(|tile| set.contains(tile)))
+                .map(|(key, _)| key.clone())
+                .collect();
+            for key in wildcards {
+                let set = self.adjacency.remove(&key).unwrap_or_default();
+                let (from, direction) = key;
+                remove(&mut self.adjacency, RemovalReason::Wildcard, set.into_iter().map(|to| (from.clone(), direction, to)).collect());
+            }
+        }
+
+        // An empty entry forbids everything, which only the closed default also does
+        if self.missing_adjacency == MissingAdjacency::Closed {
+            self.adjacency.retain(|_, set| !set.is_empty());
+        }
+
+        let mut unreachable: Vec<T> = self.tiles.keys().filter(|tile| !live.contains(*tile)).cloned().collect();
+        unreachable.sort();
+        removed.sort_by(|a, b| (&a.from, a.direction, &a.to).cmp(&(&b.from, b.direction, &b.to)));
+        MinimizeReport { unreachable, removed }
     }
 
-    pub fn from_json(json: &str) -> Result<RuleSet, WfcError> {
-        let parsed: RuleSetJson = serde_json::from_str(json)
-            .map_err(|e| WfcError::JsonParseError(e.to_string()))?;
+    /// Tiles with a compatible neighbor that can itself be placed
+    fn placeable_tiles(&self) -> HashSet<T> {
+        // A tile every neighbor of which must be unreachable cannot be placed either, so
+        // peel them off until none are left
+        let mut live: HashSet<T> = self.tiles.keys().cloned().collect();
+        loop {
+            let dead: Vec<T> = live
+                .iter()
+                .filter(|tile| {
+                    !DIRECTIONS.iter().any(|&dir| live.iter().any(|other| self.is_compatible(tile, other, dir)))
+                })
+                .cloned()
+                .collect();
+            if dead.is_empty() {
+                return live;
+            }
+            for tile in &dead {
+                live.remove(tile);
+            }
+        }
+    }
 
-        let mut rule_set = RuleSet::new();
+    /// Check the rules for mistakes that otherwise only show up as failed or lopsided
+    /// runs (see `ValidationReport`). Only the four planar directions are checked.
+    pub fn validate(&self) -> ValidationReport<T> {
+        let mut unknown_tiles: Vec<T> = self.adjacency
+            .iter()
+            .flat_map(|((from, _), set)| std::iter::once(from).chain(set))
+            .filter(|tile| !self.tiles.contains_key(*tile))
+            .cloned()
+            .collect::<HashSet<T>>()
+            .into_iter()
+            .collect();
+        unknown_tiles.sort();
 
-        for tile in parsed.tiles {
-            rule_set.add_tile(tile.id, tile.weight);
+        let mut tiles: Vec<&T> = self.tiles.keys().collect();
+        tiles.sort();
+        let dead_ends = tiles
+            .iter()
+            .flat_map(|&tile| DIRECTIONS.map(|dir| (tile, dir)))
+            .filter(|&(tile, dir)| !tiles.iter().any(|other| self.is_compatible(tile, other, dir)))
+            .map(|(tile, dir)| (tile.clone(), dir))
+            .collect();
+
+        let live = self.placeable_tiles();
+        let unreachable = tiles.iter().filter(|tile| !live.contains(**tile)).map(|&tile| tile.clone()).collect();
+
+        // Flood the compatibility graph of placeable tiles from each unvisited tile
+        let mut groups: Vec<Vec<T>> = Vec::new();
+        let mut seen: HashSet<&T> = HashSet::new();
+        for &start in tiles.iter().filter(|tile| live.contains(**tile)) {
+            if !seen.insert(start) {
+                continue;
+            }
+            let mut group = vec![start.clone()];
+            let mut stack = vec![start];
+            while let Some(tile) = stack.pop() {
+                for &other in tiles.iter().filter(|other| live.contains(**other)) {
+                    if !seen.contains(other) && DIRECTIONS.iter().any(|&dir| self.is_compatible(tile, other, dir)) {
+                        seen.insert(other);
+                        group.push(other.clone());
+                        stack.push(other);
+                    }
+                }
+            }
+            group.sort();
+            groups.push(group);
         }
+        let isolated_groups = if groups.len() > 1 { groups } else { Vec::new() };
 
-        for rule in parsed.rules {
-            // Verify tiles exist?
-            // Requirement 5.1 says "detect tiles with no valid neighbors", checking existence here is good practice but maybe not strictly required to fail if loose strings are passed.
-            // However, strictly speaking, rules should involve known tiles.
+        let mut asymmetric: Vec<(T, Direction, T)> = self.adjacency
+            .iter()
+            .filter(|((from, _), _)| self.tiles.contains_key(from))
+            .flat_map(|((from, dir), set)| set.iter().map(move |to| (from, *dir, to)))
+            .filter(|&(from, dir, to)| self.tiles.contains_key(to) && !self.allows(to, from, dir.opposite()))
+            .map(|(from, dir, to)| (from.clone(), dir, to.clone()))
+            .collect();
+        asymmetric.sort();
+
+        let zero_weight = tiles.iter().filter(|tile| self.tiles[**tile].weight == 0).map(|&tile| tile.clone()).collect();
+
+        ValidationReport { unknown_tiles, dead_ends, unreachable, isolated_groups, asymmetric, zero_weight }
+    }
 
-            if !rule_set.tiles.contains_key(&rule.from) {
-                return Err(WfcError::InvalidTileId(rule.from));
+    /// The tiles and rules added, removed or changed going from this ruleset to `other`,
+    /// e.g. between two versions of a tileset file. Rule weights, constraints and settings
+    /// are not compared.
+    pub fn diff(&self, other: &RuleSet<T>) -> RuleSetDiff<T> {
+        let sorted = |mut items: Vec<T>| {
+            items.sort();
+            items
+        };
+        let only_in = |a: &RuleSet<T>, b: &RuleSet<T>| sorted(a.tiles.keys().filter(|id| !b.tiles.contains_key(*id)).cloned().collect());
+        let changed = self.tiles.iter().filter(|(id, info)| other.tiles.get(*id).is_some_and(|theirs| theirs != *info));
+
+        let rules_only_in = |a: &RuleSet<T>, b: &RuleSet<T>| {
+            let mut rules: Vec<(T, Direction, T)> = a.adjacency
+                .iter()
+                .flat_map(|((from, direction), set)| set.iter().map(move |to| (from, *direction, to)))
+                .filter(|&(from, direction, to)| !b.get_valid_neighbors(from, direction).is_some_and(|set| set.contains(to)))
+                .map(|(from, direction, to)| (from.clone(), direction, to.clone()))
+                .collect();
+            rules.sort();
+            rules
+        };
+
+        RuleSetDiff {
+            added_tiles: only_in(other, self),
+            removed_tiles: only_in(self, other),
+            changed_tiles: sorted(changed.map(|(id, _)| id.clone()).collect()),
+            added_rules: rules_only_in(other, self),
+            removed_rules: rules_only_in(self, other),
+        }
+    }
+
+    /// Add `other`'s tiles, rules and constraints, e.g. to assemble a tileset split across
+    /// files such as terrain, roads and decorations. Rules only ever allow more, so the
+    /// rules of a tile in a direction are combined, expanded over the merged alias groups
+    /// and joined by the rules sockets from both sides imply; constraints and regions are added. A
+    /// tile, alias, rule weight or region defined differently on both sides, or a different
+    /// missing adjacency or atlas, is settled by `policy`. On error nothing is changed.
+    pub fn merge(&mut self, other: &RuleSet<T>, policy: MergeConflict) -> Result<(), WfcError> {
+        let settle = |what: String| match policy {
+            MergeConflict::PreferSelf => Ok(()),
+            MergeConflict::Error | MergeConflict::Union => Err(WfcError::MergeConflict(what)),
+        };
+        let mut merged = self.clone();
+
+        for (id, info) in &other.tiles {
+            match merged.tiles.get_mut(id) {
+                None => {
+                    merged.tiles.insert(id.clone(), info.clone());
+                }
+                Some(mine) if mine == info => {}
+                Some(mine) if policy == MergeConflict::Union => union_tile(mine, info)?,
+                Some(_) => settle(format!("tile {}", id))?,
+            }
+        }
+        // Aliases go first so the rules below expand over the merged alias groups
+        for (alias, canonical) in &other.aliases {
+            match merged.aliases.get(alias) {
+                None => {
+                    let canonical = merged.canonical(canonical).clone();
+                    merged.aliases.insert(alias.clone(), canonical.clone());
+                    merged.share_adjacency(alias.clone(), canonical);
+                }
+                Some(mine) if mine == canonical => {}
+                Some(_) => settle(format!("alias {}", alias))?,
             }
-            if !rule_set.tiles.contains_key(&rule.to) {
-                return Err(WfcError::InvalidTileId(rule.to));
+        }
+        for ((from, direction), set) in &other.adjacency {
+            for to in set {
+                merged.add_adjacency(from.clone(), to.clone(), *direction);
+            }
+        }
+        for (from, direction, to, weight) in other.rule_weights.entries() {
+            match merged.rule_weights.get(from, direction, to) {
+                None => {
+                    for from in merged.alias_group(from) {
+                        for to in merged.alias_group(to) {
+                            if merged.rule_weights.get(&from, direction, &to).is_none() {
+                                merged.rule_weights.set(from.clone(), direction, to, weight);
+                            }
+                        }
+                    }
+                }
+                Some(mine) if mine == weight => {}
+                Some(_) => settle(format!("weight of {} {:?} {}", from, direction, to))?,
             }
+        }
+        // Sockets from the two files fit each other too
+        merged.derive_socket_adjacency();
 
-            rule_set.add_adjacency(rule.from, rule.to, rule.direction);
+        let constraints = &mut merged.constraints;
+        for count in &other.constraints.counts {
+            if !constraints.counts.contains(count) {
+                constraints.counts.push(count.clone());
+            }
+        }
+        for border in &other.constraints.borders {
+            if !constraints.borders.contains(border) {
+                constraints.borders.push(border.clone());
+            }
+        }
+        for mask in &other.constraints.masks {
+            if !constraints.masks.contains(mask) {
+                constraints.masks.push(mask.clone());
+            }
+        }
+        for (name, rects) in &other.constraints.regions {
+            match constraints.regions.get(name) {
+                None => {
+                    constraints.regions.insert(name.clone(), rects.clone());
+                }
+                Some(mine) if mine == rects => {}
+                Some(_) => settle(format!("region {}", name))?,
+            }
         }
 
-        // Requirement 17.2: Test empty tile set error
-        if rule_set.tiles.is_empty() {
-            return Err(WfcError::NoTilesDefined);
+        if merged.missing_adjacency != other.missing_adjacency {
+            settle("missing adjacency".to_string())?;
+        }
+        match (&merged.atlas, &other.atlas) {
+            (None, Some(atlas)) => merged.atlas = Some(atlas.clone()),
+            (Some(mine), Some(atlas)) if mine != atlas => settle("atlas".to_string())?,
+            _ => {}
         }
 
-        Ok(rule_set)
+        *self = merged;
+        Ok(())
+    }
+
+    /// Hash of everything that affects generation: tiles with their weights and metadata,
+    /// adjacency rules and the missing-adjacency mode (not the atlas). It does not depend on
+    /// insertion or hash map order, and is the same on every platform and build, so it can
+    /// key caches of generated chunks across sessions.
+    pub fn content_hash(&self) -> String {
+        let mut records: Vec<Vec<Vec<u8>>> = Vec::new();
+        for info in self.tiles.values() {
+            let metadata = Value::Object(info.metadata.clone()).to_string();
+            records.push(vec![b"tile".to_vec(), info.id.to_string().into_bytes(), info.weight.to_le_bytes().to_vec(), metadata.into_bytes()]);
+        }
+        for ((from, dir), set) in &self.adjacency {
+            for to in set {
+                records.push(vec![b"rule".to_vec(), from.to_string().into_bytes(), direction_name(*dir).into(), to.to_string().into_bytes()]);
+            }
+        }
+        for (from, dir, to, weight) in self.rule_weights.entries() {
+            records.push(vec![
+                b"rule_weight".to_vec(),
+                from.to_string().into_bytes(),
+                direction_name(dir).into(),
+                to.to_string().into_bytes(),
+                weight.to_bits().to_le_bytes().to_vec(),
+            ]);
+        }
+        if self.missing_adjacency == MissingAdjacency::Open {
+            records.push(vec![b"open".to_vec()]);
+        }
+        if !self.constraints.is_empty() {
+            let constraints = serde_json::to_string(&self.constraints).unwrap_or_default();
+            records.push(vec![b"constraints".to_vec(), constraints.into_bytes()]);
+        }
+        records.sort();
+
+        // FNV-1a, 64-bit; every field is length-prefixed so no two rulesets share a byte stream
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        for field in records.iter().flatten() {
+            for byte in (field.len() as u64).to_le_bytes().iter().chain(field.iter()) {
+                hash ^= *byte as u64;
+                hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+            }
+        }
+        format!("{:016x}", hash)
+    }
+}
+
+/// `other`'s metadata added to `tile`'s for `MergeConflict::Union`, joining lists. Fails if
+/// anything else differs, or a metadata key holds different values that are not both lists.
+fn union_tile<T: TileKey>(tile: &mut TileInfo<T>, other: &TileInfo<T>) -> Result<(), WfcError> {
+    if (tile.weight, tile.symmetry, &tile.variant_weights, &tile.sockets) != (other.weight, other.symmetry, &other.variant_weights, &other.sockets) {
+        return Err(WfcError::MergeConflict(format!("tile {}", tile.id)));
+    }
+    for (key, value) in &other.metadata {
+        match (tile.metadata.get_mut(key), value) {
+            (None, _) => {
+                tile.metadata.insert(key.clone(), value.clone());
+            }
+            (Some(Value::Array(mine)), Value::Array(theirs)) => {
+                for item in theirs {
+                    if !mine.contains(item) {
+                        mine.push(item.clone());
+                    }
+                }
+            }
+            (Some(mine), _) if mine == value => {}
+            _ => return Err(WfcError::MergeConflict(format!("metadata {} of tile {}", key, tile.id))),
+        }
     }
+    Ok(())
 }
 
-// Wasm-exposed methods
-#[wasm_bindgen]
+fn direction_name(direction: Direction) -> &'static str {
+    match direction {
+        Direction::Up => "up",
+        Direction::Right => "right",
+        Direction::Down => "down",
+        Direction::Left => "left",
+        Direction::Forward => "forward",
+        Direction::Backward => "backward",
+        Direction::UpRight => "up_right",
+        Direction::DownRight => "down_right",
+        Direction::DownLeft => "down_left",
+        Direction::UpLeft => "up_left",
+    }
+}
+
+// String tiles: symmetry families and the JSON format
 impl RuleSet {
-    #[wasm_bindgen]
-    pub fn add_tile_wasm(&mut self, id: String, weight: u32) {
-        self.add_tile(id, weight);
-    }
-
-    #[wasm_bindgen]
-    pub fn add_adjacency_wasm(&mut self, from: String, to: String, direction: String) {
-        let dir = match direction.as_str() {
-            "Up" => Direction::Up,
-            "Down" => Direction::Down,
-            "Left" => Direction::Left,
-            "Right" => Direction::Right,
-            _ => return, // Invalid direction, silently ignore
-        };
-        self.add_adjacency(from, to, dir);
+    pub fn new() -> RuleSet {
+        RuleSet::default()
+    }
+
+    /// The classic terrain demo: `levels` in order (say deep water, water, sand, grass,
+    /// hills, mountain), each allowed next to itself and the levels directly before and
+    /// after it, so terrain always changes one step at a time. `weights[i]` is the weight of
+    /// level `i`; missing entries repeat the last weight given, or 1 if `weights` is empty.
+    pub fn from_levels(levels: &[&str], weights: &[u32]) -> Result<RuleSet, WfcError> {
+        if levels.is_empty() {
+            return Err(WfcError::NoTilesDefined);
+        }
+        let mut rules = RuleSet::new();
+        for (i, level) in levels.iter().enumerate() {
+            if rules.tiles.contains_key(*level) {
+                return Err(WfcError::Import(format!("duplicate level {:?}", level)));
+            }
+            let weight = weights.get(i).or(weights.last()).copied().unwrap_or(1);
+            rules.add_tile(level.to_string(), weight);
+        }
+        for (i, level) in levels.iter().enumerate() {
+            for other in &levels[i..levels.len().min(i + 2)] {
+                for dir in DIRECTIONS {
+                    rules.add_adjacency_symmetric(level.to_string(), other.to_string(), dir);
+                }
+            }
+        }
+        Ok(rules)
+    }
+
+    /// Add a tile and one variant per distinct orientation under `symmetry`, returning the
+    /// variant IDs in `SymmetryType::variants()` order. `weights[i]` is the weight of variant
+    /// `i` (e.g. making horizontal roads more common than vertical ones); missing entries
+    /// repeat the last weight given, or 1 if `weights` is empty.
+    pub fn add_tile_with_symmetry(&mut self, id: TileId, symmetry: SymmetryType, weights: &[u32]) -> Vec<TileId> {
+        self.add_tile_family(id, symmetry, weights, Map::new(), None)
+    }
+
+    /// `add_tile_with_symmetry` for a tile with edge sockets, each variant getting the
+    /// sockets turned to its orientation. Call `derive_socket_adjacency` once every tile is
+    /// in.
+    pub fn add_tile_with_symmetry_and_sockets(&mut self, id: TileId, symmetry: SymmetryType, weights: &[u32], sockets: EdgeSockets) -> Vec<TileId> {
+        self.add_tile_family(id, symmetry, weights, Map::new(), Some(sockets))
+    }
+
+    fn add_tile_family(&mut self, id: TileId, symmetry: SymmetryType, weights: &[u32], metadata: Map<String, Value>, sockets: Option<EdgeSockets>) -> Vec<TileId> {
+        let variants = symmetry.variants();
+        let variant_weights: Vec<u32> = (0..variants.len())
+            .map(|i| weights.get(i).or(weights.last()).copied().unwrap_or(1))
+            .collect();
+
+        let ids: Vec<TileId> = variants.iter().map(|t| t.variant_id(&id)).collect();
+        for ((variant_id, &weight), transform) in ids.iter().zip(&variant_weights).zip(&variants).skip(1) {
+            self.add_tile_with_metadata(variant_id.clone(), weight, metadata.clone());
+            if let Some(info) = self.tiles.get_mut(variant_id) {
+                info.sockets = sockets.as_ref().map(|sockets| sockets.transformed(*transform));
+            }
+        }
+        self.tiles.insert(id.clone(), TileInfo {
+            id,
+            weight: variant_weights[0],
+            metadata,
+            symmetry: Some(symmetry),
+            variant_weights,
+            sockets,
+        });
+        ids
+    }
+
+    /// Add `to` in `direction` of `from` together with every rotated/mirrored copy of that
+    /// placement. Tiles from `add_tile_with_symmetry` are swapped for the matching variant;
+    /// other tiles are treated as fully symmetric and kept as-is.
+    pub fn add_adjacency_with_symmetry(&mut self, from: TileId, to: TileId, direction: Direction) {
+        self.add_adjacency_with_transforms(from, to, direction, &Transform::ALL);
+    }
+
+    /// `add_weighted_adjacency` together with every rotated/mirrored copy of the placement,
+    /// as in `add_adjacency_with_symmetry`
+    pub fn add_weighted_adjacency_with_symmetry(&mut self, from: TileId, to: TileId, direction: Direction, weight: f64) -> Result<(), WfcError> {
+        for transform in Transform::ALL {
+            let from_variant = self.transform_tile(&from, transform);
+            let to_variant = self.transform_tile(&to, transform);
+            self.add_weighted_adjacency(from_variant, to_variant, transform.apply_direction(direction), weight)?;
+        }
+        Ok(())
+    }
+
+    /// `add_adjacency_with_symmetry` restricted to `transforms`, e.g. rotations only
+    pub fn add_adjacency_with_transforms(&mut self, from: TileId, to: TileId, direction: Direction, transforms: &[Transform]) {
+        for &transform in transforms {
+            let from_variant = self.transform_tile(&from, transform);
+            let to_variant = self.transform_tile(&to, transform);
+            self.add_adjacency(from_variant, to_variant, transform.apply_direction(direction));
+        }
+    }
+
+    /// Base tile, symmetry and orientation of a tile added by `add_tile_with_symmetry`
+    pub(crate) fn variant_origin(&self, id: &TileId) -> Option<(&TileId, SymmetryType, Transform)> {
+        self.tiles.values().find_map(|info| {
+            let symmetry = info.symmetry?;
+            symmetry.variants()
+                .into_iter()
+                .find(|t| &t.variant_id(&info.id) == id)
+                .map(|t| (&info.id, symmetry, t))
+        })
+    }
+
+    fn transform_tile(&self, id: &TileId, transform: Transform) -> TileId {
+        match self.variant_origin(id) {
+            Some((base, symmetry, orientation)) => {
+                let index = symmetry.variant_index(orientation.then(transform));
+                symmetry.variants()[index].variant_id(base)
+            }
+            None => id.clone(),
+        }
     }
 
-    #[wasm_bindgen]
     pub fn get_weight(&self, tile_id: &str) -> Option<u32> {
         self.tiles.get(tile_id).map(|info| info.weight)
     }
 
-    #[wasm_bindgen]
-    pub fn to_json(&self) -> Result<JsValue, JsValue> {
-        let json_str = self.to_json_string()
-            .map_err(|e| JsValue::from_str(&e.to_string()))?;
-        Ok(JsValue::from_str(&json_str))
+    pub fn to_json_string(&self) -> Result<String, WfcError> {
+        self.to_json_string_with(&JsonOptions::default())
+    }
+
+    /// Export with tiles sorted by ID and rules by (from, direction, to), so the same
+    /// ruleset always serializes to the same text and edits show up as small diffs
+    pub fn to_json_string_with(&self, options: &JsonOptions) -> Result<String, WfcError> {
+        let document = self.to_document(options.grouped);
+        let written = if options.pretty { serde_json::to_string_pretty(&document) } else { serde_json::to_string(&document) };
+        written.map_err(WfcError::JsonParseError)
     }
 
-    #[wasm_bindgen]
-    pub fn from_json_wasm(json: &str) -> Result<RuleSet, JsValue> {
-        RuleSet::from_json(json)
-            .map_err(|e| JsValue::from_str(&e.to_string()))
+    /// The ruleset as YAML, laid out like the JSON format with grouped rules (see
+    /// `JsonOptions::grouped`). Unlike JSON, YAML files can carry comments.
+    #[cfg(feature = "yaml")]
+    pub fn to_yaml_string(&self) -> Result<String, WfcError> {
+        serde_yaml::to_string(&self.to_document(true)).map_err(|e| WfcError::Export(e.to_string()))
+    }
+
+    /// The ruleset as TOML, laid out like the JSON format with grouped rules: each tile is
+    /// a `[[tiles]]` table and each rule a `[[rules]]` table
+    #[cfg(feature = "toml")]
+    pub fn to_toml_string(&self) -> Result<String, WfcError> {
+        toml::to_string_pretty(&self.to_document(true)).map_err(|e| WfcError::Export(e.to_string()))
+    }
+
+    /// The serialized form shared by every format, sorted like `to_json_string_with`
+    fn to_document(&self, grouped: bool) -> RuleSetJson {
+        let mut tiles: Vec<TileInfo> = self.tiles.values().cloned().collect();
+        tiles.sort_by(|a, b| a.id.cmp(&b.id));
+        let mut keys: Vec<&(TileId, Direction)> = self.adjacency
+            .keys()
+            .filter(|(from, _)| !self.aliases.contains_key(from))
+            .collect();
+        keys.sort();
+        let mut rules = Vec::new();
+        for key in keys {
+            let (from, direction) = key.clone();
+            // Rules the sockets imply are derived again on load, unless they carry a weight
+            let mut targets: Vec<(TileId, Option<f64>)> = self.adjacency[key]
+                .iter()
+                .filter(|to| !self.aliases.contains_key(*to))
+                .map(|to| (to.clone(), self.rule_weights.get(&from, direction, to)))
+                .filter(|(to, weight)| weight.is_some() || !self.sockets_allow(&from, to, direction))
+                .collect();
+            targets.sort_by(|a, b| a.0.cmp(&b.0));
+            let rule = |to, weight| RuleJson { from: from.clone(), to, direction, symmetric: false, weight };
+            if grouped {
+                // Weighted rules are written one by one after the group
+                let (weighted, plain): (Vec<_>, Vec<_>) = targets.into_iter().partition(|(_, weight)| weight.is_some());
+                if !plain.is_empty() {
+                    rules.push(rule(RuleTargets::Many(plain.into_iter().map(|(to, _)| to).collect()), None));
+                }
+                rules.extend(weighted.into_iter().map(|(to, weight)| rule(RuleTargets::One(to), weight)));
+            } else {
+                rules.extend(targets.into_iter().map(|(to, weight)| rule(RuleTargets::One(to), weight)));
+            }
+        }
+
+        RuleSetJson {
+            version: RULESET_VERSION,
+            tiles,
+            rules,
+            missing_adjacency: self.missing_adjacency,
+            atlas: self.atlas.clone(),
+            // Both live on the tiles themselves once loaded
+            tags: BTreeMap::new(),
+            symmetry: BTreeMap::new(),
+            aliases: self.aliases.iter().fold(BTreeMap::new(), |mut aliases, (alias, canonical)| {
+                let group: &mut Vec<TileId> = aliases.entry(canonical.clone()).or_default();
+                group.push(alias.clone());
+                group.sort();
+                aliases
+            }),
+            constraints: self.constraints.clone(),
+        }
+    }
+
+    /// Load a ruleset written in any supported version of the JSON format
+    pub fn from_json(json: &str) -> Result<RuleSet, WfcError> {
+        RuleSet::from_document(serde_json::from_str(json).map_err(WfcError::JsonParseError)?)
+    }
+
+    /// Load a ruleset from YAML with the same fields as the JSON format, e.g. as written by
+    /// `to_yaml_string`
+    #[cfg(feature = "yaml")]
+    pub fn from_yaml(yaml: &str) -> Result<RuleSet, WfcError> {
+        RuleSet::from_document(serde_yaml::from_str(yaml).map_err(|e| WfcError::Import(e.to_string()))?)
+    }
+
+    /// Load a ruleset from TOML with the same fields as the JSON format, e.g. as written by
+    /// `to_toml_string`
+    #[cfg(feature = "toml")]
+    pub fn from_toml(toml: &str) -> Result<RuleSet, WfcError> {
+        RuleSet::from_document(toml::from_str(toml).map_err(|e| WfcError::Import(e.to_string()))?)
+    }
+
+    fn from_document(mut parsed: RuleSetJson) -> Result<RuleSet, WfcError> {
+        if parsed.version > RULESET_VERSION {
+            return Err(WfcError::Import(format!("unsupported ruleset version {}", parsed.version)));
+        }
+
+        let mut rule_set = RuleSet::new();
+        rule_set.atlas = parsed.atlas;
+        rule_set.missing_adjacency = parsed.missing_adjacency;
+
+        // Fold the v2 `symmetry` and `tags` blocks into the tile entries they describe
+        for (id, symmetry) in parsed.symmetry {
+            let tile = parsed.tiles.iter_mut().find(|tile| tile.id == id).ok_or(WfcError::InvalidTileId(id))?;
+            tile.symmetry = Some(symmetry);
+        }
+        for (tag, ids) in parsed.tags {
+            for id in ids {
+                let tile = parsed.tiles.iter_mut().find(|tile| tile.id == id).ok_or(WfcError::InvalidTileId(id))?;
+                let tags = tile.metadata.entry("tags").or_insert_with(|| Value::Array(Vec::new()));
+                if let Value::Array(tags) = tags {
+                    if !tags.iter().any(|existing| existing.as_str() == Some(&tag)) {
+                        tags.push(Value::String(tag.clone()));
+                    }
+                }
+            }
+        }
+
+        for tile in parsed.tiles {
+            match tile.symmetry {
+                Some(symmetry) => {
+                    let weights = if tile.variant_weights.is_empty() { vec![tile.weight] } else { tile.variant_weights };
+                    rule_set.add_tile_family(tile.id, symmetry, &weights, tile.metadata, tile.sockets);
+                }
+                // Variants listed after their base were already generated by it
+                None if rule_set.variant_origin(&tile.id).is_some() => {}
+                None => {
+                    rule_set.add_tile_with_metadata(tile.id.clone(), tile.weight, tile.metadata);
+                    if let Some(info) = rule_set.tiles.get_mut(&tile.id) {
+                        info.sockets = tile.sockets;
+                    }
+                }
+            }
+        }
+
+        // Aliases are listed as tiles too; register them before the rules expand over them
+        for (canonical, aliases) in parsed.aliases {
+            if !rule_set.tiles.contains_key(&canonical) {
+                return Err(WfcError::InvalidTileId(canonical));
+            }
+            for alias in aliases {
+                if !rule_set.tiles.contains_key(&alias) {
+                    return Err(WfcError::InvalidTileId(alias));
+                }
+                rule_set.aliases.insert(alias, canonical.clone());
+            }
+        }
+
+        rule_set.derive_socket_adjacency();
+
+        let has_families = rule_set.tiles.values().any(|info| info.symmetry.is_some());
+
+        // Grouped rules are shorthand for one rule per target
+        let rules = parsed.rules.into_iter().flat_map(|rule| {
+            let targets = match rule.to {
+                RuleTargets::One(to) => vec![to],
+                RuleTargets::Many(targets) => targets,
+            };
+            let (from, direction, symmetric, weight) = (rule.from, rule.direction, rule.symmetric, rule.weight);
+            targets.into_iter().map(move |to| (from.clone(), to, direction, symmetric, weight))
+        });
+        for (from, to, direction, symmetric, weight) in rules {
+            // Verify tiles exist?
+            // Requirement 5.1 says "detect tiles with no valid neighbors", checking existence here is good practice but maybe not strictly required to fail if loose strings are passed.
+            // However, strictly speaking, rules should involve known tiles.
+
+            if !rule_set.tiles.contains_key(&from) {
+                return Err(WfcError::InvalidTileId(from));
+            }
+            if !rule_set.tiles.contains_key(&to) {
+                return Err(WfcError::InvalidTileId(to));
+            }
+
+            // Rules touching a symmetry family apply to every orientation of it
+            let in_family = has_families
+                && (rule_set.variant_origin(&from).is_some() || rule_set.variant_origin(&to).is_some());
+            let add = |rule_set: &mut RuleSet, from: TileId, to: TileId, direction: Direction| {
+                match (in_family, weight) {
+                    (true, Some(weight)) => rule_set.add_weighted_adjacency_with_symmetry(from, to, direction, weight)?,
+                    (true, None) => rule_set.add_adjacency_with_symmetry(from, to, direction),
+                    (false, Some(weight)) => rule_set.add_weighted_adjacency(from, to, direction, weight)?,
+                    (false, None) => rule_set.add_adjacency(from, to, direction),
+                }
+                Ok::<(), WfcError>(())
+            };
+            if symmetric {
+                add(&mut rule_set, to.clone(), from.clone(), direction.opposite())?;
+            }
+            add(&mut rule_set, from, to, direction)?;
+        }
+
+        // Requirement 17.2: Test empty tile set error
+        if rule_set.tiles.is_empty() {
+            return Err(WfcError::NoTilesDefined);
+        }
+
+        parsed.constraints.validate(&rule_set)?;
+        rule_set.constraints = parsed.constraints;
+
+        Ok(rule_set)
     }
 }
 
@@ -199,8 +1265,8 @@ mod tests {
     fn test_get_weight() {
         let mut rs = RuleSet::new();
         rs.add_tile("tile1".to_string(), 42);
-        assert_eq!(rs.get_weight(&"tile1".to_string()), Some(42));
-        assert_eq!(rs.get_weight(&"missing".to_string()), None);
+        assert_eq!(rs.get_weight("tile1"), Some(42));
+        assert_eq!(rs.get_weight("missing"), None);
     }
 
     #[test]
@@ -216,6 +1282,39 @@ mod tests {
         assert!(neigh.contains("b"));
     }
 
+    #[test]
+    fn test_json_export_is_canonical() {
+        let build = |order: &[&str]| {
+            let mut rs = RuleSet::new();
+            for id in order {
+                rs.add_tile(id.to_string(), 1);
+            }
+            for from in order {
+                for to in order {
+                    for dir in DIRECTIONS {
+                        if from <= to {
+                            rs.add_adjacency_symmetric(from.to_string(), to.to_string(), dir);
+                        }
+                    }
+                }
+            }
+            rs
+        };
+        let rs = build(&["sand", "grass", "water", "rock"]);
+        let json = rs.to_json_string().unwrap();
+        assert_eq!(json, build(&["rock", "water", "sand", "grass"]).to_json_string().unwrap());
+        assert!(json.contains(r#"{"from":"grass","to":"grass","direction":"Up"},{"from":"grass","to":"rock","direction":"Up"}"#));
+
+        let grouped = rs.to_json_string_with(&JsonOptions { grouped: true, pretty: false }).unwrap();
+        assert!(grouped.contains(r#"{"from":"grass","to":["grass","rock","sand","water"],"direction":"Up"}"#));
+        assert!(grouped.len() < json.len());
+        assert_eq!(RuleSet::from_json(&grouped).unwrap().content_hash(), rs.content_hash());
+
+        let pretty = rs.to_json_string_with(&JsonOptions { grouped: true, pretty: true }).unwrap();
+        assert!(pretty.lines().count() > 1);
+        assert_eq!(RuleSet::from_json(&pretty).unwrap().content_hash(), rs.content_hash());
+    }
+
     #[test]
     fn test_get_tile_info_and_all_tiles() {
         let mut rs = RuleSet::new();
@@ -268,6 +1367,628 @@ mod tests {
         assert!(rs.get_valid_neighbors(&"grass".to_string(), Direction::Right).unwrap().contains("water"));
     }
 
+    #[test]
+    fn test_v2_blocks_round_trip() {
+        let json = r#"{
+            "version": 2,
+            "tiles": [{"id": "water"}, {"id": "shore"}, {"id": "road"}],
+            "rules": [{"from": "water", "to": "shore", "direction": "Right", "symmetric": true}],
+            "tags": {"wet": ["water", "shore"]},
+            "symmetry": {"road": "I"},
+            "constraints": {
+                "counts": [{"tile": "road", "max": 4}],
+                "borders": [{"tiles": ["water"]}],
+                "regions": {"bay": [{"x": 0, "y": 0, "width": 3, "height": 2}]},
+                "masks": [{"region": "bay", "tiles": ["wet"]}]
+            }
+        }"#;
+        let rules = RuleSet::from_json(json).unwrap();
+        assert!(rules.get_tile_info(&"shore".to_string()).unwrap().matches("wet"));
+        assert_eq!(rules.tiles.len(), 4, "road expands into two variants");
+        assert_eq!(rules.constraints.counts[0].max, Some(4));
+        assert!(rules.constraints.regions["bay"][0].contains(2, 1));
+
+        let reloaded = RuleSet::from_json(&rules.to_json_string().unwrap()).unwrap();
+        assert_eq!(reloaded.constraints, rules.constraints);
+        assert_eq!(reloaded.content_hash(), rules.content_hash());
+        assert!(rules.to_json_string().unwrap().contains(r#""version":2"#));
+
+        let unknown_region = json.replace(r#""region": "bay""#, r#""region": "cove""#);
+        assert!(matches!(RuleSet::from_json(&unknown_region), Err(WfcError::Import(_))));
+        let unknown_tile = json.replace(r#"["wet"]"#, r#"["lava"]"#);
+        assert!(matches!(RuleSet::from_json(&unknown_tile), Err(WfcError::InvalidTileId(_))));
+        let future = json.replace(r#""version": 2"#, r#""version": 3"#);
+        assert!(matches!(RuleSet::from_json(&future), Err(WfcError::Import(_))));
+    }
+
+    #[test]
+    fn test_aliases_share_adjacency() {
+        let mut rules = RuleSet::new();
+        rules.add_tile("grass".to_string(), 4);
+        rules.add_tile("water".to_string(), 1);
+        rules.add_adjacency_symmetric("grass".to_string(), "water".to_string(), Direction::Right);
+        rules.add_adjacency("grass".to_string(), "grass".to_string(), Direction::Up);
+        rules.add_alias("grass_2".to_string(), "grass".to_string(), 2).unwrap();
+        // Rules added after the alias expand over the group too
+        rules.add_adjacency("water".to_string(), "grass".to_string(), Direction::Down);
+        rules.add_alias("grass_3".to_string(), "grass_2".to_string(), 1).unwrap();
+
+        let id = |s: &str| s.to_string();
+        for alias in ["grass_2", "grass_3"] {
+            assert_eq!(rules.canonical(&id(alias)), "grass");
+            assert!(rules.allows(&id(alias), &id("water"), Direction::Right));
+            assert!(rules.allows(&id("water"), &id(alias), Direction::Left));
+            assert!(rules.allows(&id("water"), &id(alias), Direction::Down));
+            assert!(rules.allows(&id(alias), &id("grass"), Direction::Up));
+            assert!(rules.allows(&id("grass"), &id(alias), Direction::Up));
+        }
+        assert_eq!(rules.weight(&id("grass_2")), Some(2));
+        assert!(matches!(rules.add_alias(id("x"), id("lava"), 1), Err(WfcError::InvalidTileId(_))));
+
+        let json = rules.to_json_string().unwrap();
+        assert!(!json.contains(r#""from":"grass_2""#), "alias rules are implied");
+        let reloaded = RuleSet::from_json(&json).unwrap();
+        assert_eq!(reloaded.content_hash(), rules.content_hash());
+        assert_eq!(reloaded.aliases, rules.aliases);
+    }
+
+    #[test]
+    fn test_content_hash_is_order_independent() {
+        let a = RuleSet::from_json(r#"{"tiles": [{"id": "x", "weight": 2}, {"id": "y"}],
+            "rules": [{"from": "x", "to": "y", "direction": "Up"}, {"from": "y", "to": "x", "direction": "Down"}]}"#).unwrap();
+        let b = RuleSet::from_json(r#"{"tiles": [{"id": "y"}, {"id": "x", "weight": 2}],
+            "rules": [{"from": "y", "to": "x", "direction": "Down"}, {"from": "x", "to": "y", "direction": "Up"}]}"#).unwrap();
+        assert_eq!(a.content_hash(), b.content_hash());
+        // Pinned so a change to the encoding is noticed; cached chunks would all be invalidated
+        assert_eq!(a.content_hash(), "1c9e49b605d90c44");
+
+        let mut heavier = a.clone();
+        heavier.add_tile("x".to_string(), 3);
+        assert_ne!(heavier.content_hash(), a.content_hash());
+        let mut open = a.clone();
+        open.missing_adjacency = MissingAdjacency::Open;
+        assert_ne!(open.content_hash(), a.content_hash());
+    }
+
+    #[test]
+    fn test_open_missing_adjacency() {
+        let json = r#"{
+            "tiles": [{"id": "lava"}, {"id": "rock"}, {"id": "snow"}],
+            "rules": [{"from": "lava", "to": "rock", "direction": "Right"}],
+            "missing_adjacency": "open"
+        }"#;
+        let rs = RuleSet::from_json(json).unwrap();
+        let (lava, rock, snow) = ("lava".to_string(), "rock".to_string(), "snow".to_string());
+        // Listed rules still restrict; everything unlisted is allowed
+        assert!(!rs.allows(&lava, &snow, Direction::Right));
+        assert!(rs.allows(&lava, &rock, Direction::Right));
+        assert!(rs.allows(&snow, &lava, Direction::Left));
+        assert!(!rs.is_compatible(&snow, &lava, Direction::Left));
+
+        let rs2 = RuleSet::from_json(&rs.to_json_string().unwrap()).unwrap();
+        assert_eq!(rs2.missing_adjacency, MissingAdjacency::Open);
+
+        let mut model = crate::model::Model::new(8, 8, rs, Some(4)).unwrap();
+        let result = model.run().unwrap();
+        assert!(model.verify_result(&result).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_minimize_removes_dead_rules() {
+        let id = |s: &str| s.to_string();
+        let mut rs = RuleSet::new();
+        for tile in ["grass", "water", "sand", "lava"] {
+            rs.add_tile(id(tile), 1);
+        }
+        for dir in DIRECTIONS {
+            rs.add_adjacency(id("grass"), id("grass"), dir);
+            rs.add_adjacency(id("water"), id("water"), dir);
+            rs.add_adjacency_symmetric(id("grass"), id("water"), dir);
+        }
+        // Sand never lists grass back, and lava only ever names sand
+        rs.add_adjacency(id("grass"), id("sand"), Direction::Right);
+        rs.add_adjacency(id("sand"), id("lava"), Direction::Up);
+        rs.add_adjacency(id("lava"), id("sand"), Direction::Left);
+        rs.adjacency.entry((id("ghost"), Direction::Up)).or_default().insert(id("grass"));
+
+        let mut minimized = rs.clone();
+        let report = minimized.minimize(false);
+        assert_eq!(report.unreachable, vec![id("lava"), id("sand")]);
+        let reasons: Vec<(&str, RemovalReason)> = report.removed.iter().map(|rule| (rule.from.as_str(), rule.reason)).collect();
+        assert_eq!(reasons, vec![
+            ("ghost", RemovalReason::UnknownTile),
+            ("grass", RemovalReason::UnreachableTile),
+            ("lava", RemovalReason::UnreachableTile),
+            ("sand", RemovalReason::UnreachableTile),
+        ]);
+        assert_eq!(minimized.adjacency.len(), 8);
+        assert!(minimized.minimize(true).removed.is_empty());
+
+        // Nothing a grid could contain has changed
+        let mut before = crate::model::Model::new(6, 6, rs, Some(9)).unwrap();
+        let mut after = crate::model::Model::new(6, 6, minimized, Some(9)).unwrap();
+        assert_eq!(before.run().unwrap(), after.run().unwrap());
+    }
+
+    #[test]
+    fn test_minimize_prunes_one_sided_and_wildcard_rules() {
+        let id = |s: &str| s.to_string();
+        let mut rs = RuleSet::new();
+        rs.missing_adjacency = MissingAdjacency::Open;
+        rs.add_tile(id("lava"), 1);
+        rs.add_tile(id("rock"), 1);
+        rs.add_adjacency(id("lava"), id("rock"), Direction::Right);
+        rs.add_adjacency(id("rock"), id("rock"), Direction::Left);
+        rs.add_adjacency(id("rock"), id("lava"), Direction::Up);
+        rs.add_adjacency(id("rock"), id("rock"), Direction::Up);
+
+        let report = rs.clone().minimize(false);
+        assert_eq!(report.removed, vec![RemovedRule {
+            from: id("lava"),
+            direction: Direction::Right,
+            to: id("rock"),
+            reason: RemovalReason::OneSided,
+        }]);
+
+        let report = rs.minimize(true);
+        assert_eq!(report.removed.len(), 3);
+        assert!(report.removed[1..].iter().all(|rule| rule.reason == RemovalReason::Wildcard));
+        // Lava's emptied entry still forbids everything to its right
+        assert!(!rs.allows(&id("lava"), &id("lava"), Direction::Right));
+        assert!(rs.get_valid_neighbors(&id("rock"), Direction::Up).is_none());
+    }
+
+    #[test]
+    fn test_from_levels() {
+        let levels = ["deep_water", "water", "sand", "grass", "hills", "mountain"];
+        let rs = RuleSet::from_levels(&levels, &[3, 5, 2]).unwrap();
+        assert_eq!(rs.get_weight("water"), Some(5));
+        assert_eq!(rs.get_weight("mountain"), Some(2));
+        for (i, a) in levels.iter().enumerate() {
+            for (j, b) in levels.iter().enumerate() {
+                for dir in DIRECTIONS {
+                    assert_eq!(rs.is_compatible(&a.to_string(), &b.to_string(), dir), i.abs_diff(j) <= 1, "{} {} {:?}", a, b, dir);
+                }
+            }
+        }
+
+        assert!(matches!(RuleSet::from_levels(&[], &[]), Err(WfcError::NoTilesDefined)));
+        assert!(matches!(RuleSet::from_levels(&["sand", "sand"], &[]), Err(WfcError::Import(_))));
+    }
+
+    #[test]
+    fn test_symmetric_adjacency() {
+        let mut rs = RuleSet::new();
+        rs.add_tile("sand".to_string(), 1);
+        rs.add_tile("water".to_string(), 1);
+        rs.add_adjacency_symmetric("sand".to_string(), "water".to_string(), Direction::Right);
+        assert!(rs.is_compatible(&"sand".to_string(), &"water".to_string(), Direction::Right));
+        assert!(rs.get_valid_neighbors(&"water".to_string(), Direction::Right).is_none());
+
+        let json = r#"{
+            "tiles": [{ "id": "sand" }, { "id": "water" }],
+            "rules": [
+                { "from": "sand", "to": "water", "direction": "Down", "symmetric": true },
+                { "from": "sand", "to": "sand", "direction": "Up" }
+            ]
+        }"#;
+        let rs = RuleSet::from_json(json).unwrap();
+        assert!(rs.get_valid_neighbors(&"water".to_string(), Direction::Up).unwrap().contains("sand"));
+        assert!(rs.get_valid_neighbors(&"sand".to_string(), Direction::Down).unwrap().contains("water"));
+        assert_eq!(rs.get_valid_neighbors(&"sand".to_string(), Direction::Down).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_metadata_round_trip() {
+        let json = r#"{
+            "tiles": [
+                { "id": "door", "weight": 2, "metadata": { "sprite": "door.png", "solid": false } },
+                { "id": "wall" }
+            ],
+            "rules": []
+        }"#;
+
+        let rs = RuleSet::from_json(json).unwrap();
+        let door = rs.get_tile_info(&"door".to_string()).unwrap();
+        assert_eq!(door.metadata["sprite"], "door.png");
+        assert!(rs.get_tile_info(&"wall".to_string()).unwrap().metadata.is_empty());
+
+        let exported = rs.to_json_string().unwrap();
+        assert!(!exported.contains(r#""id":"wall","weight":1,"metadata""#));
+        let rs2 = RuleSet::from_json(&exported).unwrap();
+        assert_eq!(rs2.get_tile_info(&"door".to_string()).unwrap().metadata["solid"], false);
+    }
+
+    #[test]
+    fn test_symmetry_variant_weights() {
+        let mut rs = RuleSet::new();
+        let ids = rs.add_tile_with_symmetry("road".to_string(), SymmetryType::I, &[5, 1]);
+        assert_eq!(ids, vec!["road".to_string(), "road_90".to_string()]);
+        assert_eq!(rs.get_weight("road"), Some(5));
+        assert_eq!(rs.get_weight("road_90"), Some(1));
+
+        rs.add_tile("grass".to_string(), 1);
+        rs.add_adjacency_with_symmetry("road".to_string(), "road".to_string(), Direction::Right);
+        rs.add_adjacency_with_symmetry("road".to_string(), "grass".to_string(), Direction::Up);
+        // A horizontal road continues sideways; its quarter turn continues vertically
+        assert!(rs.is_compatible(&"road".to_string(), &"road".to_string(), Direction::Left));
+        assert!(rs.is_compatible(&"road_90".to_string(), &"road_90".to_string(), Direction::Down));
+        assert!(rs.get_valid_neighbors(&"road_90".to_string(), Direction::Right).unwrap().contains("grass"));
+        assert!(!rs.get_valid_neighbors(&"road_90".to_string(), Direction::Up).unwrap().contains("grass"));
+    }
+
+    #[test]
+    fn test_symmetry_json_round_trip() {
+        let json = r#"{
+            "tiles": [
+                { "id": "corner", "symmetry": "L", "variant_weights": [4, 3, 2, 1] },
+                { "id": "pipe", "weight": 2, "symmetry": "I" }
+            ],
+            "rules": [
+                { "from": "corner", "to": "pipe", "direction": "Right" }
+            ]
+        }"#;
+
+        let rs = RuleSet::from_json(json).unwrap();
+        assert_eq!(rs.tiles.len(), 6);
+        assert_eq!(rs.get_weight("corner_270"), Some(1));
+        assert_eq!(rs.get_weight("pipe_90"), Some(2));
+        // The rule is rotated along with the corner
+        assert!(rs.get_valid_neighbors(&"corner_90".to_string(), Direction::Down).unwrap().contains("pipe_90"));
+
+        let rs2 = RuleSet::from_json(&rs.to_json_string().unwrap()).unwrap();
+        assert_eq!(rs2.tiles.len(), 6);
+        assert_eq!(rs2.get_tile_info(&"corner".to_string()).unwrap().variant_weights, vec![4, 3, 2, 1]);
+        assert_eq!(rs2.adjacency, rs.adjacency);
+    }
+
+    #[test]
+    fn test_validate_reports_rule_problems() {
+        let id = |name: &str| name.to_string();
+        let mut rs = RuleSet::from_levels(&["water", "sand", "grass"], &[]).unwrap();
+        assert!(rs.validate().is_ok());
+        assert_eq!(rs.validate().to_string(), "");
+
+        rs.add_tile(id("lava"), 0);
+        rs.add_tile(id("rock"), 1);
+        for dir in DIRECTIONS {
+            rs.add_adjacency_symmetric(id("lava"), id("rock"), dir);
+        }
+        rs.add_tile(id("cliff"), 1);
+        rs.add_adjacency(id("grass"), id("cliff"), Direction::Right);
+        rs.add_adjacency(id("sand"), id("snow"), Direction::Up);
+
+        let report = rs.validate();
+        assert_eq!(report.unknown_tiles, vec![id("snow")]);
+        assert_eq!(report.unreachable, vec![id("cliff")]);
+        assert_eq!(report.dead_ends.iter().filter(|(tile, _)| *tile == "cliff").count(), 4);
+        assert_eq!(report.asymmetric, vec![(id("grass"), Direction::Right, id("cliff"))]);
+        assert_eq!(report.zero_weight, vec![id("lava")]);
+        assert_eq!(report.isolated_groups, vec![vec![id("grass"), id("sand"), id("water")], vec![id("lava"), id("rock")]]);
+        let text = report.to_string();
+        assert!(text.contains("`grass` allows `cliff` Right, but `cliff` does not allow `grass` Left"), "{}", text);
+    }
+
+    #[test]
+    fn test_symmetrize_adds_missing_reverse_rules() {
+        let id = |name: &str| name.to_string();
+        let mut rs = RuleSet::new();
+        rs.add_tile(id("a"), 1);
+        rs.add_tile(id("b"), 1);
+        rs.add_adjacency(id("a"), id("b"), Direction::Right);
+        rs.add_adjacency_symmetric(id("a"), id("a"), Direction::Down);
+
+        assert_eq!(rs.symmetrize(), 1);
+        assert!(rs.allows(&id("b"), &id("a"), Direction::Left));
+        assert!(rs.validate().asymmetric.is_empty());
+        assert_eq!(rs.symmetrize(), 0);
+
+        // Open adjacency already allows pairs without rules, so nothing needs adding
+        let mut open = RuleSet::new();
+        open.add_tile(id("a"), 1);
+        open.missing_adjacency = MissingAdjacency::Open;
+        open.add_adjacency(id("a"), id("a"), Direction::Up);
+        assert_eq!(open.symmetrize(), 0);
+        assert!(open.get_valid_neighbors(&id("a"), Direction::Down).is_none());
+    }
+
+    #[test]
+    fn test_rule_weights_bias_collapse_and_round_trip() {
+        let id = |name: &str| name.to_string();
+        let mut plain = RuleSet::new();
+        for tile in ["water", "sand", "grass"] {
+            plain.add_tile(id(tile), 1);
+        }
+        for a in ["water", "sand", "grass"] {
+            for b in ["water", "sand", "grass"] {
+                for dir in DIRECTIONS {
+                    plain.add_adjacency(id(a), id(b), dir);
+                }
+            }
+        }
+        // Water beside sand is common, grass beside sand rare
+        let mut weighted = plain.clone();
+        for dir in DIRECTIONS {
+            weighted.add_weighted_adjacency(id("sand"), id("water"), dir, 8.0).unwrap();
+            weighted.add_weighted_adjacency(id("sand"), id("grass"), dir, 0.05).unwrap();
+        }
+        for bad in [-1.0, f64::NAN, f64::INFINITY] {
+            assert!(matches!(plain.clone().add_weighted_adjacency(id("sand"), id("water"), Direction::Up, bad), Err(WfcError::InvalidWeight(_))));
+        }
+        assert_eq!(weighted.rule_weight(&id("sand"), &id("water"), Direction::Up), 8.0);
+        assert_eq!(weighted.rule_weight(&id("water"), &id("sand"), Direction::Up), 1.0);
+        assert_ne!(weighted.content_hash(), plain.content_hash());
+
+        let ratio = |rules: &RuleSet| {
+            let (mut water, mut grass) = (0, 0);
+            for seed in 0..4 {
+                let grid = crate::model::Model::new(12, 12, rules.clone(), Some(seed)).unwrap().run().unwrap();
+                for i in 0..grid.len() {
+                    let neighbors = [i + 1, i + 12].into_iter().filter(|&n| n < grid.len() && (n != i + 1 || n % 12 != 0));
+                    for n in neighbors {
+                        match (grid[i].as_str(), grid[n].as_str()) {
+                            ("sand", "water") | ("water", "sand") => water += 1,
+                            ("sand", "grass") | ("grass", "sand") => grass += 1,
+                            _ => {}
+                        }
+                    }
+                }
+            }
+            water as f64 / grass.max(1) as f64
+        };
+        assert!(ratio(&weighted) > 2.0 * ratio(&plain));
+
+        // Weights survive both JSON layouts and are not folded into plain rules
+        for options in [JsonOptions::default(), JsonOptions { grouped: true, pretty: false }] {
+            let json = weighted.to_json_string_with(&options).unwrap();
+            assert!(json.contains(r#""weight":0.05"#));
+            let loaded = RuleSet::from_json(&json).unwrap();
+            assert_eq!(loaded.rule_weights, weighted.rule_weights);
+            assert_eq!(loaded.content_hash(), weighted.content_hash());
+        }
+        let json = r#"{
+            "tiles": [{ "id": "a" }, { "id": "b" }],
+            "rules": [{ "from": "a", "to": "b", "direction": "Right", "symmetric": true, "weight": 3.0 }]
+        }"#;
+        let loaded = RuleSet::from_json(json).unwrap();
+        assert_eq!(loaded.rule_weight(&id("a"), &id("b"), Direction::Right), 3.0);
+        assert_eq!(loaded.rule_weight(&id("b"), &id("a"), Direction::Left), 3.0);
+        let negative = json.replace("3.0", "-3.0");
+        assert!(matches!(RuleSet::from_json(&negative), Err(WfcError::InvalidWeight(w)) if w == -3.0));
+    }
+
+    #[test]
+    fn test_sockets_derive_adjacency() {
+        let json = r#"{
+            "tiles": [
+                { "id": "grass", "sockets": { "up": "g", "right": "g", "down": "g", "left": "g" } },
+                { "id": "road", "symmetry": "I", "sockets": { "up": "g|r|g", "right": "g", "down": "g|r|g", "left": "g" } },
+                { "id": "end", "sockets": { "up": "g|r|g", "right": "g", "down": "g", "left": "g" } }
+            ],
+            "rules": []
+        }"#;
+        let rs = RuleSet::from_json(json).unwrap();
+        let id = |name: &str| name.to_string();
+        assert!(rs.allows(&id("grass"), &id("road"), Direction::Right));
+        assert!(rs.allows(&id("road"), &id("road"), Direction::Down));
+        assert!(rs.allows(&id("road_90"), &id("road_90"), Direction::Right));
+        // The road ends in a tile below it, never above
+        assert!(rs.allows(&id("road"), &id("end"), Direction::Down));
+        assert!(!rs.allows(&id("road"), &id("end"), Direction::Up));
+        assert!(!rs.allows(&id("road"), &id("road_90"), Direction::Down));
+        assert!(!rs.allows(&id("grass"), &id("road_90"), Direction::Left));
+        assert_eq!(rs.get_tile_info(&id("road_90")).unwrap().sockets, Some(EdgeSockets::new("g", "g|r|g", "g", "g|r|g")));
+
+        let rs2 = RuleSet::from_json(&rs.to_json_string().unwrap()).unwrap();
+        assert_eq!(rs2.adjacency, rs.adjacency);
+
+        let mut built = RuleSet::new();
+        built.add_tile_with_sockets(id("grass"), 1, EdgeSockets::uniform("g"));
+        built.add_tile_with_symmetry_and_sockets(id("road"), SymmetryType::I, &[1], EdgeSockets::new("g|r|g", "g", "g|r|g", "g"));
+        built.add_tile_with_sockets(id("end"), 1, EdgeSockets::new("g|r|g", "g", "g", "g"));
+        built.derive_socket_adjacency();
+        assert_eq!(built.adjacency, rs.adjacency);
+    }
+
+    #[test]
+    fn test_diff_lists_changes_between_versions() {
+        let old = RuleSet::from_json(r#"{
+            "tiles": [{ "id": "water" }, { "id": "sand" }, { "id": "swamp" }],
+            "rules": [
+                { "from": "water", "to": ["water", "sand"], "direction": "Right" },
+                { "from": "sand", "to": "swamp", "direction": "Down", "symmetric": true }
+            ]
+        }"#).unwrap();
+        let new = RuleSet::from_json(r#"{
+            "tiles": [{ "id": "water" }, { "id": "sand", "weight": 3 }, { "id": "grass" }],
+            "rules": [
+                { "from": "water", "to": "water", "direction": "Right" },
+                { "from": "sand", "to": "grass", "direction": "Right", "symmetric": true }
+            ]
+        }"#).unwrap();
+        let id = |name: &str| name.to_string();
+        let diff = old.diff(&new);
+        assert_eq!(diff.added_tiles, [id("grass")]);
+        assert_eq!(diff.removed_tiles, [id("swamp")]);
+        assert_eq!(diff.changed_tiles, [id("sand")]);
+        assert_eq!(diff.added_rules, [(id("grass"), Direction::Left, id("sand")), (id("sand"), Direction::Right, id("grass"))]);
+        assert_eq!(diff.removed_rules, [
+            (id("sand"), Direction::Down, id("swamp")),
+            (id("swamp"), Direction::Up, id("sand")),
+            (id("water"), Direction::Right, id("sand")),
+        ]);
+        assert!(diff.to_string().starts_with("+ tile `grass`\n- tile `swamp`\n~ tile `sand`\n+ rule `grass` Left `sand`\n"));
+
+        let json = serde_json::to_string(&diff).unwrap();
+        assert_eq!(serde_json::from_str::<RuleSetDiff>(&json).unwrap(), diff);
+        assert!(old.diff(&old).is_empty());
+        assert_eq!(new.diff(&old).added_rules, diff.removed_rules);
+    }
+
+    /// A ruleset using most of the format: a symmetry family, tags, weights and constraints
+    #[cfg(any(feature = "yaml", feature = "toml"))]
+    fn feature_rich_rules() -> RuleSet {
+        RuleSet::from_json(r#"{
+            "tiles": [
+                { "id": "grass", "weight": 5, "metadata": { "sprite": "grass.png" } },
+                { "id": "road", "symmetry": "I" },
+                { "id": "water", "weight": 2 }
+            ],
+            "tags": { "walkable": ["grass", "road"] },
+            "rules": [
+                { "from": "grass", "to": ["grass", "road", "water"], "direction": "Right", "symmetric": true },
+                { "from": "grass", "to": ["grass", "road", "water"], "direction": "Down", "symmetric": true },
+                { "from": "road", "to": "road", "direction": "Down", "weight": 2.5 }
+            ],
+            "constraints": { "counts": [{ "tile": "water", "max": 4 }] }
+        }"#).unwrap()
+    }
+
+    #[test]
+    #[cfg(feature = "yaml")]
+    fn test_yaml_round_trip() {
+        let rules = feature_rich_rules();
+        let yaml = rules.to_yaml_string().unwrap();
+        let loaded = RuleSet::from_yaml(&yaml).unwrap();
+        assert_eq!(loaded.content_hash(), rules.content_hash());
+        assert_eq!(loaded.to_json_string().unwrap(), rules.to_json_string().unwrap());
+
+        let handwritten = RuleSet::from_yaml("
+# Coastline: water only ever meets sand
+tiles:
+  - id: water
+    weight: 4
+  - id: sand
+rules:
+  - { from: water, to: [water, sand], direction: Right, symmetric: true }
+  - { from: sand, to: sand, direction: Right }  # sand strips
+").unwrap();
+        assert!(handwritten.is_compatible(&"sand".to_string(), &"water".to_string(), Direction::Left));
+        assert_eq!(handwritten.weight(&"water".to_string()), Some(4));
+        assert!(matches!(RuleSet::from_yaml("tiles: 3"), Err(WfcError::Import(_))));
+        // YAML can spell out weights JSON cannot
+        let not_a_number = "tiles: [{ id: a }]\nrules: [{ from: a, to: a, direction: Up, weight: .nan }]";
+        assert!(matches!(RuleSet::from_yaml(not_a_number), Err(WfcError::InvalidWeight(_))));
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn test_toml_round_trip() {
+        let rules = feature_rich_rules();
+        let toml = rules.to_toml_string().unwrap();
+        let loaded = RuleSet::from_toml(&toml).unwrap();
+        assert_eq!(loaded.content_hash(), rules.content_hash());
+        assert_eq!(loaded.to_json_string().unwrap(), rules.to_json_string().unwrap());
+
+        let handwritten = RuleSet::from_toml(r#"
+# Coastline: water only ever meets sand
+[[tiles]]
+id = "water"
+weight = 4
+
+[[tiles]]
+id = "sand"
+
+[[rules]]
+from = "water"
+to = ["water", "sand"]
+direction = "Right"
+symmetric = true
+"#).unwrap();
+        assert!(handwritten.is_compatible(&"sand".to_string(), &"water".to_string(), Direction::Left));
+        assert!(matches!(RuleSet::from_toml("tiles = 3"), Err(WfcError::Import(_))));
+    }
+
+    #[test]
+    fn test_merge_combines_split_files() {
+        let terrain = RuleSet::from_json(r#"{
+            "tiles": [
+                { "id": "water", "weight": 4 },
+                { "id": "grass", "weight": 6, "metadata": { "tags": ["nature"], "sprite": "grass.png" } }
+            ],
+            "rules": [
+                { "from": "water", "to": "water", "direction": "Right", "symmetric": true },
+                { "from": "grass", "to": "water", "direction": "Right", "symmetric": true }
+            ]
+        }"#).unwrap();
+        let roads = RuleSet::from_json(r#"{
+            "tiles": [
+                { "id": "road" },
+                { "id": "grass", "weight": 6, "metadata": { "tags": ["walkable"] } }
+            ],
+            "rules": [{ "from": "grass", "to": "road", "direction": "Right", "symmetric": true, "weight": 2.0 }],
+            "constraints": { "counts": [{ "tile": "road", "min": 1 }] }
+        }"#).unwrap();
+        let id = |name: &str| name.to_string();
+        let tags = |rules: &RuleSet| rules.tiles[&id("grass")].tags().map(str::to_string).collect::<Vec<_>>();
+
+        // The two files disagree about grass
+        let mut merged = terrain.clone();
+        assert!(matches!(merged.merge(&roads, MergeConflict::Error), Err(WfcError::MergeConflict(_))));
+        assert_eq!(merged.content_hash(), terrain.content_hash());
+
+        merged.merge(&roads, MergeConflict::Union).unwrap();
+        assert_eq!(tags(&merged), ["nature", "walkable"]);
+        assert_eq!(merged.tiles[&id("grass")].metadata["sprite"], "grass.png");
+        assert_eq!(merged.tiles.len(), 3);
+        assert_eq!(merged.get_valid_neighbors(&id("grass"), Direction::Right).unwrap(), &HashSet::from([id("water"), id("road")]));
+        assert_eq!(merged.rule_weight(&id("grass"), &id("road"), Direction::Right), 2.0);
+        assert_eq!(merged.constraints.counts.len(), 1);
+        let reloaded = RuleSet::from_json(&merged.to_json_string().unwrap()).unwrap();
+        assert_eq!(reloaded.content_hash(), merged.content_hash());
+
+        let mut preferred = terrain.clone();
+        preferred.merge(&roads, MergeConflict::PreferSelf).unwrap();
+        assert_eq!(tags(&preferred), ["nature"]);
+        assert!(preferred.allows(&id("road"), &id("grass"), Direction::Left));
+
+        // Union cannot settle different weights
+        let mut heavier = roads.clone();
+        heavier.tiles.get_mut(&id("grass")).unwrap().weight = 9;
+        assert!(terrain.clone().merge(&heavier, MergeConflict::Union).is_err());
+        heavier.missing_adjacency = MissingAdjacency::Open;
+        assert!(terrain.clone().merge(&heavier, MergeConflict::PreferSelf).is_ok());
+    }
+
+    #[test]
+    fn test_merge_derives_sockets_and_aliases_across_files() {
+        let grass = RuleSet::from_json(r#"{
+            "tiles": [
+                { "id": "grass", "sockets": { "up": "g", "right": "g", "down": "g", "left": "g" } },
+                { "id": "meadow" }
+            ],
+            "aliases": { "grass": ["meadow"] },
+            "rules": []
+        }"#).unwrap();
+        let roads = RuleSet::from_json(r#"{
+            "tiles": [
+                { "id": "road", "symmetry": "I", "sockets": { "up": "g|r|g", "right": "g", "down": "g|r|g", "left": "g" } },
+                { "id": "pond" }
+            ],
+            "rules": [{ "from": "pond", "to": "road", "direction": "Left", "symmetric": true }]
+        }"#).unwrap();
+        let id = |name: &str| name.to_string();
+
+        let mut merged = grass.clone();
+        merged.merge(&roads, MergeConflict::Error).unwrap();
+        // Socket rules between tiles of different files, shared by the alias
+        assert!(merged.allows(&id("grass"), &id("road"), Direction::Right));
+        assert!(merged.allows(&id("road_90"), &id("meadow"), Direction::Up));
+        assert!(merged.allows(&id("meadow"), &id("road"), Direction::Left));
+        assert!(!merged.allows(&id("road"), &id("grass"), Direction::Down));
+        assert!(merged.allows(&id("road"), &id("pond"), Direction::Right));
+
+        // Merging the other way round gives the same rules
+        let mut reversed = roads.clone();
+        reversed.merge(&grass, MergeConflict::Error).unwrap();
+        assert_eq!(reversed.adjacency, merged.adjacency);
+
+        let reloaded = RuleSet::from_json(&merged.to_json_string().unwrap()).unwrap();
+        assert_eq!(reloaded.adjacency, merged.adjacency);
+        assert_eq!(reloaded.aliases, merged.aliases);
+        assert_eq!(reloaded.content_hash(), merged.content_hash());
+    }
+
     proptest! {
         #[test]
         fn test_rule_storage_and_retrieval(
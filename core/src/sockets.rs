@@ -0,0 +1,103 @@
+//! Edge sockets.
+//!
+//! Instead of listing which tiles may touch, each tile can label its four edges, and two
+//! tiles fit side by side where the labels of the touching edges agree. A label is one or
+//! more segments separated by `|`, read clockwise around the tile: the top edge left to
+//! right, the right edge top to bottom, the bottom edge right to left and the left edge
+//! bottom to top. `"grass|road|grass"` is an edge with a road crossing its middle. Touching
+//! edges are read in opposite senses, so they fit when one label is the other's segments
+//! reversed; symmetric labels such as that road fit themselves.
+//!
+//! `RuleSet::derive_socket_adjacency` turns the labels into adjacency rules. Rulesets loaded
+//! from JSON do this for every tile with a `sockets` entry, including the rotated and
+//! mirrored variants of symmetry families.
+
+use serde::{Deserialize, Serialize};
+use crate::Direction;
+use crate::symmetry::Transform;
+
+/// Socket labels of a tile's four edges
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct EdgeSockets {
+    pub up: String,
+    pub right: String,
+    pub down: String,
+    pub left: String,
+}
+
+impl EdgeSockets {
+    pub fn new(up: impl Into<String>, right: impl Into<String>, down: impl Into<String>, left: impl Into<String>) -> EdgeSockets {
+        EdgeSockets { up: up.into(), right: right.into(), down: down.into(), left: left.into() }
+    }
+
+    /// The same label on every edge, e.g. for plain terrain
+    pub fn uniform(label: impl Into<String>) -> EdgeSockets {
+        let label = label.into();
+        EdgeSockets::new(label.clone(), label.clone(), label.clone(), label)
+    }
+
+    /// Label of the edge facing `direction`; `None` for `Forward`/`Backward` and diagonals
+    pub fn edge(&self, direction: Direction) -> Option<&str> {
+        match direction {
+            Direction::Up => Some(&self.up),
+            Direction::Right => Some(&self.right),
+            Direction::Down => Some(&self.down),
+            Direction::Left => Some(&self.left),
+            _ => None,
+        }
+    }
+
+    fn edge_mut(&mut self, direction: Direction) -> &mut String {
+        match direction {
+            Direction::Up | Direction::Forward => &mut self.up,
+            Direction::Right => &mut self.right,
+            Direction::Down | Direction::Backward => &mut self.down,
+            Direction::Left => &mut self.left,
+            _ => unreachable!("sockets label the four edges"),
+        }
+    }
+
+    /// The sockets of the tile turned by `transform` (see `symmetry::Transform`). Mirroring
+    /// flips the reading order of every edge, so its labels are reversed.
+    pub fn transformed(&self, transform: Transform) -> EdgeSockets {
+        let mut result = self.clone();
+        for direction in [Direction::Up, Direction::Right, Direction::Down, Direction::Left] {
+            let label = self.edge(direction).unwrap_or_default();
+            *result.edge_mut(transform.apply_direction(direction)) = if transform.reflected { reversed(label) } else { label.to_string() };
+        }
+        result
+    }
+}
+
+/// Whether an edge labeled `a` can touch one labeled `b`
+pub fn sockets_fit(a: &str, b: &str) -> bool {
+    a.split('|').eq(b.rsplit('|'))
+}
+
+/// `label` with its segments in reverse order, the label an edge needs to fit it
+pub(crate) fn reversed(label: &str) -> String {
+    label.rsplit('|').collect::<Vec<_>>().join("|")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sockets_fit_and_transform() {
+        assert!(sockets_fit("grass", "grass"));
+        assert!(sockets_fit("grass|road|grass", "grass|road|grass"));
+        assert!(sockets_fit("sand|water", "water|sand"));
+        assert!(!sockets_fit("sand|water", "sand|water"));
+        assert!(!sockets_fit("grass", "grass|grass"));
+
+        // A coast corner with water in the top right
+        let corner = EdgeSockets::new("sand|water", "water|sand", "sand", "sand");
+        let turned = corner.transformed(Transform { rotation: 1, reflected: false });
+        assert_eq!(turned, EdgeSockets::new("sand", "sand|water", "water|sand", "sand"));
+        // Mirrored, the water is in the top left
+        let mirrored = corner.transformed(Transform { rotation: 0, reflected: true });
+        assert_eq!(mirrored, EdgeSockets::new("water|sand", "sand", "sand", "sand|water"));
+        assert_eq!(EdgeSockets::uniform("x").edge(Direction::Forward), None);
+    }
+}
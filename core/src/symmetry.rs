@@ -0,0 +1,170 @@
+//! Rotation/reflection variants of symmetric tiles.
+//!
+//! A tile declared with a `SymmetryType` expands into one variant per distinct orientation.
+//! Variant 0 keeps the base ID; the others are named `<id>_<degrees>` with an `h` suffix when
+//! mirrored (`road_90`, `corner_180h`). The web editor expands tiles in the same order
+//! (`SymmetryType::transformations`), so per-variant weights mean the same on both sides.
+//! Rules written against any variant can be expanded over all eight transforms with
+//! `RuleSet::add_adjacency_with_symmetry`.
+
+use crate::{Direction, SymmetryType, TileId};
+
+/// Element of the square's symmetry group: rotate clockwise by `rotation` quarter turns,
+/// then mirror left-to-right if `reflected`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Transform {
+    pub rotation: u8,
+    pub reflected: bool,
+}
+
+impl Transform {
+    pub const IDENTITY: Transform = Transform { rotation: 0, reflected: false };
+
+    /// All eight transforms: the four rotations, then the four mirrored rotations
+    pub const ALL: [Transform; 8] = [
+        Transform { rotation: 0, reflected: false },
+        Transform { rotation: 1, reflected: false },
+        Transform { rotation: 2, reflected: false },
+        Transform { rotation: 3, reflected: false },
+        Transform { rotation: 0, reflected: true },
+        Transform { rotation: 1, reflected: true },
+        Transform { rotation: 2, reflected: true },
+        Transform { rotation: 3, reflected: true },
+    ];
+
+    /// `self` followed by `next`
+    pub fn then(self, next: Transform) -> Transform {
+        // Mirroring reverses the sense of any rotation applied after it
+        let turn = if self.reflected { 4 - next.rotation % 4 } else { next.rotation % 4 };
+        Transform {
+            rotation: (self.rotation + turn) % 4,
+            reflected: self.reflected != next.reflected,
+        }
+    }
+
+    pub fn apply_direction(self, direction: Direction) -> Direction {
+        let mut dir = direction;
+        for _ in 0..self.rotation {
+            dir = dir.rotate_clockwise();
+        }
+        if self.reflected { dir.mirrored() } else { dir }
+    }
+
+    /// ID of the variant of `base` in this orientation
+    pub fn variant_id(self, base: &TileId) -> TileId {
+        if self == Transform::IDENTITY {
+            base.clone()
+        } else {
+            format!("{}_{}{}", base, self.rotation as u32 * 90, if self.reflected { "h" } else { "" })
+        }
+    }
+
+    fn apply_point(self, (x, y): (i32, i32)) -> (i32, i32) {
+        let (mut x, mut y) = (x, y);
+        for _ in 0..self.rotation {
+            (x, y) = (-y, x);
+        }
+        if self.reflected { (-x, y) } else { (x, y) }
+    }
+}
+
+impl SymmetryType {
+    /// One representative transform per distinct orientation, in `Transform::ALL` order.
+    /// Its length is `variant_count()`.
+    pub fn variants(&self) -> Vec<Transform> {
+        let mut shapes = Vec::new();
+        let mut variants = Vec::new();
+        for transform in Transform::ALL {
+            let shape = self.shape(transform);
+            if !shapes.contains(&shape) {
+                shapes.push(shape);
+                variants.push(transform);
+            }
+        }
+        variants
+    }
+
+    /// Index into `variants()` of the orientation reached by applying `transform` to the base tile
+    pub fn variant_index(&self, transform: Transform) -> usize {
+        let shape = self.shape(transform);
+        self.variants()
+            .iter()
+            .position(|&v| self.shape(v) == shape)
+            .expect("every transform lands on a variant")
+    }
+
+    /// A small point pattern with exactly this symmetry type's invariances
+    fn glyph(&self) -> &'static [(i32, i32)] {
+        match self {
+            SymmetryType::X => &[(0, 0), (1, 0), (-1, 0), (0, 1), (0, -1)],
+            SymmetryType::I => &[(0, -1), (0, 0), (0, 1)],
+            SymmetryType::T => &[(-1, -1), (0, -1), (1, -1), (0, 0), (0, 1)],
+            SymmetryType::L => &[(-1, -1), (-1, 0), (-1, 1), (0, 1), (1, 1)],
+            SymmetryType::Backslash => &[(-1, -1), (0, 0), (1, 1)],
+            SymmetryType::F | SymmetryType::N => &[(0, 0), (1, 0), (2, 0), (0, 1)],
+        }
+    }
+
+    fn shape(&self, transform: Transform) -> Vec<(i32, i32)> {
+        let mut points: Vec<(i32, i32)> = self.glyph().iter().map(|&p| transform.apply_point(p)).collect();
+        points.sort();
+        points
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_variant_counts_match_symmetry() {
+        for symmetry in [
+            SymmetryType::X,
+            SymmetryType::I,
+            SymmetryType::T,
+            SymmetryType::L,
+            SymmetryType::Backslash,
+            SymmetryType::F,
+            SymmetryType::N,
+        ] {
+            assert_eq!(symmetry.variants().len(), symmetry.variant_count(), "{:?}", symmetry);
+            // The web editor's table lists the same variants in the same order
+            let listed: Vec<Transform> = symmetry
+                .transformations()
+                .into_iter()
+                .map(|(degrees, reflected, flipped)| {
+                    assert!(!flipped);
+                    Transform { rotation: (degrees / 90) as u8, reflected }
+                })
+                .collect();
+            assert_eq!(listed, symmetry.variants(), "{:?}", symmetry);
+        }
+    }
+
+    #[test]
+    fn test_transform_composition() {
+        let quarter = Transform { rotation: 1, reflected: false };
+        let mirror = Transform { rotation: 0, reflected: true };
+        for dir in [Direction::Up, Direction::Right, Direction::Down, Direction::Left, Direction::UpRight, Direction::DownLeft] {
+            for a in Transform::ALL {
+                for b in [quarter, mirror] {
+                    assert_eq!(a.then(b).apply_direction(dir), b.apply_direction(a.apply_direction(dir)));
+                }
+            }
+        }
+        assert_eq!(quarter.apply_direction(Direction::Up), Direction::Right);
+        assert_eq!(quarter.apply_direction(Direction::UpLeft), Direction::UpRight);
+        assert_eq!(mirror.apply_direction(Direction::DownRight), Direction::DownLeft);
+        assert_eq!(mirror.then(mirror), Transform::IDENTITY);
+    }
+
+    #[test]
+    fn test_variant_ids_and_indices() {
+        let road = "road".to_string();
+        let ids: Vec<TileId> = SymmetryType::I.variants().iter().map(|t| t.variant_id(&road)).collect();
+        assert_eq!(ids, vec!["road".to_string(), "road_90".to_string()]);
+        // Turning a straight road twice gives back the original orientation
+        assert_eq!(SymmetryType::I.variant_index(Transform { rotation: 2, reflected: false }), 0);
+        assert_eq!(SymmetryType::I.variant_index(Transform { rotation: 3, reflected: true }), 1);
+    }
+}
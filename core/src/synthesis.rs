@@ -0,0 +1,229 @@
+use std::collections::HashSet;
+use rand::prelude::*;
+use crate::{TileId, Direction, DIRECTIONS};
+use crate::error::WfcError;
+use crate::model::{Model, MAX_DIMENSION};
+use crate::ruleset::RuleSet;
+
+/// Merrell-style model synthesis: keeps a valid full assignment at all times and improves it
+/// by re-solving one block at a time against its fixed surroundings. A block that cannot be
+/// re-solved is simply left as it was, so the grid never becomes contradictory.
+#[derive(Debug, Clone)]
+pub struct ModelSynthesis {
+    width: usize,
+    height: usize,
+    rules: RuleSet,
+    grid: Vec<TileId>,
+    rng: StdRng,
+    block_size: usize,
+}
+
+impl ModelSynthesis {
+    /// Start from a trivial assignment: every cell set to a tile that may neighbor itself in
+    /// all four directions. Fails with `Contradiction` if the ruleset has no such tile; use
+    /// `with_initial` to supply a valid starting grid instead.
+    pub fn new(width: usize, height: usize, rules: RuleSet, seed: Option<u64>) -> Result<ModelSynthesis, WfcError> {
+        let mut candidates: Vec<&TileId> = rules.get_all_tile_ids()
+            .into_iter()
+            .filter(|id| DIRECTIONS.iter().all(|&dir| rules.is_compatible(id, id, dir)))
+            .collect();
+        candidates.sort();
+        let ground = candidates.first().map(|id| (*id).clone()).ok_or(WfcError::Contradiction)?;
+
+        ModelSynthesis::with_initial(width, height, rules, vec![ground; width * height], seed)
+    }
+
+    /// Start from a caller-provided grid, which must already satisfy the ruleset
+    pub fn with_initial(width: usize, height: usize, rules: RuleSet, initial: Vec<TileId>, seed: Option<u64>) -> Result<ModelSynthesis, WfcError> {
+        if width == 0 || height == 0 || width > MAX_DIMENSION || height > MAX_DIMENSION || initial.len() != width * height {
+            return Err(WfcError::InvalidDimensions { width, height });
+        }
+        if rules.get_all_tile_ids().is_empty() {
+            return Err(WfcError::NoTilesDefined);
+        }
+        if let Some(id) = initial.iter().find(|id| rules.get_tile_info(id).is_none()) {
+            return Err(WfcError::InvalidTileId(id.clone()));
+        }
+
+        let rng = match seed {
+            Some(s) => StdRng::seed_from_u64(s),
+            None => StdRng::from_entropy(),
+        };
+
+        let synthesis = ModelSynthesis {
+            width,
+            height,
+            rules,
+            grid: initial,
+            rng,
+            block_size: 8,
+        };
+        if !synthesis.is_consistent() {
+            return Err(WfcError::Contradiction);
+        }
+        Ok(synthesis)
+    }
+
+    /// Edge length of the square blocks re-solved by `modify_block` (default 8)
+    pub fn set_block_size(&mut self, block_size: usize) {
+        self.block_size = block_size.max(1);
+    }
+
+    /// The current (always valid) assignment, row-major
+    pub fn grid(&self) -> &[TileId] {
+        &self.grid
+    }
+
+    /// Re-solve the block whose top-left corner is (x, y), keeping everything outside it fixed.
+    /// Returns `false` and leaves the grid untouched if the block has no solution.
+    pub fn modify_block(&mut self, x: usize, y: usize) -> bool {
+        let bw = self.block_size.min(self.width - x.min(self.width));
+        let bh = self.block_size.min(self.height - y.min(self.height));
+        if bw == 0 || bh == 0 {
+            return false;
+        }
+
+        let mut model = match Model::new(bw, bh, self.rules.clone(), Some(self.rng.gen())) {
+            Ok(model) => model,
+            Err(_) => return false,
+        };
+
+        // Constrain block cells that touch fixed cells outside the block
+        for by in 0..bh {
+            for bx in 0..bw {
+                let (gx, gy) = (x + bx, y + by);
+                let mut allowed: Option<HashSet<TileId>> = None;
+                for (dir, outside) in self.outside_neighbors(gx, gy, x, y, bw, bh) {
+                    let fixed = &self.grid[outside];
+                    let fits: HashSet<TileId> = self.rules.get_all_tile_ids()
+                        .into_iter()
+                        .filter(|id| self.rules.is_compatible(id, fixed, dir))
+                        .cloned()
+                        .collect();
+                    allowed = Some(match allowed {
+                        Some(prev) => prev.intersection(&fits).cloned().collect(),
+                        None => fits,
+                    });
+                }
+                if let Some(allowed) = allowed {
+                    if model.restrict_cell(by * bw + bx, &allowed).is_err() {
+                        return false;
+                    }
+                }
+            }
+        }
+
+        match model.run() {
+            Ok(block) => {
+                for by in 0..bh {
+                    for bx in 0..bw {
+                        self.grid[(y + by) * self.width + x + bx] = block[by * bw + bx].clone();
+                    }
+                }
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Sweep overlapping blocks across the grid `passes` times, starting each pass at a
+    /// random offset, and return the final assignment
+    pub fn run(&mut self, passes: usize) -> Vec<TileId> {
+        let stride = (self.block_size / 2).max(1);
+        for _ in 0..passes {
+            let offset_x = self.rng.gen_range(0..stride);
+            let offset_y = self.rng.gen_range(0..stride);
+            for y in block_starts(self.height, stride, offset_y) {
+                for x in block_starts(self.width, stride, offset_x) {
+                    self.modify_block(x, y);
+                }
+            }
+        }
+        self.grid.clone()
+    }
+
+    /// Neighbors of (gx, gy) lying outside the block, with the direction pointing at them
+    fn outside_neighbors(&self, gx: usize, gy: usize, x: usize, y: usize, bw: usize, bh: usize) -> Vec<(Direction, usize)> {
+        let mut out = Vec::new();
+        if gy == y && gy > 0 {
+            out.push((Direction::Up, (gy - 1) * self.width + gx));
+        }
+        if gx == x + bw - 1 && gx + 1 < self.width {
+            out.push((Direction::Right, gy * self.width + gx + 1));
+        }
+        if gy == y + bh - 1 && gy + 1 < self.height {
+            out.push((Direction::Down, (gy + 1) * self.width + gx));
+        }
+        if gx == x && gx > 0 {
+            out.push((Direction::Left, gy * self.width + gx - 1));
+        }
+        out
+    }
+
+    fn is_consistent(&self) -> bool {
+        (0..self.height).all(|y| (0..self.width).all(|x| {
+            let tile = &self.grid[y * self.width + x];
+            let right_ok = x + 1 >= self.width || {
+                let right = &self.grid[y * self.width + x + 1];
+                self.rules.is_compatible(tile, right, Direction::Right)
+            };
+            let down_ok = y + 1 >= self.height || {
+                let below = &self.grid[(y + 1) * self.width + x];
+                self.rules.is_compatible(tile, below, Direction::Down)
+            };
+            right_ok && down_ok
+        }))
+    }
+}
+
+/// Block origins along one axis: every `stride` cells from `offset`, plus 0 so the
+/// leading edge is covered too
+fn block_starts(len: usize, stride: usize, offset: usize) -> Vec<usize> {
+    let mut starts: Vec<usize> = (offset..len).step_by(stride).collect();
+    if offset > 0 {
+        starts.insert(0, 0);
+    }
+    starts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coast_rules() -> RuleSet {
+        let mut rules = RuleSet::new();
+        for (id, weight) in [("water", 3), ("sand", 1), ("grass", 3)] {
+            rules.add_tile(id.to_string(), weight);
+        }
+        for (a, b) in [("water", "water"), ("sand", "sand"), ("grass", "grass"), ("water", "sand"), ("sand", "grass")] {
+            for dir in DIRECTIONS {
+                rules.add_adjacency(a.to_string(), b.to_string(), dir);
+                rules.add_adjacency(b.to_string(), a.to_string(), dir);
+            }
+        }
+        rules
+    }
+
+    #[test]
+    fn test_synthesis_stays_valid_and_varies() {
+        let mut synthesis = ModelSynthesis::new(20, 20, coast_rules(), Some(11)).unwrap();
+        synthesis.set_block_size(6);
+        let grid = synthesis.run(2);
+
+        assert_eq!(grid.len(), 400);
+        assert!(synthesis.is_consistent());
+        let distinct: HashSet<&TileId> = grid.iter().collect();
+        assert!(distinct.len() > 1, "blocks should have been re-solved");
+    }
+
+    #[test]
+    fn test_requires_valid_initial_grid() {
+        let rules = coast_rules();
+        let bad = vec!["water".to_string(), "grass".to_string()];
+        assert!(matches!(ModelSynthesis::with_initial(2, 1, rules.clone(), bad, Some(1)), Err(WfcError::Contradiction)));
+
+        let mut no_ground = RuleSet::new();
+        no_ground.add_tile("a".to_string(), 1);
+        assert!(ModelSynthesis::new(2, 2, no_ground, Some(1)).is_err());
+    }
+}
@@ -0,0 +1,914 @@
+//! Cell connectivity used by the solver.
+//!
+//! A topology numbers its cells `0..cell_count()` and lays them out row-major in a
+//! `dimensions()` rectangle, which is what `Model::result`, wave snapshots and traces use
+//! for indices and coordinates. `neighbors` says which cells touch and in which direction,
+//! expressed in the frame of the cell being asked about.
+//!
+//! New layouts only need to implement `Topology` and be passed to `Model::with_topology`;
+//! the solver itself never assumes a square grid. Networks that come as data rather than
+//! code, such as room graphs or Voronoi regions, can be described as a `CellGraph`.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use crate::Direction;
+use crate::error::WfcError;
+use crate::model::{MAX_CELLS, MAX_DIMENSION};
+
+pub trait Topology: fmt::Debug + Send + Sync {
+    /// Width and height of the row-major layout of cell indices
+    fn dimensions(&self) -> (usize, usize);
+
+    fn cell_count(&self) -> usize {
+        let (width, height) = self.dimensions();
+        width * height
+    }
+
+    /// Cells adjacent to `index`, each with the direction it lies in as seen from `index`
+    fn neighbors(&self, index: usize) -> Vec<(usize, Direction)>;
+
+    /// Cell index at layout position (x, y), if it is inside the layout
+    fn index(&self, x: usize, y: usize) -> Option<usize> {
+        let (width, height) = self.dimensions();
+        (x < width && y < height).then_some(y * width + x)
+    }
+
+    /// Whether the cell is generated at all. The solver never collapses cells outside the
+    /// area, and they should be nobody's neighbor.
+    fn contains(&self, _index: usize) -> bool {
+        true
+    }
+}
+
+/// Plain bounded rectangle; the solver's default topology
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SquareGrid {
+    pub width: usize,
+    pub height: usize,
+}
+
+impl SquareGrid {
+    pub fn new(width: usize, height: usize) -> SquareGrid {
+        SquareGrid { width, height }
+    }
+}
+
+impl Topology for SquareGrid {
+    fn dimensions(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    fn neighbors(&self, index: usize) -> Vec<(usize, Direction)> {
+        let (x, y) = (index % self.width, index / self.width);
+        let mut neighbors = Vec::with_capacity(4);
+
+        if y > 0 {
+            neighbors.push((index - self.width, Direction::Up));
+        }
+        if x < self.width - 1 {
+            neighbors.push((index + 1, Direction::Right));
+        }
+        if y < self.height - 1 {
+            neighbors.push((index + self.width, Direction::Down));
+        }
+        if x > 0 {
+            neighbors.push((index - 1, Direction::Left));
+        }
+
+        neighbors
+    }
+}
+
+/// Rectangle whose opposite edges are joined, so the output tiles seamlessly (a torus).
+/// Either axis can be left open: wrapping only `x` gives a cylinder, like a world map that
+/// continues east-west but ends at the poles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WrappingGrid {
+    pub width: usize,
+    pub height: usize,
+    /// Join the left and right edges
+    pub wrap_x: bool,
+    /// Join the top and bottom edges
+    pub wrap_y: bool,
+}
+
+impl WrappingGrid {
+    pub fn new(width: usize, height: usize) -> WrappingGrid {
+        WrappingGrid::with_axes(width, height, true, true)
+    }
+
+    pub fn with_axes(width: usize, height: usize, wrap_x: bool, wrap_y: bool) -> WrappingGrid {
+        WrappingGrid { width, height, wrap_x, wrap_y }
+    }
+}
+
+impl Topology for WrappingGrid {
+    fn dimensions(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    fn neighbors(&self, index: usize) -> Vec<(usize, Direction)> {
+        let (w, h) = (self.width, self.height);
+        let (x, y) = (index % w, index / w);
+        let mut neighbors = Vec::with_capacity(4);
+        if y > 0 || self.wrap_y {
+            neighbors.push(((y + h - 1) % h * w + x, Direction::Up));
+        }
+        if x + 1 < w || self.wrap_x {
+            neighbors.push((y * w + (x + 1) % w, Direction::Right));
+        }
+        if y + 1 < h || self.wrap_y {
+            neighbors.push(((y + 1) % h * w + x, Direction::Down));
+        }
+        if x > 0 || self.wrap_x {
+            neighbors.push((y * w + (x + w - 1) % w, Direction::Left));
+        }
+        neighbors
+    }
+}
+
+/// A `width` x `height` x `depth` box of cells, indexed `x + y * width + z * width * height`
+/// like `vox::write_vox` expects, so the row-major layout is the layers stacked into a
+/// `width` x `height * depth` strip. Interior cells have six neighbors; layer `z + 1` is
+/// `Forward`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VoxelGrid {
+    pub width: usize,
+    pub height: usize,
+    pub depth: usize,
+}
+
+impl VoxelGrid {
+    pub fn new(width: usize, height: usize, depth: usize) -> VoxelGrid {
+        VoxelGrid { width, height, depth }
+    }
+}
+
+impl Topology for VoxelGrid {
+    fn dimensions(&self) -> (usize, usize) {
+        (self.width, self.height * self.depth)
+    }
+
+    fn neighbors(&self, index: usize) -> Vec<(usize, Direction)> {
+        let layer = self.width * self.height;
+        let (x, y, z) = (index % self.width, index % layer / self.width, index / layer);
+        let mut neighbors = Vec::with_capacity(6);
+        if y > 0 {
+            neighbors.push((index - self.width, Direction::Up));
+        }
+        if x + 1 < self.width {
+            neighbors.push((index + 1, Direction::Right));
+        }
+        if y + 1 < self.height {
+            neighbors.push((index + self.width, Direction::Down));
+        }
+        if x > 0 {
+            neighbors.push((index - 1, Direction::Left));
+        }
+        if z + 1 < self.depth {
+            neighbors.push((index + layer, Direction::Forward));
+        }
+        if z > 0 {
+            neighbors.push((index - layer, Direction::Backward));
+        }
+        neighbors
+    }
+}
+
+/// Pointy-top hexagons in "odd-r" offset rows: odd rows are shifted half a cell right, and
+/// interior cells have six neighbors. Rules stay keyed by `Direction`: the two upper
+/// neighbors are both `Up`, the two lower ones both `Down`, and the row neighbors `Left` and
+/// `Right`, which suits tilesets whose vertical edges look alike.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HexGrid {
+    pub width: usize,
+    pub height: usize,
+}
+
+impl HexGrid {
+    pub fn new(width: usize, height: usize) -> HexGrid {
+        HexGrid { width, height }
+    }
+}
+
+impl Topology for HexGrid {
+    fn dimensions(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    fn neighbors(&self, index: usize) -> Vec<(usize, Direction)> {
+        let (x, y) = ((index % self.width) as isize, (index / self.width) as isize);
+        // Columns of the two diagonal neighbors in the rows above and below
+        let shift = if y % 2 == 0 { [x - 1, x] } else { [x, x + 1] };
+        let candidates = [
+            (shift[0], y - 1, Direction::Up),
+            (shift[1], y - 1, Direction::Up),
+            (x + 1, y, Direction::Right),
+            (shift[1], y + 1, Direction::Down),
+            (shift[0], y + 1, Direction::Down),
+            (x - 1, y, Direction::Left),
+        ];
+        candidates
+            .into_iter()
+            .filter(|&(nx, ny, _)| nx >= 0 && ny >= 0 && nx < self.width as isize && ny < self.height as isize)
+            .map(|(nx, ny, dir)| (ny as usize * self.width + nx as usize, dir))
+            .collect()
+    }
+}
+
+/// Another square-celled topology whose cells also touch their four corner neighbors, for
+/// tilesets such as cliffs and shadows that 4-neighborhood rules cannot express. The
+/// `UpRight` neighbor is the `Right` neighbor of the `Up` one and so on, so wrapping edges
+/// carry over. Rules are needed in the diagonal directions too, unless the ruleset's missing
+/// adjacency is open.
+#[derive(Debug, Clone)]
+pub struct DiagonalGrid {
+    inner: Arc<dyn Topology>,
+}
+
+impl DiagonalGrid {
+    pub fn new(inner: Arc<dyn Topology>) -> DiagonalGrid {
+        DiagonalGrid { inner }
+    }
+}
+
+impl Topology for DiagonalGrid {
+    fn dimensions(&self) -> (usize, usize) {
+        self.inner.dimensions()
+    }
+
+    fn neighbors(&self, index: usize) -> Vec<(usize, Direction)> {
+        let step = |from: usize, direction: Direction| {
+            self.inner.neighbors(from).into_iter().find(|&(_, dir)| dir == direction).map(|(neighbor, _)| neighbor)
+        };
+        let mut neighbors = self.inner.neighbors(index);
+        let corners = [
+            (Direction::Up, Direction::Right, Direction::UpRight),
+            (Direction::Down, Direction::Right, Direction::DownRight),
+            (Direction::Down, Direction::Left, Direction::DownLeft),
+            (Direction::Up, Direction::Left, Direction::UpLeft),
+        ];
+        for (vertical, horizontal, diagonal) in corners {
+            if let Some(corner) = step(index, vertical).and_then(|edge| step(edge, horizontal)) {
+                neighbors.push((corner, diagonal));
+            }
+        }
+        neighbors
+    }
+
+    fn contains(&self, index: usize) -> bool {
+        self.inner.contains(index)
+    }
+}
+
+/// Another topology with some cells switched off, for islands, irregular rooms and other
+/// shapes that are not rectangles. Disabled cells keep their index but touch nothing and
+/// are never collapsed; `Model::result` reports `Model::set_outside_tile`'s tile there.
+#[derive(Debug, Clone)]
+pub struct MaskedGrid {
+    inner: Arc<dyn Topology>,
+    enabled: Vec<bool>,
+}
+
+impl MaskedGrid {
+    /// `enabled` holds one flag per cell of `inner`
+    pub fn new(inner: Arc<dyn Topology>, enabled: Vec<bool>) -> Result<MaskedGrid, WfcError> {
+        if enabled.len() != inner.cell_count() {
+            let (width, height) = inner.dimensions();
+            return Err(WfcError::InvalidDimensions { width, height });
+        }
+        Ok(MaskedGrid { inner, enabled })
+    }
+
+    /// `inner` with the cells at `excluded` switched off
+    pub fn excluding(inner: Arc<dyn Topology>, excluded: &[usize]) -> Result<MaskedGrid, WfcError> {
+        let mut enabled = vec![true; inner.cell_count()];
+        for &index in excluded {
+            match enabled.get_mut(index) {
+                Some(flag) => *flag = false,
+                None => {
+                    let (width, height) = inner.dimensions();
+                    return Err(WfcError::InvalidDimensions { width, height });
+                }
+            }
+        }
+        MaskedGrid::new(inner, enabled)
+    }
+
+    pub fn is_enabled(&self, index: usize) -> bool {
+        self.enabled[index]
+    }
+}
+
+impl Topology for MaskedGrid {
+    fn dimensions(&self) -> (usize, usize) {
+        self.inner.dimensions()
+    }
+
+    fn neighbors(&self, index: usize) -> Vec<(usize, Direction)> {
+        if !self.enabled[index] {
+            return Vec::new();
+        }
+        self.inner
+            .neighbors(index)
+            .into_iter()
+            .filter(|&(n, _)| self.enabled[n])
+            .collect()
+    }
+
+    fn contains(&self, index: usize) -> bool {
+        self.enabled[index]
+    }
+}
+
+/// Arbitrary cells joined by explicit edges, for rooms, road networks and other layouts
+/// that are not grids. Cells are laid out as a single row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GraphTopology {
+    edges: Vec<Vec<(usize, Direction)>>,
+}
+
+impl GraphTopology {
+    pub fn new(cell_count: usize) -> GraphTopology {
+        GraphTopology { edges: vec![Vec::new(); cell_count] }
+    }
+
+    /// Place `b` in `direction` of `a` (and so `a` in the opposite direction of `b`)
+    pub fn connect(&mut self, a: usize, b: usize, direction: Direction) {
+        self.edges[a].push((b, direction));
+        self.edges[b].push((a, direction.opposite()));
+    }
+}
+
+impl Topology for GraphTopology {
+    fn dimensions(&self) -> (usize, usize) {
+        (self.edges.len(), 1)
+    }
+
+    fn neighbors(&self, index: usize) -> Vec<(usize, Direction)> {
+        self.edges[index].clone()
+    }
+}
+
+/// A cell network as data, e.g. the rooms of a dungeon or the regions of a Voronoi map:
+/// named nodes and the labeled edges between them. Cell `i` of a model built from it
+/// (see `Model::with_graph`) is `nodes[i]`, and its rules apply across each edge in the
+/// edge's direction, so a square-grid ruleset works unchanged.
+///
+/// ```json
+/// { "nodes": ["hall", "kitchen", "cellar"],
+///   "edges": [{ "from": "hall", "to": "kitchen", "direction": "Right" },
+///             { "from": "kitchen", "to": "cellar", "direction": "Down" }] }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CellGraph {
+    pub nodes: Vec<String>,
+    pub edges: Vec<GraphEdge>,
+}
+
+/// `to` lies in `direction` of `from`, and so `from` in the opposite direction of `to`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GraphEdge {
+    pub from: String,
+    pub to: String,
+    pub direction: Direction,
+}
+
+impl CellGraph {
+    pub fn from_json(json: &str) -> Result<CellGraph, WfcError> {
+        serde_json::from_str(json).map_err(WfcError::JsonParseError)
+    }
+
+    pub fn to_json_string(&self) -> Result<String, WfcError> {
+        serde_json::to_string(self).map_err(WfcError::JsonParseError)
+    }
+
+    /// Add a node, returning its cell index
+    pub fn add_node(&mut self, name: impl Into<String>) -> usize {
+        self.nodes.push(name.into());
+        self.nodes.len() - 1
+    }
+
+    /// Place `to` in `direction` of `from`
+    pub fn connect(&mut self, from: impl Into<String>, to: impl Into<String>, direction: Direction) {
+        self.edges.push(GraphEdge { from: from.into(), to: to.into(), direction });
+    }
+
+    /// Cell index of the node called `name`
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.nodes.iter().position(|node| node == name)
+    }
+
+    /// The solver's view of the graph. Fails with `InvalidGraph` if a node is listed twice
+    /// or an edge names a node that is not listed.
+    pub fn topology(&self) -> Result<GraphTopology, WfcError> {
+        let mut indices = HashMap::new();
+        for (index, name) in self.nodes.iter().enumerate() {
+            if indices.insert(name.as_str(), index).is_some() {
+                return Err(WfcError::InvalidGraph(format!("duplicate node {}", name)));
+            }
+        }
+        let index = |name: &str| indices.get(name).copied().ok_or_else(|| WfcError::InvalidGraph(format!("unknown node {}", name)));
+        let mut topology = GraphTopology::new(self.nodes.len());
+        for edge in &self.edges {
+            topology.connect(index(&edge.from)?, index(&edge.to)?, edge.direction);
+        }
+        Ok(topology)
+    }
+}
+
+/// Edges of a triangle-grid cell
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TriDirection {
+    Left,
+    Right,
+    /// The horizontal edge: below an up-pointing triangle, above a down-pointing one
+    Base,
+}
+
+impl TriDirection {
+    pub const ALL: [TriDirection; 3] = [TriDirection::Left, TriDirection::Right, TriDirection::Base];
+
+    /// The square-grid direction rules are keyed by for this edge of a triangle.
+    /// `Base` is `Down` for up-pointing triangles and `Up` for down-pointing ones, so a
+    /// pair of triangles sharing a base always sees opposite directions.
+    pub fn to_direction(self, points_up: bool) -> Direction {
+        match self {
+            TriDirection::Left => Direction::Left,
+            TriDirection::Right => Direction::Right,
+            TriDirection::Base if points_up => Direction::Down,
+            TriDirection::Base => Direction::Up,
+        }
+    }
+
+    /// The edge rules in `direction` apply to; `None` for `Forward`/`Backward` and diagonals
+    pub fn from_direction(direction: Direction) -> Option<TriDirection> {
+        match direction {
+            Direction::Left => Some(TriDirection::Left),
+            Direction::Right => Some(TriDirection::Right),
+            Direction::Up | Direction::Down => Some(TriDirection::Base),
+            _ => None,
+        }
+    }
+}
+
+/// Rows of alternating up- and down-pointing triangles; every cell has at most three
+/// neighbors. Cell (x, y) points up when `x + y` is even.
+///
+/// Rules are still keyed by `Direction` (see `TriDirection::to_direction`): a tile allowed
+/// across a `Base` edge in either orientation needs both its `Up` and `Down` adjacency,
+/// which `RuleSet::add_tri_adjacency` records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TriangleGrid {
+    pub width: usize,
+    pub height: usize,
+}
+
+impl TriangleGrid {
+    pub fn new(width: usize, height: usize) -> TriangleGrid {
+        TriangleGrid { width, height }
+    }
+
+    pub fn points_up(&self, index: usize) -> bool {
+        (index % self.width + index / self.width).is_multiple_of(2)
+    }
+
+    /// Neighbors of `index` keyed by triangle edge
+    pub fn tri_neighbors(&self, index: usize) -> Vec<(usize, TriDirection)> {
+        let (x, y) = (index % self.width, index / self.width);
+        let mut neighbors = Vec::with_capacity(3);
+
+        if x > 0 {
+            neighbors.push((index - 1, TriDirection::Left));
+        }
+        if x < self.width - 1 {
+            neighbors.push((index + 1, TriDirection::Right));
+        }
+        if self.points_up(index) {
+            if y < self.height - 1 {
+                neighbors.push((index + self.width, TriDirection::Base));
+            }
+        } else if y > 0 {
+            neighbors.push((index - self.width, TriDirection::Base));
+        }
+
+        neighbors
+    }
+}
+
+impl Topology for TriangleGrid {
+    fn dimensions(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    fn neighbors(&self, index: usize) -> Vec<(usize, Direction)> {
+        let points_up = self.points_up(index);
+        self.tri_neighbors(index)
+            .into_iter()
+            .map(|(neighbor, edge)| (neighbor, edge.to_direction(points_up)))
+            .collect()
+    }
+}
+
+/// Faces of a `CubeMap`, in cell-index order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CubeFace {
+    Front,
+    Right,
+    Back,
+    Left,
+    Top,
+    Bottom,
+}
+
+impl CubeFace {
+    pub const ALL: [CubeFace; 6] = [
+        CubeFace::Front,
+        CubeFace::Right,
+        CubeFace::Back,
+        CubeFace::Left,
+        CubeFace::Top,
+        CubeFace::Bottom,
+    ];
+}
+
+/// Integer 3D vector on the cube lattice
+type Vec3 = [i64; 3];
+
+/// Where a face sits on the lattice: its (0, 0) cell and the unit steps for +x and +y,
+/// oriented so each face reads left-to-right, top-to-bottom when viewed from outside
+struct FaceFrame {
+    origin: Vec3,
+    right: Vec3,
+    down: Vec3,
+}
+
+/// Six `size`x`size` faces stitched into a seamless cube, e.g. for planet surfaces.
+///
+/// Face `f` occupies indices `f * size * size ..`, so the row-major layout is a
+/// `size` x `6 * size` strip of faces stacked in `CubeFace::ALL` order. Every cell has
+/// exactly four neighbors. Across a seam the neighboring face's frame is rotated relative
+/// to this one; directions are always reported in the asking cell's frame, so rules that
+/// are not rotation-symmetric will see the rotation at face edges and corners.
+#[derive(Debug, Clone)]
+pub struct CubeMap {
+    size: usize,
+    neighbors: Vec<[(usize, Direction); 4]>,
+}
+
+impl CubeMap {
+    pub fn new(size: usize) -> Result<CubeMap, WfcError> {
+        if size == 0 || size > MAX_DIMENSION || 6 * size * size > MAX_CELLS {
+            return Err(WfcError::InvalidDimensions { width: size, height: 6 * size });
+        }
+
+        let frames: Vec<FaceFrame> = CubeFace::ALL.iter().map(|&face| face_frame(face, size as i64)).collect();
+        let mut cube = CubeMap { size, neighbors: Vec::with_capacity(6 * size * size) };
+        for (face, frame) in frames.iter().enumerate() {
+            for y in 0..size as i64 {
+                for x in 0..size as i64 {
+                    let point = add(frame.origin, add(scale(frame.right, x), scale(frame.down, y)));
+                    let step = |dir: Direction, offset| (cube.locate(&frames, face, point, offset), dir);
+                    let entry = [
+                        step(Direction::Up, scale(frame.down, -1)),
+                        step(Direction::Right, frame.right),
+                        step(Direction::Down, frame.down),
+                        step(Direction::Left, scale(frame.right, -1)),
+                    ];
+                    cube.neighbors.push(entry);
+                }
+            }
+        }
+        Ok(cube)
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Cell index of (x, y) on `face`
+    pub fn index(&self, face: CubeFace, x: usize, y: usize) -> usize {
+        self.face_offset(face) + y * self.size + x
+    }
+
+    /// Face and in-face coordinates of a cell index
+    pub fn coords(&self, index: usize) -> (CubeFace, usize, usize) {
+        let area = self.size * self.size;
+        let local = index % area;
+        (CubeFace::ALL[index / area], local % self.size, local / self.size)
+    }
+
+    fn face_offset(&self, face: CubeFace) -> usize {
+        let position = CubeFace::ALL.iter().position(|&f| f == face).unwrap_or(0);
+        position * self.size * self.size
+    }
+
+    /// Index of the cell reached by moving from `point` on `face` by `offset`. Stepping off
+    /// a face's edge lands on the lattice plane of the face whose normal is `offset`; pulling
+    /// that point back one unit along the old face's normal puts it on the new face.
+    fn locate(&self, frames: &[FaceFrame], face: usize, point: Vec3, offset: Vec3) -> usize {
+        let n = self.size as i64;
+        let moved = add(point, offset);
+        let in_face = |frame: &FaceFrame, p: Vec3| {
+            let rel = sub(p, frame.origin);
+            let (x, y) = (dot(rel, frame.right), dot(rel, frame.down));
+            let on_plane = dot(rel, normal(frame)) == 0;
+            (on_plane && (0..n).contains(&x) && (0..n).contains(&y)).then_some((x as usize, y as usize))
+        };
+
+        if let Some((x, y)) = in_face(&frames[face], moved) {
+            return face * self.size * self.size + y * self.size + x;
+        }
+        let pulled = sub(moved, outward(&frames[face], n));
+        frames
+            .iter()
+            .enumerate()
+            .find_map(|(f, frame)| in_face(frame, pulled).map(|(x, y)| f * self.size * self.size + y * self.size + x))
+            .expect("cube lattice step must land on a face")
+    }
+}
+
+impl Topology for CubeMap {
+    fn dimensions(&self) -> (usize, usize) {
+        (self.size, 6 * self.size)
+    }
+
+    fn neighbors(&self, index: usize) -> Vec<(usize, Direction)> {
+        self.neighbors[index].to_vec()
+    }
+}
+
+/// Faces lie on the planes -1 and `n` of an `n`-wide lattice cube, so cells on different
+/// faces never share a lattice point
+fn face_frame(face: CubeFace, n: i64) -> FaceFrame {
+    let (origin, right, down) = match face {
+        CubeFace::Front => ([0, 0, n], [1, 0, 0], [0, 1, 0]),
+        CubeFace::Right => ([n, 0, n - 1], [0, 0, -1], [0, 1, 0]),
+        CubeFace::Back => ([n - 1, 0, -1], [-1, 0, 0], [0, 1, 0]),
+        CubeFace::Left => ([-1, 0, 0], [0, 0, 1], [0, 1, 0]),
+        CubeFace::Top => ([0, -1, 0], [1, 0, 0], [0, 0, 1]),
+        CubeFace::Bottom => ([0, n, n - 1], [1, 0, 0], [0, 0, -1]),
+    };
+    FaceFrame { origin, right, down }
+}
+
+/// Unit normal of a face's plane (sign irrelevant, only used for the on-plane test)
+fn normal(frame: &FaceFrame) -> Vec3 {
+    let [a, b, c] = frame.right;
+    let [d, e, f] = frame.down;
+    [b * f - c * e, c * d - a * f, a * e - b * d]
+}
+
+/// Unit vector pointing out of the cube from a face
+fn outward(frame: &FaceFrame, n: i64) -> Vec3 {
+    let normal = normal(frame);
+    let axis = normal.iter().position(|&v| v != 0).unwrap_or(0);
+    let mut out = [0; 3];
+    out[axis] = if frame.origin[axis] == n { 1 } else { -1 };
+    out
+}
+
+fn add(a: Vec3, b: Vec3) -> Vec3 {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+fn sub(a: Vec3, b: Vec3) -> Vec3 {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn scale(a: Vec3, k: i64) -> Vec3 {
+    [a[0] * k, a[1] * k, a[2] * k]
+}
+
+fn dot(a: Vec3, b: Vec3) -> i64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Model;
+    use crate::ruleset::RuleSet;
+
+    #[test]
+    fn test_square_grid_neighbors() {
+        let grid = SquareGrid::new(3, 2);
+        assert_eq!(grid.cell_count(), 6);
+        assert_eq!(grid.neighbors(0), vec![(1, Direction::Right), (3, Direction::Down)]);
+        assert_eq!(grid.neighbors(4).len(), 3);
+    }
+
+    fn assert_mutual(topology: &dyn Topology) {
+        for index in 0..topology.cell_count() {
+            for (other, dir) in topology.neighbors(index) {
+                assert!(
+                    topology.neighbors(other).contains(&(index, dir.opposite())),
+                    "{:?}: {} -> {} ({:?}) not mutual", topology, index, other, dir
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_wrapping_hex_masked_and_graph_neighbors() {
+        let torus = WrappingGrid::new(4, 3);
+        assert_eq!(torus.neighbors(0), vec![(8, Direction::Up), (1, Direction::Right), (4, Direction::Down), (3, Direction::Left)]);
+        assert_mutual(&torus);
+
+        let cylinder = WrappingGrid::with_axes(4, 3, true, false);
+        assert_eq!(cylinder.neighbors(0), vec![(1, Direction::Right), (4, Direction::Down), (3, Direction::Left)]);
+        assert_eq!(cylinder.neighbors(11), vec![(7, Direction::Up), (8, Direction::Right), (10, Direction::Left)]);
+        assert_mutual(&cylinder);
+        assert_eq!(WrappingGrid::with_axes(4, 3, false, false).neighbors(5), SquareGrid::new(4, 3).neighbors(5));
+
+        let hex = HexGrid::new(5, 4);
+        assert_eq!(hex.neighbors(7).len(), 6);
+        assert_eq!(hex.neighbors(0).len(), 2);
+        assert_mutual(&hex);
+
+        let mut enabled = vec![true; 9];
+        enabled[4] = false;
+        let masked = MaskedGrid::new(Arc::new(SquareGrid::new(3, 3)), enabled).unwrap();
+        assert!(masked.neighbors(4).is_empty());
+        assert_eq!(masked.neighbors(1), vec![(2, Direction::Right), (0, Direction::Left)]);
+        assert_mutual(&masked);
+        assert!(MaskedGrid::new(Arc::new(SquareGrid::new(3, 3)), vec![true; 8]).is_err());
+        let excluding = MaskedGrid::excluding(Arc::new(SquareGrid::new(3, 3)), &[4]).unwrap();
+        assert!(!excluding.contains(4) && excluding.contains(3));
+        assert_eq!(excluding.neighbors(1), masked.neighbors(1));
+        assert!(MaskedGrid::excluding(Arc::new(SquareGrid::new(3, 3)), &[9]).is_err());
+
+        let mut graph = GraphTopology::new(3);
+        graph.connect(0, 1, Direction::Right);
+        graph.connect(1, 2, Direction::Down);
+        assert_eq!(graph.dimensions(), (3, 1));
+        assert_eq!(graph.neighbors(1), vec![(0, Direction::Left), (2, Direction::Down)]);
+        assert_mutual(&graph);
+        assert_eq!(graph.index(2, 0), Some(2));
+        assert_eq!(graph.index(0, 1), None);
+
+        let diagonal = DiagonalGrid::new(Arc::new(SquareGrid::new(3, 3)));
+        assert_eq!(diagonal.neighbors(4).len(), 8);
+        assert_eq!(diagonal.neighbors(0), vec![(1, Direction::Right), (3, Direction::Down), (4, Direction::DownRight)]);
+        assert_mutual(&diagonal);
+        let diagonal_torus = DiagonalGrid::new(Arc::new(WrappingGrid::new(4, 3)));
+        assert!(diagonal_torus.neighbors(0).contains(&(11, Direction::UpLeft)));
+        assert_mutual(&diagonal_torus);
+
+        let voxels = VoxelGrid::new(3, 3, 3);
+        assert_eq!(voxels.cell_count(), 27);
+        assert_eq!(voxels.neighbors(13).len(), 6);
+        assert_eq!(voxels.neighbors(0), vec![(1, Direction::Right), (3, Direction::Down), (9, Direction::Forward)]);
+        assert_mutual(&voxels);
+    }
+
+    #[test]
+    fn test_model_solves_on_wrapping_and_hex_grids() {
+        let mut rules = RuleSet::new();
+        for id in ["a", "b", "c"] {
+            rules.add_tile(id.to_string(), 1);
+        }
+        // Each tile may only touch the next one along, cyclically, and itself
+        for (x, y) in [("a", "a"), ("b", "b"), ("c", "c"), ("a", "b"), ("b", "c"), ("c", "a")] {
+            for dir in [Direction::Up, Direction::Right, Direction::Down, Direction::Left] {
+                rules.add_adjacency_symmetric(x.to_string(), y.to_string(), dir);
+            }
+        }
+        let topologies: [Arc<dyn Topology>; 2] = [Arc::new(WrappingGrid::new(6, 6)), Arc::new(HexGrid::new(6, 6))];
+        for topology in topologies {
+            let mut model = Model::with_topology(topology.clone(), rules.clone(), Some(5)).unwrap();
+            let result = model.run().unwrap();
+            for index in 0..topology.cell_count() {
+                for (other, dir) in topology.neighbors(index) {
+                    assert!(rules.is_compatible(&result[index], &result[other], dir), "{:?}", topology);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_model_solves_on_cell_graph() {
+        // A ring of five rooms with a cellar under the first
+        let json = r#"{
+            "nodes": ["hall", "study", "kitchen", "pantry", "porch", "cellar"],
+            "edges": [
+                { "from": "hall", "to": "study", "direction": "Right" },
+                { "from": "study", "to": "kitchen", "direction": "Right" },
+                { "from": "kitchen", "to": "pantry", "direction": "Right" },
+                { "from": "pantry", "to": "porch", "direction": "Right" },
+                { "from": "porch", "to": "hall", "direction": "Right" },
+                { "from": "hall", "to": "cellar", "direction": "Down" }
+            ]
+        }"#;
+        let graph = CellGraph::from_json(json).unwrap();
+        assert_eq!(CellGraph::from_json(&graph.to_json_string().unwrap()).unwrap(), graph);
+        let topology = graph.topology().unwrap();
+        assert_eq!(topology.neighbors(graph.index_of("hall").unwrap()).len(), 3);
+        assert_mutual(&topology);
+
+        let rules = crate::bench::Workload::large_grid(1).rules;
+        let mut model = Model::with_graph(&graph, rules.clone(), Some(2)).unwrap();
+        model.force(5, 0, "water".to_string()).unwrap();
+        let result = model.run().unwrap();
+        assert_eq!(result.len(), 6);
+        for edge in &graph.edges {
+            let (from, to) = (graph.index_of(&edge.from).unwrap(), graph.index_of(&edge.to).unwrap());
+            assert!(rules.is_compatible(&result[from], &result[to], edge.direction));
+        }
+        assert_ne!(result[0], "grass");
+
+        let mut broken = graph.clone();
+        broken.connect("hall", "attic", Direction::Up);
+        assert!(matches!(broken.topology(), Err(WfcError::InvalidGraph(_))));
+        broken.add_node("attic");
+        assert!(broken.topology().is_ok());
+        broken.add_node("hall");
+        assert!(matches!(Model::with_graph(&broken, rules, None), Err(WfcError::InvalidGraph(_))));
+    }
+
+    #[test]
+    fn test_triangle_grid_neighbors() {
+        let grid = TriangleGrid::new(4, 2);
+        // (0, 0) points up: right neighbor and the down-pointing triangle below
+        assert!(grid.points_up(0));
+        assert_eq!(grid.tri_neighbors(0), vec![(1, TriDirection::Right), (4, TriDirection::Base)]);
+        // (1, 0) points down and has no base neighbor on the top row
+        assert_eq!(grid.tri_neighbors(1).len(), 2);
+        assert_eq!(grid.neighbors(4), vec![(5, Direction::Right), (0, Direction::Up)]);
+        for index in 0..grid.cell_count() {
+            assert!(grid.neighbors(index).len() <= 3);
+        }
+    }
+
+    #[test]
+    fn test_model_solves_on_triangles() {
+        let mut rules = RuleSet::new();
+        rules.add_tile("a".to_string(), 1);
+        rules.add_tile("b".to_string(), 1);
+        rules.add_tri_adjacency("a".to_string(), "b".to_string(), TriDirection::Base);
+        rules.add_tri_adjacency("b".to_string(), "a".to_string(), TriDirection::Base);
+        for edge in [TriDirection::Left, TriDirection::Right] {
+            rules.add_tri_adjacency("a".to_string(), "a".to_string(), edge);
+            rules.add_tri_adjacency("b".to_string(), "b".to_string(), edge);
+        }
+
+        let grid = TriangleGrid::new(6, 4);
+        let mut model = Model::with_topology(std::sync::Arc::new(grid), rules, Some(1)).unwrap();
+        let result = model.run().unwrap();
+        // Rows alternate tiles because every base edge joins a and b
+        for index in 0..grid.cell_count() {
+            for (other, edge) in grid.tri_neighbors(index) {
+                assert_eq!(result[index] == result[other], edge != TriDirection::Base);
+            }
+        }
+    }
+
+    #[test]
+    fn test_cube_map_is_closed_and_symmetric() {
+        let cube = CubeMap::new(3).unwrap();
+        assert_eq!(cube.cell_count(), 54);
+        for index in 0..cube.cell_count() {
+            let neighbors = cube.neighbors(index);
+            assert_eq!(neighbors.len(), 4);
+            for (other, _) in neighbors {
+                assert_ne!(other, index);
+                assert!(cube.neighbors(other).iter().any(|&(back, _)| back == index), "{} -> {} not mutual", index, other);
+            }
+        }
+    }
+
+    #[test]
+    fn test_cube_map_seams() {
+        let cube = CubeMap::new(2).unwrap();
+        // Front's right edge meets Right's left edge in the same row
+        let front = cube.index(CubeFace::Front, 1, 0);
+        assert!(cube.neighbors(front).contains(&(cube.index(CubeFace::Right, 0, 0), Direction::Right)));
+        // Front's top edge meets Top's bottom edge
+        let front_top = cube.index(CubeFace::Front, 0, 0);
+        assert!(cube.neighbors(front_top).contains(&(cube.index(CubeFace::Top, 0, 1), Direction::Up)));
+        // Back wraps around to Left
+        let back = cube.index(CubeFace::Back, 1, 1);
+        assert!(cube.neighbors(back).contains(&(cube.index(CubeFace::Left, 0, 1), Direction::Right)));
+        assert_eq!(cube.coords(back), (CubeFace::Back, 1, 1));
+        assert!(CubeMap::new(0).is_err());
+    }
+
+    #[test]
+    fn test_model_solves_on_cube() {
+        let mut rules = RuleSet::new();
+        rules.add_tile("land".to_string(), 1);
+        rules.add_tile("sea".to_string(), 1);
+        for dir in [Direction::Up, Direction::Right, Direction::Down, Direction::Left] {
+            for (a, b) in [("land", "land"), ("sea", "sea"), ("land", "sea"), ("sea", "land")] {
+                rules.add_adjacency(a.to_string(), b.to_string(), dir);
+            }
+        }
+
+        let cube = CubeMap::new(4).unwrap();
+        let mut model = Model::with_topology(std::sync::Arc::new(cube), rules, Some(3)).unwrap();
+        assert_eq!((model.width(), model.height()), (4, 24));
+        assert_eq!(model.run().unwrap().len(), 96);
+    }
+}
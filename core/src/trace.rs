@@ -0,0 +1,289 @@
+//! Machine-readable solver trace.
+//!
+//! A trace is a JSON-lines stream: one `TraceEvent` object per line, tagged by `"type"`.
+//! The first line is always a `start` header carrying `schema` and `version`; consumers
+//! should reject versions they do not understand. Within a version, fields are only ever
+//! added, never renamed or removed.
+//!
+//! ```text
+//! {"type":"start","schema":"wfc-trace","version":1,"width":2,"height":1,"seed":7,"tiles":["a","b"]}
+//! {"type":"observe","step":1,"cell":0,"x":0,"y":0,"tile":"a"}
+//! {"type":"ban","step":1,"cell":1,"x":1,"y":0,"tile":"a"}
+//! {"type":"finish","step":2,"success":true}
+//! ```
+//!
+//! Full traces of large grids run to millions of lines, so a `TraceFilter` can thin them
+//! out per event type. The header then lists the sampling in effect.
+//!
+//! A complete trace pins down every decision of a run, so `decision_hash` condenses one
+//! into a single value that native and wasm builds can compare.
+
+use std::fmt;
+use std::io::{BufRead, Write};
+use std::sync::{Arc, Mutex};
+use serde::{Deserialize, Serialize};
+use crate::{TileId, TileKey};
+use crate::error::WfcError;
+use crate::model::Model;
+
+pub const TRACE_SCHEMA: &str = "wfc-trace";
+pub const TRACE_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TraceEvent {
+    /// Header line describing the run
+    Start {
+        schema: String,
+        version: u32,
+        width: usize,
+        height: usize,
+        seed: Option<u64>,
+        tiles: Vec<TileId>,
+        /// Present when the trace was thinned out by a `TraceFilter`
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        sampling: Option<TraceFilter>,
+    },
+    /// A cell was collapsed to `tile`
+    Observe { step: u64, cell: usize, x: usize, y: usize, tile: TileId },
+    /// `tile` was removed from a cell's possibilities by propagation
+    Ban { step: u64, cell: usize, x: usize, y: usize, tile: TileId },
+    /// The decision to place `tile` in a cell was undone
+    Backtrack { step: u64, cell: usize, x: usize, y: usize, tile: TileId },
+    /// The run ended, either fully collapsed or with an unrecoverable contradiction
+    Finish { step: u64, success: bool },
+}
+
+/// Which events a trace keeps: every `n`th event of each type, counted separately per
+/// type, where 0 drops the type entirely. `start` and `finish` lines are always written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceFilter {
+    pub observe: u32,
+    pub ban: u32,
+    pub backtrack: u32,
+}
+
+impl Default for TraceFilter {
+    fn default() -> Self {
+        TraceFilter { observe: 1, ban: 1, backtrack: 1 }
+    }
+}
+
+impl TraceFilter {
+    /// Only `observe` events, enough to replay the order cells were collapsed in
+    pub fn observations_only() -> TraceFilter {
+        TraceFilter { observe: 1, ban: 0, backtrack: 0 }
+    }
+}
+
+/// Destination for trace lines. Cloning a model does not clone its trace; the clone starts untraced.
+#[derive(Default)]
+pub struct Trace {
+    out: Option<Box<dyn Write + Send>>,
+    filter: TraceFilter,
+    /// Events seen so far of each filtered type: observe, ban, backtrack
+    seen: [u64; 3],
+}
+
+impl Trace {
+    pub fn new<W: Write + Send + 'static>(writer: W) -> Trace {
+        Trace { out: Some(Box::new(writer)), ..Trace::default() }
+    }
+
+    /// Thin the trace out as described by `filter`. Set this before handing the trace to
+    /// `Model::set_trace` so the header records it.
+    pub fn set_filter(&mut self, filter: TraceFilter) {
+        self.filter = filter;
+    }
+
+    /// The filter in effect, unless every event is kept
+    pub fn sampling(&self) -> Option<TraceFilter> {
+        Some(self.filter).filter(|filter| *filter != TraceFilter::default())
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.out.is_some()
+    }
+
+    /// Whether `ban` events are recorded at all, so callers can skip preparing them
+    pub(crate) fn wants_bans(&self) -> bool {
+        self.is_enabled() && self.filter.ban > 0
+    }
+
+    /// Write one event line, if the filter keeps it. Tracing stops after the first I/O
+    /// failure rather than interrupting generation.
+    pub(crate) fn record(&mut self, event: &TraceEvent) {
+        let sampled = match event {
+            TraceEvent::Observe { .. } => Some((0, self.filter.observe)),
+            TraceEvent::Ban { .. } => Some((1, self.filter.ban)),
+            TraceEvent::Backtrack { .. } => Some((2, self.filter.backtrack)),
+            TraceEvent::Start { .. } | TraceEvent::Finish { .. } => None,
+        };
+        if let Some((kind, every)) = sampled {
+            let seen = self.seen[kind];
+            self.seen[kind] += 1;
+            if every == 0 || !seen.is_multiple_of(every as u64) {
+                return;
+            }
+        }
+        if let Some(out) = &mut self.out {
+            let ok = serde_json::to_writer(&mut *out, event).is_ok() && out.write_all(b"\n").is_ok();
+            let flushed = !matches!(event, TraceEvent::Finish { .. }) || out.flush().is_ok();
+            if !ok || !flushed {
+                self.out = None;
+            }
+        }
+    }
+}
+
+impl Clone for Trace {
+    fn clone(&self) -> Self {
+        Trace::default()
+    }
+}
+
+impl fmt::Debug for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Trace").field("enabled", &self.is_enabled()).finish()
+    }
+}
+
+/// Run `model` to completion with a full trace and return the FNV-1a hash of the trace
+/// bytes as 16 hex digits. Two builds that produce the same hash for the same ruleset and
+/// seed made exactly the same observations, bans and backtracks. A run that fails with a
+/// contradiction or backtrack limit still hashes; its trace ends with `"success":false`.
+/// Replaces any trace already set on `model`.
+pub fn decision_hash<T: TileKey>(model: &mut Model<T>) -> Result<String, WfcError> {
+    let hasher = TraceHasher::default();
+    model.set_trace(Trace::new(hasher.clone()));
+    match model.run() {
+        Ok(_) | Err(WfcError::Contradiction | WfcError::BacktrackLimitExceeded { .. }) => {}
+        Err(e) => return Err(e),
+    }
+    model.set_trace(Trace::default());
+    let hash = *hasher.0.lock().unwrap_or_else(|e| e.into_inner());
+    Ok(format!("{:016x}", hash))
+}
+
+/// Writer folding everything written to it into a running FNV-1a hash
+#[derive(Clone)]
+struct TraceHasher(Arc<Mutex<u64>>);
+
+impl Default for TraceHasher {
+    fn default() -> Self {
+        TraceHasher(Arc::new(Mutex::new(0xcbf29ce484222325)))
+    }
+}
+
+impl Write for TraceHasher {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let mut hash = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        *hash = buf.iter().fold(*hash, |h, b| (h ^ *b as u64).wrapping_mul(0x100000001b3));
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Parse a JSON-lines trace, checking the header's schema and version
+pub fn parse_trace<R: BufRead>(reader: R) -> Result<Vec<TraceEvent>, WfcError> {
+    let mut events = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        events.push(serde_json::from_str::<TraceEvent>(&line)?);
+    }
+
+    match events.first() {
+        Some(TraceEvent::Start { schema, version, .. }) if schema == TRACE_SCHEMA && *version <= TRACE_VERSION => Ok(events),
+        Some(TraceEvent::Start { schema, version, .. }) => Err(WfcError::InvalidTrace(format!(
+            "unsupported trace {} v{}", schema, version
+        ))),
+        _ => Err(WfcError::InvalidTrace("missing start header".to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use crate::Direction;
+    use crate::model::Model;
+    use crate::ruleset::RuleSet;
+
+    /// Shared in-memory writer so the test can read back what the model wrote
+    #[derive(Clone, Default)]
+    struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_trace_records_run() {
+        let mut rules = RuleSet::new();
+        rules.add_tile("a".to_string(), 1);
+        rules.add_tile("b".to_string(), 1);
+        rules.add_adjacency("a".to_string(), "b".to_string(), Direction::Right);
+        rules.add_adjacency("b".to_string(), "a".to_string(), Direction::Left);
+
+        let buf = SharedBuf::default();
+        let mut model = Model::new(2, 1, rules, Some(7)).unwrap();
+        model.set_trace(Trace::new(buf.clone()));
+        model.run().unwrap();
+
+        let bytes = buf.0.lock().unwrap().clone();
+        let events = parse_trace(bytes.as_slice()).unwrap();
+        assert!(matches!(&events[0], TraceEvent::Start { version: TRACE_VERSION, seed: Some(7), .. }));
+        assert!(events.iter().any(|e| matches!(e, TraceEvent::Observe { .. })));
+        assert!(events.iter().any(|e| matches!(e, TraceEvent::Ban { .. })));
+        assert!(matches!(events.last(), Some(TraceEvent::Finish { success: true, .. })));
+    }
+
+    #[test]
+    fn test_trace_filter_samples_events() {
+        let rules = crate::bench::Workload::large_grid(12).rules;
+        let run = |filter: TraceFilter| {
+            let buf = SharedBuf::default();
+            let mut trace = Trace::new(buf.clone());
+            trace.set_filter(filter);
+            let mut model = Model::new(12, 12, rules.clone(), Some(3)).unwrap();
+            model.set_trace(trace);
+            model.run().unwrap();
+            let bytes = buf.0.lock().unwrap().clone();
+            parse_trace(bytes.as_slice()).unwrap()
+        };
+        let count = |events: &[TraceEvent], ban: bool| {
+            events.iter().filter(|e| if ban { matches!(e, TraceEvent::Ban { .. }) } else { matches!(e, TraceEvent::Observe { .. }) }).count()
+        };
+
+        let full = run(TraceFilter::default());
+        assert!(matches!(&full[0], TraceEvent::Start { sampling: None, .. }));
+
+        let observed = run(TraceFilter::observations_only());
+        assert!(matches!(&observed[0], TraceEvent::Start { sampling: Some(_), .. }));
+        assert_eq!(count(&observed, false), count(&full, false));
+        assert_eq!(count(&observed, true), 0);
+        assert!(matches!(observed.last(), Some(TraceEvent::Finish { success: true, .. })));
+
+        let sparse = run(TraceFilter { observe: 1, ban: 10, backtrack: 1 });
+        assert_eq!(count(&sparse, true), count(&full, true).div_ceil(10));
+    }
+
+    #[test]
+    fn test_parse_rejects_unknown_version() {
+        let trace = r#"{"type":"start","schema":"wfc-trace","version":99,"width":1,"height":1,"seed":null,"tiles":[]}"#;
+        assert!(matches!(parse_trace(trace.as_bytes()), Err(WfcError::InvalidTrace(_))));
+        assert!(parse_trace(r#"{"type":"finish","step":0,"success":true}"#.as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,179 @@
+//! Soft weight biases applied when a cell collapses.
+//!
+//! Adjacency rules only say whether two tiles may touch. Markov-style transition weights
+//! additionally bias *how likely* a tile is given what has already been placed next to it,
+//! which is how gradients such as deep water -> shallows -> beach come out smooth instead of
+//! noisy. Usage decay does the opposite for repetition, making a tile less likely right
+//! next to where it was just used, while cluster bonuses grow coherent blobs of a tile or tag.
+//! Weight gradients vary a tile's weight across the map, e.g. snow fading out to the south.
+
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use crate::{Direction, TileId, TileKey};
+use crate::ruleset::RuleSet;
+
+/// Multipliers applied to a tile's weight for each already-collapsed neighbor.
+/// Pairs without an entry use a factor of 1.
+#[derive(Debug, Clone)]
+pub struct TransitionWeights<T = TileId> {
+    factors: HashMap<(T, Direction, T), f64>,
+}
+
+impl<T: TileKey> PartialEq for TransitionWeights<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.factors == other.factors
+    }
+}
+
+impl<T> Default for TransitionWeights<T> {
+    fn default() -> Self {
+        TransitionWeights { factors: HashMap::new() }
+    }
+}
+
+impl<T: TileKey> TransitionWeights<T> {
+    pub fn new() -> TransitionWeights<T> {
+        TransitionWeights::default()
+    }
+
+    /// Scale the weight of `to` by `factor` whenever it is placed in `direction` of a
+    /// collapsed `from`. A factor of 0 forbids the transition without touching the rules.
+    pub fn set(&mut self, from: T, direction: Direction, to: T, factor: f64) {
+        self.factors.insert((from, direction, to), factor.max(0.0));
+    }
+
+    /// `set` in every direction
+    pub fn set_all_directions(&mut self, from: T, to: T, factor: f64) {
+        for direction in [Direction::Up, Direction::Right, Direction::Down, Direction::Left] {
+            self.set(from.clone(), direction, to.clone(), factor);
+        }
+    }
+
+    /// The factor set for `(from, direction, to)`, if any
+    pub fn get(&self, from: &T, direction: Direction, to: &T) -> Option<f64> {
+        if self.factors.is_empty() {
+            return None;
+        }
+        self.factors.get(&(from.clone(), direction, to.clone())).copied()
+    }
+
+    pub fn factor(&self, from: &T, direction: Direction, to: &T) -> f64 {
+        // Lookups run once per candidate per neighbor, so `get` avoids cloning for the
+        // common miss
+        self.get(from, direction, to).unwrap_or(1.0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.factors.is_empty()
+    }
+
+    /// Every `(from, direction, to)` factor that has been set, sorted
+    pub fn entries(&self) -> Vec<(&T, Direction, &T, f64)> {
+        let mut entries: Vec<_> = self.factors
+            .iter()
+            .map(|((from, dir, to), &factor)| (from, *dir, to, factor))
+            .collect();
+        entries.sort_by(|a, b| (a.0, a.1 as u8, a.2).cmp(&(b.0, b.1 as u8, b.2)));
+        entries
+    }
+}
+
+/// Temporarily lower a tile's weight around cells that already hold it. A placement at
+/// distance 1 scales the weight by `1 - strength`; the penalty fades linearly and is gone
+/// beyond `radius` (Manhattan distance on the grid layout). Multiple placements stack.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct UsageDecay {
+    /// Fraction of the weight removed by an adjacent placement, in 0..=1
+    pub strength: f64,
+    pub radius: usize,
+}
+
+impl Default for UsageDecay {
+    fn default() -> Self {
+        UsageDecay { strength: 0.5, radius: 3 }
+    }
+}
+
+impl UsageDecay {
+    /// Weight multiplier for one placement `distance` cells away
+    pub fn factor(&self, distance: usize) -> f64 {
+        if distance == 0 || distance > self.radius {
+            return 1.0;
+        }
+        let closeness = (self.radius + 1 - distance) as f64 / self.radius as f64;
+        1.0 - self.strength.clamp(0.0, 1.0) * closeness
+    }
+}
+
+/// Weight multipliers for placing a tile next to collapsed neighbors of the same group, so
+/// forests and lakes come out as blobs instead of speckle. Keys are tile IDs or tags (see
+/// `TileInfo::tags`); a factor above 1 encourages clustering, below 1 discourages it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ClusterBonus {
+    factors: HashMap<String, f64>,
+}
+
+impl ClusterBonus {
+    pub fn new() -> ClusterBonus {
+        ClusterBonus::default()
+    }
+
+    /// Multiply a tile's weight by `factor` for each orthogonal neighbor that shares `key`
+    pub fn set(&mut self, key: impl Into<String>, factor: f64) {
+        self.factors.insert(key.into(), factor.max(0.0));
+    }
+
+    /// Multiplier for placing `tile` next to `neighbor`. When they share several keys the
+    /// strongest factor wins, so a tile tagged `forest` is not boosted twice for one neighbor.
+    pub fn factor<T: TileKey>(&self, rules: &RuleSet<T>, tile: &T, neighbor: &T) -> f64 {
+        let (Some(tile), Some(neighbor)) = (rules.get_tile_info(tile), rules.get_tile_info(neighbor)) else {
+            return 1.0;
+        };
+        self.factors
+            .iter()
+            .filter(|(key, _)| tile.matches(key) && neighbor.matches(key))
+            .map(|(_, &factor)| factor)
+            .reduce(f64::max)
+            .unwrap_or(1.0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.factors.is_empty()
+    }
+
+    /// Key/factor pairs that have been set
+    pub fn entries(&self) -> impl Iterator<Item = (&str, f64)> {
+        self.factors.iter().map(|(key, &factor)| (key.as_str(), factor))
+    }
+}
+
+/// Axis of the grid layout: `X` runs left to right, `Y` top to bottom
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Axis {
+    X,
+    Y,
+}
+
+/// Replace the weight of the tiles matching `key` (an ID or tag) with one interpolated
+/// linearly along `axis`, from `start` in the first row/column to `end` in the last. Where
+/// the weight is 0 the tile is ruled out as with a zero transition factor, so a cell left
+/// with only zero-weight options contradicts.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WeightGradient {
+    pub key: String,
+    pub axis: Axis,
+    pub start: f64,
+    pub end: f64,
+}
+
+impl WeightGradient {
+    /// Weight at `(x, y)` of a `width`x`height` grid
+    pub fn weight_at(&self, x: usize, y: usize, width: usize, height: usize) -> f64 {
+        let (position, extent) = match self.axis {
+            Axis::X => (x, width),
+            Axis::Y => (y, height),
+        };
+        let t = if extent > 1 { position as f64 / (extent - 1) as f64 } else { 0.0 };
+        (self.start + (self.end - self.start) * t).max(0.0)
+    }
+}
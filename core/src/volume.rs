@@ -0,0 +1,148 @@
+//! Volumes of voxels.
+//!
+//! `Model3D` runs the regular solver over a `VoxelGrid`, where every cell also has a
+//! `Forward` (`z + 1`) and `Backward` (`z - 1`) neighbor. Rulesets need no separate format:
+//! vertical adjacencies are ordinary rules with `"direction": "Forward"` or `"Backward"`,
+//! and a ruleset without any of them lets every layer be solved on its own: `Model3D` then
+//! allows any tile above or below any other. Results are indexed
+//! `x + y * width + z * width * height`, so they can be handed to `vox::write_vox` as is.
+
+use std::sync::Arc;
+use crate::{Direction, TileId, TileKey};
+use crate::error::WfcError;
+use crate::model::Model;
+use crate::ruleset::RuleSet;
+use crate::topology::VoxelGrid;
+
+pub struct Model3D<T: TileKey = TileId> {
+    model: Model<T>,
+    width: usize,
+    height: usize,
+    depth: usize,
+}
+
+impl<T: TileKey> Model3D<T> {
+    /// A ruleset with no `Forward` or `Backward` rules at all gets every vertical pair
+    /// allowed, so its layers are independent even under closed missing adjacency.
+    pub fn new(width: usize, height: usize, depth: usize, mut rules: RuleSet<T>, seed: Option<u64>) -> Result<Model3D<T>, WfcError> {
+        if width == 0 || height == 0 || depth == 0 {
+            return Err(WfcError::InvalidDimensions { width, height: height * depth });
+        }
+        let vertical = |direction: &Direction| matches!(direction, Direction::Forward | Direction::Backward);
+        if !rules.adjacency.keys().any(|(_, direction)| vertical(direction)) {
+            let ids: Vec<T> = rules.get_all_tile_ids().into_iter().cloned().collect();
+            for from in &ids {
+                for to in &ids {
+                    rules.add_adjacency(from.clone(), to.clone(), Direction::Forward);
+                    rules.add_adjacency(from.clone(), to.clone(), Direction::Backward);
+                }
+            }
+        }
+        let model = Model::with_topology(Arc::new(VoxelGrid::new(width, height, depth)), rules, seed)?;
+        Ok(Model3D { model, width, height, depth })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// The underlying solver, whose grid is the layers stacked into `width` x `height * depth`
+    pub fn model(&self) -> &Model<T> {
+        &self.model
+    }
+
+    pub fn model_mut(&mut self) -> &mut Model<T> {
+        &mut self.model
+    }
+
+    pub fn index(&self, x: usize, y: usize, z: usize) -> usize {
+        x + y * self.width + z * self.width * self.height
+    }
+
+    pub fn coords(&self, index: usize) -> (usize, usize, usize) {
+        let layer = self.width * self.height;
+        (index % self.width, index % layer / self.width, index / layer)
+    }
+
+    /// Solve the volume, returning one tile per voxel
+    pub fn run(&mut self) -> Result<Vec<T>, WfcError> {
+        self.model.run()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Direction;
+    use crate::topology::Topology;
+
+    #[test]
+    fn test_model3d_respects_vertical_rules() {
+        // Flat layers of one tile each, alternating between floors and open space
+        let rules = RuleSet::from_json(r#"{
+            "tiles": [{"id": "floor"}, {"id": "air"}],
+            "rules": [
+                {"from": "floor", "to": "floor", "direction": "Right", "symmetric": true},
+                {"from": "floor", "to": "floor", "direction": "Down", "symmetric": true},
+                {"from": "air", "to": "air", "direction": "Right", "symmetric": true},
+                {"from": "air", "to": "air", "direction": "Down", "symmetric": true},
+                {"from": "floor", "to": "air", "direction": "Forward", "symmetric": true},
+                {"from": "air", "to": "floor", "direction": "Forward", "symmetric": true}
+            ]
+        }"#).unwrap();
+        let mut volume = Model3D::new(4, 3, 5, rules.clone(), Some(7)).unwrap();
+        let grid = volume.run().unwrap();
+        assert_eq!(grid.len(), 60);
+
+        let topology = VoxelGrid::new(4, 3, 5);
+        for (index, tile) in grid.iter().enumerate() {
+            for (neighbor, direction) in topology.neighbors(index) {
+                assert!(rules.is_compatible(tile, &grid[neighbor], direction));
+            }
+        }
+        let (x, y, z) = volume.coords(volume.index(3, 2, 4));
+        assert_eq!((x, y, z), (3, 2, 4));
+        assert_ne!(grid[volume.index(0, 0, 0)], grid[volume.index(0, 0, 1)]);
+        assert_eq!(grid[volume.index(0, 0, 0)], grid[volume.index(3, 2, 2)]);
+        assert!(rules.is_compatible(&"floor".to_string(), &"air".to_string(), Direction::Backward));
+
+        assert!(Model3D::new(4, 3, 0, rules, None).is_err());
+    }
+
+    #[test]
+    fn test_model3d_solves_planar_layers_independently() {
+        // Stripes that only say how tiles sit side by side, nothing about above or below
+        let rules = RuleSet::from_json(r#"{
+            "tiles": [{"id": "red"}, {"id": "blue"}],
+            "rules": [
+                {"from": "red", "to": "blue", "direction": "Right", "symmetric": true},
+                {"from": "blue", "to": "red", "direction": "Right", "symmetric": true},
+                {"from": "red", "to": "red", "direction": "Down", "symmetric": true},
+                {"from": "blue", "to": "blue", "direction": "Down", "symmetric": true}
+            ]
+        }"#).unwrap();
+        let topology = VoxelGrid::new(3, 3, 2);
+        let mut layers_differ = false;
+        for seed in 0..20 {
+            let mut volume = Model3D::new(3, 3, 2, rules.clone(), Some(seed)).unwrap();
+            let grid = volume.run().unwrap();
+            for (index, tile) in grid.iter().enumerate() {
+                for (neighbor, direction) in topology.neighbors(index) {
+                    if !matches!(direction, Direction::Forward | Direction::Backward) {
+                        assert!(rules.is_compatible(tile, &grid[neighbor], direction));
+                    }
+                }
+            }
+            layers_differ |= grid[volume.index(0, 0, 0)] != grid[volume.index(0, 0, 1)];
+        }
+        assert!(layers_differ);
+    }
+}
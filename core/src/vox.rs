@@ -0,0 +1,129 @@
+//! MagicaVoxel `.vox` export.
+//!
+//! Volumes are given as a flat tile array indexed `x + y * width + z * width * height`.
+//! Axes map directly onto MagicaVoxel's x/y/z (z is "up" in the editor); a 2D grid
+//! exports as a single-layer volume with `depth = 1`.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::Write;
+use crate::TileId;
+use crate::error::WfcError;
+use crate::image::Pixel;
+use crate::render::tile_color;
+
+const VOX_VERSION: i32 = 150;
+const MAX_EXTENT: usize = 256;
+
+/// Write a tile volume as a `.vox` file. Each distinct non-empty tile gets one palette
+/// slot, colored from `palette` (or `render::tile_color`). Tiles listed in `empty`
+/// (e.g. "air") produce no voxel. Returns the tile -> palette index mapping used.
+pub fn write_vox<W: Write>(
+    mut writer: W,
+    (width, height, depth): (usize, usize, usize),
+    cells: &[TileId],
+    palette: &HashMap<TileId, Pixel>,
+    empty: &HashSet<TileId>,
+) -> Result<BTreeMap<TileId, u8>, WfcError> {
+    if width == 0 || height == 0 || depth == 0 || width > MAX_EXTENT || height > MAX_EXTENT || depth > MAX_EXTENT {
+        return Err(WfcError::Export(format!("{}x{}x{} is outside the .vox size limit of {}", width, height, depth, MAX_EXTENT)));
+    }
+    if cells.len() != width * height * depth {
+        return Err(WfcError::Export(format!("expected {} cells, got {}", width * height * depth, cells.len())));
+    }
+
+    // Palette index 0 is reserved for "no voxel", so tiles use 1..=255
+    let mut indices: BTreeMap<TileId, u8> = BTreeMap::new();
+    for id in cells.iter().filter(|id| !empty.contains(*id)) {
+        indices.entry(id.clone()).or_insert(0);
+    }
+    if indices.len() > 255 {
+        return Err(WfcError::Export(format!("{} distinct tiles exceed the 255-color .vox palette", indices.len())));
+    }
+    for (i, index) in indices.values_mut().enumerate() {
+        *index = i as u8 + 1;
+    }
+
+    let mut xyzi = Vec::new();
+    let mut count: u32 = 0;
+    for (i, id) in cells.iter().enumerate() {
+        if let Some(&index) = indices.get(id) {
+            let (x, y, z) = (i % width, i / width % height, i / (width * height));
+            xyzi.extend_from_slice(&[x as u8, y as u8, z as u8, index]);
+            count += 1;
+        }
+    }
+
+    let mut rgba = vec![0u8; 256 * 4];
+    for (id, &index) in &indices {
+        let color = palette.get(id).copied().unwrap_or_else(|| tile_color(id));
+        let slot = (index as usize - 1) * 4;
+        rgba[slot..slot + 4].copy_from_slice(&color);
+    }
+
+    let mut size = Vec::with_capacity(12);
+    for extent in [width, height, depth] {
+        size.extend_from_slice(&(extent as i32).to_le_bytes());
+    }
+    let mut xyzi_content = count.to_le_bytes().to_vec();
+    xyzi_content.extend_from_slice(&xyzi);
+
+    let mut children = Vec::new();
+    write_chunk(&mut children, b"SIZE", &size);
+    write_chunk(&mut children, b"XYZI", &xyzi_content);
+    write_chunk(&mut children, b"RGBA", &rgba);
+
+    let mut out = Vec::with_capacity(children.len() + 20);
+    out.extend_from_slice(b"VOX ");
+    out.extend_from_slice(&VOX_VERSION.to_le_bytes());
+    out.extend_from_slice(b"MAIN");
+    out.extend_from_slice(&0i32.to_le_bytes());
+    out.extend_from_slice(&(children.len() as i32).to_le_bytes());
+    out.extend_from_slice(&children);
+    writer.write_all(&out)?;
+
+    Ok(indices)
+}
+
+fn write_chunk(out: &mut Vec<u8>, id: &[u8; 4], content: &[u8]) {
+    out.extend_from_slice(id);
+    out.extend_from_slice(&(content.len() as i32).to_le_bytes());
+    out.extend_from_slice(&0i32.to_le_bytes());
+    out.extend_from_slice(content);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_vox_layout() {
+        let cells: Vec<TileId> = ["stone", "air", "grass", "stone"].iter().map(|s| s.to_string()).collect();
+        let empty: HashSet<TileId> = ["air".to_string()].into_iter().collect();
+        let mut bytes = Vec::new();
+        let indices = write_vox(&mut bytes, (2, 1, 2), &cells, &HashMap::new(), &empty).unwrap();
+
+        assert_eq!(indices.get("grass"), Some(&1));
+        assert_eq!(indices.get("stone"), Some(&2));
+        assert!(!indices.contains_key("air"));
+
+        assert_eq!(&bytes[0..4], b"VOX ");
+        assert_eq!(&bytes[8..12], b"MAIN");
+        // SIZE chunk follows the MAIN header
+        assert_eq!(&bytes[20..24], b"SIZE");
+        assert_eq!(i32::from_le_bytes(bytes[32..36].try_into().unwrap()), 2);
+        // XYZI: 3 voxels, first is stone at the origin
+        assert_eq!(&bytes[44..48], b"XYZI");
+        assert_eq!(u32::from_le_bytes(bytes[56..60].try_into().unwrap()), 3);
+        assert_eq!(&bytes[60..64], &[0, 0, 0, 2]);
+        // grass at x=0, z=1
+        assert_eq!(&bytes[64..68], &[0, 0, 1, 1]);
+    }
+
+    #[test]
+    fn test_write_vox_rejects_bad_sizes() {
+        let cells = vec!["a".to_string(); 2];
+        let none = HashSet::new();
+        assert!(write_vox(Vec::new(), (3, 1, 1), &cells, &HashMap::new(), &none).is_err());
+        assert!(write_vox(Vec::new(), (300, 1, 1), &cells, &HashMap::new(), &none).is_err());
+    }
+}
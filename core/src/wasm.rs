@@ -1,20 +1,324 @@
+use std::collections::HashSet;
+use std::sync::Arc;
 use wasm_bindgen::prelude::*;
-use crate::model::Model;
-use crate::ruleset::RuleSet;
+use serde::Serialize;
+use crate::model::{CancelToken, Model, ModelSnapshot, RunConfig, StepStatus, ALGORITHM_VERSION, MAX_CELLS, MAX_DIMENSION};
+use crate::{Direction, SymmetryType};
+use crate::ruleset::{MergeConflict, MissingAdjacency, RuleSet, ValidationReport};
+use crate::constraints::{CountConstraint, Rect};
+use crate::connectivity::Connected;
+use crate::sockets::EdgeSockets;
+use crate::topology::CellGraph;
 use crate::error::WfcError;
 
 impl From<WfcError> for JsValue {
+    /// Errors cross into JS as `Error` objects carrying the stable `code`/`codeStr` of the
+    /// Rust error, so callers can branch on them without parsing messages.
     fn from(error: WfcError) -> Self {
-        JsValue::from_str(&error.to_string())
+        let js_error = js_sys::Error::new(&error.to_string());
+        let _ = js_sys::Reflect::set(&js_error, &"code".into(), &error.code().into());
+        let _ = js_sys::Reflect::set(&js_error, &"codeStr".into(), &error.code_str().into());
+        js_error.into()
     }
 }
 
+/// The JS-facing `RuleSet` class. `ruleset::RuleSet` is generic over the tile type, which
+/// wasm-bindgen cannot export, so JS gets this wrapper around the string-keyed ruleset.
+#[wasm_bindgen(js_name = RuleSet)]
+#[derive(Debug, Clone, Default)]
+pub struct JsRuleSet {
+    inner: RuleSet,
+}
+
+#[wasm_bindgen(js_class = RuleSet)]
+impl JsRuleSet {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> JsRuleSet {
+        JsRuleSet::default()
+    }
+
+    #[wasm_bindgen]
+    pub fn add_tile_wasm(&mut self, id: String, weight: u32) {
+        self.inner.add_tile(id, weight);
+    }
+
+    /// Add a tile and its rotated/reflected variants (see `RuleSet::add_tile_with_symmetry`).
+    /// `symmetry` uses the JSON names (`"X"`, `"I"`, `"T"`, `"L"`, `"\\"`, `"F"`, `"N"`); every
+    /// variant gets `weight`. Returns the variant IDs in symmetry order, base tile first.
+    #[wasm_bindgen]
+    pub fn add_tile_with_symmetry_wasm(&mut self, id: String, weight: u32, symmetry: &str) -> Result<Vec<String>, JsValue> {
+        let symmetry: SymmetryType = serde_json::from_value(serde_json::Value::from(symmetry))
+            .map_err(|_| JsValue::from_str(&format!("Unknown symmetry type {:?}", symmetry)))?;
+        Ok(self.inner.add_tile_with_symmetry(id, symmetry, &[weight]))
+    }
+
+    /// Add a tile with socket labels on its four edges (see the `sockets` module). Call
+    /// `derive_socket_adjacency_wasm` once every tile is added.
+    #[wasm_bindgen]
+    pub fn add_tile_with_sockets_wasm(&mut self, id: String, weight: u32, up: String, right: String, down: String, left: String) {
+        self.inner.add_tile_with_sockets(id, weight, EdgeSockets::new(up, right, down, left));
+    }
+
+    /// Allow every pair of tiles whose touching sockets fit (see
+    /// `RuleSet::derive_socket_adjacency`)
+    #[wasm_bindgen]
+    pub fn derive_socket_adjacency_wasm(&mut self) {
+        self.inner.derive_socket_adjacency();
+    }
+
+    /// Add `alias` as a tile sharing every adjacency rule of `canonical` (see
+    /// `RuleSet::add_alias`)
+    #[wasm_bindgen]
+    pub fn add_alias_wasm(&mut self, alias: String, canonical: String, weight: u32) -> Result<(), JsValue> {
+        Ok(self.inner.add_alias(alias, canonical, weight)?)
+    }
+
+    #[wasm_bindgen]
+    pub fn add_adjacency_wasm(&mut self, from: String, to: String, direction: String) {
+        if let Some(dir) = parse_direction(&direction) {
+            self.inner.add_adjacency(from, to, dir);
+        }
+    }
+
+    /// Add an adjacency whose weight scales `to`'s weight next to a placed `from` (see
+    /// `RuleSet::add_weighted_adjacency`)
+    #[wasm_bindgen]
+    pub fn add_weighted_adjacency_wasm(&mut self, from: String, to: String, direction: String, weight: f64) -> Result<(), JsValue> {
+        if let Some(dir) = parse_direction(&direction) {
+            self.inner.add_weighted_adjacency(from, to, dir, weight)?;
+        }
+        Ok(())
+    }
+
+    /// Add an adjacency together with its rotated and mirrored copies, swapping symmetry
+    /// tiles for their matching variants (see `RuleSet::add_adjacency_with_symmetry`)
+    #[wasm_bindgen]
+    pub fn add_adjacency_with_symmetry_wasm(&mut self, from: String, to: String, direction: String) {
+        if let Some(dir) = parse_direction(&direction) {
+            self.inner.add_adjacency_with_symmetry(from, to, dir);
+        }
+    }
+
+    /// Add the missing reverse of every rule (see `RuleSet::symmetrize`), returning how many
+    /// were added
+    #[wasm_bindgen]
+    pub fn symmetrize_wasm(&mut self) -> usize {
+        self.inner.symmetrize()
+    }
+
+    /// Treat tile/direction pairs without rules as allowing anything (`MissingAdjacency::Open`)
+    #[wasm_bindgen]
+    pub fn set_open_adjacency(&mut self, open: bool) {
+        self.inner.missing_adjacency = if open { MissingAdjacency::Open } else { MissingAdjacency::Closed };
+    }
+
+    /// Rule problems found by `RuleSet::validate`, as a JS object with one array per kind
+    /// of problem and a `messages` array of readable explanations
+    #[wasm_bindgen]
+    pub fn validate_wasm(&self) -> Result<JsValue, JsValue> {
+        #[derive(Serialize)]
+        struct Validation {
+            #[serde(flatten)]
+            report: ValidationReport,
+            messages: Vec<String>,
+        }
+        let report = self.inner.validate();
+        let messages = report.to_string().lines().map(str::to_string).collect();
+        serde_wasm_bindgen::to_value(&Validation { report, messages })
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    /// See `RuleSet::content_hash`
+    #[wasm_bindgen]
+    pub fn content_hash_wasm(&self) -> String {
+        self.inner.content_hash()
+    }
+
+    #[wasm_bindgen]
+    pub fn get_weight(&self, tile_id: &str) -> Option<u32> {
+        self.inner.get_weight(tile_id)
+    }
+
+    #[wasm_bindgen]
+    pub fn to_json(&self) -> Result<JsValue, JsValue> {
+        let json_str = self.inner.to_json_string()
+            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        Ok(JsValue::from_str(&json_str))
+    }
+
+    #[wasm_bindgen]
+    pub fn from_json_wasm(json: &str) -> Result<JsRuleSet, JsValue> {
+        RuleSet::from_json(json)
+            .map(|inner| JsRuleSet { inner })
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+}
+
+/// What this build of the library can do, for frontends that adapt their UI to it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BuildInfo {
+    pub crate_version: &'static str,
+    /// See `model::ALGORITHM_VERSION`
+    pub algorithm_version: u32,
+    /// Cargo features compiled in
+    pub features: Vec<&'static str>,
+    pub max_dimension: usize,
+    pub max_cells: usize,
+}
+
+impl BuildInfo {
+    pub fn current() -> BuildInfo {
+        let features = [
+            ("png", cfg!(feature = "png")),
+            ("cli", cfg!(feature = "cli")),
+            ("serve", cfg!(feature = "serve")),
+            ("tiled", cfg!(feature = "tiled")),
+            ("parallel", cfg!(all(feature = "parallel", not(target_arch = "wasm32")))),
+            ("metrics", cfg!(feature = "metrics")),
+            ("debug-ui", cfg!(feature = "debug-ui")),
+            ("yaml", cfg!(feature = "yaml")),
+            ("toml", cfg!(feature = "toml")),
+        ]
+            .into_iter()
+            .filter_map(|(name, enabled)| enabled.then_some(name))
+            .collect();
+        BuildInfo {
+            crate_version: env!("CARGO_PKG_VERSION"),
+            algorithm_version: ALGORITHM_VERSION,
+            features,
+            max_dimension: MAX_DIMENSION,
+            max_cells: MAX_CELLS,
+        }
+    }
+}
+
+/// `BuildInfo::current()` as a JS object
+#[wasm_bindgen]
+pub fn build_info() -> Result<JsValue, JsValue> {
+    serde_wasm_bindgen::to_value(&BuildInfo::current())
+        .map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// `Model::estimate_memory` as a JS object, for sizing a run before any rules are loaded
+#[wasm_bindgen]
+pub fn estimate_memory(width: usize, height: usize, tile_count: usize) -> Result<JsValue, JsValue> {
+    serde_wasm_bindgen::to_value(&Model::estimate_memory(width, height, tile_count))
+        .map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// `bench::canonical_decision_hash`; compare against the native value to check this build
+/// makes the same decisions
+#[wasm_bindgen]
+pub fn canonical_decision_hash() -> Result<String, JsValue> {
+    Ok(crate::bench::canonical_decision_hash()?)
+}
+
+/// `trace::decision_hash` of running `rules_json` on a `width`x`height` grid with `seed`,
+/// for checking that a specific ruleset and seed behave the same natively and in wasm
+#[wasm_bindgen]
+pub fn decision_hash(rules_json: &str, width: usize, height: usize, seed: u64) -> Result<String, JsValue> {
+    let mut model = Model::new(width, height, RuleSet::from_json(rules_json)?, Some(seed))?;
+    Ok(crate::trace::decision_hash(&mut model)?)
+}
+
+/// `RuleSet::from_levels` as ruleset JSON, ready for `WfcModel::load_rules`
+#[wasm_bindgen]
+pub fn levels_ruleset(levels: Vec<String>, weights: Vec<u32>) -> Result<String, JsValue> {
+    let levels: Vec<&str> = levels.iter().map(String::as_str).collect();
+    Ok(RuleSet::from_levels(&levels, &weights)?.to_json_string()?)
+}
+
+/// Ruleset JSON files merged in order with `RuleSet::merge`, ready for
+/// `WfcModel::load_rules`. `policy` is `"error"`, `"union"` or `"prefer_self"`, where the
+/// earlier file is "self".
+#[wasm_bindgen]
+pub fn merge_rulesets(rules_jsons: Vec<String>, policy: &str) -> Result<String, JsValue> {
+    let policy = match policy {
+        "error" => MergeConflict::Error,
+        "union" => MergeConflict::Union,
+        "prefer_self" => MergeConflict::PreferSelf,
+        _ => return Err(JsValue::from_str(&format!("Unknown merge policy: {}", policy))),
+    };
+    let mut merged: Option<RuleSet> = None;
+    for json in &rules_jsons {
+        let rules = RuleSet::from_json(json)?;
+        match &mut merged {
+            Some(merged) => merged.merge(&rules, policy)?,
+            None => merged = Some(rules),
+        }
+    }
+    Ok(merged.unwrap_or_default().to_json_string()?)
+}
+
+/// `RuleSet::diff` from `old_json` to `new_json`, as a JS object with one array per kind of
+/// change
+#[wasm_bindgen]
+pub fn diff_rulesets(old_json: &str, new_json: &str) -> Result<JsValue, JsValue> {
+    let diff = RuleSet::from_json(old_json)?.diff(&RuleSet::from_json(new_json)?);
+    serde_wasm_bindgen::to_value(&diff)
+        .map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// Invalid directions yield `None` and are silently ignored by the callers
+fn parse_direction(direction: &str) -> Option<Direction> {
+    match direction {
+        "Up" => Some(Direction::Up),
+        "Down" => Some(Direction::Down),
+        "Left" => Some(Direction::Left),
+        "Right" => Some(Direction::Right),
+        "Forward" => Some(Direction::Forward),
+        "Backward" => Some(Direction::Backward),
+        "UpRight" => Some(Direction::UpRight),
+        "DownRight" => Some(Direction::DownRight),
+        "DownLeft" => Some(Direction::DownLeft),
+        "UpLeft" => Some(Direction::UpLeft),
+        _ => None,
+    }
+}
+
+/// Tiles matching any of `keys` (IDs or tags); an unmatched key is an `InvalidTileId`
+fn matching_tiles(rules: &RuleSet, keys: &[String]) -> Result<HashSet<String>, WfcError> {
+    let mut allowed = HashSet::new();
+    for key in keys {
+        let matching = rules.tiles_matching(key);
+        if matching.is_empty() {
+            return Err(WfcError::InvalidTileId(key.clone()));
+        }
+        allowed.extend(matching);
+    }
+    Ok(allowed)
+}
+
+/// Stops the run of the `WfcModel` it came from (see `WfcModel::cancel_handle`)
+#[wasm_bindgen]
+pub struct CancelHandle(CancelToken);
+
+#[wasm_bindgen]
+impl CancelHandle {
+    #[wasm_bindgen]
+    pub fn cancel(&self) {
+        self.0.cancel();
+    }
+}
+
+/// The web editor's solver. It stays on `Model<TileId>` rather than `intern::InternedModel`:
+/// snapshots, frames and greedy previews are all exchanged with the editor by tile name.
 #[wasm_bindgen]
 pub struct WfcModel {
     model: Option<Model>,
     width: usize,
     height: usize,
     seed: Option<u64>,
+    wrap_x: bool,
+    wrap_y: bool,
+    /// Constrain corner neighbors too (see `Model::with_diagonals`)
+    diagonals: bool,
+    /// Cells to generate and the tile reported elsewhere (see `Model::with_mask`)
+    area: Option<(Vec<bool>, Option<String>)>,
+    /// Cell network to solve over instead of the grid (see `Model::with_graph`)
+    graph: Option<CellGraph>,
+    /// Shared with every model loaded into this one
+    cancel: CancelToken,
     // Store the result here so we can retrieve it later
     result: Option<Vec<String>>, 
 }
@@ -24,7 +328,7 @@ impl WfcModel {
     #[wasm_bindgen(constructor)]
     pub fn new(width: usize, height: usize, seed: Option<u64>) -> Result<WfcModel, JsValue> {
         // Requirements 15.1, 15.2
-        if width == 0 || height == 0 || width > 500 || height > 500 {
+        if width == 0 || height == 0 || width > MAX_DIMENSION || height > MAX_DIMENSION {
             return Err(WfcError::InvalidDimensions { width, height }.into());
         }
 
@@ -33,10 +337,65 @@ impl WfcModel {
             width,
             height,
             seed,
+            wrap_x: false,
+            wrap_y: false,
+            diagonals: false,
+            area: None,
+            graph: None,
+            cancel: CancelToken::new(),
             result: None,
         })
     }
 
+    /// Join the left/right (`horizontal`) and top/bottom (`vertical`) edges of the grid.
+    /// Takes effect on the next `load_rules`.
+    #[wasm_bindgen]
+    pub fn set_wrap(&mut self, horizontal: bool, vertical: bool) {
+        self.wrap_x = horizontal;
+        self.wrap_y = vertical;
+    }
+
+    /// Also apply rules between diagonal neighbors (`UpRight`, `DownRight`, `DownLeft`,
+    /// `UpLeft`), which the ruleset then has to list. Ignored with a mask. Takes effect on
+    /// the next `load_rules`.
+    #[wasm_bindgen]
+    pub fn set_diagonals(&mut self, enabled: bool) {
+        self.diagonals = enabled;
+    }
+
+    /// Generate only the cells whose `inside` entry is non-zero (row-major, one per cell),
+    /// reporting `outside_tile` for the rest (see `Model::with_mask`); wrapping is ignored.
+    /// An empty `inside` generates the whole grid again. Takes effect on the next `load_rules`.
+    #[wasm_bindgen]
+    pub fn set_mask(&mut self, inside: Vec<u8>, outside_tile: Option<String>) -> Result<(), JsValue> {
+        if inside.is_empty() {
+            self.area = None;
+            return Ok(());
+        }
+        if inside.len() != self.width * self.height {
+            return Err(WfcError::InvalidDimensions { width: self.width, height: self.height }.into());
+        }
+        self.area = Some((inside.into_iter().map(|flag| flag != 0).collect(), outside_tile));
+        Ok(())
+    }
+
+    /// Solve over the nodes of a `topology::CellGraph` given as JSON instead of the grid,
+    /// ignoring the size, wrapping, diagonals and mask; results list one tile per node in
+    /// the order of `nodes`. `None` goes back to the grid. Takes effect on the next
+    /// `load_rules`.
+    #[wasm_bindgen]
+    pub fn set_graph(&mut self, graph_json: Option<String>) -> Result<(), JsValue> {
+        self.graph = match graph_json {
+            Some(json) => {
+                let graph = CellGraph::from_json(&json)?;
+                graph.topology()?;
+                Some(graph)
+            }
+            None => None,
+        };
+        Ok(())
+    }
+
     #[wasm_bindgen]
     pub fn load_rules(&mut self, rules_json: &str) -> Result<(), JsValue> {
         // Requirement 15.3
@@ -44,18 +403,57 @@ impl WfcModel {
         
         // Initialize the model with the loaded rules
         // We re-create the model whenever rules are loaded
-        self.model = Some(Model::new(self.width, self.height, rules, self.seed)?);
+        let mut model = match (&self.graph, &self.area) {
+            (Some(graph), _) => Model::with_graph(graph, rules, self.seed)?,
+            (None, Some((inside, outside))) => {
+                let mut model = Model::with_mask(self.width, self.height, inside, rules, self.seed)?;
+                if let Some(tile) = outside {
+                    model.set_outside_tile(tile.clone())?;
+                }
+                model
+            }
+            (None, None) if self.diagonals => Model::with_diagonals(self.width, self.height, self.wrap_x, self.wrap_y, rules, self.seed)?,
+            (None, None) => Model::with_wrap(self.width, self.height, self.wrap_x, self.wrap_y, rules, self.seed)?,
+        };
+        model.set_cancel_token(self.cancel.clone());
+        self.model = Some(model);
         self.result = None; // Reset result
         
         Ok(())
     }
 
+    /// Generate the grid, returning `false` on a contradiction. If `on_progress` is given,
+    /// it is called as `on_progress(collapsed, total)` every `every_n` steps (by default
+    /// about 1% of the cells) and once at the end (see `Model::run_with_progress`). An
+    /// exception thrown by the callback aborts the run and is rethrown.
     #[wasm_bindgen]
-    pub fn run(&mut self) -> Result<bool, JsValue> {
+    pub fn run(&mut self, on_progress: Option<js_sys::Function>, every_n: Option<usize>) -> Result<bool, JsValue> {
         // Requirement 15.4
+        let cancel = self.cancel.clone();
         match &mut self.model {
             Some(model) => {
-                match model.run() {
+                let outcome = match on_progress {
+                    Some(callback) => {
+                        let every_n = every_n.unwrap_or(model.width() * model.height() / 100);
+                        let mut thrown = None;
+                        let outcome = model.run_with_progress(every_n, |collapsed, total| {
+                            if thrown.is_none() {
+                                if let Err(e) = callback.call2(&JsValue::NULL, &collapsed.into(), &total.into()) {
+                                    thrown = Some(e);
+                                    cancel.cancel();
+                                }
+                            }
+                        });
+                        if let Some(e) = thrown {
+                            cancel.reset();
+                            self.result = None;
+                            return Err(e);
+                        }
+                        outcome
+                    },
+                    None => model.run(),
+                };
+                match outcome {
                     Ok(grid) => {
                         self.result = Some(grid);
                         Ok(true)
@@ -71,6 +469,272 @@ impl WfcModel {
         }
     }
 
+    /// Advance the solver at most `n` observe/propagate cycles (see `Model::run_steps`),
+    /// so a page or worker can generate in slices between other work. Returns `"running"`
+    /// while cells remain, `"done"` once the grid is ready for `get_grid`, or
+    /// `"contradiction"` if the rules cannot be satisfied.
+    #[wasm_bindgen]
+    pub fn run_steps(&mut self, n: usize) -> Result<String, JsValue> {
+        let model = self.loaded_model_mut()?;
+        let status = match model.run_steps(n) {
+            Ok(StepStatus::Running) => "running",
+            Ok(StepStatus::Done) => {
+                let grid = model.result()?;
+                self.result = Some(grid);
+                "done"
+            },
+            Err(WfcError::Contradiction) => {
+                self.result = None;
+                "contradiction"
+            },
+            Err(e) => return Err(e.into()),
+        };
+        Ok(status.to_string())
+    }
+
+    /// `run`, retrying with derived seeds after a failure (see `Model::run_with_retries`).
+    /// Returns the index of the successful attempt, or `undefined` if all `attempts` end in
+    /// a contradiction or pass the backtracking limits; `seed()` then gives the seed that
+    /// produced the grid. Running over the time or step budget or being cancelled throws.
+    #[wasm_bindgen]
+    pub fn run_with_retries(&mut self, attempts: u32) -> Result<Option<u32>, JsValue> {
+        let model = self.loaded_model_mut()?;
+        match model.run_with_retries(attempts) {
+            Ok(outcome) => {
+                self.result = Some(outcome.grid);
+                Ok(Some(outcome.attempt))
+            },
+            Err(WfcError::Contradiction | WfcError::BacktrackLimitExceeded { .. }) => {
+                self.result = None;
+                Ok(None)
+            },
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Abort the current run before its next step; it throws a `cancelled` error and the
+    /// next `run` continues where it stopped. `run` blocks the page, so during a run this
+    /// is only reachable from its callbacks; call it on a `cancel_handle()` from there.
+    #[wasm_bindgen]
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+
+    /// A separate object that cancels this model's run, usable while the model itself is
+    /// busy running
+    #[wasm_bindgen]
+    pub fn cancel_handle(&self) -> CancelHandle {
+        CancelHandle(self.cancel.clone())
+    }
+
+    /// Seed of the loaded model, drawn at random if none was given to the constructor
+    #[wasm_bindgen]
+    pub fn seed(&self) -> Result<u64, JsValue> {
+        Ok(self.loaded_model()?.seed())
+    }
+
+    /// `run`, also returning periodic frames of the grid (see `Model::run_collect_frames`)
+    /// as a JS object, or `null` if the run hit a contradiction
+    #[wasm_bindgen]
+    pub fn run_collect_frames(&mut self, every_n_steps: usize) -> Result<JsValue, JsValue> {
+        let model = self.model.as_mut()
+            .ok_or_else(|| JsValue::from_str("Model not initialized. Call load_rules() first."))?;
+        match model.run_collect_frames(every_n_steps) {
+            Ok(frames) => {
+                self.result = Some(model.result()?);
+                serde_wasm_bindgen::to_value(&frames)
+                    .map_err(|e| JsValue::from_str(&e.to_string()))
+            },
+            Err(WfcError::Contradiction) => {
+                self.result = None;
+                Ok(JsValue::NULL)
+            },
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Fast rough solve without backtracking (see `Model::run_greedy`), as a JS object with
+    /// `cells` (tile IDs, `null` where in conflict) and `conflicts`. The model cannot be
+    /// run afterwards; call `load_rules()` again for the full solve.
+    #[wasm_bindgen]
+    pub fn run_greedy(&mut self) -> Result<JsValue, JsValue> {
+        let model = self.model.as_mut()
+            .ok_or_else(|| JsValue::from_str("Model not initialized. Call load_rules() first."))?;
+        self.result = None;
+        serde_wasm_bindgen::to_value(&model.run_greedy()?)
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    /// Per-cell possibility counts and tile shares of the current wave, as a JS object
+    #[wasm_bindgen]
+    pub fn wave_snapshot(&self) -> Result<JsValue, JsValue> {
+        let model = self.loaded_model()?;
+        serde_wasm_bindgen::to_value(&model.wave_snapshot())
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    /// Per-cell possibility counts of the current wave (a `Uint32Array` in JS)
+    #[wasm_bindgen]
+    pub fn wave_counts(&self) -> Result<Vec<u32>, JsValue> {
+        let model = self.loaded_model()?;
+        Ok(model.wave_snapshot().counts)
+    }
+
+    /// Row-major `cells x tiles` tile shares of the current wave (a `Float32Array` in JS)
+    #[wasm_bindgen]
+    pub fn wave_probabilities(&self) -> Result<Vec<f32>, JsValue> {
+        let model = self.loaded_model()?;
+        Ok(model.wave_snapshot().probabilities)
+    }
+
+    /// Per-cell entropy in bits and possibility counts of the current wave
+    /// (`model::EntropyMap`), for drawing an uncertainty heatmap while stepping
+    #[wasm_bindgen]
+    pub fn entropy_map(&self) -> Result<JsValue, JsValue> {
+        let model = self.loaded_model()?;
+        serde_wasm_bindgen::to_value(&model.entropy_map())
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    /// Step at which each cell was collapsed (a `Uint32Array` in JS); 0 for cells still open
+    #[wasm_bindgen]
+    pub fn collapse_order(&self) -> Result<Vec<u32>, JsValue> {
+        let model = self.loaded_model()?;
+        Ok(model.collapse_order().into_iter().map(|step| step.unwrap_or(0) as u32).collect())
+    }
+
+    /// Options and entropy each cell had when it was collapsed (`model::CellConfidence`),
+    /// as an array of `{options, entropy}` objects with `null` for cells still open
+    #[wasm_bindgen]
+    pub fn confidence_map(&self) -> Result<JsValue, JsValue> {
+        let model = self.loaded_model()?;
+        serde_wasm_bindgen::to_value(&model.confidence_map())
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    /// Indices of the cells changed since the previous call (a `Uint32Array` in JS), so a
+    /// renderer can redraw only those tiles after each step
+    #[wasm_bindgen]
+    pub fn take_dirty(&mut self) -> Result<Vec<u32>, JsValue> {
+        let model = self.loaded_model_mut()?;
+        Ok(model.take_dirty().into_iter().map(|index| index as u32).collect())
+    }
+
+    /// Pin a cell to `tile_id` before `run` (see `Model::set_tile`). Throws if the tile is
+    /// unknown or contradicts the cells pinned so far.
+    #[wasm_bindgen]
+    pub fn set_tile(&mut self, x: usize, y: usize, tile_id: &str) -> Result<(), JsValue> {
+        self.loaded_model_mut()?.set_tile(x, y, tile_id)?;
+        self.result = None;
+        Ok(())
+    }
+
+    /// Limit the `width`x`height` rectangle at `(x, y)` to the tiles matching `tiles` (IDs or
+    /// tags) before `run` (see `Model::mask_rect`). Throws on an unknown key, a rectangle
+    /// outside the grid, or a mask that contradicts the cells pinned so far.
+    #[wasm_bindgen]
+    pub fn mask_rect(&mut self, x: usize, y: usize, width: usize, height: usize, tiles: Vec<String>) -> Result<(), JsValue> {
+        let model = self.loaded_model_mut()?;
+        let allowed = matching_tiles(model.rules(), &tiles)?;
+        model.mask_rect(Rect { x, y, width, height }, &allowed)?;
+        self.result = None;
+        Ok(())
+    }
+
+    /// Require between `min` and `max` cells of the tiles matching `tile` (an ID or tag)
+    /// across the grid (see `Model::add_count_constraint`)
+    #[wasm_bindgen]
+    pub fn add_count_constraint(&mut self, tile: String, min: usize, max: Option<usize>) -> Result<(), JsValue> {
+        self.loaded_model_mut()?.add_count_constraint(CountConstraint { tile, min, max })?;
+        self.result = None;
+        Ok(())
+    }
+
+    /// Keep every cell holding a tile matching `tiles` (IDs or tags) in one connected region,
+    /// backtracking as needed (see `connectivity::Connected`)
+    #[wasm_bindgen]
+    pub fn add_connectivity_constraint(&mut self, tiles: Vec<String>) -> Result<(), JsValue> {
+        let model = self.loaded_model_mut()?;
+        let connected = Connected::new(matching_tiles(model.rules(), &tiles)?);
+        model.add_wave_constraint(Arc::new(connected))?;
+        self.result = None;
+        Ok(())
+    }
+
+    /// Limit the cells on the given edges (`"Up"`, `"Right"`, `"Down"`, `"Left"`; every edge
+    /// if empty) to the tiles matching `tiles` before `run` (see `Model::mask_border`)
+    #[wasm_bindgen]
+    pub fn mask_border(&mut self, edges: Vec<String>, tiles: Vec<String>) -> Result<(), JsValue> {
+        let edges = edges
+            .iter()
+            .map(|edge| parse_direction(edge).ok_or_else(|| JsValue::from_str(&format!("Unknown edge {:?}", edge))))
+            .collect::<Result<Vec<Direction>, JsValue>>()?;
+        let model = self.loaded_model_mut()?;
+        let allowed = matching_tiles(model.rules(), &tiles)?;
+        model.mask_border(&edges, &allowed)?;
+        self.result = None;
+        Ok(())
+    }
+
+    /// Backtracking limits and restart policy from a JS object shaped like
+    /// `model::RunConfig`, e.g. `{ max_backtracks: 500, max_backtrack_depth: null,
+    /// max_restarts: 3, on_exhaustion: "Restart", max_millis: 2000 }`. A run that uses up
+    /// `max_iterations` or `max_millis` throws a `budget_exceeded` error instead of
+    /// blocking the page; calling `run` again continues it.
+    #[wasm_bindgen]
+    pub fn set_run_config(&mut self, config: JsValue) -> Result<(), JsValue> {
+        let config: RunConfig = serde_wasm_bindgen::from_value(config)
+            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        self.loaded_model_mut()?.set_run_config(config);
+        Ok(())
+    }
+
+    /// The run so far as a JSON string (see `Model::to_snapshot`), for pausing a long
+    /// generation and resuming it later with `load_snapshot`
+    #[wasm_bindgen]
+    pub fn to_snapshot(&self) -> Result<String, JsValue> {
+        let model = self.loaded_model()?;
+        Ok(model.to_snapshot()?.to_json_string()?)
+    }
+
+    /// Replace the model with one restored from `to_snapshot`, taking over its dimensions
+    #[wasm_bindgen]
+    pub fn load_snapshot(&mut self, snapshot_json: &str) -> Result<(), JsValue> {
+        let mut model = Model::from_snapshot(ModelSnapshot::from_json(snapshot_json)?)?;
+        model.set_cancel_token(self.cancel.clone());
+        self.width = model.width();
+        self.height = model.height();
+        self.model = Some(model);
+        self.result = None;
+        Ok(())
+    }
+
+    /// Reproducibility manifest of the finished run, as a JSON string
+    #[wasm_bindgen]
+    pub fn manifest(&self) -> Result<String, JsValue> {
+        let model = self.loaded_model()?;
+        model.manifest()
+            .and_then(|manifest| manifest.to_json_string())
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    /// Current heap footprint of the wave and backtracking log (`model::MemoryUsage`), as a
+    /// JS object with `wave` and `history` byte counts
+    #[wasm_bindgen]
+    pub fn memory_usage(&self) -> Result<JsValue, JsValue> {
+        let model = self.loaded_model()?;
+        serde_wasm_bindgen::to_value(&model.memory_usage())
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    /// Predicted peak footprint of running the loaded rules at this size (see
+    /// `Model::estimate_memory`), to warn before a large run exhausts the wasm heap
+    #[wasm_bindgen]
+    pub fn estimated_memory(&self) -> Result<JsValue, JsValue> {
+        let model = self.loaded_model()?;
+        estimate_memory(model.width(), model.height(), model.rules().tiles.len())
+    }
+
     #[wasm_bindgen]
     pub fn get_grid(&self) -> Result<JsValue, JsValue> {
         // Requirement 15.5: Return grid to JavaScript
@@ -84,15 +748,37 @@ impl WfcModel {
     }
 }
 
+impl WfcModel {
+    fn loaded_model(&self) -> Result<&Model, JsValue> {
+        self.model.as_ref()
+            .ok_or_else(|| JsValue::from_str("Model not initialized. Call load_rules() first."))
+    }
+
+    fn loaded_model_mut(&mut self) -> Result<&mut Model, JsValue> {
+        self.model.as_mut()
+            .ok_or_else(|| JsValue::from_str("Model not initialized. Call load_rules() first."))
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    #[cfg(target_arch = "wasm32")]
     use super::*;
-    use crate::ruleset::RuleSet;
 
     // Note: Testing Wasm bindings in standard `cargo test` is difficult because `JsValue` 
     // interactions usually require a Wasm environment.
     // However, we can test the logic structure if we conditionally compile.
     
+    #[test]
+    fn test_build_info() {
+        let info = super::BuildInfo::current();
+        assert_eq!(info.crate_version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(info.max_cells, info.max_dimension * info.max_dimension);
+        assert_eq!(info.features.contains(&"png"), cfg!(feature = "png"));
+        assert_eq!(info.features.contains(&"yaml"), cfg!(feature = "yaml"));
+        assert_eq!(info.features.contains(&"toml"), cfg!(feature = "toml"));
+    }
+
     #[test]
     #[cfg(target_arch = "wasm32")] // Only run on wasm32
     fn test_error_conversion() {
@@ -63,6 +63,7 @@ export interface Tile {
     left: TileSocketAssignment[];
   };
   symmetry?: SymmetryType; // Optional symmetry type for automatic rotation/reflection
+  variant_weights?: number[]; // Optional per-variant weights, in symmetry variant order (named as in the core's ruleset JSON)
 }
 
 /**
@@ -75,20 +75,21 @@ function transformSockets(
 
 /**
  * Get transformations for a symmetry type
- * Returns array of (rotation_degrees, reflect_horizontal, reflect_vertical)
+ * Returns array of (rotation_degrees, reflect_horizontal, reflect_vertical),
+ * in the core's `SymmetryType::variants()` order so variant weights agree
  */
 function getSymmetryTransformations(symmetry: SymmetryType): Array<[number, boolean, boolean]> {
   switch (symmetry) {
     case 'X':
       return [[0, false, false]];
     case 'I':
-      return [[0, false, false], [0, true, false]];
+      return [[0, false, false], [90, false, false]];
     case 'T':
       return [[0, false, false], [90, false, false], [180, false, false], [270, false, false]];
     case 'L':
       return [[0, false, false], [90, false, false], [180, false, false], [270, false, false]];
     case '\\':
-      return [[0, false, false], [0, true, true]];
+      return [[0, false, false], [90, false, false]];
     case 'F':
       return [
         [0, false, false], [90, false, false], [180, false, false], [270, false, false],
@@ -102,6 +103,17 @@ function getSymmetryTransformations(symmetry: SymmetryType): Array<[number, bool
   }
 }
 
+/**
+ * Weight of the variant at `index`; missing entries repeat the last given weight
+ */
+function variantWeight(baseTile: Tile, index: number): number {
+  const weights = baseTile.variant_weights;
+  if (!weights || weights.length === 0) {
+    return baseTile.weight;
+  }
+  return weights[Math.min(index, weights.length - 1)];
+}
+
 /**
  * Generate variant tiles from a base tile with symmetry
  * Returns a map of variant ID -> variant tile
@@ -136,7 +148,7 @@ export function generateTileVariants(baseTile: Tile): Map<string, Tile> {
       displayName: `${baseTile.displayName}${index === 0 ? '' : ` (${rotation}°${reflectH ? ' H' : ''}${reflectV ? ' V' : ''})`}`,
       sprite: baseTile.sprite,
       color: baseTile.color,
-      weight: baseTile.weight,
+      weight: variantWeight(baseTile, index),
       sockets: transformedSockets,
       symmetry: undefined, // Variants don't have symmetry themselves
     };
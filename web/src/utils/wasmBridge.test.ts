@@ -62,4 +62,18 @@ describe('WasmBridge Utility', () => {
     expect(parsed.tiles[0]).toEqual({ id: 'A', weight: 1 });
     expect(parsed.rules.length).toBeGreaterThan(0);
   });
+
+  it('convertToRulesJson should weight symmetry variants in core order', () => {
+    const road: Tile = {
+      ...createTile('road', { top: 'r', right: 'g', bottom: 'r', left: 'g' }),
+      symmetry: 'I',
+      variant_weights: [5, 1],
+    };
+    const tiles = new Map<string, Tile>([[road.id, road]]);
+
+    const parsed = JSON.parse(WasmBridge.convertToRulesJson(tiles));
+
+    // Same IDs and weights as `RuleSet::add_tile_with_symmetry` gives in Rust
+    expect(parsed.tiles).toEqual([{ id: 'road', weight: 5 }, { id: 'road_90', weight: 1 }]);
+  });
 });